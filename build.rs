@@ -0,0 +1,21 @@
+use std::path::Path;
+
+/// 管理界面运行必需的静态资源，缺失时直接中止构建
+const REQUIRED_ASSETS: &[&str] = &["index.html", "login.html"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=static");
+
+    let dir = Path::new("static");
+    for name in REQUIRED_ASSETS {
+        let path = dir.join(name);
+        match std::fs::metadata(&path) {
+            Ok(meta) if meta.len() > 0 => {}
+            Ok(_) => panic!("embedded asset {} is empty", path.display()),
+            Err(e) => panic!("embedded asset {} is missing: {}", path.display(), e),
+        }
+    }
+
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=BUILD_TARGET={}", target);
+}
@@ -0,0 +1,39 @@
+//! 嵌入示例：为代理响应添加请求头，并拦截指定路径
+//!
+//! cargo run --example custom_hook
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use proxy_server::hooks::{HookContext, ProxyHook, ProxyHooks};
+
+struct Tagging;
+
+impl ProxyHook for Tagging {
+    fn on_request(&self, ctx: &HookContext, _req: &mut Request) -> Option<Response> {
+        ctx.path
+            .starts_with("/internal/")
+            .then(|| (StatusCode::FORBIDDEN, "blocked by hook").into_response())
+    }
+
+    fn on_response(&self, ctx: &HookContext, resp: &mut Response) {
+        if let Some(rule) = ctx
+            .rule
+            .as_deref()
+            .and_then(|r| HeaderValue::from_str(r).ok())
+        {
+            resp.headers_mut().insert("x-proxy-rule", rule);
+        }
+    }
+
+    fn on_error(&self, ctx: &HookContext, status: StatusCode) {
+        eprintln!("{} {} failed: {}", ctx.method, ctx.path, status);
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    proxy_server::run(ProxyHooks::default().register(Tagging)).await
+}
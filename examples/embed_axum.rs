@@ -0,0 +1,34 @@
+//! 嵌入示例：在已有的 Axum 应用中挂载代理，/proxy/gh/... 转发到 GitHub
+//!
+//! cargo run --example embed_axum
+
+use axum::{routing::get, Router};
+use proxy_server::engine::{ProxyRule, ProxyServer, RuleOptions};
+use std::net::SocketAddr;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let rules = vec![ProxyRule {
+        id: 1,
+        name: "github".to_string(),
+        source: "/gh/{*path}".to_string(),
+        target: "https://github.com/{*path}".to_string(),
+        timeout_secs: 30,
+        enabled: true,
+        options: RuleOptions::default(),
+        created_at: String::new(),
+        updated_at: String::new(),
+    }];
+    let proxy = ProxyServer::builder().with_rules(rules).build()?;
+
+    let app = Router::new()
+        .route("/hello", get(|| async { "hello from the host app" }))
+        .nest_service("/proxy", proxy.router());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use axum::{body::Body, response::Response};
+use chrono::Local;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::AccessLogFormat;
+use crate::logger::RollingFileWriter;
+
+/// 一条访问日志
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessLogEntry {
+    pub time: String,
+    pub client_ip: String,
+    pub method: String,
+    pub path: String,
+    pub protocol: String,
+    pub rule: Option<String>,
+    pub target: Option<String>,
+    pub status: u16,
+    pub bytes_out: u64,
+    pub duration_ms: u64,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub trace_id: String,
+}
+
+/// 规则的请求日志记录方式，同时作用于访问日志与应用日志中的转发记录
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum RuleLogging {
+    /// 不记录
+    Off,
+    /// 只记录状态码 >= 400 的请求
+    Errors,
+    /// 每 every 个请求记录 1 个
+    Sampled { every: u64 },
+    #[default]
+    Full,
+}
+
+impl RuleLogging {
+    pub fn is_full(&self) -> bool {
+        *self == Self::Full
+    }
+
+    /// 按规则的请求计数决定本次请求是否记录
+    pub fn decide(&self, counter: &AtomicU64) -> LogDecision {
+        match *self {
+            Self::Off => LogDecision::Never,
+            Self::Errors => LogDecision::OnError,
+            Self::Sampled { every } => {
+                if counter
+                    .fetch_add(1, Ordering::Relaxed)
+                    .is_multiple_of(every.max(1))
+                {
+                    LogDecision::Always
+                } else {
+                    LogDecision::Never
+                }
+            }
+            Self::Full => LogDecision::Always,
+        }
+    }
+}
+
+/// 单个请求的日志决定，OnError 要等响应状态确定后判断
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogDecision {
+    #[default]
+    Always,
+    OnError,
+    Never,
+}
+
+impl LogDecision {
+    pub fn allows(self, status: u16) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnError => status >= 400,
+            Self::Never => false,
+        }
+    }
+}
+
+/// 访问日志写入器，独立于应用日志滚动
+#[derive(Clone)]
+pub struct AccessLogger {
+    writer: RollingFileWriter,
+    format: AccessLogFormat,
+}
+
+impl AccessLogger {
+    pub fn new(writer: RollingFileWriter, format: AccessLogFormat) -> Self {
+        Self { writer, format }
+    }
+
+    /// 按日志格式生成当前时间
+    pub fn timestamp(&self) -> String {
+        match self.format {
+            AccessLogFormat::Json => Local::now().to_rfc3339(),
+            AccessLogFormat::Combined => Local::now().format("%d/%b/%Y:%H:%M:%S %z").to_string(),
+        }
+    }
+
+    pub fn write(&self, entry: &AccessLogEntry) {
+        let mut line = match self.format {
+            AccessLogFormat::Json => serde_json::to_string(entry).unwrap_or_default(),
+            AccessLogFormat::Combined => format_combined(entry),
+        };
+        line.push('\n');
+
+        if let Err(e) = self.writer.make_writer().write_all(line.as_bytes()) {
+            tracing::error!("Failed to write access log: {}", e);
+        }
+    }
+
+    /// 包装响应体，在响应体发送结束（或客户端中断）时写入日志，记录实际发送字节数与总耗时
+    pub fn wrap_response(
+        &self,
+        resp: Response,
+        mut entry: AccessLogEntry,
+        start: Instant,
+    ) -> Response {
+        let (parts, body) = resp.into_parts();
+        entry.status = parts.status.as_u16();
+
+        let guard = PendingEntry {
+            logger: self.clone(),
+            entry: Some(entry),
+            bytes: Arc::new(AtomicU64::new(0)),
+            start,
+        };
+        let bytes = guard.bytes.clone();
+        let stream = body.into_data_stream().map(move |chunk| {
+            let _ = &guard;
+            if let Ok(ref data) = chunk {
+                bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+            chunk
+        });
+
+        Response::from_parts(parts, Body::from_stream(stream))
+    }
+}
+
+struct PendingEntry {
+    logger: AccessLogger,
+    entry: Option<AccessLogEntry>,
+    bytes: Arc<AtomicU64>,
+    start: Instant,
+}
+
+impl Drop for PendingEntry {
+    fn drop(&mut self) {
+        if let Some(mut entry) = self.entry.take() {
+            entry.bytes_out = self.bytes.load(Ordering::Relaxed);
+            entry.duration_ms = self.start.elapsed().as_millis() as u64;
+            self.logger.write(&entry);
+        }
+    }
+}
+
+/// Apache combined 格式，末尾追加规则、目标与耗时
+fn format_combined(e: &AccessLogEntry) -> String {
+    format!(
+        "{} - - [{}] \"{} {} {}\" {} {} \"{}\" \"{}\" rule=\"{}\" target=\"{}\" duration_ms={} trace_id={}",
+        e.client_ip,
+        e.time,
+        e.method,
+        e.path,
+        e.protocol,
+        e.status,
+        e.bytes_out,
+        e.referer.as_deref().unwrap_or("-"),
+        e.user_agent.as_deref().unwrap_or("-"),
+        e.rule.as_deref().unwrap_or("-"),
+        e.target.as_deref().unwrap_or("-"),
+        e.duration_ms,
+        e.trace_id
+    )
+}
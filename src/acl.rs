@@ -0,0 +1,89 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::config::AdminConfig;
+
+/// IP 访问控制列表，支持单个地址和 CIDR 网段
+#[derive(Debug, Clone, Default)]
+pub struct IpAcl {
+    nets: Vec<IpNet>,
+}
+
+impl IpAcl {
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> anyhow::Result<Self> {
+        let nets = entries
+            .iter()
+            .map(|e| e.as_ref().trim())
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                e.parse::<IpNet>()
+                    .or_else(|_| e.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| anyhow::anyhow!("Invalid IP or CIDR: {}", e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { nets })
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    /// IPv4 映射的 IPv6 地址按 IPv4 匹配
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+}
+
+/// 管理接口访问控制，在认证之前执行，凭据泄露时也无法从白名单外访问
+#[derive(Debug, Clone)]
+pub struct AdminAcl {
+    acl: IpAcl,
+    localhost_bypass: bool,
+}
+
+impl AdminAcl {
+    pub fn from_config(config: &AdminConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            acl: IpAcl::parse(&config.allowed_cidrs)?,
+            localhost_bypass: config.localhost_bypass,
+        })
+    }
+
+    fn allows(&self, ip: IpAddr) -> bool {
+        let loopback = match ip {
+            IpAddr::V6(v6) => v6
+                .to_ipv4_mapped()
+                .map_or(v6.is_loopback(), |v4| v4.is_loopback()),
+            IpAddr::V4(v4) => v4.is_loopback(),
+        };
+        self.acl.is_empty() || self.acl.contains(ip) || (self.localhost_bypass && loopback)
+    }
+}
+
+/// Unix 套接字连接没有对端地址，由套接字文件权限控制访问
+pub async fn admin_acl_middleware(
+    State(acl): State<Arc<AdminAcl>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match connect_info {
+        Some(ConnectInfo(client)) if !acl.allows(client.ip()) => {
+            tracing::warn!(client_ip = %client.ip(), path = %req.uri().path(), "Admin access denied by IP allowlist");
+            StatusCode::FORBIDDEN.into_response()
+        }
+        _ => next.run(req).await,
+    }
+}
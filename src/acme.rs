@@ -0,0 +1,591 @@
+use anyhow::{bail, Context, Result};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use dashmap::DashMap;
+use reqwest::Client;
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{AcmeCertificateConfig, AcmeConfig};
+use crate::dns01::DnsProvider;
+use crate::events::AdminEvents;
+use crate::secrets::{Secrets, SYSTEM_PREFIX};
+use crate::tasks::TaskRegistry;
+use crate::tls::CertStore;
+
+/// HTTP-01 验证路径前缀
+pub const HTTP01_PATH: &str = "/.well-known/acme-challenge/:token";
+
+/// 轮询订单与授权状态的间隔和最大次数
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+const POLL_ATTEMPTS: usize = 60;
+
+/// 续期检查周期
+const RENEW_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+
+/// 进行中的 HTTP-01 验证，token -> key authorization
+#[derive(Clone, Default)]
+pub struct Http01Tokens(Arc<DashMap<String, String>>);
+
+/// 在代理端口上响应 HTTP-01 验证请求
+pub async fn http01_handler(
+    State(tokens): State<Http01Tokens>,
+    Path(token): Path<String>,
+) -> Result<String, StatusCode> {
+    tokens
+        .0
+        .get(&token)
+        .map(|v| v.clone())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    #[serde(default)]
+    certificate: Option<String>,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    #[serde(default)]
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+/// 已登录的 ACME 账户，请求使用 ES256 签名的 JWS
+struct Account<'a> {
+    client: &'a Client,
+    key: EcdsaKeyPair,
+    jwk: Value,
+    kid: String,
+    directory: Directory,
+    nonce: parking_lot::Mutex<Option<String>>,
+}
+
+impl<'a> Account<'a> {
+    async fn login(client: &'a Client, config: &AcmeConfig, pkcs8: &[u8]) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8, &rng)
+            .map_err(|e| anyhow::anyhow!("Invalid ACME account key: {}", e))?;
+        // 公钥为未压缩点 0x04 || x || y
+        let point = key.public_key().as_ref();
+        let jwk = json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        });
+        let directory: Directory = client
+            .get(&config.directory_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid ACME directory")?;
+
+        let mut account = Self {
+            client,
+            key,
+            jwk,
+            kid: String::new(),
+            directory,
+            nonce: parking_lot::Mutex::new(None),
+        };
+
+        let mut payload = json!({ "termsOfServiceAgreed": true });
+        if let Some(ref email) = config.contact_email {
+            payload["contact"] = json!([format!("mailto:{}", email)]);
+        }
+        let new_account = account.directory.new_account.clone();
+        let resp = account.post(&new_account, Some(&payload)).await?;
+        account.kid = location(&resp)?;
+        Ok(account)
+    }
+
+    /// JWK 指纹（RFC 7638），成员按字典序排列
+    fn thumbprint(&self) -> String {
+        let canonical = format!(
+            r#"{{"crv":"P-256","kty":"EC","x":{},"y":{}}}"#,
+            self.jwk["x"], self.jwk["y"]
+        );
+        URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()))
+    }
+
+    async fn fresh_nonce(&self) -> Result<String> {
+        if let Some(nonce) = self.nonce.lock().take() {
+            return Ok(nonce);
+        }
+        let resp = self.client.head(&self.directory.new_nonce).send().await?;
+        replay_nonce(&resp).context("ACME server returned no nonce")
+    }
+
+    /// 发送签名请求，payload 为 None 时为 POST-as-GET；nonce 失效时重试一次
+    async fn post(&self, url: &str, payload: Option<&Value>) -> Result<reqwest::Response> {
+        let payload = match payload {
+            Some(p) => URL_SAFE_NO_PAD.encode(serde_json::to_vec(p)?),
+            None => String::new(),
+        };
+
+        for attempt in 0..2 {
+            let mut protected = json!({
+                "alg": "ES256",
+                "nonce": self.fresh_nonce().await?,
+                "url": url,
+            });
+            if self.kid.is_empty() {
+                protected["jwk"] = self.jwk.clone();
+            } else {
+                protected["kid"] = json!(self.kid);
+            }
+            let protected = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&protected)?);
+            let signature = self
+                .key
+                .sign(
+                    &SystemRandom::new(),
+                    format!("{}.{}", protected, payload).as_bytes(),
+                )
+                .map_err(|_| anyhow::anyhow!("Failed to sign ACME request"))?;
+            let body = json!({
+                "protected": protected,
+                "payload": payload,
+                "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+            });
+
+            let resp = self
+                .client
+                .post(url)
+                .header("content-type", "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await?;
+            *self.nonce.lock() = replay_nonce(&resp);
+
+            if resp.status().is_success() {
+                return Ok(resp);
+            }
+            let status = resp.status();
+            let problem: Value = resp.json().await.unwrap_or_default();
+            if attempt == 0 && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                continue;
+            }
+            bail!("ACME request {} failed: {} {}", url, status, problem);
+        }
+        unreachable!()
+    }
+}
+
+fn replay_nonce(resp: &reqwest::Response) -> Option<String> {
+    resp.headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+fn location(resp: &reqwest::Response) -> Result<String> {
+    resp.headers()
+        .get("location")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .context("ACME response missing Location header")
+}
+
+/// 证书文件名，通配符域名中的 * 替换为 _
+fn cert_file_stem(domains: &[String]) -> String {
+    domains
+        .first()
+        .map(|d| d.replace('*', "_"))
+        .unwrap_or_default()
+}
+
+/// ACME 证书签发与续期
+#[derive(Clone)]
+pub struct AcmeManager {
+    config: Arc<AcmeConfig>,
+    client: Client,
+    certs: Arc<CertStore>,
+    providers: Arc<HashMap<String, DnsProvider>>,
+    http01: Http01Tokens,
+    secrets: Secrets,
+    events: AdminEvents,
+}
+
+impl AcmeManager {
+    pub fn new(
+        config: AcmeConfig,
+        client: Client,
+        certs: Arc<CertStore>,
+        secrets: Secrets,
+        events: AdminEvents,
+    ) -> Result<Self> {
+        let mut providers = HashMap::new();
+        for (name, provider) in &config.dns_providers {
+            providers.insert(
+                name.clone(),
+                DnsProvider::from_config(provider, client.clone())
+                    .with_context(|| format!("Invalid DNS provider '{}'", name))?,
+            );
+        }
+        for cert in &config.certificates {
+            if cert.domains.is_empty() {
+                bail!("ACME certificate has no domains");
+            }
+            match cert.dns_provider {
+                Some(ref name) if !providers.contains_key(name) => {
+                    bail!(
+                        "ACME certificate references unknown DNS provider '{}'",
+                        name
+                    )
+                }
+                None if cert.domains.iter().any(|d| d.starts_with("*.")) => bail!(
+                    "Wildcard certificate {} requires a dns_provider",
+                    cert.domains.join(",")
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            config: Arc::new(config),
+            client,
+            certs,
+            providers: Arc::new(providers),
+            http01: Http01Tokens::default(),
+            secrets,
+            events,
+        })
+    }
+
+    pub fn http01_tokens(&self) -> Http01Tokens {
+        self.http01.clone()
+    }
+
+    /// 是否有证书使用 HTTP-01 验证
+    pub fn uses_http01(&self) -> bool {
+        self.config
+            .certificates
+            .iter()
+            .any(|c| c.dns_provider.is_none())
+    }
+
+    fn storage_path(&self, file: &str) -> PathBuf {
+        PathBuf::from(&self.config.storage_dir).join(file)
+    }
+
+    /// 私钥保存在加密密钥存储中，旧版本写入存储目录的私钥文件读取后迁移并删除；
+    /// binary 为 true 时文件内容以 base64 保存
+    fn private_key(&self, name: &str, legacy_file: &str, binary: bool) -> Result<Option<String>> {
+        let name = format!("{}acme.{}", SYSTEM_PREFIX, name);
+        if let Some(key) = self.secrets.get(&name) {
+            return Ok(Some(key));
+        }
+        let path = self.storage_path(legacy_file);
+        let Ok(key) = std::fs::read(&path) else {
+            return Ok(None);
+        };
+        let key = if binary {
+            STANDARD.encode(key)
+        } else {
+            String::from_utf8(key)?
+        };
+        self.secrets.set(&name, &key)?;
+        std::fs::remove_file(&path)?;
+        tracing::info!(file = %path.display(), "Migrated ACME private key into secrets store");
+        Ok(Some(key))
+    }
+
+    /// 加载已保存的证书，启动时调用，使 TLS 在续期任务完成前即可使用
+    pub fn load_saved(&self) {
+        for cert in &self.config.certificates {
+            let stem = cert_file_stem(&cert.domains);
+            let key_pem = match self.private_key(&stem, &format!("{}.key", stem), false) {
+                Ok(Some(key_pem)) => key_pem,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(domains = %cert.domains.join(","), error = %e, "Failed to load ACME private key");
+                    continue;
+                }
+            };
+            let Ok(cert_pem) = std::fs::read(self.storage_path(&format!("{}.crt", stem))) else {
+                continue;
+            };
+            match self
+                .certs
+                .install(&cert.domains, &cert_pem, key_pem.as_bytes())
+            {
+                Ok(()) => {
+                    tracing::info!(domains = %cert.domains.join(","), "Loaded ACME certificate")
+                }
+                Err(e) => {
+                    tracing::warn!(domains = %cert.domains.join(","), error = %e, "Failed to load saved ACME certificate")
+                }
+            }
+        }
+    }
+
+    /// 注册续期任务，首次立即检查
+    pub fn start_renewal_task(&self, tasks: &TaskRegistry) {
+        let manager = self.clone();
+        tasks.spawn_periodic("acme_renewal", RENEW_CHECK_INTERVAL, move || {
+            let manager = manager.clone();
+            async move { manager.renew_due().await }
+        });
+    }
+
+    async fn renew_due(&self) -> Result<()> {
+        let mut failed = Vec::new();
+        for cert in &self.config.certificates {
+            if !self.needs_renewal(cert) {
+                continue;
+            }
+            let domains = cert.domains.join(",");
+            tracing::info!(domains = %domains, "Requesting ACME certificate");
+            let error = match self.issue(cert).await {
+                Ok(()) => {
+                    tracing::info!(domains = %domains, "ACME certificate issued");
+                    None
+                }
+                Err(e) => {
+                    tracing::error!(domains = %domains, error = %e, "ACME certificate request failed");
+                    failed.push(format!("{}: {}", domains, e));
+                    Some(e.to_string())
+                }
+            };
+            self.events.publish(
+                "certificate_renewal",
+                serde_json::json!({ "domains": cert.domains, "success": error.is_none(), "error": error }),
+            );
+        }
+        if !failed.is_empty() {
+            bail!(failed.join("; "));
+        }
+        Ok(())
+    }
+
+    fn needs_renewal(&self, cert: &AcmeCertificateConfig) -> bool {
+        let path = self.storage_path(&format!("{}.crt", cert_file_stem(&cert.domains)));
+        let Ok(pem) = std::fs::read(&path) else {
+            return true;
+        };
+        let Ok((_, pem)) = x509_parser::pem::parse_x509_pem(&pem) else {
+            return true;
+        };
+        let Ok(x509) = pem.parse_x509() else {
+            return true;
+        };
+        let remaining = x509.validity().not_after.timestamp() - chrono::Utc::now().timestamp();
+        remaining < self.config.renew_before_days as i64 * 86400
+    }
+
+    /// 账户私钥（PKCS#8），以 base64 保存
+    fn account_key(&self) -> Result<Vec<u8>> {
+        if let Some(key) = self.private_key("account", "account.pk8", true)? {
+            return Ok(STANDARD.decode(key)?);
+        }
+        let key =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+                .map_err(|_| anyhow::anyhow!("Failed to generate ACME account key"))?;
+        self.secrets.set(
+            &format!("{}acme.account", SYSTEM_PREFIX),
+            &STANDARD.encode(key.as_ref()),
+        )?;
+        Ok(key.as_ref().to_vec())
+    }
+
+    /// 完成一次订单：验证全部域名、提交 CSR、下载证书并安装
+    async fn issue(&self, cert: &AcmeCertificateConfig) -> Result<()> {
+        std::fs::create_dir_all(&self.config.storage_dir)?;
+        let account = Account::login(&self.client, &self.config, &self.account_key()?).await?;
+        let thumbprint = account.thumbprint();
+
+        let identifiers: Vec<Value> = cert
+            .domains
+            .iter()
+            .map(|d| json!({ "type": "dns", "value": d }))
+            .collect();
+        let new_order = account.directory.new_order.clone();
+        let resp = account
+            .post(&new_order, Some(&json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = location(&resp)?;
+        let order: Order = resp.json().await?;
+
+        let provider = cert
+            .dns_provider
+            .as_ref()
+            .and_then(|name| self.providers.get(name));
+        let kind = if provider.is_some() {
+            "dns-01"
+        } else {
+            "http-01"
+        };
+
+        // 收集待验证的挑战，DNS 记录按名称分组
+        let mut pending = Vec::new();
+        let mut records: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut tokens = Vec::new();
+        for authz_url in &order.authorizations {
+            let authz: Authorization = account.post(authz_url, None).await?.json().await?;
+            if authz.status == "valid" {
+                continue;
+            }
+            let challenge = authz
+                .challenges
+                .iter()
+                .find(|c| c.kind == kind)
+                .with_context(|| format!("No {} challenge for {}", kind, authz.identifier.value))?;
+            let key_authorization = format!("{}.{}", challenge.token, thumbprint);
+            if provider.is_some() {
+                let value = URL_SAFE_NO_PAD.encode(digest::digest(
+                    &digest::SHA256,
+                    key_authorization.as_bytes(),
+                ));
+                records
+                    .entry(format!("_acme-challenge.{}", authz.identifier.value))
+                    .or_default()
+                    .push(value);
+            } else {
+                self.http01
+                    .0
+                    .insert(challenge.token.clone(), key_authorization);
+                tokens.push(challenge.token.clone());
+            }
+            pending.push((challenge.url.clone(), authz_url.clone()));
+        }
+
+        let result = async {
+            if let Some(provider) = provider {
+                for (name, values) in &records {
+                    provider.set_txt(name, values).await?;
+                }
+                if !records.is_empty() {
+                    tokio::time::sleep(Duration::from_secs(self.config.dns_propagation_secs)).await;
+                }
+            }
+            for (challenge_url, authz_url) in &pending {
+                account.post(challenge_url, Some(&json!({}))).await?;
+                wait_authorization(&account, authz_url, kind).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        // 无论验证是否成功都清理验证记录
+        if let Some(provider) = provider {
+            for (name, values) in &records {
+                if let Err(e) = provider.clear_txt(name, values).await {
+                    tracing::warn!(record = %name, error = %e, "Failed to remove ACME TXT record");
+                }
+            }
+        }
+        for token in &tokens {
+            self.http01.0.remove(token);
+        }
+        result?;
+
+        // 提交 CSR 并等待签发
+        let key_pair = rcgen::KeyPair::generate()?;
+        let csr =
+            rcgen::CertificateParams::new(cert.domains.clone())?.serialize_request(&key_pair)?;
+        account
+            .post(
+                &order.finalize,
+                Some(&json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })),
+            )
+            .await?;
+
+        let mut order = order;
+        for _ in 0..POLL_ATTEMPTS {
+            order = account.post(&order_url, None).await?.json().await?;
+            match order.status.as_str() {
+                "valid" => break,
+                "invalid" => bail!("ACME order invalid: {}", order.error.unwrap_or_default()),
+                _ => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        let certificate_url = order
+            .certificate
+            .filter(|_| order.status == "valid")
+            .context("Timed out waiting for ACME order")?;
+        let cert_pem = account.post(&certificate_url, None).await?.text().await?;
+        let key_pem = key_pair.serialize_pem();
+
+        self.certs
+            .install(&cert.domains, cert_pem.as_bytes(), key_pem.as_bytes())?;
+        let stem = cert_file_stem(&cert.domains);
+        self.secrets
+            .set(&format!("{}acme.{}", SYSTEM_PREFIX, stem), &key_pem)?;
+        std::fs::write(self.storage_path(&format!("{}.crt", stem)), cert_pem)?;
+        Ok(())
+    }
+}
+
+async fn wait_authorization(account: &Account<'_>, url: &str, kind: &str) -> Result<()> {
+    for _ in 0..POLL_ATTEMPTS {
+        let authz: Authorization = account.post(url, None).await?.json().await?;
+        match authz.status.as_str() {
+            "valid" => return Ok(()),
+            "pending" | "processing" => tokio::time::sleep(POLL_INTERVAL).await,
+            status => {
+                let error = authz
+                    .challenges
+                    .iter()
+                    .find(|c| c.kind == kind)
+                    .and_then(|c| c.error.clone())
+                    .unwrap_or_default();
+                bail!(
+                    "Authorization for {} {}: {}",
+                    authz.identifier.value,
+                    status,
+                    error
+                );
+            }
+        }
+    }
+    bail!("Timed out waiting for authorization {}", url)
+}
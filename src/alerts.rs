@@ -0,0 +1,989 @@
+use anyhow::Context;
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    Json,
+};
+use dashmap::DashMap;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::api::ApiResponse;
+use crate::config::{AlertChannelKind, AlertsConfig, SmtpConfig, SmtpSecurity};
+use crate::connections::ActiveRequests;
+use crate::db::{AlertRule, AlertRuleInput, Database};
+use crate::events::{AdminEvent, AdminEvents};
+use crate::rolling::RollingStats;
+use crate::tasks::TaskRegistry;
+use crate::tls::CertStore;
+use crate::upstreams::UpstreamHealth;
+use crate::AdminState;
+
+/// 单个通道的发送超时
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 保留的告警开始与恢复记录条数
+const MAX_HISTORY: usize = 100;
+
+/// 告警规则名称的告警类型
+const RULE_ALERT: &str = "alert_rule";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+/// 告警规则可用的指标，滚动窗口类指标按规则的 window_secs 统计
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertMetric {
+    /// 5xx 与代理错误占比，0~1
+    ErrorRate,
+    RequestsPerSec,
+    Requests,
+    P50Ms,
+    P95Ms,
+    P99Ms,
+    /// 进行中的代理请求数
+    ActiveRequests,
+    /// 被标记为不健康的上游数
+    UnhealthyUpstreams,
+}
+
+impl AlertMetric {
+    fn windowed(self) -> bool {
+        !matches!(self, Self::ActiveRequests | Self::UnhealthyUpstreams)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+    #[serde(rename = "==")]
+    Eq,
+    #[serde(rename = "!=")]
+    Ne,
+}
+
+impl Comparison {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Gt => value > threshold,
+            Self::Ge => value >= threshold,
+            Self::Lt => value < threshold,
+            Self::Le => value <= threshold,
+            Self::Eq => value == threshold,
+            Self::Ne => value != threshold,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+        }
+    }
+}
+
+/// 告警规则求值所需的指标来源
+pub struct MetricSources {
+    pub rolling: RollingStats,
+    pub active: ActiveRequests,
+    pub upstreams: UpstreamHealth,
+}
+
+impl MetricSources {
+    fn value(&self, metric: AlertMetric, window_secs: u64) -> f64 {
+        if !metric.windowed() {
+            return match metric {
+                AlertMetric::ActiveRequests => self.active.count() as f64,
+                _ => self.upstreams.unhealthy_count() as f64,
+            };
+        }
+        let window = self.rolling.window(window_secs);
+        match metric {
+            AlertMetric::ErrorRate => window.error_rate,
+            AlertMetric::RequestsPerSec => window.requests_per_sec,
+            AlertMetric::Requests => window.requests as f64,
+            AlertMetric::P50Ms => window.p50_ms as f64,
+            AlertMetric::P95Ms => window.p95_ms as f64,
+            _ => window.p99_ms as f64,
+        }
+    }
+}
+
+/// 告警规则最近一次求值的结果
+#[derive(Default)]
+struct RuleState {
+    value: Option<f64>,
+    /// 条件开始成立的时间，尚未达到 duration_secs 时规则处于 pending
+    pending_since: Option<(Instant, String)>,
+    evaluated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleStatus {
+    Disabled,
+    Inactive,
+    Pending,
+    Firing,
+}
+
+/// 告警规则及其当前状态
+#[derive(Debug, Serialize)]
+pub struct AlertRuleView {
+    #[serde(flatten)]
+    pub rule: AlertRule,
+    pub state: RuleStatus,
+    pub value: Option<f64>,
+    pub pending_since: Option<String>,
+    pub evaluated_at: Option<String>,
+}
+
+/// 发送给各通道的告警
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    /// 告警类型：upstream_down, error_rate, cert_expiry, alert_rule
+    pub alert: &'static str,
+    /// 同一类型下区分告警对象，如上游地址
+    pub key: String,
+    pub status: AlertStatus,
+    pub severity: Severity,
+    pub summary: String,
+    pub details: Value,
+    pub instance: String,
+    pub started_at: String,
+    pub time: String,
+}
+
+impl Alert {
+    fn title(&self) -> String {
+        let status = match self.status {
+            AlertStatus::Firing => "FIRING",
+            AlertStatus::Resolved => "RESOLVED",
+        };
+        format!("[{}] {} ({})", status, self.summary, self.instance)
+    }
+}
+
+struct Firing {
+    alert: Alert,
+    last_sent: Instant,
+}
+
+enum ChannelKind {
+    Webhook {
+        url: String,
+        headers: HeaderMap,
+    },
+    Slack {
+        webhook_url: String,
+        channel: Option<String>,
+    },
+    Email {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+        to: Vec<Mailbox>,
+    },
+}
+
+struct Channel {
+    kind: ChannelKind,
+    events: Vec<String>,
+}
+
+impl Channel {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ChannelKind::Webhook { .. } => "webhook",
+            ChannelKind::Slack { .. } => "slack",
+            ChannelKind::Email { .. } => "email",
+        }
+    }
+
+    fn subscribed(&self, alert: &str) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == alert)
+    }
+
+    async fn send(&self, client: &Client, alert: &Alert) -> anyhow::Result<()> {
+        match self.kind {
+            ChannelKind::Webhook {
+                ref url,
+                ref headers,
+            } => {
+                client
+                    .post(url)
+                    .timeout(SEND_TIMEOUT)
+                    .headers(headers.clone())
+                    .json(alert)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            ChannelKind::Slack {
+                ref webhook_url,
+                ref channel,
+            } => {
+                let icon = match alert.status {
+                    AlertStatus::Firing => ":rotating_light:",
+                    AlertStatus::Resolved => ":white_check_mark:",
+                };
+                let mut payload = json!({ "text": format!("{} {}", icon, alert.title()) });
+                if let Some(channel) = channel {
+                    payload["channel"] = json!(channel);
+                }
+                client
+                    .post(webhook_url)
+                    .timeout(SEND_TIMEOUT)
+                    .json(&payload)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            ChannelKind::Email {
+                ref transport,
+                ref from,
+                ref to,
+            } => {
+                let mut builder = Message::builder()
+                    .from(from.clone())
+                    .subject(alert.title())
+                    .header(ContentType::TEXT_PLAIN);
+                for to in to {
+                    builder = builder.to(to.clone());
+                }
+                let body = format!(
+                    "{}\n\nalert: {}\nseverity: {:?}\nstarted at: {}\n\n{}",
+                    alert.summary,
+                    alert.alert,
+                    alert.severity,
+                    alert.started_at,
+                    serde_json::to_string_pretty(&alert.details).unwrap_or_default()
+                );
+                transport.send(builder.body(body)?).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn smtp_transport(config: &SmtpConfig) -> anyhow::Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let mut builder = match config.security {
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        SmtpSecurity::Starttls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+        }
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    };
+    if let Some(port) = config.port {
+        builder = builder.port(port);
+    }
+    if let Some(ref username) = config.username {
+        builder = builder.credentials(Credentials::new(
+            username.clone(),
+            config.password.clone().unwrap_or_default(),
+        ));
+    }
+    Ok(builder.timeout(Some(SEND_TIMEOUT)).build())
+}
+
+fn build_channel(kind: &AlertChannelKind) -> anyhow::Result<ChannelKind> {
+    Ok(match kind {
+        AlertChannelKind::Webhook { url, headers } => {
+            let mut map = HeaderMap::new();
+            for (name, value) in headers {
+                map.insert(
+                    HeaderName::from_bytes(name.as_bytes())?,
+                    HeaderValue::from_str(value)?,
+                );
+            }
+            ChannelKind::Webhook {
+                url: url.clone(),
+                headers: map,
+            }
+        }
+        AlertChannelKind::Slack {
+            webhook_url,
+            channel,
+        } => ChannelKind::Slack {
+            webhook_url: webhook_url.clone(),
+            channel: channel.clone(),
+        },
+        AlertChannelKind::Email(smtp) => {
+            if smtp.to.is_empty() {
+                anyhow::bail!("email channel has no recipients");
+            }
+            ChannelKind::Email {
+                transport: smtp_transport(smtp)?,
+                from: smtp
+                    .from
+                    .parse()
+                    .with_context(|| format!("invalid sender address '{}'", smtp.from))?,
+                to: smtp
+                    .to
+                    .iter()
+                    .map(|to| {
+                        to.parse()
+                            .with_context(|| format!("invalid recipient address '{}'", to))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            }
+        }
+    })
+}
+
+/// 本机主机名，读取失败时使用进程名
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "proxy-server".to_string())
+}
+
+struct Inner {
+    channels: Vec<Channel>,
+    client: Client,
+    instance: String,
+    repeat_interval: Option<Duration>,
+    firing: DashMap<String, Firing>,
+    rules: ArcSwap<Vec<AlertRule>>,
+    rule_states: DashMap<i64, RuleState>,
+    history: Mutex<VecDeque<Alert>>,
+}
+
+/// 告警状态与通知通道：告警开始与恢复时各通知一次，持续期间按 repeat_interval_secs 重复通知
+#[derive(Clone)]
+pub struct Alerts {
+    inner: Arc<Inner>,
+}
+
+impl Alerts {
+    pub fn new(client: Client, config: &AlertsConfig) -> anyhow::Result<Self> {
+        let channels = config
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(i, channel)| {
+                Ok(Channel {
+                    kind: build_channel(&channel.kind)
+                        .with_context(|| format!("Invalid alert channel #{}", i + 1))?,
+                    events: channel.events.clone(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                channels,
+                client,
+                instance: config.instance.clone().unwrap_or_else(hostname),
+                repeat_interval: (config.repeat_interval_secs > 0)
+                    .then(|| Duration::from_secs(config.repeat_interval_secs)),
+                firing: DashMap::new(),
+                rules: ArcSwap::from_pointee(Vec::new()),
+                rule_states: DashMap::new(),
+                history: Mutex::new(VecDeque::new()),
+            }),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.inner.channels.is_empty()
+    }
+
+    /// 从数据库重新加载告警规则，规则增删改后调用
+    pub fn load_rules(&self, db: &Database) -> anyhow::Result<()> {
+        self.inner.rules.store(Arc::new(db.list_alert_rules()?));
+        Ok(())
+    }
+
+    /// 告警条件成立：新告警立即通知，已在告警中时只更新内容
+    pub fn fire(
+        &self,
+        alert: &'static str,
+        key: &str,
+        severity: Severity,
+        summary: String,
+        details: Value,
+    ) {
+        let now = now();
+        let id = format!("{}:{}", alert, key);
+        let notify = match self.inner.firing.get_mut(&id) {
+            Some(mut firing) => {
+                firing.alert.severity = severity;
+                firing.alert.summary = summary;
+                firing.alert.details = details;
+                firing.alert.time = now;
+                None
+            }
+            None => {
+                let alert = Alert {
+                    alert,
+                    key: key.to_string(),
+                    status: AlertStatus::Firing,
+                    severity,
+                    summary,
+                    details,
+                    instance: self.inner.instance.clone(),
+                    started_at: now.clone(),
+                    time: now,
+                };
+                tracing::warn!(alert = alert.alert, key = %alert.key, "Alert firing: {}", alert.summary);
+                self.record(&alert);
+                self.inner.firing.insert(
+                    id,
+                    Firing {
+                        alert: alert.clone(),
+                        last_sent: Instant::now(),
+                    },
+                );
+                Some(alert)
+            }
+        };
+        if let Some(alert) = notify {
+            self.dispatch(alert);
+        }
+    }
+
+    /// 告警条件不再成立，之前在告警中时发送恢复通知
+    pub fn resolve(&self, alert: &'static str, key: &str, summary: String) {
+        let Some((_, firing)) = self.inner.firing.remove(&format!("{}:{}", alert, key)) else {
+            return;
+        };
+        let mut alert = firing.alert;
+        tracing::info!(alert = alert.alert, key = %alert.key, "Alert resolved: {}", summary);
+        alert.status = AlertStatus::Resolved;
+        alert.summary = summary;
+        alert.time = now();
+        self.record(&alert);
+        self.dispatch(alert);
+    }
+
+    fn record(&self, alert: &Alert) {
+        let mut history = self.inner.history.lock();
+        if history.len() >= MAX_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(alert.clone());
+    }
+
+    /// 对全部告警规则求值：条件持续 duration_secs 后告警，不再成立时恢复
+    fn evaluate_rules(&self, metrics: &MetricSources) {
+        let rules = self.inner.rules.load();
+        for rule in rules.iter() {
+            if !rule.enabled {
+                self.inner.rule_states.remove(&rule.id);
+                self.resolve(
+                    RULE_ALERT,
+                    &rule.name,
+                    format!("Alert rule {} disabled", rule.name),
+                );
+                continue;
+            }
+            let window_secs = rule.window_secs.clamp(1, 900);
+            let value = metrics.value(rule.metric, window_secs);
+            let mut state = self.inner.rule_states.entry(rule.id).or_default();
+            state.value = Some(value);
+            state.evaluated_at = Some(now());
+            if !rule.comparison.holds(value, rule.threshold) {
+                state.pending_since = None;
+                drop(state);
+                self.resolve(
+                    RULE_ALERT,
+                    &rule.name,
+                    format!("{} back to {}", rule.name, format_value(value)),
+                );
+                continue;
+            }
+            let since = state
+                .pending_since
+                .get_or_insert_with(|| (Instant::now(), now()))
+                .0;
+            drop(state);
+            if since.elapsed() < Duration::from_secs(rule.duration_secs) {
+                continue;
+            }
+            let window = if rule.metric.windowed() {
+                format!(" over the last {}s", window_secs)
+            } else {
+                String::new()
+            };
+            self.fire(
+                RULE_ALERT,
+                &rule.name,
+                rule.severity,
+                format!(
+                    "{}: {} is {}{} ({} {})",
+                    rule.name,
+                    metric_name(rule.metric),
+                    format_value(value),
+                    window,
+                    rule.comparison.symbol(),
+                    format_value(rule.threshold)
+                ),
+                json!({
+                    "rule_id": rule.id,
+                    "metric": rule.metric,
+                    "comparison": rule.comparison,
+                    "threshold": rule.threshold,
+                    "value": value,
+                    "window_secs": window_secs,
+                    "duration_secs": rule.duration_secs,
+                }),
+            );
+        }
+
+        // 已删除或改名的规则不再求值，恢复其遗留的告警
+        let names: HashSet<&str> = rules.iter().map(|rule| rule.name.as_str()).collect();
+        let stale: Vec<String> = self
+            .inner
+            .firing
+            .iter()
+            .filter(|firing| {
+                firing.alert.alert == RULE_ALERT && !names.contains(firing.alert.key.as_str())
+            })
+            .map(|firing| firing.alert.key.clone())
+            .collect();
+        for key in stale {
+            self.resolve(RULE_ALERT, &key, format!("Alert rule {} removed", key));
+        }
+        self.inner
+            .rule_states
+            .retain(|id, _| rules.iter().any(|rule| rule.id == *id));
+    }
+
+    fn rule_views(&self) -> Vec<AlertRuleView> {
+        self.inner
+            .rules
+            .load()
+            .iter()
+            .map(|rule| {
+                let state = self.inner.rule_states.get(&rule.id);
+                let firing = self
+                    .inner
+                    .firing
+                    .contains_key(&format!("{}:{}", RULE_ALERT, rule.name));
+                let pending_since = state
+                    .as_ref()
+                    .and_then(|state| state.pending_since.as_ref())
+                    .map(|(_, at)| at.clone());
+                AlertRuleView {
+                    state: if !rule.enabled {
+                        RuleStatus::Disabled
+                    } else if firing {
+                        RuleStatus::Firing
+                    } else if pending_since.is_some() {
+                        RuleStatus::Pending
+                    } else {
+                        RuleStatus::Inactive
+                    },
+                    value: state.as_ref().and_then(|state| state.value),
+                    pending_since,
+                    evaluated_at: state.as_ref().and_then(|state| state.evaluated_at.clone()),
+                    rule: rule.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// 重复通知持续中的告警
+    fn repeat_due(&self) {
+        let Some(interval) = self.inner.repeat_interval else {
+            return;
+        };
+        let due: Vec<Alert> = self
+            .inner
+            .firing
+            .iter_mut()
+            .filter(|firing| firing.last_sent.elapsed() >= interval)
+            .map(|mut firing| {
+                firing.last_sent = Instant::now();
+                firing.alert.clone()
+            })
+            .collect();
+        for alert in due {
+            self.dispatch(alert);
+        }
+    }
+
+    /// 后台发送到订阅了该告警类型的通道，失败只记录日志
+    fn dispatch(&self, alert: Alert) {
+        if !self.enabled() {
+            return;
+        }
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let sends = inner
+                .channels
+                .iter()
+                .filter(|channel| channel.subscribed(alert.alert))
+                .map(|channel| async {
+                    if let Err(e) = channel.send(&inner.client, &alert).await {
+                        tracing::warn!(channel = channel.name(), alert = alert.alert, error = %e, "Failed to send alert");
+                    }
+                });
+            futures::future::join_all(sends).await;
+        });
+    }
+
+    /// 上游健康与关键上游探测事件转换为 upstream_down 告警
+    fn on_event(&self, event: &AdminEvent) {
+        let upstream = event.data["upstream"].as_str().unwrap_or_default();
+        let error = event.data["error"].as_str().unwrap_or_default();
+        let (key, down, summary) = match event.kind {
+            "upstream_health" => (
+                format!("traffic {}", upstream),
+                event.data["health"] == "unhealthy",
+                format!("Upstream {} is failing requests", upstream),
+            ),
+            "upstream_probe" => (
+                format!("probe {}", upstream),
+                event.data["reachable"] == false,
+                format!("Required upstream {} is unreachable", upstream),
+            ),
+            _ => return,
+        };
+        if down {
+            self.fire(
+                "upstream_down",
+                &key,
+                Severity::Critical,
+                summary,
+                json!({ "upstream": upstream, "error": error }),
+            );
+        } else {
+            self.resolve(
+                "upstream_down",
+                &key,
+                format!("Upstream {} recovered", upstream),
+            );
+        }
+    }
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn metric_name(metric: AlertMetric) -> String {
+    serde_json::to_value(metric)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+/// 启动告警：定期对告警规则求值；配置了通道时还订阅上游健康事件，并定期检查错误率、证书有效期与重复通知
+pub fn start(
+    alerts: &Alerts,
+    tasks: &TaskRegistry,
+    config: &AlertsConfig,
+    events: &AdminEvents,
+    metrics: MetricSources,
+    certs: Vec<Arc<CertStore>>,
+) {
+    let rolling = metrics.rolling.clone();
+    let rule_alerts = alerts.clone();
+    tasks.spawn_periodic(
+        "alert_rules",
+        Duration::from_secs(config.evaluation_interval_secs.max(1)),
+        move || {
+            rule_alerts.evaluate_rules(&metrics);
+            async { Ok(()) }
+        },
+    );
+
+    if !alerts.enabled() {
+        return;
+    }
+    tracing::info!(
+        channels = alerts.inner.channels.len(),
+        "Alert notifications enabled"
+    );
+
+    if config.upstream_down {
+        let mut rx = events.subscribe();
+        let alerts = alerts.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => alerts.on_event(&event),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Alerts lagged behind upstream health events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    let alerts = alerts.clone();
+    let error_rate = config.error_rate.clone();
+    let cert_expiry_days = config.cert_expiry_days;
+    tasks.spawn_periodic(
+        "alerts",
+        Duration::from_secs(config.check_interval_secs.max(10)),
+        move || {
+            if let Some(ref config) = error_rate {
+                let window = rolling.window(config.window_secs.min(900));
+                if window.requests >= config.min_requests && window.error_rate >= config.threshold {
+                    alerts.fire(
+                        "error_rate",
+                        "global",
+                        Severity::Critical,
+                        format!(
+                            "Error rate {:.1}% over the last {}s exceeds {:.1}%",
+                            window.error_rate * 100.0,
+                            window.window_secs,
+                            config.threshold * 100.0
+                        ),
+                        json!({
+                            "error_rate": window.error_rate,
+                            "threshold": config.threshold,
+                            "requests": window.requests,
+                            "window_secs": window.window_secs,
+                        }),
+                    );
+                } else {
+                    alerts.resolve(
+                        "error_rate",
+                        "global",
+                        format!("Error rate back to {:.1}%", window.error_rate * 100.0),
+                    );
+                }
+            }
+
+            if cert_expiry_days > 0 {
+                let now = chrono::Utc::now().timestamp();
+                for cert in certs.iter().flat_map(|store| store.expirations()) {
+                    let domains = cert.domains.join(",");
+                    let days = (cert.not_after - now).div_euclid(86400);
+                    if days < cert_expiry_days as i64 {
+                        let (severity, summary) = if cert.not_after <= now {
+                            (
+                                Severity::Critical,
+                                format!("Certificate for {} has expired", domains),
+                            )
+                        } else {
+                            (
+                                Severity::Warning,
+                                format!("Certificate for {} expires in {} days", domains, days),
+                            )
+                        };
+                        let expires_at = chrono::DateTime::from_timestamp(cert.not_after, 0)
+                            .map(|t| t.to_rfc3339());
+                        alerts.fire(
+                            "cert_expiry",
+                            &domains,
+                            severity,
+                            summary,
+                            json!({ "domains": cert.domains, "expires_at": expires_at }),
+                        );
+                    } else {
+                        alerts.resolve(
+                            "cert_expiry",
+                            &domains,
+                            format!("Certificate for {} was renewed", domains),
+                        );
+                    }
+                }
+            }
+
+            alerts.repeat_due();
+            async { Ok(()) }
+        },
+    );
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChannelTest {
+    pub channel: &'static str,
+    pub error: Option<String>,
+}
+
+/// 向全部通道发送一条测试告警并返回各通道的结果
+pub async fn test_handler(
+    State(state): State<AdminState>,
+) -> Result<Json<ApiResponse<Vec<ChannelTest>>>, StatusCode> {
+    let inner = &state.alerts.inner;
+    if inner.channels.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let now = now();
+    let alert = Alert {
+        alert: "test",
+        key: "test".to_string(),
+        status: AlertStatus::Firing,
+        severity: Severity::Warning,
+        summary: "Test alert from proxy-server".to_string(),
+        details: json!({}),
+        instance: inner.instance.clone(),
+        started_at: now.clone(),
+        time: now,
+    };
+    let results = futures::future::join_all(inner.channels.iter().map(|channel| async {
+        ChannelTest {
+            channel: channel.name(),
+            error: channel
+                .send(&inner.client, &alert)
+                .await
+                .err()
+                .map(|e| format!("{:#}", e)),
+        }
+    }))
+    .await;
+    Ok(Json(ApiResponse::ok(results)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlertsOverview {
+    /// 当前处于告警中的告警，包括内置告警与告警规则
+    pub firing: Vec<Alert>,
+    pub rules: Vec<AlertRuleView>,
+    /// 最近的告警开始与恢复记录，新的在前
+    pub recent: Vec<Alert>,
+}
+
+/// 当前告警、告警规则状态与最近的告警记录
+pub async fn list_handler(State(state): State<AdminState>) -> Json<ApiResponse<AlertsOverview>> {
+    let inner = &state.alerts.inner;
+    let mut firing: Vec<Alert> = inner
+        .firing
+        .iter()
+        .map(|firing| firing.alert.clone())
+        .collect();
+    firing.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Json(ApiResponse::ok(AlertsOverview {
+        firing,
+        rules: state.alerts.rule_views(),
+        recent: inner.history.lock().iter().rev().cloned().collect(),
+    }))
+}
+
+/// 告警规则列表及其当前状态
+pub async fn rules_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<AlertRuleView>>> {
+    Json(ApiResponse::ok(state.alerts.rule_views()))
+}
+
+fn validate_rule(
+    state: &AdminState,
+    id: Option<i64>,
+    rule: &AlertRuleInput,
+) -> Result<(), StatusCode> {
+    if rule.name.trim().is_empty()
+        || !rule.threshold.is_finite()
+        || !(1..=900).contains(&rule.window_secs)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if state
+        .alerts
+        .inner
+        .rules
+        .load()
+        .iter()
+        .any(|existing| existing.name == rule.name && Some(existing.id) != id)
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(())
+}
+
+fn reload_rules(state: &AdminState) -> Result<(), StatusCode> {
+    state.alerts.load_rules(&state.db).map_err(|e| {
+        tracing::error!("Failed to load alert rules: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// 创建告警规则，名称重复时返回 409
+pub async fn create_rule_handler(
+    State(state): State<AdminState>,
+    Json(rule): Json<AlertRuleInput>,
+) -> Result<Json<ApiResponse<i64>>, StatusCode> {
+    validate_rule(&state, None, &rule)?;
+    let id = state.db.create_alert_rule(&rule).map_err(|e| {
+        tracing::error!("Failed to create alert rule: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    reload_rules(&state)?;
+    tracing::info!(id, name = %rule.name, "Alert rule created");
+    Ok(Json(ApiResponse::ok(id)))
+}
+
+pub async fn update_rule_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+    Json(rule): Json<AlertRuleInput>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    validate_rule(&state, Some(id), &rule)?;
+    let updated = state.db.update_alert_rule(id, &rule).map_err(|e| {
+        tracing::error!("Failed to update alert rule: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // 条件变化后重新计算持续时间
+    state.alerts.inner.rule_states.remove(&id);
+    reload_rules(&state)?;
+    tracing::info!(id, name = %rule.name, "Alert rule updated");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+pub async fn delete_rule_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let deleted = state.db.delete_alert_rule(id).map_err(|e| {
+        tracing::error!("Failed to delete alert rule: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    reload_rules(&state)?;
+    tracing::info!(id, "Alert rule deleted");
+    Ok(Json(ApiResponse::ok(())))
+}
@@ -1,6 +1,21 @@
-use axum::{extract::Path, extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::db::{ProxyRule, RuleFixture, RuleImport, RuleInput, RuleOptions, RulePage, RuleQuery};
+use crate::listeners;
+use crate::pipeline::Pipeline;
+use crate::reloads::{self, ReloadSummary, RulesRejected};
+use crate::rolling::WindowStats;
+use crate::rules_file;
+use crate::signed_urls;
+use crate::simulate::{FixtureRequest, FixtureResponse};
+use crate::stats::RuleStatsSnapshot;
+use crate::tasks::TaskStatus;
 use crate::AdminState;
 
 #[derive(Debug, Deserialize)]
@@ -10,6 +25,8 @@ pub struct CreateRuleRequest {
     pub target: String,
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
+    #[serde(default)]
+    pub options: Option<RuleOptions>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,6 +36,8 @@ pub struct UpdateRuleRequest {
     pub target: String,
     pub timeout_secs: u64,
     pub enabled: bool,
+    #[serde(default)]
+    pub options: Option<RuleOptions>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,7 +50,7 @@ pub struct UpdateConfigRequest {
     pub value: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
@@ -55,10 +74,11 @@ impl<T> ApiResponse<T> {
 
 pub async fn list_rules(
     State(state): State<AdminState>,
-) -> Result<Json<ApiResponse<Vec<crate::db::ProxyRule>>>, StatusCode> {
+    Query(query): Query<RuleQuery>,
+) -> Result<Json<ApiResponse<RulePage>>, StatusCode> {
     state
         .db
-        .get_all_rules()
+        .query_rules(&query)
         .map(|rules| Json(ApiResponse::ok(rules)))
         .map_err(|e| {
             tracing::error!("Failed to list rules: {}", e);
@@ -66,22 +86,141 @@ pub async fn list_rules(
         })
 }
 
+/// 规则引用的密钥必须已存在
+fn check_secret_refs(state: &AdminState, options: Option<&RuleOptions>) -> Result<(), StatusCode> {
+    match options
+        .into_iter()
+        .flat_map(RuleOptions::secret_refs)
+        .find(|name| !state.secrets.contains(name))
+    {
+        Some(name) => {
+            tracing::warn!(secret = %name, "Rule references an unknown secret");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        None => Ok(()),
+    }
+}
+
+/// 规则引用的插件必须已在配置文件中加载
+fn check_plugins(state: &AdminState, options: Option<&RuleOptions>) -> Result<(), StatusCode> {
+    match options
+        .into_iter()
+        .flat_map(|options| &options.plugins)
+        .find(|name| !state.plugins.contains(name))
+    {
+        Some(name) => {
+            tracing::warn!(plugin = %name, "Rule references an unknown plugin");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        None => Ok(()),
+    }
+}
+
+/// 规则的处理管线需能生成：实验与故障注入设置有效，脚本能编译
+fn check_pipeline(options: Option<&RuleOptions>) -> Result<(), StatusCode> {
+    let Some(options) = options else {
+        return Ok(());
+    };
+    Pipeline::build(options).map(|_| ()).map_err(|e| {
+        tracing::warn!("Invalid rule options: {}", e);
+        StatusCode::BAD_REQUEST
+    })
+}
+
+/// 出站代理地址需有效，且不能与只支持直连的选项同时使用
+fn check_upstream_proxy(options: Option<&RuleOptions>) -> Result<(), StatusCode> {
+    let Some(options) = options else {
+        return Ok(());
+    };
+    validate_upstream_proxy(options).map_err(|e| {
+        tracing::warn!("Invalid upstream proxy: {}", e);
+        StatusCode::BAD_REQUEST
+    })
+}
+
+pub(crate) fn validate_upstream_proxy(options: &RuleOptions) -> Result<(), String> {
+    let Some(ref proxy) = options.upstream_proxy else {
+        return Ok(());
+    };
+    if options.preserve_header_case || options.upstream_proxy_protocol {
+        return Err("upstream_proxy cannot be combined with preserve_header_case or upstream_proxy_protocol".to_string());
+    }
+    proxy.validate()
+}
+
+/// 修改规则后重载：新规则集有规则编译失败或冲突时仍使用原规则，返回 422 与各规则的错误，
+/// 修改已保存，修正后再次保存即可生效
+pub(crate) fn reloaded<T: Serialize>(
+    state: &AdminState,
+    trigger: &'static str,
+    data: T,
+) -> Response {
+    match state.reload_rules(trigger) {
+        Ok(()) => Json(ApiResponse::ok(data)).into_response(),
+        Err(e) => rules_failed("reload rules", e),
+    }
+}
+
+/// 规则写入或重载失败：规则集校验未通过时返回 422 与各规则的错误，其他错误返回 500
+pub(crate) fn rules_failed(action: &str, e: anyhow::Error) -> Response {
+    match e.downcast::<RulesRejected>() {
+        Ok(rejected) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ApiResponse {
+                success: false,
+                message: Some(rejected.to_string()),
+                data: Some(rejected.failures),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to {}: {:#}", action, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::<()> {
+                    success: false,
+                    message: Some(format!("{:#}", e)),
+                    data: None,
+                }),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 规则保存后投递配置变更 Webhook，附带保存后的完整规则
+fn rule_changed(state: &AdminState, event: &'static str, id: i64) {
+    match state.db.get_rule(id) {
+        Ok(Some(rule)) => state.webhooks.notify(event, serde_json::json!(rule)),
+        Ok(None) => {}
+        Err(e) => tracing::error!("Failed to load rule for webhook: {}", e),
+    }
+}
+
 pub async fn create_rule(
     State(state): State<AdminState>,
     Json(req): Json<CreateRuleRequest>,
-) -> Result<Json<ApiResponse<i64>>, StatusCode> {
-    match state
-        .db
-        .create_rule(&req.name, &req.source, &req.target, req.timeout_secs)
-    {
+) -> Result<Response, StatusCode> {
+    rules_file::check_writable(&state)?;
+    check_secret_refs(&state, req.options.as_ref())?;
+    check_plugins(&state, req.options.as_ref())?;
+    check_pipeline(req.options.as_ref())?;
+    check_upstream_proxy(req.options.as_ref())?;
+    match state.db.create_rule(
+        &RuleInput {
+            name: &req.name,
+            source: &req.source,
+            target: &req.target,
+            timeout_secs: req.timeout_secs,
+            options: req.options.as_ref(),
+        },
+        reloads::check,
+    ) {
         Ok(id) => {
-            let _ = state.reload_rules();
-            Ok(Json(ApiResponse::ok(id)))
-        }
-        Err(e) => {
-            tracing::error!("Failed to create rule: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            rule_changed(&state, "rule.created", id);
+            Ok(reloaded(&state, "create_rule", id))
         }
+        Err(e) => Ok(rules_failed("create rule", e)),
     }
 }
 
@@ -89,34 +228,47 @@ pub async fn update_rule(
     State(state): State<AdminState>,
     Path(id): Path<i64>,
     Json(req): Json<UpdateRuleRequest>,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
+) -> Result<Response, StatusCode> {
+    rules_file::check_writable(&state)?;
+    check_secret_refs(&state, req.options.as_ref())?;
+    check_plugins(&state, req.options.as_ref())?;
+    check_pipeline(req.options.as_ref())?;
+    check_upstream_proxy(req.options.as_ref())?;
     match state.db.update_rule(
         id,
-        &req.name,
-        &req.source,
-        &req.target,
-        req.timeout_secs,
+        &RuleInput {
+            name: &req.name,
+            source: &req.source,
+            target: &req.target,
+            timeout_secs: req.timeout_secs,
+            options: req.options.as_ref(),
+        },
         req.enabled,
+        reloads::check,
     ) {
         Ok(_) => {
-            let _ = state.reload_rules();
-            Ok(Json(ApiResponse::ok(())))
-        }
-        Err(e) => {
-            tracing::error!("Failed to update rule: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            rule_changed(&state, "rule.updated", id);
+            Ok(reloaded(&state, "update_rule", ()))
         }
+        Err(e) => Ok(rules_failed("update rule", e)),
     }
 }
 
 pub async fn delete_rule(
     State(state): State<AdminState>,
     Path(id): Path<i64>,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
+) -> Result<Response, StatusCode> {
+    rules_file::check_writable(&state)?;
+    let rule = state.db.get_rule(id).ok().flatten();
     match state.db.delete_rule(id) {
         Ok(_) => {
-            let _ = state.reload_rules();
-            Ok(Json(ApiResponse::ok(())))
+            state.stats.remove(id);
+            let data = match rule {
+                Some(rule) => serde_json::json!(rule),
+                None => serde_json::json!({ "id": id }),
+            };
+            state.webhooks.notify("rule.deleted", data);
+            Ok(reloaded(&state, "delete_rule", ()))
         }
         Err(e) => {
             tracing::error!("Failed to delete rule: {}", e);
@@ -129,19 +281,136 @@ pub async fn toggle_rule(
     State(state): State<AdminState>,
     Path(id): Path<i64>,
     Json(req): Json<ToggleRuleRequest>,
-) -> Result<Json<ApiResponse<()>>, StatusCode> {
-    match state.db.toggle_rule(id, req.enabled) {
+) -> Result<Response, StatusCode> {
+    rules_file::check_writable(&state)?;
+    match state.db.toggle_rule(id, req.enabled, reloads::check) {
         Ok(_) => {
-            let _ = state.reload_rules();
-            Ok(Json(ApiResponse::ok(())))
+            rule_changed(&state, "rule.updated", id);
+            Ok(reloaded(&state, "toggle_rule", ()))
         }
-        Err(e) => {
-            tracing::error!("Failed to toggle rule: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        Err(e) => Ok(rules_failed("toggle rule", e)),
+    }
+}
+
+/// 导出全部规则，可直接用于导入
+pub async fn export_rules(
+    State(state): State<AdminState>,
+) -> Result<Json<ApiResponse<Vec<ProxyRule>>>, StatusCode> {
+    state
+        .db
+        .get_all_rules()
+        .map(|rules| Json(ApiResponse::ok(rules)))
+        .map_err(|e| {
+            tracing::error!("Failed to export rules: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 以请求中的规则列表整体替换现有规则，按名称对应已有规则
+pub async fn import_rules(
+    State(state): State<AdminState>,
+    Json(rules): Json<Vec<RuleImport>>,
+) -> Result<Response, StatusCode> {
+    rules_file::check_writable(&state)?;
+    for rule in &rules {
+        check_secret_refs(&state, Some(&rule.options))?;
+        check_plugins(&state, Some(&rule.options))?;
+        check_pipeline(Some(&rule.options))?;
+        check_upstream_proxy(Some(&rule.options))?;
+    }
+    let before = state.db.get_all_rules().map_err(|e| {
+        tracing::error!("Failed to import rules: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    match state.db.import_rules(&rules, reloads::check) {
+        Ok(summary) => {
+            let remaining = state.db.get_all_rules().unwrap_or_default();
+            for rule in before {
+                if !remaining.iter().any(|r| r.id == rule.id) {
+                    state.stats.remove(rule.id);
+                }
+            }
+            tracing::info!(
+                created = summary.created,
+                updated = summary.updated,
+                deleted = summary.deleted,
+                "Rules imported"
+            );
+            state.webhooks.notify(
+                "rules.imported",
+                serde_json::json!({ "summary": summary, "rules": remaining }),
+            );
+            Ok(reloaded(&state, "import_rules", summary))
         }
+        Err(e) => Ok(rules_failed("import rules", e)),
     }
 }
 
+pub async fn get_rule_stats(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<RuleStatsSnapshot>>, StatusCode> {
+    state
+        .stats
+        .snapshot(&state.db, id)
+        .map(|stats| Json(ApiResponse::ok(stats)))
+        .map_err(|e| {
+            tracing::error!("Failed to get rule stats: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFixtureRequest {
+    pub name: String,
+    pub request: FixtureRequest,
+    #[serde(default)]
+    pub response: FixtureResponse,
+}
+
+pub async fn list_fixtures(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<Vec<RuleFixture>>>, StatusCode> {
+    state
+        .db
+        .list_fixtures(id)
+        .map(|fixtures| Json(ApiResponse::ok(fixtures)))
+        .map_err(|e| {
+            tracing::error!("Failed to list fixtures: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn create_fixture(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+    Json(req): Json<CreateFixtureRequest>,
+) -> Result<Json<ApiResponse<i64>>, StatusCode> {
+    state
+        .db
+        .create_fixture(id, &req.name, &req.request, &req.response)
+        .map(|fixture_id| Json(ApiResponse::ok(fixture_id)))
+        .map_err(|e| {
+            tracing::error!("Failed to create fixture: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn delete_fixture(
+    State(state): State<AdminState>,
+    Path((id, fixture_id)): Path<(i64, i64)>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    state
+        .db
+        .delete_fixture(id, fixture_id)
+        .map(|_| Json(ApiResponse::ok(())))
+        .map_err(|e| {
+            tracing::error!("Failed to delete fixture: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
 pub async fn get_configs(
     State(state): State<AdminState>,
 ) -> Result<Json<ApiResponse<Vec<crate::db::SystemConfig>>>, StatusCode> {
@@ -160,6 +429,19 @@ pub async fn update_config(
     Path(key): Path<String>,
     Json(req): Json<UpdateConfigRequest>,
 ) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if key == signed_urls::MODE_KEY {
+        state.signed_urls.set_mode(&req.value).map_err(|e| {
+            tracing::warn!("Invalid config value: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    }
+    // 监听器定义只能通过 /api/listeners 修改
+    if key == listeners::LISTENERS_KEY {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if key == "proxy_port" {
+        listeners::apply_proxy_port(&state, &req.value).await?;
+    }
     tracing::info!("Updating config: {} = {}", key, req.value);
     match state.db.set_config(&key, &req.value) {
         Ok(_) => {
@@ -170,6 +452,7 @@ pub async fn update_config(
                     .store(std::sync::Arc::new(new_path.clone()));
                 tracing::info!("Updated direct_proxy_path to: {}", new_path);
             }
+            state.config_changed(&key, Some(&req.value));
             Ok(Json(ApiResponse::ok(())))
         }
         Err(e) => {
@@ -201,3 +484,39 @@ pub async fn get_proxy_status(
         direct_proxy_path: direct_path.as_ref().clone(),
     })))
 }
+
+/// 最近 1/5/15 分钟的请求统计
+#[derive(Debug, Serialize)]
+pub struct StatusDetail {
+    pub windows: Vec<WindowStats>,
+}
+
+pub async fn get_status_detail(State(state): State<AdminState>) -> Json<ApiResponse<StatusDetail>> {
+    Json(ApiResponse::ok(StatusDetail {
+        windows: [60, 300, 900]
+            .into_iter()
+            .map(|secs| state.rolling.window(secs))
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReloadQuery {
+    #[serde(default = "default_reload_limit")]
+    pub limit: usize,
+}
+
+fn default_reload_limit() -> usize {
+    20
+}
+
+pub async fn list_reloads(
+    State(state): State<AdminState>,
+    Query(query): Query<ReloadQuery>,
+) -> Json<ApiResponse<Vec<ReloadSummary>>> {
+    Json(ApiResponse::ok(state.reloads.recent(query.limit)))
+}
+
+pub async fn list_tasks(State(state): State<AdminState>) -> Json<ApiResponse<Vec<TaskStatus>>> {
+    Json(ApiResponse::ok(state.tasks.snapshot()))
+}
@@ -1,22 +1,46 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
 use axum::{
-    extract::State,
-    http::{Request, StatusCode},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderName, Method, Request, StatusCode},
     middleware::Next,
-    response::{IntoResponse, Response},
+    response::{AppendHeaders, IntoResponse, Response},
     Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Duration, Utc};
 use dashmap::DashMap;
+use parking_lot::RwLock;
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
+use crate::api::ApiResponse;
+use crate::config::{AuthConfig, Role};
+use crate::db::Database;
+use crate::endpoints::constant_time_eq;
+use crate::login_limit::LoginLimiter;
 use crate::AdminState;
 
+/// 新密码最小长度
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// CSRF 令牌 cookie 名，前端读取后通过请求头回传（双重提交）
+const CSRF_COOKIE: &str = "csrf_token";
+const CSRF_HEADER: &str = "x-csrf-token";
+
 /// Session 数据
 #[derive(Clone)]
 pub struct Session {
     pub username: String,
+    pub role: Role,
+    /// 单点登录返回的用户组，密码登录为空
+    pub groups: Vec<String>,
     pub expires_at: i64,
+    /// 开启会话绑定时，创建时 User-Agent / 客户端 IP 的摘要
+    pub binding: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,91 +56,409 @@ pub struct LoginResponse {
     pub message: Option<String>,
 }
 
-/// 认证状态 - 使用 DashMap 实现无锁并发
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// 认证状态 - 使用 DashMap 实现无锁并发，session 同步写入数据库
 #[derive(Clone)]
 pub struct AuthState {
     pub username: String,
-    pub password: String,
+    /// Argon2id 密码哈希（PHC 格式），持久化在 admin_users 表
+    password_hash: Arc<RwLock<String>>,
+    db: Database,
     pub sessions: Arc<DashMap<String, Session>>,
+    session_ttl: Duration,
+    sliding_expiration: bool,
+    pub limiter: LoginLimiter,
+    /// 是否允许本地用户名密码登录，配置 OIDC 后可禁用
+    pub password_login: bool,
+    bind_user_agent: bool,
+    bind_ip: bool,
 }
 
+/// 登录尝试记录保留天数
+const ATTEMPT_RETENTION_DAYS: u32 = 30;
+
+/// 滑动过期时，有效期至少顺延该秒数（且不超过有效期的 1/10）才写回，避免每个请求都写数据库
+const SLIDE_MIN_SECS: i64 = 60;
+
 impl AuthState {
-    pub fn new(username: String, password: String) -> Self {
-        Self {
-            username,
-            password,
-            sessions: Arc::new(DashMap::new()),
+    /// 从数据库加载密码哈希，首次启动时将配置文件中的密码哈希后写入数据库
+    pub fn new(db: Database, config: &AuthConfig) -> anyhow::Result<Self> {
+        let username = config.username.clone();
+        let password_hash = match db.get_password_hash(&username)? {
+            Some(hash) => hash,
+            None => {
+                let hash = hash_password(&config.password)?;
+                db.set_password_hash(&username, &hash)?;
+                tracing::info!("Migrated admin password for '{}' to database", username);
+                hash
+            }
+        };
+
+        // 恢复未过期的 session，重启后无需重新登录
+        let sessions = DashMap::new();
+        for (token, session) in db.load_sessions(Utc::now().timestamp())? {
+            sessions.insert(token, session);
         }
+        if !sessions.is_empty() {
+            tracing::info!("Restored {} sessions", sessions.len());
+        }
+
+        Ok(Self {
+            username,
+            password_hash: Arc::new(RwLock::new(password_hash)),
+            db,
+            sessions: Arc::new(sessions),
+            session_ttl: Duration::seconds(config.session_ttl_secs as i64),
+            sliding_expiration: config.sliding_expiration,
+            limiter: LoginLimiter::new(
+                config.max_login_failures,
+                std::time::Duration::from_secs(config.lockout_secs),
+            ),
+            password_login: !config
+                .oidc
+                .as_ref()
+                .is_some_and(|oidc| oidc.disable_password_login),
+            bind_user_agent: config.bind_user_agent,
+            bind_ip: config.bind_ip,
+        })
     }
 
-    #[inline]
+    /// 校验用户名与密码（Argon2 计算较慢，应在阻塞线程中调用）
     pub fn validate(&self, username: &str, password: &str) -> bool {
-        self.username == username && self.password == password
+        // 用户名不匹配时同样计算哈希，避免通过耗时区分用户名
+        let hash = self.password_hash.read().clone();
+        let password_ok = verify_password(&hash, password);
+        self.username == username && password_ok
     }
 
-    pub fn create_session(&self, username: &str) -> String {
-        let token = generate_token();
+    /// 修改密码并使其它 session 失效
+    pub fn change_password(&self, new_password: &str, keep_token: &str) -> anyhow::Result<()> {
+        let hash = hash_password(new_password)?;
+        self.db.set_password_hash(&self.username, &hash)?;
+        *self.password_hash.write() = hash;
+        self.db.delete_other_sessions(keep_token)?;
+        self.sessions.retain(|token, _| token == keep_token);
+        Ok(())
+    }
+
+    pub fn create_session(
+        &self,
+        username: &str,
+        role: Role,
+        groups: Vec<String>,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> anyhow::Result<String> {
+        let token = generate_token()?;
         let session = Session {
             username: username.to_string(),
-            expires_at: (Utc::now() + Duration::hours(24)).timestamp(),
+            role,
+            groups,
+            expires_at: (Utc::now() + self.session_ttl).timestamp(),
+            binding: self.binding(headers, client_ip),
         };
+        if let Err(e) = self.db.save_session(&token, &session) {
+            tracing::error!("Failed to persist session: {}", e);
+        }
         self.sessions.insert(token.clone(), session);
-        token
+        Ok(token)
+    }
+
+    /// 会话绑定摘要，未开启绑定时为 None；Unix 套接字连接没有客户端 IP
+    fn binding(&self, headers: &HeaderMap, client_ip: Option<IpAddr>) -> Option<String> {
+        if !self.bind_user_agent && !self.bind_ip {
+            return None;
+        }
+        let mut ctx = digest::Context::new(&digest::SHA256);
+        if self.bind_user_agent {
+            let user_agent = headers
+                .get(header::USER_AGENT)
+                .map(|v| v.as_bytes())
+                .unwrap_or_default();
+            ctx.update(b"ua:");
+            ctx.update(user_agent);
+        }
+        if self.bind_ip {
+            let ip = client_ip.map_or_else(|| "unix".to_string(), |ip| ip.to_string());
+            ctx.update(b"\nip:");
+            ctx.update(ip.as_bytes());
+        }
+        Some(URL_SAFE_NO_PAD.encode(ctx.finish().as_ref()))
+    }
+
+    /// 校验 session 并返回角色，开启滑动过期时顺延有效期
+    pub fn validate_session(
+        &self,
+        token: &str,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Role> {
+        let now = Utc::now().timestamp();
+        let binding = self.binding(headers, client_ip);
+        let (role, extended) = match self.sessions.get_mut(token) {
+            Some(session) if session.expires_at <= now => return None,
+            Some(session) if binding.is_some() && session.binding != binding => {
+                tracing::warn!(username = %session.username, client_ip = ?client_ip, "Session used from a different client");
+                return None;
+            }
+            None => return None,
+            Some(mut session) => {
+                let expires_at = (Utc::now() + self.session_ttl).timestamp();
+                let min_step = SLIDE_MIN_SECS.min(self.session_ttl.num_seconds() / 10);
+                if self.sliding_expiration && expires_at - session.expires_at >= min_step {
+                    session.expires_at = expires_at;
+                    (session.role, Some(session.clone()))
+                } else {
+                    (session.role, None)
+                }
+            }
+        };
+        if let Some(session) = extended {
+            if let Err(e) = self.db.save_session(token, &session) {
+                tracing::error!("Failed to persist session: {}", e);
+            }
+        }
+        Some(role)
+    }
+
+    /// 请求携带的有效 session 对应的用户名
+    pub fn session_username(&self, headers: &HeaderMap) -> Option<String> {
+        let token = token_from_headers(headers)?;
+        self.sessions.get(&token).map(|s| s.username.clone())
     }
 
-    #[inline]
-    pub fn validate_session(&self, token: &str) -> bool {
+    /// 代理请求通过 cookie 携带的管理界面 session，用于身份透传
+    pub fn cookie_session(
+        &self,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+    ) -> Option<Session> {
+        let token = cookie(headers, "token")?;
+        self.validate_session(token, headers, client_ip)?;
+        self.sessions.get(token).map(|s| s.clone())
+    }
+
+    /// session 剩余有效秒数，无效或已过期时返回 None
+    pub fn remaining_secs(&self, token: &str) -> Option<i64> {
+        let now = Utc::now().timestamp();
         self.sessions
             .get(token)
-            .map(|s| s.expires_at > Utc::now().timestamp())
-            .unwrap_or(false)
+            .map(|s| s.expires_at - now)
+            .filter(|remaining| *remaining > 0)
     }
 
     pub fn remove_session(&self, token: &str) {
         self.sessions.remove(token);
+        if let Err(e) = self.db.delete_session(token) {
+            tracing::error!("Failed to delete session: {}", e);
+        }
     }
 
     /// 清理过期 session
-    pub fn cleanup_expired(&self) {
+    pub fn cleanup_expired(&self) -> anyhow::Result<()> {
         let now = Utc::now().timestamp();
         self.sessions.retain(|_, s| s.expires_at > now);
+        self.db.delete_expired_sessions(now)?;
+        self.limiter.cleanup_expired();
+        self.db.prune_login_attempts(ATTEMPT_RETENTION_DAYS)?;
+        Ok(())
     }
+
+    /// 记录登录尝试，写入失败只记日志
+    pub fn record_attempt(&self, username: &str, client_ip: &str, success: bool, reason: &str) {
+        let username: String = username.chars().take(128).collect();
+        if let Err(e) = self
+            .db
+            .record_login_attempt(&username, client_ip, success, reason)
+        {
+            tracing::error!("Failed to record login attempt: {}", e);
+        }
+    }
+}
+
+/// 会话 token：32 字节系统安全随机数，base64url 编码
+pub fn generate_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("Failed to generate session token"))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
 }
 
-fn generate_token() -> String {
-    use std::collections::hash_map::RandomState;
-    use std::hash::{BuildHasher, Hasher};
-    use std::time::{SystemTime, UNIX_EPOCH};
+/// 登录成功后下发的 cookie：会话 token 仅限 HTTP 访问，CSRF 令牌需前端可读
+pub fn session_cookies(token: &str) -> AppendHeaders<[(HeaderName, String); 2]> {
+    let mut csrf = [0u8; 32];
+    if SystemRandom::new().fill(&mut csrf).is_err() {
+        tracing::error!("Failed to generate CSRF token");
+    }
+    AppendHeaders([
+        (
+            header::SET_COOKIE,
+            format!("token={}; Path=/; HttpOnly; SameSite=Lax", token),
+        ),
+        (
+            header::SET_COOKIE,
+            format!(
+                "{}={}; Path=/; SameSite=Strict",
+                CSRF_COOKIE,
+                URL_SAFE_NO_PAD.encode(csrf)
+            ),
+        ),
+    ])
+}
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    let rand = RandomState::new().build_hasher().finish();
-    format!("{:x}{:x}", timestamp, rand)
+/// 登出时清除会话 cookie
+fn clear_session_cookies() -> AppendHeaders<[(HeaderName, String); 2]> {
+    AppendHeaders([
+        (
+            header::SET_COOKIE,
+            "token=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0".to_string(),
+        ),
+        (
+            header::SET_COOKIE,
+            format!("{}=; Path=/; SameSite=Strict; Max-Age=0", CSRF_COOKIE),
+        ),
+    ])
 }
 
-/// 登录处理
+/// 登录处理，同一 IP 或用户名连续失败过多时临时锁定，响应中返回 RateLimit-* 剩余次数
 pub async fn login_handler(
     State(state): State<AdminState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
-) -> Json<LoginResponse> {
-    if state.auth.validate(&req.username, &req.password) {
-        let token = state.auth.create_session(&req.username);
-        Json(LoginResponse {
-            success: true,
-            token: Some(token),
-            message: None,
-        })
+) -> Response {
+    // Unix 套接字连接没有对端地址
+    let client_addr = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let client_ip = client_addr.map_or_else(|| "unix".to_string(), |ip| ip.to_string());
+    let username = req.username.clone();
+
+    let mut resp = login(&state, client_addr, &client_ip, &headers, req).await;
+    if let Some(quota) = state.auth.limiter.quota(&client_ip, &username) {
+        quota.apply(resp.headers_mut());
+    }
+    resp
+}
+
+async fn login(
+    state: &AdminState,
+    client_addr: Option<IpAddr>,
+    client_ip: &str,
+    headers: &HeaderMap,
+    req: LoginRequest,
+) -> Response {
+    if !state.auth.password_login {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(LoginResponse {
+                success: false,
+                token: None,
+                message: Some("已禁用密码登录，请使用单点登录".to_string()),
+            }),
+        )
+            .into_response();
+    }
+
+    if let Some(remaining) = state.auth.limiter.check(client_ip, &req.username) {
+        state
+            .auth
+            .record_attempt(&req.username, client_ip, false, "locked");
+        let secs = (remaining.as_millis() as u64).div_ceil(1000);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, secs.to_string())],
+            Json(LoginResponse {
+                success: false,
+                token: None,
+                message: Some(format!(
+                    "登录失败次数过多，请 {} 分钟后重试",
+                    secs.div_ceil(60)
+                )),
+            }),
+        )
+            .into_response();
+    }
+
+    let auth = state.auth.clone();
+    let (username, password) = (req.username.clone(), req.password);
+    let valid = tokio::task::spawn_blocking(move || auth.validate(&username, &password))
+        .await
+        .unwrap_or(false);
+    if valid {
+        state.auth.limiter.record_success(client_ip, &req.username);
+        state
+            .auth
+            .record_attempt(&req.username, client_ip, true, "ok");
+        let token = match state.auth.create_session(
+            &state.auth.username,
+            Role::Admin,
+            Vec::new(),
+            headers,
+            client_addr,
+        ) {
+            Ok(token) => token,
+            Err(e) => {
+                tracing::error!("Failed to create session: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        (
+            session_cookies(&token),
+            Json(LoginResponse {
+                success: true,
+                token: Some(token),
+                message: None,
+            }),
+        )
+            .into_response()
     } else {
+        if state.auth.limiter.record_failure(client_ip, &req.username) {
+            tracing::warn!(client_ip = %client_ip, username = %req.username, "Login locked after repeated failures");
+        }
+        state
+            .auth
+            .record_attempt(&req.username, client_ip, false, "invalid_credentials");
         Json(LoginResponse {
             success: false,
             token: None,
             message: Some("用户名或密码错误".to_string()),
         })
+        .into_response()
     }
 }
 
+/// 修改管理员密码，成功后其它 session 失效
+pub async fn change_password_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if req.new_password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let token = token_from_headers(&headers).unwrap_or_default();
+    let auth = state.auth.clone();
+    tokio::task::spawn_blocking(move || {
+        if !auth.validate(&auth.username, &req.current_password) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        auth.change_password(&req.new_password, &token)
+            .map_err(|e| {
+                tracing::error!("Failed to change password: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    tracing::info!("Admin password changed");
+    Ok(Json(ApiResponse::ok(())))
+}
+
 /// 登出处理
 pub async fn logout_handler(
     State(state): State<AdminState>,
@@ -125,7 +467,18 @@ pub async fn logout_handler(
     if let Some(token) = extract_token(&req) {
         state.auth.remove_session(&token);
     }
-    Json(serde_json::json!({"success": true}))
+    (
+        clear_session_cookies(),
+        Json(serde_json::json!({"success": true})),
+    )
+}
+
+/// 可用的登录方式，供登录页展示
+pub async fn login_methods_handler(State(state): State<AdminState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "password": state.auth.password_login,
+        "oidc": state.oidc.is_some(),
+    }))
 }
 
 /// 验证会话
@@ -133,34 +486,64 @@ pub async fn check_session_handler(
     State(state): State<AdminState>,
     req: Request<axum::body::Body>,
 ) -> impl IntoResponse {
-    let valid = extract_token(&req)
-        .map(|t| state.auth.validate_session(&t))
-        .unwrap_or(false);
-    Json(serde_json::json!({"valid": valid}))
+    // 仅查询状态，不顺延有效期
+    let token = extract_token(&req).unwrap_or_default();
+    let remaining = state.auth.remaining_secs(&token);
+    let role = remaining
+        .and_then(|_| state.auth.sessions.get(&token))
+        .map(|s| s.role);
+    Json(serde_json::json!({
+        "valid": remaining.is_some(),
+        "role": role,
+        "expires_in_secs": remaining,
+        "sliding_expiration": state.auth.sliding_expiration,
+    }))
 }
 
 /// 认证中间件
 pub async fn auth_middleware(
     State(state): State<AdminState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     req: Request<axum::body::Body>,
     next: Next,
 ) -> Response {
     let path = req.uri().path();
 
-    // 白名单路径 - 只允许登录相关和静态资源
+    // 白名单路径 - 只允许登录相关、主备心跳与集群 gossip（令牌认证）和静态资源
     if matches!(
         path,
-        "/api/login" | "/api/session" | "/login" | "/favicon.ico"
+        "/api/login"
+            | "/api/login/methods"
+            | "/api/oidc/login"
+            | "/api/oidc/callback"
+            | "/api/session"
+            | "/api/ha/heartbeat"
+            | "/api/ha/rules"
+            | "/api/cluster/gossip"
+            | "/api/cluster/state"
+            | "/api/identity/jwks"
+            | "/login"
+            | "/favicon.ico"
     ) || path.starts_with("/static/")
     {
         return next.run(req).await;
     }
 
-    // 验证 token
-    if let Some(token) = extract_token(&req) {
-        if state.auth.validate_session(&token) {
-            return next.run(req).await;
+    // 验证 token，viewer 只允许只读请求
+    let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    if let Some(role) =
+        extract_token(&req).and_then(|t| state.auth.validate_session(&t, req.headers(), client_ip))
+    {
+        let read_only = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+        if role == Role::Viewer && !read_only && path != "/api/logout" {
+            return (StatusCode::FORBIDDEN, "Forbidden").into_response();
         }
+        // 通过 cookie 认证的修改请求需校验 CSRF 令牌，Bearer 请求不受浏览器自动携带影响
+        if !read_only && path.starts_with("/api/") && !csrf_valid(req.headers()) {
+            tracing::warn!(path = %path, "Rejected request with missing or invalid CSRF token");
+            return (StatusCode::FORBIDDEN, "CSRF token mismatch").into_response();
+        }
+        return next.run(req).await;
     }
 
     // 页面请求重定向到登录页，API 请求返回 401
@@ -171,27 +554,281 @@ pub async fn auth_middleware(
     }
 }
 
+fn hash_password(password: &str) -> anyhow::Result<String> {
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| anyhow::anyhow!("Failed to generate password salt"))?;
+    let salt = SaltString::encode_b64(&salt).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
 #[inline]
 fn extract_token<B>(req: &Request<B>) -> Option<String> {
-    // Authorization header
-    if let Some(auth) = req.headers().get("Authorization") {
-        if let Ok(s) = auth.to_str() {
-            if let Some(token) = s.strip_prefix("Bearer ") {
-                return Some(token.to_string());
-            }
+    token_from_headers(req.headers())
+}
+
+fn token_from_headers(headers: &HeaderMap) -> Option<String> {
+    bearer_token(headers)
+        .or_else(|| cookie(headers, "token"))
+        .map(str::to_string)
+}
+
+/// 转发给上游前移除管理界面的会话 cookie，避免上游拿到可访问管理接口的 token
+pub fn strip_session_cookies(headers: &mut HeaderMap) {
+    let cookies: Vec<String> = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|s| s.split(';'))
+        .map(str::trim)
+        .filter(|part| {
+            let name = part.split('=').next().unwrap_or_default();
+            !part.is_empty() && name != "token" && name != CSRF_COOKIE
+        })
+        .map(str::to_string)
+        .collect();
+    headers.remove(header::COOKIE);
+    if cookies.is_empty() {
+        return;
+    }
+    if let Ok(value) = cookies.join("; ").parse() {
+        headers.insert(header::COOKIE, value);
+    }
+}
+
+/// Authorization 请求头中的 Bearer token
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|s| s.split(';'))
+        .find_map(|part| part.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// 双重提交校验：请求头中的 CSRF 令牌须与 cookie 一致，Bearer 认证的请求无需校验
+fn csrf_valid(headers: &HeaderMap) -> bool {
+    if bearer_token(headers).is_some() {
+        return true;
+    }
+    match (
+        cookie(headers, CSRF_COOKIE),
+        headers.get(CSRF_HEADER).and_then(|v| v.to_str().ok()),
+    ) {
+        (Some(expected), Some(token)) if !expected.is_empty() => {
+            constant_time_eq(token.as_bytes(), expected.as_bytes())
         }
+        _ => false,
     }
+}
 
-    // Cookie
-    if let Some(cookie) = req.headers().get("Cookie") {
-        if let Ok(s) = cookie.to_str() {
-            for part in s.split(';') {
-                if let Some(token) = part.trim().strip_prefix("token=") {
-                    return Some(token.to_string());
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use axum::body::Body;
+
+    fn csrf_headers(cookie_token: Option<&str>, header_token: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = cookie_token {
+            let value = format!("token=abc; {}={}", CSRF_COOKIE, token);
+            headers.insert(header::COOKIE, value.parse().unwrap());
+        }
+        if let Some(token) = header_token {
+            headers.insert(CSRF_HEADER, token.parse().unwrap());
         }
+        headers
+    }
+
+    #[test]
+    fn csrf_requires_matching_cookie_and_header() {
+        assert!(csrf_valid(&csrf_headers(Some("t1"), Some("t1"))));
+        assert!(!csrf_valid(&csrf_headers(Some("t1"), Some("t2"))));
+        assert!(!csrf_valid(&csrf_headers(Some("t1"), None)));
+        assert!(!csrf_valid(&csrf_headers(None, Some("t1"))));
+        assert!(!csrf_valid(&csrf_headers(Some(""), Some(""))));
+
+        let mut bearer = HeaderMap::new();
+        bearer.insert(header::AUTHORIZATION, "Bearer abc".parse().unwrap());
+        assert!(csrf_valid(&bearer));
+    }
+
+    /// 经过认证中间件的管理接口，处理函数直接返回 200
+    fn app(state: &AdminState) -> axum::Router {
+        axum::Router::new()
+            .route(
+                "/api/rules",
+                axum::routing::get(|| async { StatusCode::OK }).post(|| async { StatusCode::OK }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
+            .with_state(state.clone())
+    }
+
+    async fn post(state: &AdminState, headers: &[(HeaderName, String)]) -> StatusCode {
+        let mut req = Request::post("/api/rules");
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+        testing::send(app(state), req.body(Body::empty()).unwrap())
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn cookie_session_writes_require_csrf_token() {
+        let state = testing::admin_state(&testing::config());
+        let token = state
+            .auth
+            .create_session("admin", Role::Admin, Vec::new(), &HeaderMap::new(), None)
+            .unwrap();
+        let session = format!("token={}; {}=t1", token, CSRF_COOKIE);
+
+        let missing = post(&state, &[(header::COOKIE, session.clone())]).await;
+        assert_eq!(missing, StatusCode::FORBIDDEN);
+        let mismatched = post(
+            &state,
+            &[
+                (header::COOKIE, session.clone()),
+                (HeaderName::from_static(CSRF_HEADER), "t2".to_string()),
+            ],
+        )
+        .await;
+        assert_eq!(mismatched, StatusCode::FORBIDDEN);
+        let matched = post(
+            &state,
+            &[
+                (header::COOKIE, session.clone()),
+                (HeaderName::from_static(CSRF_HEADER), "t1".to_string()),
+            ],
+        )
+        .await;
+        assert_eq!(matched, StatusCode::OK);
+
+        // 只读请求与 Bearer 认证的请求不校验 CSRF 令牌
+        let get = Request::get("/api/rules")
+            .header(header::COOKIE, session)
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(
+            testing::send(app(&state), get).await.status(),
+            StatusCode::OK
+        );
+        let bearer = post(
+            &state,
+            &[(header::AUTHORIZATION, format!("Bearer {}", token))],
+        )
+        .await;
+        assert_eq!(bearer, StatusCode::OK);
+
+        // 未登录时返回 401
+        assert_eq!(post(&state, &[]).await, StatusCode::UNAUTHORIZED);
+    }
+
+    fn user_agent(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, value.parse().unwrap());
+        headers
+    }
+
+    fn auth_state(bind_user_agent: bool, bind_ip: bool) -> AuthState {
+        let mut config = testing::config();
+        config.auth.bind_user_agent = bind_user_agent;
+        config.auth.bind_ip = bind_ip;
+        AuthState::new(Database::in_memory().unwrap(), &config.auth).unwrap()
     }
 
-    None
+    #[test]
+    fn bound_session_rejects_other_clients() {
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let auth = auth_state(true, true);
+        let token = auth
+            .create_session("admin", Role::Admin, Vec::new(), &user_agent("a"), Some(ip))
+            .unwrap();
+        assert_eq!(
+            auth.validate_session(&token, &user_agent("a"), Some(ip)),
+            Some(Role::Admin)
+        );
+        assert_eq!(
+            auth.validate_session(&token, &user_agent("b"), Some(ip)),
+            None
+        );
+        assert_eq!(
+            auth.validate_session(&token, &user_agent("a"), Some(other_ip)),
+            None
+        );
+        assert_eq!(
+            auth.validate_session(&token, &HeaderMap::new(), Some(ip)),
+            None
+        );
+        assert_eq!(auth.validate_session(&token, &user_agent("a"), None), None);
+
+        // 只绑定 User-Agent 时换 IP 不影响
+        let auth = auth_state(true, false);
+        let token = auth
+            .create_session("admin", Role::Admin, Vec::new(), &user_agent("a"), Some(ip))
+            .unwrap();
+        assert!(auth
+            .validate_session(&token, &user_agent("a"), Some(other_ip))
+            .is_some());
+        assert!(auth
+            .validate_session(&token, &user_agent("b"), Some(ip))
+            .is_none());
+    }
+
+    #[test]
+    fn unbound_session_accepts_any_client() {
+        let auth = auth_state(false, false);
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let token = auth
+            .create_session(
+                "admin",
+                Role::Viewer,
+                Vec::new(),
+                &user_agent("a"),
+                Some(ip),
+            )
+            .unwrap();
+        assert_eq!(
+            auth.validate_session(&token, &user_agent("b"), None),
+            Some(Role::Viewer)
+        );
+        assert_eq!(
+            auth.validate_session("unknown", &user_agent("a"), Some(ip)),
+            None
+        );
+    }
+
+    #[test]
+    fn tokens_are_random() {
+        let a = generate_token().unwrap();
+        let b = generate_token().unwrap();
+        assert_eq!(a.len(), 43);
+        assert_ne!(a, b);
+    }
 }
@@ -0,0 +1,346 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderName, StatusCode},
+    response::Response,
+    Json,
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api;
+use crate::config::DatabaseBackupConfig;
+use crate::db::{AdminUser, Database, ProxyRule, SystemConfig, SCHEMA_VERSION};
+use crate::pipeline::Pipeline;
+use crate::reloads;
+use crate::rules_file;
+use crate::signed_urls;
+use crate::tasks::TaskRegistry;
+use crate::AdminState;
+
+/// 完整配置备份：规则、系统配置、管理员账号与密钥名称
+///
+/// 密钥值以主密钥加密，不随备份导出，恢复后需重新录入 `missing_secrets` 中的密钥
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConfigBackup {
+    /// 导出时的数据库结构版本，高于当前版本的备份不能恢复
+    pub schema_version: i64,
+    #[serde(default)]
+    pub created_at: String,
+    pub rules: Vec<ProxyRule>,
+    pub system_config: Vec<SystemConfig>,
+    /// 为空时保留现有账号
+    #[serde(default)]
+    pub users: Vec<AdminUser>,
+    #[serde(default)]
+    pub secrets: Vec<SecretInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecretInfo {
+    pub name: String,
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreSummary {
+    pub rules: usize,
+    pub system_config: usize,
+    pub users: usize,
+    /// 备份中有而本实例没有的密钥，需要重新录入
+    pub missing_secrets: Vec<String>,
+}
+
+fn internal_error(e: anyhow::Error) -> StatusCode {
+    tracing::error!("Backup operation failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// 导出完整配置，附带下载文件名
+pub async fn backup_handler(
+    State(state): State<AdminState>,
+) -> Result<([(HeaderName, String); 1], Json<ConfigBackup>), StatusCode> {
+    let now = Local::now();
+    let backup = ConfigBackup {
+        schema_version: state.db.schema_version().map_err(internal_error)?,
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        rules: state.db.get_all_rules().map_err(internal_error)?,
+        system_config: state.db.get_all_configs().map_err(internal_error)?,
+        users: state.db.list_admin_users().map_err(internal_error)?,
+        secrets: state
+            .db
+            .list_secrets()
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|secret| SecretInfo {
+                name: secret.name,
+                updated_at: secret.updated_at,
+            })
+            .collect(),
+    };
+    tracing::info!(rules = backup.rules.len(), "Configuration backup exported");
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"proxy-backup-{}.json\"",
+                now.format("%Y%m%d-%H%M%S")
+            ),
+        )],
+        Json(backup),
+    ))
+}
+
+/// 以备份整体替换规则、系统配置与管理员账号，在一个事务中写入，结构版本不支持或规则无效时不写入；
+/// 规则集有规则编译失败或冲突时返回 422 与各规则的错误
+pub async fn restore_handler(
+    State(state): State<AdminState>,
+    Json(backup): Json<ConfigBackup>,
+) -> Result<Response, StatusCode> {
+    if backup.schema_version > SCHEMA_VERSION {
+        tracing::warn!(
+            version = backup.schema_version,
+            supported = SCHEMA_VERSION,
+            "Backup is from a newer schema version"
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    rules_file::check_writable(&state)?;
+    for rule in &backup.rules {
+        if let Some(name) = rule
+            .options
+            .plugins
+            .iter()
+            .find(|name| !state.plugins.contains(name))
+        {
+            tracing::warn!(rule = %rule.name, plugin = %name, "Backup rule references an unknown plugin");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if let Err(e) = Pipeline::build(&rule.options)
+            .map_err(|e| e.to_string())
+            .and_then(|_| api::validate_upstream_proxy(&rule.options))
+        {
+            tracing::warn!(rule = %rule.name, "Invalid rule in backup: {}", e);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let before = state.db.get_all_rules().map_err(internal_error)?;
+    if let Err(e) = state.db.restore(
+        &backup.rules,
+        &backup.system_config,
+        &backup.users,
+        reloads::check,
+    ) {
+        return Ok(api::rules_failed("restore backup", e));
+    }
+    for rule in before {
+        if !backup.rules.iter().any(|r| r.id == rule.id) {
+            state.stats.remove(rule.id);
+        }
+    }
+
+    apply_system_config(&state, &backup.system_config);
+
+    let missing_secrets: Vec<String> = backup
+        .rules
+        .iter()
+        .flat_map(|rule| rule.options.secret_refs())
+        .chain(backup.secrets.iter().map(|secret| secret.name.as_str()))
+        .filter(|name| !state.secrets.contains(name))
+        .map(str::to_string)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    tracing::info!(
+        rules = backup.rules.len(),
+        configs = backup.system_config.len(),
+        users = backup.users.len(),
+        missing_secrets = missing_secrets.len(),
+        "Configuration restored from backup"
+    );
+    let summary = RestoreSummary {
+        rules: backup.rules.len(),
+        system_config: backup.system_config.len(),
+        users: backup.users.len(),
+        missing_secrets,
+    };
+    Ok(api::reloaded(&state, "restore", summary))
+}
+
+/// 使整体写入的系统配置立即生效；监听器与代理端口在重启后生效
+pub(crate) fn apply_system_config(state: &AdminState, configs: &[SystemConfig]) {
+    for config in configs {
+        match config.key.as_str() {
+            "direct_proxy_path" => state
+                .direct_proxy_path
+                .store(Arc::new(config.value.clone())),
+            signed_urls::MODE_KEY => {
+                if let Err(e) = state.signed_urls.set_mode(&config.value) {
+                    tracing::warn!("Invalid direct proxy mode: {}", e);
+                }
+            }
+            _ => {}
+        }
+        state.events.publish(
+            "config_changed",
+            serde_json::json!({ "key": config.key, "value": config.value }),
+        );
+    }
+}
+
+/// 定时数据库快照的文件名前缀，清理旧备份时只处理这类文件
+const SNAPSHOT_PREFIX: &str = "proxy-";
+
+/// 启动定时数据库快照任务，未配置备份目录时不启动
+pub fn start_snapshot_task(tasks: &TaskRegistry, db: Database, config: &DatabaseBackupConfig) {
+    let Some(dir) = config.directory.clone().map(PathBuf::from) else {
+        return;
+    };
+    let retention = config.retention;
+    tracing::info!(dir = %dir.display(), interval_secs = config.interval_secs, "Database snapshots enabled");
+    tasks.spawn_periodic(
+        "db_backup",
+        Duration::from_secs(config.interval_secs.max(60)),
+        move || {
+            let (db, dir) = (db.clone(), dir.clone());
+            async move {
+                tokio::task::spawn_blocking(move || snapshot(&db, &dir, retention)).await?
+            }
+        },
+    )
+}
+
+/// 先备份到临时文件再改名，目录中的 `.db` 文件总是完整的快照
+fn snapshot(db: &Database, dir: &Path, retention: Option<usize>) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let name = format!(
+        "{}{}.db",
+        SNAPSHOT_PREFIX,
+        Local::now().format("%Y%m%d-%H%M%S")
+    );
+    let tmp = dir.join(format!("{}.tmp", name));
+    let _ = std::fs::remove_file(&tmp);
+    db.backup_to(&tmp)?;
+    std::fs::rename(&tmp, dir.join(&name))?;
+    tracing::info!(file = %name, "Database snapshot written");
+
+    if let Some(retention) = retention.filter(|n| *n > 0) {
+        // 文件名中的时间戳按字典序即按时间排序
+        let mut snapshots: Vec<PathBuf> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name().is_some_and(|file| {
+                    let file = file.to_string_lossy();
+                    file.starts_with(SNAPSHOT_PREFIX) && file.ends_with(".db")
+                })
+            })
+            .collect();
+        snapshots.sort();
+        let excess = snapshots.len().saturating_sub(retention);
+        for path in &snapshots[..excess] {
+            std::fs::remove_file(path)?;
+            tracing::debug!(file = %path.display(), "Old database snapshot removed");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RuleOptions;
+    use crate::testing;
+
+    fn rule(id: i64, source: &str) -> ProxyRule {
+        ProxyRule {
+            id,
+            name: format!("rule-{}", id),
+            source: source.to_string(),
+            target: "http://upstream/{*path}".to_string(),
+            timeout_secs: 30,
+            enabled: true,
+            options: RuleOptions::default(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    fn backup(rules: Vec<ProxyRule>) -> ConfigBackup {
+        ConfigBackup {
+            schema_version: SCHEMA_VERSION,
+            created_at: String::new(),
+            rules,
+            system_config: vec![SystemConfig {
+                id: 1,
+                key: "direct_proxy_path".to_string(),
+                value: "restored".to_string(),
+            }],
+            users: Vec::new(),
+            secrets: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn restore_replaces_rules_and_config() {
+        let state = testing::admin_state(&testing::config());
+        let resp = restore_handler(
+            State(state.clone()),
+            Json(backup(vec![rule(7, "/a/{*path}"), rule(9, "/b/{*path}")])),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let ids: Vec<i64> = state
+            .db
+            .get_all_rules()
+            .unwrap()
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, [7, 9]);
+        assert_eq!(state.rules.load().len(), 2);
+        assert_eq!(state.direct_proxy_path.load().as_str(), "restored");
+    }
+
+    #[tokio::test]
+    async fn conflicting_backup_is_not_written() {
+        let state = testing::admin_state(&testing::config());
+        state
+            .db
+            .restore(&[rule(1, "/old/{*path}")], &[], &[], reloads::check)
+            .unwrap();
+
+        // 第二条规则被第一条完全遮蔽
+        let resp = restore_handler(
+            State(state.clone()),
+            Json(backup(vec![
+                rule(1, "/api/{*path}"),
+                rule(2, "/api/{*path}"),
+            ])),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let rules = state.db.get_all_rules().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].source, "/old/{*path}");
+        assert_eq!(state.direct_proxy_path.load().as_str(), "proxy");
+    }
+
+    #[tokio::test]
+    async fn newer_schema_is_rejected() {
+        let state = testing::admin_state(&testing::config());
+        let mut newer = backup(vec![rule(1, "/a/{*path}")]);
+        newer.schema_version = SCHEMA_VERSION + 1;
+        let status = restore_handler(State(state.clone()), Json(newer))
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(state.db.get_all_rules().unwrap().is_empty());
+    }
+}
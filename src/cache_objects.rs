@@ -0,0 +1,242 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    Json,
+};
+use futures::{stream, StreamExt};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Component, PathBuf};
+
+use crate::api::ApiResponse;
+use crate::cache_store::{CacheStore, CachedObject};
+use crate::proxy::{rule_proxy_handler, ProxyState};
+
+/// 单次预热的地址数上限
+const MAX_PREWARM_URLS: usize = 1000;
+
+/// 同时预热的地址数
+const PREWARM_CONCURRENCY: usize = 4;
+
+/// 缓存命中时代理返回的响应头
+const CACHE_HEADER: &str = "x-proxy-cache";
+
+/// 比较路径时忽略开头的 ./ 与 /，URL 中的路径通常会被客户端规范化
+fn normalized(path: &std::path::Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir | Component::RootDir))
+        .collect()
+}
+
+fn store_error(e: std::io::Error) -> StatusCode {
+    if e.kind() == std::io::ErrorKind::Unsupported {
+        return StatusCode::NOT_IMPLEMENTED;
+    }
+    tracing::error!("Failed to access cache store: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+async fn find(store: &dyn CacheStore, path: &str) -> Result<CachedObject, StatusCode> {
+    let wanted = normalized(std::path::Path::new(path));
+    store
+        .list()
+        .await
+        .map_err(store_error)?
+        .into_iter()
+        .find(|object| normalized(&object.path) == wanted)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    /// 只列出该路径下的对象，如某个规则的 cache_dir
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+/// 缓存对象列表：路径、大小、sha256、是否固定与最近访问时间
+pub async fn list_handler(
+    State(state): State<ProxyState>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<ApiResponse<Vec<CachedObject>>>, StatusCode> {
+    let prefix = query
+        .prefix
+        .as_deref()
+        .map(|prefix| normalized(std::path::Path::new(prefix)));
+    let objects = state
+        .cache_store
+        .list()
+        .await
+        .map_err(store_error)?
+        .into_iter()
+        .filter(|object| {
+            prefix
+                .as_ref()
+                .is_none_or(|prefix| normalized(&object.path).starts_with(prefix))
+        })
+        .collect();
+    Ok(Json(ApiResponse::ok(objects)))
+}
+
+/// 单个缓存对象，索引中没有摘要（启动扫描得到的文件）时读取内容计算
+pub async fn inspect_handler(
+    State(state): State<ProxyState>,
+    Path(path): Path<String>,
+) -> Result<Json<ApiResponse<CachedObject>>, StatusCode> {
+    let mut object = find(state.cache_store.as_ref(), &path).await?;
+    if object.sha256.is_none() {
+        let mut body = state
+            .cache_store
+            .read(&object.path, 0, object.size)
+            .await
+            .map_err(store_error)?;
+        let mut context = digest::Context::new(&digest::SHA256);
+        while let Some(chunk) = body.next().await {
+            context.update(&chunk.map_err(store_error)?);
+        }
+        object.sha256 = Some(
+            context
+                .finish()
+                .as_ref()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        );
+    }
+    Ok(Json(ApiResponse::ok(object)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PinRequest {
+    pub pinned: bool,
+}
+
+/// 固定或取消固定对象，固定的对象不会因容量上限被清理
+pub async fn pin_handler(
+    State(state): State<ProxyState>,
+    Path(path): Path<String>,
+    Json(req): Json<PinRequest>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let object = find(state.cache_store.as_ref(), &path).await?;
+    if !state
+        .cache_store
+        .pin(&object.path, req.pinned)
+        .map_err(store_error)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::info!(path = %object.path.display(), pinned = req.pinned, "Cache object pin updated");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+/// 删除缓存对象，下次请求时重新从上游获取
+pub async fn purge_handler(
+    State(state): State<ProxyState>,
+    Path(path): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let object = find(state.cache_store.as_ref(), &path).await?;
+    if !state
+        .cache_store
+        .remove(&object.path)
+        .await
+        .map_err(store_error)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::info!(path = %object.path.display(), "Cache object purged");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrewarmRequest {
+    /// 经过代理的下载地址，如 http://proxy:3000/npm/lodash/-/lodash-4.17.21.tgz，也可只写路径
+    pub urls: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrewarmResult {
+    pub url: String,
+    pub status: u16,
+    pub bytes: u64,
+    /// 请求前已在缓存中
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 按地址列表预热缓存：依次以 GET 经规则转发并读完响应体，镜像规则会写入缓存，
+/// 适合在断网的构建环境使用前预先下载依赖
+pub async fn prewarm_handler(
+    State(state): State<ProxyState>,
+    Json(req): Json<PrewarmRequest>,
+) -> Result<Json<ApiResponse<Vec<PrewarmResult>>>, StatusCode> {
+    if req.urls.is_empty() || req.urls.len() > MAX_PREWARM_URLS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let results: Vec<PrewarmResult> = stream::iter(req.urls)
+        .map(|url| prewarm(state.clone(), url))
+        .buffered(PREWARM_CONCURRENCY)
+        .collect()
+        .await;
+    tracing::info!(
+        urls = results.len(),
+        failed = results.iter().filter(|r| r.status != 200).count(),
+        "Cache prewarm finished"
+    );
+    Ok(Json(ApiResponse::ok(results)))
+}
+
+async fn prewarm(state: ProxyState, url: String) -> PrewarmResult {
+    let mut result = PrewarmResult {
+        url,
+        status: 0,
+        bytes: 0,
+        cached: false,
+        error: None,
+    };
+    let uri: axum::http::Uri = match result.url.parse() {
+        Ok(uri) => uri,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    let mut req = Request::new(Body::empty());
+    *req.uri_mut() = uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/")
+        .parse()
+        .unwrap_or_default();
+    // 镜像元数据中的下载地址按 Host 生成
+    if let Some(value) = uri
+        .authority()
+        .and_then(|a| HeaderValue::from_str(a.as_str()).ok())
+    {
+        req.headers_mut().insert(header::HOST, value);
+    }
+
+    let client = ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0)));
+    let resp = match rule_proxy_handler(State(state), client, req).await {
+        Ok(resp) => resp,
+        Err(status) => {
+            result.status = status.as_u16();
+            return result;
+        }
+    };
+    result.status = resp.status().as_u16();
+    result.cached = resp.headers().get(CACHE_HEADER).is_some_and(|v| v == "HIT");
+    let mut body = resp.into_body().into_data_stream();
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) => result.bytes += chunk.len() as u64,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+    result
+}
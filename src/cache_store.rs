@@ -0,0 +1,761 @@
+use anyhow::bail;
+use bytes::{Bytes, BytesMut};
+use dashmap::{DashMap, DashSet};
+use futures::{future::BoxFuture, stream, stream::BoxStream, Stream, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+use crate::config::{CacheBackend, CacheStoreConfig};
+use crate::s3_store::S3Store;
+use crate::tasks::TaskRegistry;
+
+/// 读取缓存文件的块大小
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// 超过容量上限时清理到上限的该比例，避免每次写入都触发清理
+const LOW_WATERMARK_PERCENT: u64 = 90;
+
+/// 本地文件缓存索引的保存间隔
+const INDEX_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+pub type StoreFuture<'a, T> = BoxFuture<'a, std::io::Result<T>>;
+pub type ByteStream = BoxStream<'static, std::io::Result<Bytes>>;
+
+/// 缓存对象的元数据
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectMeta {
+    pub len: u64,
+    /// 写入缓存的时间，用于有效期判断与按缓存时间清理
+    pub modified: SystemTime,
+}
+
+/// 管理接口列出的缓存对象
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedObject {
+    pub path: PathBuf,
+    pub size: u64,
+    /// 写入缓存时计算的 sha256（小写十六进制），启动扫描得到的文件为空
+    pub sha256: Option<String>,
+    /// 固定的对象不会因容量上限被清理
+    pub pinned: bool,
+    /// 最近访问时间（Unix 秒），内存存储为缓存时间
+    pub accessed_at: u64,
+}
+
+fn unsupported(operation: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("cache backend does not support {}", operation),
+    )
+}
+
+/// 缓存存储后端。对象以缓存路径（规则的 cache_dir 加相对路径）标识；
+/// 写入先在本地暂存文件中完成长度与摘要校验，再整体提交
+pub trait CacheStore: Send + Sync {
+    /// 对象不存在时返回 None
+    fn head<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, Option<ObjectMeta>>;
+
+    /// 读取 [start, end) 范围的内容
+    fn read<'a>(&'a self, path: &'a Path, start: u64, end: u64) -> StoreFuture<'a, ByteStream>;
+
+    /// 写入时暂存文件所在的本地目录
+    fn staging_dir(&self, path: &Path) -> PathBuf;
+
+    /// 将长度为 len、摘要为 sha256 的暂存文件提交为 path 对应的对象
+    fn commit<'a>(
+        &'a self,
+        path: &'a Path,
+        staged: &'a Path,
+        len: u64,
+        sha256: &'a str,
+    ) -> StoreFuture<'a, ()>;
+
+    /// 列出全部对象，按路径排序；对象存储不支持，返回 Unsupported 错误
+    fn list(&self) -> StoreFuture<'_, Vec<CachedObject>> {
+        Box::pin(async { Err(unsupported("listing objects")) })
+    }
+
+    /// 删除对象，返回对象是否存在
+    fn remove<'a>(&'a self, _path: &'a Path) -> StoreFuture<'a, bool> {
+        Box::pin(async { Err(unsupported("removing objects")) })
+    }
+
+    /// 固定或取消固定对象，返回对象是否存在
+    fn pin(&self, _path: &Path, _pinned: bool) -> std::io::Result<bool> {
+        Err(unsupported("pinning objects"))
+    }
+
+    /// root 下的对象总大小超过 max_bytes 时删除最早缓存（本地文件存储为最久未访问）的对象，
+    /// 返回删除的数量；默认不处理，对象存储可使用存储桶的生命周期规则
+    fn evict<'a>(&'a self, _root: &'a Path, _max_bytes: u64) -> StoreFuture<'a, usize> {
+        Box::pin(async { Ok(0) })
+    }
+
+    /// 开始使用缓存目录 root，本地文件存储据此建立索引
+    fn track(&self, _root: &Path) {}
+
+    /// 输出 Prometheus 指标
+    fn render_prometheus(&self, _out: &mut String) {}
+
+    /// 退出前保存索引等状态
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// 按配置创建缓存存储
+pub fn build(
+    config: &CacheStoreConfig,
+    tasks: &TaskRegistry,
+) -> anyhow::Result<Arc<dyn CacheStore>> {
+    Ok(match config.backend {
+        CacheBackend::Disk => Arc::new(DiskStore::open(config, tasks)),
+        CacheBackend::Memory => Arc::new(MemoryStore::new(config.memory_max_size_mb * 1024 * 1024)),
+        CacheBackend::S3 => {
+            if config.s3.bucket.is_empty() {
+                bail!("cache_store.s3.bucket is required for the s3 cache backend");
+            }
+            Arc::new(S3Store::new(&config.s3)?)
+        }
+    })
+}
+
+/// 非本地文件存储的暂存目录
+pub fn temp_staging_dir() -> PathBuf {
+    std::env::temp_dir().join("proxy-cache-staging")
+}
+
+pub fn file_stream<R>(file: R) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buf = BytesMut::with_capacity(READ_CHUNK_BYTES);
+        match file.read_buf(&mut buf).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok(buf.freeze()), Some(file))),
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// 本地文件存储，对象保存在缓存路径对应的文件中。已使用的缓存目录及文件的最近访问时间
+/// 定期保存到索引文件，启动时重新扫描这些目录重建索引，超过容量上限时按最近访问时间清理
+#[derive(Clone)]
+pub struct DiskStore {
+    inner: Arc<DiskInner>,
+}
+
+struct DiskInner {
+    /// 全部缓存目录的容量上限，None 表示不限制
+    max_bytes: Option<u64>,
+    index_path: PathBuf,
+    index: Mutex<DiskIndex>,
+    /// 已建立索引的缓存目录
+    roots: DashSet<PathBuf>,
+    /// 索引文件中的访问时间、摘要与固定标记，扫描缓存目录时合并
+    saved: Mutex<IndexFile>,
+    evicting: AtomicBool,
+    evictions: AtomicU64,
+    evicted_bytes: AtomicU64,
+}
+
+#[derive(Default)]
+struct DiskIndex {
+    entries: HashMap<PathBuf, IndexEntry>,
+    bytes: u64,
+    /// 上次保存后有变化
+    dirty: bool,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    len: u64,
+    /// 最近访问时间（Unix 秒）
+    accessed: u64,
+    sha256: Option<String>,
+    pinned: bool,
+}
+
+/// 索引文件内容
+#[derive(Default, Serialize, Deserialize)]
+struct IndexFile {
+    roots: Vec<PathBuf>,
+    accessed: HashMap<PathBuf, u64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    sha256: HashMap<PathBuf, String>,
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pinned: HashSet<PathBuf>,
+}
+
+impl DiskIndex {
+    /// 重新缓存的文件保留固定标记
+    fn insert(&mut self, path: PathBuf, mut entry: IndexEntry) {
+        if let Some(old) = self.entries.get(&path) {
+            entry.pinned |= old.pinned;
+        }
+        self.bytes += entry.len;
+        if let Some(old) = self.entries.insert(path, entry) {
+            self.bytes -= old.len;
+        }
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<IndexEntry> {
+        let entry = self.entries.remove(path)?;
+        self.bytes -= entry.len;
+        self.dirty = true;
+        Some(entry)
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl DiskStore {
+    /// 读取索引文件并在后台扫描其中的缓存目录，定期保存索引
+    pub fn open(config: &CacheStoreConfig, tasks: &TaskRegistry) -> Self {
+        let index_path = PathBuf::from(&config.index_path);
+        let mut saved = match std::fs::read(&index_path) {
+            Ok(data) => serde_json::from_slice::<IndexFile>(&data).unwrap_or_else(|e| {
+                tracing::warn!(path = %index_path.display(), "Invalid cache index, rebuilding: {}", e);
+                IndexFile::default()
+            }),
+            Err(_) => IndexFile::default(),
+        };
+        let roots = std::mem::take(&mut saved.roots);
+        let store = Self {
+            inner: Arc::new(DiskInner {
+                max_bytes: config.disk_max_size_mb.map(|mb| mb * 1024 * 1024),
+                index_path,
+                index: Mutex::new(DiskIndex::default()),
+                roots: DashSet::new(),
+                saved: Mutex::new(saved),
+                evicting: AtomicBool::new(false),
+                evictions: AtomicU64::new(0),
+                evicted_bytes: AtomicU64::new(0),
+            }),
+        };
+        for root in &roots {
+            store.track(root);
+        }
+
+        let inner = store.inner.clone();
+        tasks.spawn_periodic("cache_index_save", INDEX_SAVE_INTERVAL, move || {
+            let inner = inner.clone();
+            async move {
+                tokio::task::spawn_blocking(move || inner.save())
+                    .await
+                    .map_err(anyhow::Error::from)?
+            }
+        });
+        store
+    }
+}
+
+impl DiskInner {
+    /// 扫描缓存目录，未在索引文件中记录访问时间的文件使用修改时间
+    fn scan(&self, root: &Path) {
+        let mut files = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                } else if !name.starts_with('.') && !name.ends_with(".tmp") {
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.push((entry.path(), metadata.len(), unix_secs(modified)));
+                }
+            }
+        }
+
+        let count = files.len();
+        {
+            let mut saved = self.saved.lock();
+            let mut index = self.index.lock();
+            for (path, len, modified) in files {
+                let entry = IndexEntry {
+                    len,
+                    accessed: saved.accessed.remove(&path).unwrap_or(modified),
+                    sha256: saved.sha256.remove(&path),
+                    pinned: saved.pinned.remove(&path),
+                };
+                // 扫描期间写入或访问过的文件以索引中的记录为准
+                if !index.entries.contains_key(&path) {
+                    index.insert(path, entry);
+                }
+            }
+            index.dirty = true;
+        }
+        tracing::info!(path = %root.display(), files = count, "Cache index rebuilt");
+        if let Some(max_bytes) = self.max_bytes {
+            self.evict_lru(None, max_bytes);
+        }
+    }
+
+    fn touch(&self, path: &Path) {
+        let mut index = self.index.lock();
+        if let Some(entry) = index.entries.get_mut(path) {
+            entry.accessed = unix_secs(SystemTime::now());
+            index.dirty = true;
+        }
+    }
+
+    /// root 下（None 表示全部缓存目录）的文件总大小超过 max_bytes 时按最近访问时间从早到晚删除
+    /// 未固定的文件，返回删除的文件数
+    fn evict_lru(&self, root: Option<&Path>, max_bytes: u64) -> usize {
+        let victims = {
+            let mut index = self.index.lock();
+            let mut candidates: Vec<(u64, u64, PathBuf)> = index
+                .entries
+                .iter()
+                .filter(|(path, _)| root.is_none_or(|root| path.starts_with(root)))
+                .map(|(path, entry)| (entry.accessed, entry.len, path.clone()))
+                .collect();
+            let mut total: u64 = match root {
+                Some(_) => candidates.iter().map(|(_, len, _)| len).sum(),
+                None => index.bytes,
+            };
+            candidates.retain(|(_, _, path)| !index.entries[path].pinned);
+            if total <= max_bytes {
+                return 0;
+            }
+            let target = max_bytes / 100 * LOW_WATERMARK_PERCENT;
+            candidates.sort_by_key(|(accessed, _, _)| *accessed);
+            let mut victims = Vec::new();
+            for (_, len, path) in candidates {
+                if total <= target {
+                    break;
+                }
+                index.remove(&path);
+                total = total.saturating_sub(len);
+                victims.push((path, len));
+            }
+            victims
+        };
+
+        let mut removed = 0;
+        for (path, len) in victims {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {
+                    removed += 1;
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                    self.evicted_bytes.fetch_add(len, Ordering::Relaxed);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "Failed to evict mirror cache file: {}", e)
+                }
+            }
+        }
+        if removed > 0 && root.is_none() {
+            tracing::info!(
+                removed,
+                "Disk cache exceeded its size limit, evicted least recently used files"
+            );
+        }
+        removed
+    }
+
+    /// 索引有变化时写入索引文件
+    fn save(&self) -> anyhow::Result<()> {
+        let file = {
+            let mut index = self.index.lock();
+            if !index.dirty {
+                return Ok(());
+            }
+            index.dirty = false;
+            IndexFile {
+                roots: self.roots.iter().map(|root| root.clone()).collect(),
+                accessed: index
+                    .entries
+                    .iter()
+                    .map(|(path, entry)| (path.clone(), entry.accessed))
+                    .collect(),
+                sha256: index
+                    .entries
+                    .iter()
+                    .filter_map(|(path, entry)| Some((path.clone(), entry.sha256.clone()?)))
+                    .collect(),
+                pinned: index
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| entry.pinned)
+                    .map(|(path, _)| path.clone())
+                    .collect(),
+            }
+        };
+        if let Some(dir) = self.index_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut tmp = self.index_path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&file)?)?;
+        std::fs::rename(&tmp, &self.index_path)?;
+        Ok(())
+    }
+}
+
+impl CacheStore for DiskStore {
+    fn track(&self, root: &Path) {
+        if self.inner.roots.contains(root) || !self.inner.roots.insert(root.to_path_buf()) {
+            return;
+        }
+        let (inner, root) = (self.inner.clone(), root.to_path_buf());
+        tokio::task::spawn_blocking(move || inner.scan(&root));
+    }
+
+    fn head<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, Option<ObjectMeta>> {
+        Box::pin(async move {
+            match tokio::fs::metadata(path).await {
+                Ok(metadata) if metadata.is_file() => Ok(Some(ObjectMeta {
+                    len: metadata.len(),
+                    modified: metadata.modified()?,
+                })),
+                Ok(_) => Ok(None),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    // 文件被外部删除时同步索引
+                    self.inner.index.lock().remove(path);
+                    Ok(None)
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn read<'a>(&'a self, path: &'a Path, start: u64, end: u64) -> StoreFuture<'a, ByteStream> {
+        Box::pin(async move {
+            let mut file = tokio::fs::File::open(path).await?;
+            file.seek(SeekFrom::Start(start)).await?;
+            self.inner.touch(path);
+            Ok(file_stream(file.take(end.saturating_sub(start))).boxed())
+        })
+    }
+
+    fn staging_dir(&self, path: &Path) -> PathBuf {
+        path.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+
+    fn commit<'a>(
+        &'a self,
+        path: &'a Path,
+        staged: &'a Path,
+        len: u64,
+        sha256: &'a str,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            tokio::fs::rename(staged, path).await?;
+            let entry = IndexEntry {
+                len,
+                accessed: unix_secs(SystemTime::now()),
+                sha256: Some(sha256.to_string()),
+                pinned: false,
+            };
+            let over_budget = {
+                let mut index = self.inner.index.lock();
+                index.insert(path.to_path_buf(), entry);
+                self.inner.max_bytes.is_some_and(|max| index.bytes > max)
+            };
+            if over_budget && !self.inner.evicting.swap(true, Ordering::AcqRel) {
+                let inner = self.inner.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Some(max_bytes) = inner.max_bytes {
+                        inner.evict_lru(None, max_bytes);
+                    }
+                    inner.evicting.store(false, Ordering::Release);
+                });
+            }
+            Ok(())
+        })
+    }
+
+    fn evict<'a>(&'a self, root: &'a Path, max_bytes: u64) -> StoreFuture<'a, usize> {
+        let (inner, root) = (self.inner.clone(), root.to_path_buf());
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || inner.evict_lru(Some(&root), max_bytes))
+                .await
+                .map_err(std::io::Error::other)
+        })
+    }
+
+    fn list(&self) -> StoreFuture<'_, Vec<CachedObject>> {
+        let mut objects: Vec<CachedObject> = self
+            .inner
+            .index
+            .lock()
+            .entries
+            .iter()
+            .map(|(path, entry)| CachedObject {
+                path: path.clone(),
+                size: entry.len,
+                sha256: entry.sha256.clone(),
+                pinned: entry.pinned,
+                accessed_at: entry.accessed,
+            })
+            .collect();
+        objects.sort_by(|a, b| a.path.cmp(&b.path));
+        Box::pin(async move { Ok(objects) })
+    }
+
+    /// 只删除索引中的文件，不会删除缓存目录以外的文件
+    fn remove<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, bool> {
+        Box::pin(async move {
+            if self.inner.index.lock().remove(path).is_none() {
+                return Ok(false);
+            }
+            match tokio::fs::remove_file(path).await {
+                Ok(()) => Ok(true),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    fn pin(&self, path: &Path, pinned: bool) -> std::io::Result<bool> {
+        let mut index = self.inner.index.lock();
+        let Some(entry) = index.entries.get_mut(path) else {
+            return Ok(false);
+        };
+        entry.pinned = pinned;
+        index.dirty = true;
+        Ok(true)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        self.inner.save()
+    }
+
+    fn render_prometheus(&self, out: &mut String) {
+        let (bytes, objects) = {
+            let index = self.inner.index.lock();
+            (index.bytes, index.entries.len())
+        };
+        let _ = writeln!(
+            out,
+            "# HELP proxy_cache_disk_bytes Total size of indexed disk cache files"
+        );
+        let _ = writeln!(out, "# TYPE proxy_cache_disk_bytes gauge");
+        let _ = writeln!(out, "proxy_cache_disk_bytes {}", bytes);
+        let _ = writeln!(
+            out,
+            "# HELP proxy_cache_disk_objects Number of indexed disk cache files"
+        );
+        let _ = writeln!(out, "# TYPE proxy_cache_disk_objects gauge");
+        let _ = writeln!(out, "proxy_cache_disk_objects {}", objects);
+        if let Some(max_bytes) = self.inner.max_bytes {
+            let _ = writeln!(
+                out,
+                "# HELP proxy_cache_disk_max_bytes Configured disk cache size limit"
+            );
+            let _ = writeln!(out, "# TYPE proxy_cache_disk_max_bytes gauge");
+            let _ = writeln!(out, "proxy_cache_disk_max_bytes {}", max_bytes);
+        }
+        let _ = writeln!(
+            out,
+            "# HELP proxy_cache_evictions_total Number of cache files evicted for size limits"
+        );
+        let _ = writeln!(out, "# TYPE proxy_cache_evictions_total counter");
+        let _ = writeln!(
+            out,
+            "proxy_cache_evictions_total {}",
+            self.inner.evictions.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP proxy_cache_evicted_bytes_total Bytes of cache files evicted for size limits"
+        );
+        let _ = writeln!(out, "# TYPE proxy_cache_evicted_bytes_total counter");
+        let _ = writeln!(
+            out,
+            "proxy_cache_evicted_bytes_total {}",
+            self.inner.evicted_bytes.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// 进程内存存储，总大小超过上限时删除最早缓存的对象
+pub struct MemoryStore {
+    objects: DashMap<PathBuf, MemoryObject>,
+    size: AtomicU64,
+    max_bytes: u64,
+}
+
+struct MemoryObject {
+    data: Bytes,
+    modified: SystemTime,
+    sha256: String,
+    pinned: bool,
+}
+
+impl MemoryStore {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            objects: DashMap::new(),
+            size: AtomicU64::new(0),
+            max_bytes,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> bool {
+        match self.objects.remove(path) {
+            Some((_, object)) => {
+                self.size
+                    .fetch_sub(object.data.len() as u64, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// root 下（None 表示全部）的对象总大小超过 max_bytes 时删除最早缓存的未固定对象
+    fn evict_under(&self, root: Option<&Path>, max_bytes: u64) -> usize {
+        let mut total = 0;
+        let mut objects: Vec<(SystemTime, u64, PathBuf)> = self
+            .objects
+            .iter()
+            .filter(|entry| root.is_none_or(|root| entry.key().starts_with(root)))
+            .inspect(|entry| total += entry.value().data.len() as u64)
+            .filter(|entry| !entry.value().pinned)
+            .map(|entry| {
+                let object = entry.value();
+                (
+                    object.modified,
+                    object.data.len() as u64,
+                    entry.key().clone(),
+                )
+            })
+            .collect();
+        if total <= max_bytes {
+            return 0;
+        }
+        let target = max_bytes / 100 * LOW_WATERMARK_PERCENT;
+        objects.sort_by_key(|(modified, _, _)| *modified);
+        let mut removed = 0;
+        for (_, len, path) in objects {
+            if total <= target {
+                break;
+            }
+            if self.remove(&path) {
+                total = total.saturating_sub(len);
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn head<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, Option<ObjectMeta>> {
+        let meta = self.objects.get(path).map(|object| ObjectMeta {
+            len: object.data.len() as u64,
+            modified: object.modified,
+        });
+        Box::pin(async move { Ok(meta) })
+    }
+
+    fn read<'a>(&'a self, path: &'a Path, start: u64, end: u64) -> StoreFuture<'a, ByteStream> {
+        let data = self.objects.get(path).map(|object| object.data.clone());
+        Box::pin(async move {
+            let data = data.ok_or_else(|| std::io::Error::from(std::io::ErrorKind::NotFound))?;
+            let end = (end as usize).min(data.len());
+            let start = (start as usize).min(end);
+            Ok(stream::once(async move { Ok(data.slice(start..end)) }).boxed())
+        })
+    }
+
+    fn staging_dir(&self, _path: &Path) -> PathBuf {
+        temp_staging_dir()
+    }
+
+    fn commit<'a>(
+        &'a self,
+        path: &'a Path,
+        staged: &'a Path,
+        len: u64,
+        sha256: &'a str,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            if len > self.max_bytes {
+                return Err(std::io::Error::other(
+                    "object exceeds the memory cache size",
+                ));
+            }
+            let data = Bytes::from(tokio::fs::read(staged).await?);
+            self.size.fetch_add(data.len() as u64, Ordering::Relaxed);
+            let mut object = MemoryObject {
+                data,
+                modified: SystemTime::now(),
+                sha256: sha256.to_string(),
+                pinned: false,
+            };
+            if let Some(old) = self.objects.get(path) {
+                object.pinned = old.pinned;
+            }
+            if let Some(old) = self.objects.insert(path.to_path_buf(), object) {
+                self.size
+                    .fetch_sub(old.data.len() as u64, Ordering::Relaxed);
+            }
+            if self.size.load(Ordering::Relaxed) > self.max_bytes {
+                self.evict_under(None, self.max_bytes);
+            }
+            Ok(())
+        })
+    }
+
+    fn evict<'a>(&'a self, root: &'a Path, max_bytes: u64) -> StoreFuture<'a, usize> {
+        let removed = self.evict_under(Some(root), max_bytes);
+        Box::pin(async move { Ok(removed) })
+    }
+
+    fn list(&self) -> StoreFuture<'_, Vec<CachedObject>> {
+        let mut objects: Vec<CachedObject> = self
+            .objects
+            .iter()
+            .map(|entry| {
+                let object = entry.value();
+                CachedObject {
+                    path: entry.key().clone(),
+                    size: object.data.len() as u64,
+                    sha256: Some(object.sha256.clone()),
+                    pinned: object.pinned,
+                    accessed_at: unix_secs(object.modified),
+                }
+            })
+            .collect();
+        objects.sort_by(|a, b| a.path.cmp(&b.path));
+        Box::pin(async move { Ok(objects) })
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, bool> {
+        let removed = MemoryStore::remove(self, path);
+        Box::pin(async move { Ok(removed) })
+    }
+
+    fn pin(&self, path: &Path, pinned: bool) -> std::io::Result<bool> {
+        Ok(self
+            .objects
+            .get_mut(path)
+            .map(|mut object| object.pinned = pinned)
+            .is_some())
+    }
+}
@@ -0,0 +1,434 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::api::{
+    self, ApiResponse, CreateRuleRequest, ToggleRuleRequest, UpdateConfigRequest, UpdateRuleRequest,
+};
+use crate::backup::{self, ConfigBackup};
+use crate::db::{PendingChange, RuleImport};
+use crate::rules_file;
+use crate::AdminState;
+
+/// 变更审批设置
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeControl {
+    pub enabled: bool,
+    /// 提交人自己审批前需等待的秒数，其他管理员可随时审批
+    pub approval_delay_secs: u64,
+}
+
+/// 需要审批的变更请求
+enum Mutation {
+    CreateRule(CreateRuleRequest),
+    UpdateRule(i64, UpdateRuleRequest),
+    DeleteRule(i64),
+    ToggleRule(i64, ToggleRuleRequest),
+    ImportRules(Vec<RuleImport>),
+    UpdateConfig(String, UpdateConfigRequest),
+    Restore(Box<ConfigBackup>),
+}
+
+impl Mutation {
+    /// 不需要审批的请求返回 None，请求体或路径参数无效时返回 Some(Err)
+    fn parse(method: &str, path: &str, payload: &Value) -> Option<Result<Self, StatusCode>> {
+        let segments: Vec<&str> = path.strip_prefix("/api/")?.split('/').collect();
+        let id = |s: &str| s.parse::<i64>().map_err(|_| StatusCode::BAD_REQUEST);
+        Some(match (method, segments.as_slice()) {
+            ("POST", ["rules"]) => body(payload).map(Self::CreateRule),
+            ("POST", ["rules", "import"]) => body(payload).map(Self::ImportRules),
+            ("PUT", ["rules", rule_id]) => {
+                id(rule_id).and_then(|rule_id| Ok(Self::UpdateRule(rule_id, body(payload)?)))
+            }
+            ("DELETE", ["rules", rule_id]) => id(rule_id).map(Self::DeleteRule),
+            ("POST", ["rules", rule_id, "toggle"]) => {
+                id(rule_id).and_then(|rule_id| Ok(Self::ToggleRule(rule_id, body(payload)?)))
+            }
+            ("PUT", ["configs", key]) => {
+                body(payload).map(|req| Self::UpdateConfig(key.to_string(), req))
+            }
+            ("POST", ["restore"]) => body(payload).map(Self::Restore),
+            _ => return None,
+        })
+    }
+
+    /// 通过对应的 API 处理函数执行变更；处理函数返回非 2xx 响应（如规则集校验未通过）时视为失败，
+    /// 错误为状态码与响应中的消息
+    async fn apply(self, state: AdminState) -> Result<(), String> {
+        let state = State(state);
+        let resp = match self {
+            Self::CreateRule(req) => api::create_rule(state, Json(req)).await,
+            Self::UpdateRule(id, req) => api::update_rule(state, Path(id), Json(req)).await,
+            Self::DeleteRule(id) => api::delete_rule(state, Path(id)).await,
+            Self::ToggleRule(id, req) => api::toggle_rule(state, Path(id), Json(req)).await,
+            Self::ImportRules(rules) => api::import_rules(state, Json(rules)).await,
+            Self::UpdateConfig(key, req) => api::update_config(state, Path(key), Json(req))
+                .await
+                .map(IntoResponse::into_response),
+            Self::Restore(backup) => backup::restore_handler(state, Json(*backup)).await,
+        }
+        .map_err(|status| status.to_string())?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let message = axum::body::to_bytes(resp.into_body(), 1024 * 1024)
+            .await
+            .ok()
+            .and_then(|body| serde_json::from_slice::<ApiResponse<Value>>(&body).ok())
+            .and_then(|body| body.message);
+        Err(match message {
+            Some(message) => format!("{}: {}", status, message),
+            None => status.to_string(),
+        })
+    }
+}
+
+fn body<T: for<'de> Deserialize<'de>>(payload: &Value) -> Result<T, StatusCode> {
+    serde_json::from_value(payload.clone()).map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+/// 开启审批后，规则与系统配置的变更请求（含从备份恢复）保存为待审批变更并返回 202
+pub async fn approval_middleware(
+    State(state): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let method = req.method().as_str().to_string();
+    let path = req.uri().path().to_string();
+    if !state.changes.enabled || Mutation::parse(&method, &path, &Value::Null).is_none() {
+        return next.run(req).await;
+    }
+    // 规则由规则文件管理时不进入审批
+    if path.starts_with("/api/rules") || path == "/api/restore" {
+        if let Err(status) = rules_file::check_writable(&state) {
+            return status.into_response();
+        }
+    }
+
+    let requested_by = state
+        .auth
+        .session_username(req.headers())
+        .unwrap_or_default();
+    let bytes = match axum::body::to_bytes(req.into_body(), 1024 * 1024).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let payload = if bytes.is_empty() {
+        Value::Null
+    } else {
+        match serde_json::from_slice(&bytes) {
+            Ok(payload) => payload,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    };
+    // 提交时即校验请求体，避免审批时才发现无效
+    if let Some(Err(status)) = Mutation::parse(&method, &path, &payload) {
+        return status.into_response();
+    }
+
+    let now = Utc::now().timestamp();
+    match state.db.create_pending_change(
+        &method,
+        &path,
+        &payload,
+        &requested_by,
+        now,
+        now + state.changes.approval_delay_secs as i64,
+    ) {
+        Ok(change) => {
+            tracing::info!(id = change.id, method = %method, path = %path, requested_by = %requested_by, "Change submitted for approval");
+            (StatusCode::ACCEPTED, Json(ApiResponse::ok(change))).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to create pending change: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListChangesQuery {
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+pub async fn list_handler(
+    State(state): State<AdminState>,
+    Query(query): Query<ListChangesQuery>,
+) -> Result<Json<ApiResponse<Vec<PendingChange>>>, StatusCode> {
+    state
+        .db
+        .list_pending_changes(query.status.as_deref())
+        .map(|changes| Json(ApiResponse::ok(changes)))
+        .map_err(|e| {
+            tracing::error!("Failed to list changes: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 审批并应用变更：提交人本人需等待审批延迟后才能审批
+pub async fn approve_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<PendingChange>>, StatusCode> {
+    let approver = state.auth.session_username(&headers).unwrap_or_default();
+    let change = get_change(&state, id)?;
+    if change.status != "pending" {
+        return Err(StatusCode::CONFLICT);
+    }
+    if approver == change.requested_by && Utc::now().timestamp() < change.approvable_at {
+        return Err(StatusCode::CONFLICT);
+    }
+    // 先标记为执行中，避免并发审批重复应用
+    if !transition(&state, id, "pending", "applying", &approver, None)? {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let result = match Mutation::parse(&change.method, &change.path, &change.payload) {
+        Some(Ok(mutation)) => mutation.apply(state.clone()).await,
+        Some(Err(status)) => Err(status.to_string()),
+        None => Err(StatusCode::BAD_REQUEST.to_string()),
+    };
+    let (status, error) = match result {
+        Ok(()) => ("applied", None),
+        Err(error) => ("failed", Some(error)),
+    };
+    transition(&state, id, "applying", status, &approver, error.as_deref())?;
+    tracing::info!(id, approver = %approver, status, "Change approved");
+    get_change(&state, id).map(|change| Json(ApiResponse::ok(change)))
+}
+
+pub async fn reject_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<PendingChange>>, StatusCode> {
+    let approver = state.auth.session_username(&headers).unwrap_or_default();
+    get_change(&state, id)?;
+    if !transition(&state, id, "pending", "rejected", &approver, None)? {
+        return Err(StatusCode::CONFLICT);
+    }
+    tracing::info!(id, approver = %approver, "Change rejected");
+    get_change(&state, id).map(|change| Json(ApiResponse::ok(change)))
+}
+
+fn get_change(state: &AdminState, id: i64) -> Result<PendingChange, StatusCode> {
+    state
+        .db
+        .get_pending_change(id)
+        .map_err(|e| {
+            tracing::error!("Failed to get change: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// 按状态迁移变更，当前状态不是 `from` 时返回 false
+fn transition(
+    state: &AdminState,
+    id: i64,
+    from: &str,
+    to: &str,
+    decided_by: &str,
+    error: Option<&str>,
+) -> Result<bool, StatusCode> {
+    state
+        .db
+        .transition_pending_change(id, from, to, decided_by, error, Utc::now().timestamp())
+        .map_err(|e| {
+            tracing::error!("Failed to update change: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Role;
+    use crate::testing;
+    use serde_json::json;
+
+    /// 直接以待审批变更的形式提交请求，返回变更 ID
+    fn submit(state: &AdminState, method: &str, path: &str, payload: Value) -> i64 {
+        state
+            .db
+            .create_pending_change(method, path, &payload, "alice", 0, 0)
+            .unwrap()
+            .id
+    }
+
+    async fn approve(state: &AdminState, id: i64) -> PendingChange {
+        approve_handler(State(state.clone()), Path(id), HeaderMap::new())
+            .await
+            .unwrap()
+            .0
+            .data
+            .unwrap()
+    }
+
+    /// 以指定管理员的 session 发起请求的请求头
+    fn session(state: &AdminState, username: &str) -> HeaderMap {
+        let token = state
+            .auth
+            .create_session(username, Role::Admin, Vec::new(), &HeaderMap::new(), None)
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn parses_only_approvable_requests() {
+        let rule = json!({ "name": "api", "source": "/api/{*path}", "target": "http://a/{*path}" });
+        assert!(matches!(
+            Mutation::parse("POST", "/api/rules", &rule),
+            Some(Ok(Mutation::CreateRule(_)))
+        ));
+        assert!(matches!(
+            Mutation::parse("DELETE", "/api/rules/3", &Value::Null),
+            Some(Ok(Mutation::DeleteRule(3)))
+        ));
+        assert!(matches!(
+            Mutation::parse("PUT", "/api/rules/abc", &rule),
+            Some(Err(StatusCode::BAD_REQUEST))
+        ));
+        assert!(matches!(
+            Mutation::parse("POST", "/api/rules", &json!({ "name": 1 })),
+            Some(Err(StatusCode::BAD_REQUEST))
+        ));
+        assert!(Mutation::parse("GET", "/api/rules", &Value::Null).is_none());
+        assert!(Mutation::parse("POST", "/api/changes/1/approve", &Value::Null).is_none());
+        assert!(Mutation::parse("POST", "/rules", &rule).is_none());
+    }
+
+    #[tokio::test]
+    async fn requester_waits_for_delay_and_others_approve_once() {
+        let state = testing::admin_state(&testing::config());
+        let rule = json!({ "name": "api", "source": "/api/{*path}", "target": "http://a/{*path}" });
+        let now = Utc::now().timestamp();
+        let id = state
+            .db
+            .create_pending_change("POST", "/api/rules", &rule, "alice", now, now + 3600)
+            .unwrap()
+            .id;
+
+        let alice = session(&state, "alice");
+        let resp = approve_handler(State(state.clone()), Path(id), alice).await;
+        assert_eq!(resp.err(), Some(StatusCode::CONFLICT));
+        assert_eq!(state.db.get_all_rules().unwrap().len(), 0);
+
+        let bob = session(&state, "bob");
+        let change = approve_handler(State(state.clone()), Path(id), bob.clone())
+            .await
+            .unwrap()
+            .0
+            .data
+            .unwrap();
+        assert_eq!(change.status, "applied");
+        assert_eq!(change.decided_by.as_deref(), Some("bob"));
+        assert_eq!(state.db.get_all_rules().unwrap().len(), 1);
+
+        // 已处理的变更不能再次审批或拒绝
+        let again = approve_handler(State(state.clone()), Path(id), bob.clone()).await;
+        assert_eq!(again.err(), Some(StatusCode::CONFLICT));
+        let reject = reject_handler(State(state.clone()), Path(id), bob).await;
+        assert_eq!(reject.err(), Some(StatusCode::CONFLICT));
+        assert_eq!(state.db.get_all_rules().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn requester_can_approve_after_delay() {
+        let state = testing::admin_state(&testing::config());
+        let rule = json!({ "name": "api", "source": "/api/{*path}", "target": "http://a/{*path}" });
+        let id = submit(&state, "POST", "/api/rules", rule);
+        let alice = session(&state, "alice");
+        let change = approve_handler(State(state.clone()), Path(id), alice)
+            .await
+            .unwrap()
+            .0
+            .data
+            .unwrap();
+        assert_eq!(change.status, "applied");
+    }
+
+    #[tokio::test]
+    async fn rejected_change_is_not_applied() {
+        let state = testing::admin_state(&testing::config());
+        let rule = json!({ "name": "api", "source": "/api/{*path}", "target": "http://a/{*path}" });
+        let id = submit(&state, "POST", "/api/rules", rule);
+        let bob = session(&state, "bob");
+        let change = reject_handler(State(state.clone()), Path(id), bob.clone())
+            .await
+            .unwrap()
+            .0
+            .data
+            .unwrap();
+        assert_eq!(change.status, "rejected");
+
+        let resp = approve_handler(State(state.clone()), Path(id), bob).await;
+        assert_eq!(resp.err(), Some(StatusCode::CONFLICT));
+        assert_eq!(state.db.get_all_rules().unwrap().len(), 0);
+        let missing = reject_handler(State(state.clone()), Path(id + 1), HeaderMap::new()).await;
+        assert_eq!(missing.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn rejected_rule_change_is_recorded_as_failed() {
+        let state = testing::admin_state(&testing::config());
+        let rule = json!({ "name": "api", "source": "/api/{*path}", "target": "http://a/{*path}" });
+        let first = submit(&state, "POST", "/api/rules", rule.clone());
+        assert_eq!(approve(&state, first).await.status, "applied");
+
+        // 与已有规则相同的来源会被前面的规则完全遮蔽，规则集校验不通过
+        let conflicting = submit(&state, "POST", "/api/rules", rule);
+        let change = approve(&state, conflicting).await;
+        assert_eq!(change.status, "failed");
+        assert!(change.error.unwrap().starts_with("422"));
+        assert_eq!(state.db.get_all_rules().unwrap().len(), 1);
+        assert_eq!(state.rules.load().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn restore_requires_approval() {
+        let mut config = testing::config();
+        config.change_approval.enabled = true;
+        let state = testing::admin_state(&config);
+        let mut app = axum::Router::new()
+            .route(
+                "/api/restore",
+                axum::routing::post(|| async { StatusCode::OK }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                approval_middleware,
+            ))
+            .with_state(state.clone());
+        let backup = json!({ "schema_version": 1, "rules": [], "system_config": [] });
+        let req = Request::post("/api/restore")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(backup.to_string()))
+            .unwrap();
+
+        std::future::poll_fn(|cx| tower::Service::<Request>::poll_ready(&mut app, cx))
+            .await
+            .unwrap();
+        let resp = tower::Service::call(&mut app, req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let pending = state.db.list_pending_changes(Some("pending")).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].path, "/api/restore");
+    }
+}
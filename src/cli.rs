@@ -0,0 +1,483 @@
+use anyhow::Context;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::io::Read;
+
+use crate::api::{self, ApiResponse};
+use crate::config::Config;
+use crate::db::{Database, ImportSummary, ProxyRule, RuleImport, RuleInput, RuleOptions};
+use crate::listeners::LISTENERS_KEY;
+use crate::pipeline::Pipeline;
+use crate::reloads;
+use crate::signed_urls;
+
+const USAGE: &str = "\
+Usage: proxy-server [OPTIONS] <COMMAND>
+
+Commands:
+  rules list [--json]                     List rules
+  rules add <name> <source> <target>      Create a rule [--timeout SECS] [--options JSON]
+  rules rm <id>                           Delete a rule
+  rules toggle <id> [on|off]              Enable/disable a rule, flips it when state is omitted
+  config get [key]                        Show system configs
+  config set <key> <value>                Update a system config
+  export [file]                           Export all rules as JSON (stdout by default)
+  import <file|->                         Replace rules with a JSON/YAML list, matched by name
+
+Options:
+  --config <path>    Config file used to locate the local database [default: config.yaml]
+  --api <url>        Talk to a running instance's admin API instead of the local database
+                     (env: PROXY_API_URL)
+  --token <token>    Admin API session token from /api/login (env: PROXY_API_TOKEN)
+
+Without any command the proxy server is started.";
+
+const COMMANDS: &[&str] = &["rules", "config", "export", "import", "help"];
+
+enum Command {
+    Help,
+    RulesList { json: bool },
+    RulesAdd(Box<RuleImport>),
+    RulesRemove(i64),
+    RulesToggle(i64, Option<bool>),
+    ConfigGet(Option<String>),
+    ConfigSet(String, String),
+    Export(Option<String>),
+    Import(String),
+}
+
+/// 命令行管理子命令，供 Ansible 等脚本在无界面环境下管理规则与系统配置；
+/// 默认直接读写本地数据库，指定 `--api` 时通过运行中实例的管理接口操作（立即生效，受变更审批约束）
+pub struct Cli {
+    command: Command,
+    config: String,
+    api: Option<String>,
+    token: Option<String>,
+}
+
+impl Cli {
+    /// 解析命令行参数，没有子命令时返回 None，由调用方启动代理服务
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Option<Self>> {
+        let mut config = "config.yaml".to_string();
+        let mut api = std::env::var("PROXY_API_URL").ok();
+        let mut token = std::env::var("PROXY_API_TOKEN").ok();
+        let mut json = false;
+        let mut timeout_secs = 30;
+        let mut options = RuleOptions::default();
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next()
+                    .with_context(|| format!("{} requires a value", flag))
+            };
+            match arg.as_str() {
+                "--config" => config = value("--config")?,
+                "--api" => api = Some(value("--api")?),
+                "--token" => token = Some(value("--token")?),
+                "--json" => json = true,
+                "--timeout" => {
+                    timeout_secs = value("--timeout")?
+                        .parse()
+                        .context("--timeout must be a number of seconds")?
+                }
+                "--options" => {
+                    options = serde_json::from_str(&value("--options")?)
+                        .context("--options must be a JSON object of rule options")?
+                }
+                "-h" | "--help" => positional.insert(0, "help".to_string()),
+                _ => positional.push(arg),
+            }
+        }
+
+        let Some(first) = positional.first() else {
+            return Ok(None);
+        };
+        if !COMMANDS.contains(&first.as_str()) {
+            anyhow::bail!("unknown command: {}\n\n{}", first, USAGE);
+        }
+        let args: Vec<&str> = positional.iter().map(String::as_str).collect();
+        let id = |s: &str| s.parse::<i64>().context("rule id must be a number");
+        let command = match args.as_slice() {
+            ["help", ..] => Command::Help,
+            ["rules", "list"] => Command::RulesList { json },
+            ["rules", "add", name, source, target] => Command::RulesAdd(Box::new(RuleImport {
+                name: name.to_string(),
+                source: source.to_string(),
+                target: target.to_string(),
+                timeout_secs,
+                enabled: true,
+                options,
+            })),
+            ["rules", "rm", rule_id] => Command::RulesRemove(id(rule_id)?),
+            ["rules", "toggle", rule_id] => Command::RulesToggle(id(rule_id)?, None),
+            ["rules", "toggle", rule_id, state] => {
+                let enabled = match *state {
+                    "on" | "true" | "enable" => true,
+                    "off" | "false" | "disable" => false,
+                    other => anyhow::bail!("invalid state: {}, expected on or off", other),
+                };
+                Command::RulesToggle(id(rule_id)?, Some(enabled))
+            }
+            ["config", "get"] => Command::ConfigGet(None),
+            ["config", "get", key] => Command::ConfigGet(Some(key.to_string())),
+            ["config", "set", key, value] => Command::ConfigSet(key.to_string(), value.to_string()),
+            ["export"] => Command::Export(None),
+            ["export", file] => Command::Export(Some(file.to_string())),
+            ["import", file] => Command::Import(file.to_string()),
+            _ => anyhow::bail!("invalid arguments: {}\n\n{}", args.join(" "), USAGE),
+        };
+        Ok(Some(Self {
+            command,
+            config,
+            api,
+            token,
+        }))
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        if let Command::Help = self.command {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        let backend = match self.api {
+            Some(url) => Backend::Api(AdminApi::new(url, self.token)?),
+            None => {
+                let config = Config::load(&self.config)
+                    .with_context(|| format!("failed to load {}", self.config))?;
+                Backend::Local(
+                    Database::new(&config.database.path)?,
+                    config.plugins.into_keys().collect(),
+                )
+            }
+        };
+
+        let local_write = matches!(backend, Backend::Local(..))
+            && !matches!(
+                self.command,
+                Command::RulesList { .. } | Command::ConfigGet(_) | Command::Export(_)
+            );
+        match self.command {
+            Command::Help => {}
+            Command::RulesList { json } => {
+                let rules = backend.rules().await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&rules)?);
+                } else {
+                    print_rules(&rules);
+                }
+            }
+            Command::RulesAdd(rule) => {
+                if let Some(id) = backend.create_rule(&rule).await? {
+                    println!("Created rule {}", id);
+                }
+            }
+            Command::RulesRemove(id) => {
+                if backend.delete_rule(id).await?.is_some() {
+                    println!("Deleted rule {}", id);
+                }
+            }
+            Command::RulesToggle(id, enabled) => {
+                let enabled = match enabled {
+                    Some(enabled) => enabled,
+                    None => {
+                        let rules = backend.rules().await?;
+                        let rule = rules
+                            .iter()
+                            .find(|rule| rule.id == id)
+                            .with_context(|| format!("rule {} not found", id))?;
+                        !rule.enabled
+                    }
+                };
+                if backend.toggle_rule(id, enabled).await?.is_some() {
+                    let state = if enabled { "Enabled" } else { "Disabled" };
+                    println!("{} rule {}", state, id);
+                }
+            }
+            Command::ConfigGet(key) => {
+                let configs = backend.configs().await?;
+                match key {
+                    Some(key) => {
+                        let (_, value) = configs
+                            .iter()
+                            .find(|(k, _)| *k == key)
+                            .with_context(|| format!("config {} not set", key))?;
+                        println!("{}", value);
+                    }
+                    None => {
+                        for (key, value) in configs {
+                            println!("{}={}", key, value);
+                        }
+                    }
+                }
+            }
+            Command::ConfigSet(key, value) => {
+                if backend.set_config(&key, &value).await?.is_some() {
+                    println!("Updated {}", key);
+                }
+            }
+            Command::Export(file) => {
+                let output = serde_json::to_string_pretty(&backend.rules().await?)?;
+                match file {
+                    Some(path) => std::fs::write(&path, output + "\n")
+                        .with_context(|| format!("failed to write {}", path))?,
+                    None => println!("{}", output),
+                }
+            }
+            Command::Import(file) => {
+                let content = if file == "-" {
+                    let mut content = String::new();
+                    std::io::stdin().read_to_string(&mut content)?;
+                    content
+                } else {
+                    std::fs::read_to_string(&file)
+                        .with_context(|| format!("failed to read {}", file))?
+                };
+                // YAML 兼容 JSON，导出的文件与手写的 YAML 都可导入
+                let rules: Vec<RuleImport> =
+                    serde_yaml::from_str(&content).context("invalid rule list")?;
+                if let Some(summary) = backend.import_rules(&rules).await? {
+                    println!(
+                        "Imported {} rules: {} created, {} updated, {} deleted",
+                        rules.len(),
+                        summary.created,
+                        summary.updated,
+                        summary.deleted
+                    );
+                }
+            }
+        }
+        if local_write {
+            eprintln!("Note: a running server picks up local database changes after restart, use --api to apply them immediately");
+        }
+        Ok(())
+    }
+}
+
+fn print_rules(rules: &[ProxyRule]) {
+    println!(
+        "{:<6} {:<8} {:<24} {:<32} TARGET",
+        "ID", "ENABLED", "NAME", "SOURCE"
+    );
+    for rule in rules {
+        println!(
+            "{:<6} {:<8} {:<24} {:<32} {}",
+            rule.id, rule.enabled, rule.name, rule.source, rule.target
+        );
+    }
+}
+
+/// 写操作的返回值为 None 表示管理接口开启了变更审批，请求已提交待审批
+enum Backend {
+    /// 直接读写本地数据库，附带配置文件中的插件名用于校验规则
+    Local(Database, HashSet<String>),
+    Api(AdminApi),
+}
+
+impl Backend {
+    async fn rules(&self) -> anyhow::Result<Vec<ProxyRule>> {
+        match self {
+            Self::Local(db, _) => db.get_all_rules(),
+            Self::Api(api) => api
+                .call(Method::GET, "/api/rules/export", None)
+                .await?
+                .context("empty response"),
+        }
+    }
+
+    async fn create_rule(&self, rule: &RuleImport) -> anyhow::Result<Option<i64>> {
+        match self {
+            Self::Local(db, plugins) => {
+                check_options(db, plugins, &rule.options)?;
+                db.create_rule(
+                    &RuleInput {
+                        name: &rule.name,
+                        source: &rule.source,
+                        target: &rule.target,
+                        timeout_secs: rule.timeout_secs,
+                        options: Some(&rule.options),
+                    },
+                    reloads::check,
+                )
+                .map(Some)
+            }
+            Self::Api(api) => {
+                let body = json!({
+                    "name": rule.name,
+                    "source": rule.source,
+                    "target": rule.target,
+                    "timeout_secs": rule.timeout_secs,
+                    "options": rule.options,
+                });
+                api.call(Method::POST, "/api/rules", Some(body)).await
+            }
+        }
+    }
+
+    async fn delete_rule(&self, id: i64) -> anyhow::Result<Option<()>> {
+        match self {
+            Self::Local(db, _) => {
+                if db.get_rule(id)?.is_none() {
+                    anyhow::bail!("rule {} not found", id);
+                }
+                db.delete_rule(id).map(Some)
+            }
+            Self::Api(api) => {
+                api.call(Method::DELETE, &format!("/api/rules/{}", id), None)
+                    .await
+            }
+        }
+    }
+
+    async fn toggle_rule(&self, id: i64, enabled: bool) -> anyhow::Result<Option<()>> {
+        match self {
+            Self::Local(db, _) => {
+                if db.get_rule(id)?.is_none() {
+                    anyhow::bail!("rule {} not found", id);
+                }
+                db.toggle_rule(id, enabled, reloads::check).map(Some)
+            }
+            Self::Api(api) => {
+                let body = json!({ "enabled": enabled });
+                api.call(
+                    Method::POST,
+                    &format!("/api/rules/{}/toggle", id),
+                    Some(body),
+                )
+                .await
+            }
+        }
+    }
+
+    async fn configs(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let configs = match self {
+            Self::Local(db, _) => db.get_all_configs()?,
+            Self::Api(api) => api
+                .call(Method::GET, "/api/configs", None)
+                .await?
+                .context("empty response")?,
+        };
+        Ok(configs
+            .into_iter()
+            .map(|config| (config.key, config.value))
+            .collect())
+    }
+
+    async fn set_config(&self, key: &str, value: &str) -> anyhow::Result<Option<()>> {
+        match self {
+            Self::Local(db, _) => {
+                // 与管理接口相同的限制：监听器只能通过 /api/listeners 修改
+                if key == LISTENERS_KEY {
+                    anyhow::bail!("{} can only be changed via /api/listeners", key);
+                }
+                if key == signed_urls::MODE_KEY {
+                    signed_urls::requires_signature(value)?;
+                }
+                db.set_config(key, value).map(Some)
+            }
+            Self::Api(api) => {
+                let body = json!({ "value": value });
+                api.call(Method::PUT, &format!("/api/configs/{}", key), Some(body))
+                    .await
+            }
+        }
+    }
+
+    async fn import_rules(&self, rules: &[RuleImport]) -> anyhow::Result<Option<ImportSummary>> {
+        match self {
+            Self::Local(db, plugins) => {
+                for rule in rules {
+                    check_options(db, plugins, &rule.options)
+                        .with_context(|| format!("rule {}", rule.name))?;
+                }
+                db.import_rules(rules, reloads::check).map(Some)
+            }
+            Self::Api(api) => {
+                api.call(
+                    Method::POST,
+                    "/api/rules/import",
+                    Some(serde_json::to_value(rules)?),
+                )
+                .await
+            }
+        }
+    }
+}
+
+/// 与管理接口相同的规则选项校验：引用的密钥与插件必须存在，处理管线能生成，出站代理设置有效
+fn check_options(
+    db: &Database,
+    plugins: &HashSet<String>,
+    options: &RuleOptions,
+) -> anyhow::Result<()> {
+    let secrets = db.list_secrets()?;
+    if let Some(name) = options
+        .secret_refs()
+        .find(|name| !secrets.iter().any(|secret| secret.name == *name))
+    {
+        anyhow::bail!("unknown secret: {}", name);
+    }
+    if let Some(name) = options.plugins.iter().find(|name| !plugins.contains(*name)) {
+        anyhow::bail!("unknown plugin: {}", name);
+    }
+    Pipeline::build(options)?;
+    api::validate_upstream_proxy(options).map_err(anyhow::Error::msg)
+}
+
+/// 管理接口客户端，使用 `/api/login` 返回的会话令牌认证（Bearer 请求不需要 CSRF 令牌）
+struct AdminApi {
+    client: reqwest::Client,
+    base: String,
+    token: Option<String>,
+}
+
+impl AdminApi {
+    fn new(base: String, token: Option<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().build()?,
+            base: base.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// 开启变更审批时写请求返回 202，提示后返回 None
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> anyhow::Result<Option<T>> {
+        let mut req = self
+            .client
+            .request(method, format!("{}{}", self.base, path));
+        if let Some(ref token) = self.token {
+            req = req.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            req = req.json(&body);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        if status == StatusCode::ACCEPTED {
+            let change: ApiResponse<Value> = resp.json().await?;
+            let id = change.data.as_ref().and_then(|change| change.get("id"));
+            eprintln!(
+                "Change submitted for approval (id {})",
+                id.map(Value::to_string).unwrap_or_default()
+            );
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("{} {}: {}", status, path, text.trim());
+        }
+        // 无返回数据的操作 data 为 null，按 () 解析
+        let resp: ApiResponse<Value> = resp.json().await?;
+        Ok(Some(serde_json::from_value(
+            resp.data.unwrap_or(Value::Null),
+        )?))
+    }
+}
@@ -0,0 +1,577 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dashmap::DashMap;
+use reqwest::Client;
+use ring::digest;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::api::ApiResponse;
+use crate::backup;
+use crate::config::ClusterConfig;
+use crate::db::{Database, ProxyKey, ProxyRule, SystemConfig};
+use crate::endpoints::constant_time_eq;
+use crate::reloads;
+use crate::secrets::SYSTEM_PREFIX;
+use crate::tasks::TaskRegistry;
+use crate::AdminState;
+
+/// 节点间令牌请求头
+const TOKEN_HEADER: &str = "x-cluster-token";
+
+/// 只能在主节点执行的写操作路径
+const LEADER_WRITES: &[&str] = &[
+    "/api/rules",
+    "/api/configs",
+    "/api/listeners",
+    "/api/restore",
+    "/api/changes",
+    "/api/secrets",
+    "/api/proxy-keys",
+];
+
+/// gossip 中交换的节点信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberInfo {
+    pub id: String,
+    pub url: String,
+    /// 规则与系统配置的摘要，与主节点不一致的节点需要同步
+    pub state_hash: String,
+}
+
+/// 节点间交换的 gossip 消息：发送方自身与它所知的存活节点
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gossip {
+    pub from: MemberInfo,
+    pub members: Vec<MemberInfo>,
+}
+
+/// 主节点的规则、系统配置、密钥与 API Key，其余节点整体同步
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterSnapshot {
+    pub rules: Vec<ProxyRule>,
+    pub system_config: Vec<SystemConfig>,
+    /// 规则引用的密钥，不含各节点自己的内部密钥（system.）
+    #[serde(default)]
+    pub secrets: Vec<SyncedSecret>,
+    #[serde(default)]
+    pub proxy_keys: Vec<SyncedKey>,
+}
+
+/// 同步的密钥，value 保持加密，各节点需配置相同的 secrets.key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncedSecret {
+    pub name: String,
+    pub value: String,
+}
+
+/// 同步的 API Key，只包含摘要
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncedKey {
+    pub id: i64,
+    pub name: String,
+    pub key_hash: String,
+    pub prefix: String,
+    pub rule_id: Option<i64>,
+    pub created_at: String,
+}
+
+#[derive(Debug)]
+struct Member {
+    /// 通过 gossip 间接得知、尚未联系上的节点只有地址
+    info: Option<MemberInfo>,
+    last_seen: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// 集群成员与主节点选举：id 最小的存活节点为主节点，各节点根据自己看到的成员独立判断
+#[derive(Clone)]
+pub struct Cluster {
+    config: Arc<ClusterConfig>,
+    id: String,
+    url: String,
+    /// 按管理接口地址索引的其他节点
+    members: Arc<DashMap<String, Member>>,
+    /// 本节点规则变更或发现主节点状态变化时立即开始一轮 gossip
+    wake: Arc<Notify>,
+}
+
+impl Cluster {
+    pub fn new(config: &ClusterConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        if config.token.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!("cluster.token is required when cluster is enabled");
+        }
+        let Some(url) = config.advertise_url.as_deref().map(normalize) else {
+            anyhow::bail!("cluster.advertise_url is required when cluster is enabled");
+        };
+        let members = DashMap::new();
+        for peer in config.peers.iter().map(|peer| normalize(peer)) {
+            if peer != url {
+                members.insert(peer, Member::unknown());
+            }
+        }
+        Ok(Some(Self {
+            config: Arc::new(config.clone()),
+            id: config.node_id.clone().unwrap_or_else(|| url.clone()),
+            url,
+            members: Arc::new(members),
+            wake: Arc::new(Notify::new()),
+        }))
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        match (self.config.token.as_deref(), headers.get(TOKEN_HEADER)) {
+            (Some(expected), Some(token)) if !expected.is_empty() => {
+                constant_time_eq(token.as_bytes(), expected.as_bytes())
+            }
+            _ => false,
+        }
+    }
+
+    fn alive(&self, member: &Member) -> bool {
+        member
+            .last_seen
+            .is_some_and(|seen| seen.elapsed() < Duration::from_secs(self.config.peer_timeout_secs))
+    }
+
+    /// 当前看到的主节点，为本节点时返回 None
+    fn leader(&self) -> Option<MemberInfo> {
+        self.members
+            .iter()
+            .filter(|member| self.alive(member))
+            .filter_map(|member| member.info.clone())
+            .filter(|info| info.id < self.id)
+            .min_by(|a, b| a.id.cmp(&b.id))
+    }
+
+    /// 本节点规则变更后立即通知其他节点
+    pub fn changed(&self) {
+        self.wake.notify_one();
+    }
+
+    /// 定期与所有已知节点交换 gossip，并在与主节点不一致时同步
+    pub fn start(&self, tasks: &TaskRegistry, client: Client, admin: AdminState) {
+        let interval = Duration::from_secs(self.config.gossip_interval_secs.max(1));
+        let cluster = self.clone();
+        let (periodic_client, periodic_admin) = (client.clone(), admin.clone());
+        tasks.spawn_periodic("cluster_gossip", interval, move || {
+            let cluster = cluster.clone();
+            let client = periodic_client.clone();
+            let admin = periodic_admin.clone();
+            async move { cluster.round(&client, &admin, interval).await }
+        });
+
+        let cluster = self.clone();
+        tokio::spawn(async move {
+            loop {
+                cluster.wake.notified().await;
+                if let Err(e) = cluster.round(&client, &admin, interval).await {
+                    tracing::warn!("Cluster gossip failed: {:#}", e);
+                }
+            }
+        });
+        tracing::info!(id = %self.id, url = %self.url, "Cluster mode enabled");
+    }
+
+    fn local(&self, admin: &AdminState) -> anyhow::Result<MemberInfo> {
+        Ok(MemberInfo {
+            id: self.id.clone(),
+            url: self.url.clone(),
+            state_hash: state_hash(&snapshot(admin)?)?,
+        })
+    }
+
+    fn gossip(&self, admin: &AdminState) -> anyhow::Result<Gossip> {
+        Ok(Gossip {
+            from: self.local(admin)?,
+            members: self
+                .members
+                .iter()
+                .filter(|member| self.alive(member))
+                .filter_map(|member| member.info.clone())
+                .collect(),
+        })
+    }
+
+    /// 记录直接联系上的节点，并加入它所知的其他节点
+    fn merge(&self, gossip: Gossip) {
+        for info in gossip.members {
+            if info.url != self.url {
+                self.members.entry(info.url).or_insert_with(Member::unknown);
+            }
+        }
+        let url = normalize(&gossip.from.url);
+        self.members.insert(
+            url,
+            Member {
+                info: Some(gossip.from),
+                last_seen: Some(Instant::now()),
+                last_error: None,
+            },
+        );
+    }
+
+    async fn round(
+        &self,
+        client: &Client,
+        admin: &AdminState,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        let gossip = self.gossip(admin)?;
+        let local_hash = gossip.from.state_hash.clone();
+        let urls: Vec<String> = self.members.iter().map(|m| m.key().clone()).collect();
+        let exchanges = urls.into_iter().map(|url| {
+            let request = client
+                .post(format!("{}/api/cluster/gossip", url))
+                .json(&gossip);
+            async move { (url, self.send::<Gossip>(request, timeout).await) }
+        });
+        for (url, result) in futures::future::join_all(exchanges).await {
+            match result {
+                Ok(reply) => {
+                    // 配置的地址与节点公布的地址不同时，以公布的地址为准
+                    if normalize(&reply.from.url) != url {
+                        self.members.remove(&url);
+                    }
+                    self.merge(reply)
+                }
+                Err(e) => {
+                    if let Some(mut member) = self.members.get_mut(&url) {
+                        member.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        }
+
+        let Some(leader) = self.leader() else {
+            return Ok(());
+        };
+        if leader.state_hash == local_hash {
+            return Ok(());
+        }
+        let request = client.get(format!("{}/api/cluster/state", leader.url));
+        let snapshot: ClusterSnapshot = self.send(request, timeout).await?;
+        self.apply(admin, &snapshot)?;
+        tracing::info!(
+            leader = %leader.id,
+            rules = snapshot.rules.len(),
+            "Synced rules and configuration from cluster leader"
+        );
+        Ok(())
+    }
+
+    fn apply(&self, admin: &AdminState, snapshot: &ClusterSnapshot) -> anyhow::Result<()> {
+        let (secrets, keys) = credentials(admin, &snapshot.secrets, &snapshot.proxy_keys)?;
+        let before = admin.db.get_all_rules()?;
+        admin.db.restore_cluster(
+            &snapshot.rules,
+            &snapshot.system_config,
+            &secrets,
+            &keys,
+            reloads::check,
+        )?;
+        synced(admin, before, &snapshot.rules)?;
+        backup::apply_system_config(admin, &snapshot.system_config);
+        admin.reload_rules("cluster_sync")
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+        timeout: Duration,
+    ) -> anyhow::Result<T> {
+        let resp: ApiResponse<T> = request
+            .header(
+                TOKEN_HEADER,
+                self.config.token.as_deref().unwrap_or_default(),
+            )
+            .timeout(timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        resp.data
+            .ok_or_else(|| anyhow::anyhow!("Empty response from cluster peer"))
+    }
+}
+
+impl Member {
+    fn unknown() -> Self {
+        Self {
+            info: None,
+            last_seen: None,
+            last_error: None,
+        }
+    }
+}
+
+fn normalize(url: &str) -> String {
+    url.trim_end_matches('/').to_string()
+}
+
+fn snapshot(admin: &AdminState) -> anyhow::Result<ClusterSnapshot> {
+    Ok(ClusterSnapshot {
+        rules: admin.db.get_all_rules()?,
+        system_config: admin.db.get_all_configs()?,
+        secrets: synced_secrets(&admin.db)?,
+        proxy_keys: synced_keys(&admin.db)?,
+    })
+}
+
+/// 需要同步的密钥，不含内部密钥（system.）
+pub(crate) fn synced_secrets(db: &Database) -> anyhow::Result<Vec<SyncedSecret>> {
+    Ok(db
+        .list_secrets()?
+        .into_iter()
+        .filter(|secret| !secret.name.starts_with(SYSTEM_PREFIX))
+        .map(|secret| SyncedSecret {
+            name: secret.name,
+            value: secret.value,
+        })
+        .collect())
+}
+
+pub(crate) fn synced_keys(db: &Database) -> anyhow::Result<Vec<SyncedKey>> {
+    Ok(db
+        .list_proxy_keys()?
+        .into_iter()
+        .map(|key| SyncedKey {
+            id: key.id,
+            name: key.name,
+            key_hash: key.key_hash,
+            prefix: key.prefix,
+            rule_id: key.rule_id,
+            created_at: key.created_at,
+        })
+        .collect())
+}
+
+/// 写入数据库的密钥（名称与加密值）与 API Key
+type Credentials = (Vec<(String, String)>, Vec<ProxyKey>);
+
+/// 转换为写入数据库的密钥与 API Key；主密钥不一致时返回错误，避免写入后本节点启动时无法解密
+pub(crate) fn credentials(
+    admin: &AdminState,
+    secrets: &[SyncedSecret],
+    keys: &[SyncedKey],
+) -> anyhow::Result<Credentials> {
+    for secret in secrets {
+        admin.secrets.check_sealed(&secret.name, &secret.value)?;
+    }
+    let secrets = secrets
+        .iter()
+        .map(|secret| (secret.name.clone(), secret.value.clone()))
+        .collect();
+    let keys = keys
+        .iter()
+        .map(|key| ProxyKey {
+            id: key.id,
+            name: key.name.clone(),
+            key_hash: key.key_hash.clone(),
+            prefix: key.prefix.clone(),
+            rule_id: key.rule_id,
+            created_at: key.created_at.clone(),
+        })
+        .collect();
+    Ok((secrets, keys))
+}
+
+/// 同步写入后刷新内存中的密钥与 API Key，并清理已删除规则的统计
+pub(crate) fn synced(
+    admin: &AdminState,
+    before: Vec<ProxyRule>,
+    rules: &[ProxyRule],
+) -> anyhow::Result<()> {
+    admin.secrets.reload()?;
+    admin.proxy_keys.reload(&admin.db)?;
+    for rule in before {
+        if !rules.iter().any(|r| r.id == rule.id) {
+            admin.stats.remove(rule.id);
+        }
+    }
+    Ok(())
+}
+
+/// 规则、系统配置、密钥与 API Key 的摘要，系统配置按键排序，与行 id 无关；
+/// 密钥比较加密值，同步后与主节点一致
+fn state_hash(snapshot: &ClusterSnapshot) -> anyhow::Result<String> {
+    let mut configs: Vec<(&str, &str)> = snapshot
+        .system_config
+        .iter()
+        .map(|config| (config.key.as_str(), config.value.as_str()))
+        .collect();
+    configs.sort_unstable();
+    let json = serde_json::to_vec(&(
+        &snapshot.rules,
+        configs,
+        &snapshot.secrets,
+        &snapshot.proxy_keys,
+    ))?;
+    Ok(URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, &json).as_ref()))
+}
+
+fn cluster_of(state: &AdminState, headers: &HeaderMap) -> Result<Cluster, StatusCode> {
+    match state.cluster {
+        Some(ref cluster) if cluster.authorized(headers) => Ok(cluster.clone()),
+        Some(_) => Err(StatusCode::UNAUTHORIZED),
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+/// 接收其他节点的 gossip，回复本节点的视图，使用共享令牌认证
+pub async fn gossip_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(gossip): Json<Gossip>,
+) -> Result<Json<ApiResponse<Gossip>>, StatusCode> {
+    let cluster = cluster_of(&state, &headers)?;
+    let (from, hash) = (gossip.from.id.clone(), gossip.from.state_hash.clone());
+    cluster.merge(gossip);
+    let reply = cluster.gossip(&state).map_err(|e| {
+        tracing::error!("Failed to build cluster gossip: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // 主节点的状态变化后立即同步，不等下一轮
+    if cluster.leader().is_some_and(|leader| leader.id == from) && hash != reply.from.state_hash {
+        cluster.wake.notify_one();
+    }
+    Ok(Json(ApiResponse::ok(reply)))
+}
+
+/// 供其他节点同步的规则与系统配置，使用共享令牌认证
+pub async fn state_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<ClusterSnapshot>>, StatusCode> {
+    cluster_of(&state, &headers)?;
+    snapshot(&state)
+        .map(|snapshot| Json(ApiResponse::ok(snapshot)))
+        .map_err(|e| {
+            tracing::error!("Failed to get cluster state: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderInfo {
+    pub id: String,
+    pub url: String,
+}
+
+/// 非主节点拒绝修改规则与系统配置，返回 409 与主节点地址
+pub async fn leader_middleware(
+    State(state): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD);
+    let path = req.uri().path();
+    if write && LEADER_WRITES.iter().any(|prefix| path.starts_with(prefix)) {
+        if let Some(leader) = state.cluster.as_ref().and_then(Cluster::leader) {
+            tracing::warn!(leader = %leader.id, path, "Rejected write on a cluster follower");
+            return (
+                StatusCode::CONFLICT,
+                Json(ApiResponse {
+                    success: false,
+                    message: Some(format!(
+                        "writes must be sent to the cluster leader {}",
+                        leader.url
+                    )),
+                    data: Some(LeaderInfo {
+                        id: leader.id,
+                        url: leader.url,
+                    }),
+                }),
+            )
+                .into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberStatus {
+    pub id: Option<String>,
+    pub url: String,
+    pub alive: bool,
+    pub leader: bool,
+    /// 与本节点的规则与系统配置一致
+    pub in_sync: bool,
+    pub last_seen_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClusterStatus {
+    pub enabled: bool,
+    pub node_id: Option<String>,
+    pub leader_id: Option<String>,
+    pub is_leader: bool,
+    pub state_hash: Option<String>,
+    pub members: Vec<MemberStatus>,
+}
+
+/// 集群成员与主节点
+pub async fn status_handler(
+    State(state): State<AdminState>,
+) -> Result<Json<ApiResponse<ClusterStatus>>, StatusCode> {
+    let Some(ref cluster) = state.cluster else {
+        return Ok(Json(ApiResponse::ok(ClusterStatus {
+            enabled: false,
+            node_id: None,
+            leader_id: None,
+            is_leader: true,
+            state_hash: None,
+            members: Vec::new(),
+        })));
+    };
+    let local = cluster.local(&state).map_err(|e| {
+        tracing::error!("Failed to get cluster state: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let leader = cluster.leader().unwrap_or_else(|| local.clone());
+
+    let mut members = vec![MemberStatus {
+        id: Some(local.id.clone()),
+        url: local.url.clone(),
+        alive: true,
+        leader: leader.id == local.id,
+        in_sync: true,
+        last_seen_secs: Some(0),
+        last_error: None,
+    }];
+    members.extend(cluster.members.iter().map(|member| {
+        let info = member.info.as_ref();
+        MemberStatus {
+            id: info.map(|info| info.id.clone()),
+            url: member.key().clone(),
+            alive: cluster.alive(&member),
+            leader: info.is_some_and(|info| info.id == leader.id),
+            in_sync: info.is_some_and(|info| info.state_hash == local.state_hash),
+            last_seen_secs: member.last_seen.map(|seen| seen.elapsed().as_secs()),
+            last_error: member.last_error.clone(),
+        }
+    }));
+    members.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.url.cmp(&b.url)));
+
+    Ok(Json(ApiResponse::ok(ClusterStatus {
+        enabled: true,
+        is_leader: leader.id == local.id,
+        leader_id: Some(leader.id),
+        node_id: Some(local.id),
+        state_hash: Some(local.state_hash),
+        members,
+    })))
+}
@@ -1,58 +1,1429 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::net::IpAddr;
 use std::path::Path;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub admin: AdminConfig,
-    pub proxy: ProxyConfig,
+    /// 代理监听器，可配置为单个监听器或列表；环境变量覆盖第一个监听器
+    #[serde(deserialize_with = "one_or_many")]
+    pub proxy: Vec<ProxyConfig>,
     pub auth: AuthConfig,
     pub database: DatabaseConfig,
     pub logging: LoggingConfig,
     #[serde(default = "default_timeout")]
     pub default_timeout_secs: u64,
+    /// 缓冲转发的请求体上限(字节)，超过时返回 413，规则可单独设置 max_body_bytes
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: u64,
+    #[serde(default)]
+    pub lifecycle: LifecycleConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+    #[serde(default)]
+    pub endpoints: EndpointsConfig,
+    #[serde(default)]
+    pub change_approval: ChangeApprovalConfig,
+    #[serde(default)]
+    pub change_webhooks: ChangeWebhooksConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub ha: HaConfig,
+    #[serde(default)]
+    pub direct_proxy: DirectProxyConfig,
+    #[serde(default)]
+    pub socks: SocksConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+    #[serde(default)]
+    pub cache_store: CacheStoreConfig,
+    #[serde(default)]
+    pub dns: DnsConfig,
+    /// WASM 插件，键为插件名，规则通过 `plugins` 选项引用
+    #[serde(default)]
+    pub plugins: HashMap<String, PluginConfig>,
+    #[serde(default)]
+    pub rules_file: RulesFileConfig,
+    #[serde(default)]
+    pub cluster: ClusterConfig,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AdminConfig {
     pub host: String,
     pub port: u16,
+    /// 是否监听 TCP 端口，仅使用 Unix 套接字时可关闭
+    #[serde(default = "default_true")]
+    pub tcp_enabled: bool,
+    /// Unix 域套接字路径
+    #[serde(default, alias = "socket")]
+    pub unix_socket: Option<String>,
+    /// Unix 套接字文件权限（八进制，如 "660"）
+    #[serde(default)]
+    pub unix_socket_mode: Option<String>,
+    /// 允许访问管理接口的 IP / CIDR，为空时不限制
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+    /// 配置白名单后仍始终允许本机回环地址访问，便于误配置时应急
+    #[serde(default = "default_true")]
+    pub localhost_bypass: bool,
+    #[serde(default)]
+    pub compression: AdminCompressionConfig,
+}
+
+/// 管理接口响应压缩
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminCompressionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 小于该大小(字节)的响应不压缩
+    #[serde(default = "default_compression_min_size")]
+    pub min_size_bytes: u16,
+    /// 不压缩的内容类型前缀，用于已压缩的导出文件等
+    #[serde(default = "default_compression_exclude")]
+    pub exclude_content_types: Vec<String>,
+    /// 客户端支持时优先使用 zstd
+    #[serde(default = "default_true")]
+    pub zstd: bool,
+}
+
+impl Default for AdminCompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size_bytes: default_compression_min_size(),
+            exclude_content_types: default_compression_exclude(),
+            zstd: true,
+        }
+    }
+}
+
+fn default_compression_min_size() -> u16 {
+    1024
+}
+
+fn default_compression_exclude() -> Vec<String> {
+    [
+        "application/gzip",
+        "application/x-gzip",
+        "application/zip",
+        "application/zstd",
+        "audio/",
+        "video/",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProxyConfig {
     pub host: String,
     pub port: u16,
+    /// 配置后代理端口使用 HTTPS
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// 只处理带有其中任一标签的规则，为空时处理全部规则
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rule_tags: Vec<String>,
+    /// 是否提供直接代理（/{path}/https://...）
+    #[serde(default = "default_true")]
+    pub direct_proxy: bool,
+    /// 是否监听 TCP 端口，仅使用 Unix 套接字时可关闭
+    #[serde(default = "default_true")]
+    pub tcp_enabled: bool,
+    /// TCP 连接开头带有 PROXY 协议头（v1 / v2），客户端地址取协议头中的源地址，
+    /// 开启后没有协议头的连接会被关闭
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub proxy_protocol: bool,
+    /// Unix 域套接字路径，套接字上不使用 TLS，由本机前端终结
+    #[serde(default, alias = "socket", skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<String>,
+    /// Unix 套接字文件权限（八进制，如 "660"）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket_mode: Option<String>,
+    /// 按 TLS SNI 将连接原样转发到其他 HTTPS 后端，只作用于 TCP 端口
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sni_passthrough: Option<SniPassthroughConfig>,
+}
+
+/// TLS SNI 透传：查看 ClientHello 中的服务器名称，匹配的连接不终结 TLS，原样转发到后端；
+/// 未匹配的 TLS 连接转发到默认后端，没有默认后端时由监听器的 tls 终结，监听器没有 tls 时关闭；
+/// 非 TLS 连接照常按 HTTP 处理
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SniPassthroughConfig {
+    /// 服务器名称到后端地址（host:port），`*.example.com` 匹配任意子域名，精确匹配优先
+    #[serde(default)]
+    pub routes: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_backend: Option<String>,
+    /// 连接后端后先发送 PROXY 协议 v2 头部，携带客户端地址
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub proxy_protocol: bool,
+}
+
+/// 单个值或列表
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TlsConfig {
+    /// 服务端证书链（PEM），全部证书由 ACME 签发时可留空
+    #[serde(default)]
+    pub cert_path: String,
+    /// 服务端私钥（PEM）
+    #[serde(default)]
+    pub key_path: String,
+    /// 客户端 CA 证书（PEM），配置后启用 mTLS
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+    /// 是否拒绝未出示客户端证书的连接
+    #[serde(default)]
+    pub client_cert_required: bool,
+    /// 通过 ACME 自动签发与续期证书
+    #[serde(default)]
+    pub acme: Option<AcmeConfig>,
+}
+
+/// ACME 自动证书配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AcmeConfig {
+    #[serde(default = "default_acme_directory")]
+    pub directory_url: String,
+    #[serde(default)]
+    pub contact_email: Option<String>,
+    /// 账户密钥与证书保存目录
+    #[serde(default = "default_acme_storage_dir")]
+    pub storage_dir: String,
+    /// 证书剩余有效期少于该天数时续期
+    #[serde(default = "default_acme_renew_before_days")]
+    pub renew_before_days: u32,
+    /// 写入 DNS 记录后等待生效的时间(秒)
+    #[serde(default = "default_acme_dns_propagation")]
+    pub dns_propagation_secs: u64,
+    /// DNS 服务商，按名称被证书引用
+    #[serde(default)]
+    pub dns_providers: HashMap<String, DnsProviderConfig>,
+    #[serde(default)]
+    pub certificates: Vec<AcmeCertificateConfig>,
+}
+
+/// 一张证书包含的域名，配置 dns_provider 时使用 DNS-01 验证，否则使用 HTTP-01
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AcmeCertificateConfig {
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub dns_provider: Option<String>,
+}
+
+/// DNS-01 验证使用的 DNS 服务商
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DnsProviderConfig {
+    Cloudflare {
+        api_token: String,
+        /// 为空时按域名查找
+        #[serde(default)]
+        zone_id: Option<String>,
+    },
+    Route53 {
+        access_key_id: String,
+        secret_access_key: String,
+        hosted_zone_id: String,
+        #[serde(default)]
+        session_token: Option<String>,
+    },
+    /// RFC 2136 动态更新，可选 TSIG 签名
+    Rfc2136 {
+        /// 主 DNS 服务器地址，如 10.0.0.53:53
+        server: String,
+        zone: String,
+        #[serde(default)]
+        tsig_key_name: Option<String>,
+        /// Base64 编码的 TSIG 密钥
+        #[serde(default)]
+        tsig_secret: Option<String>,
+        /// hmac-sha256 或 hmac-sha512
+        #[serde(default = "default_tsig_algorithm")]
+        tsig_algorithm: String,
+    },
+}
+
+fn default_acme_directory() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+}
+
+fn default_acme_storage_dir() -> String {
+    "./data/acme".to_string()
+}
+
+fn default_acme_renew_before_days() -> u32 {
+    30
+}
+
+fn default_acme_dns_propagation() -> u64 {
+    60
+}
+
+fn default_tsig_algorithm() -> String {
+    "hmac-sha256".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuthConfig {
+    pub username: String,
+    pub password: String,
+    /// 登录会话有效期(秒)
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl_secs: u64,
+    /// 滑动过期：会话期间的访问会将有效期顺延为完整的 session_ttl_secs
+    #[serde(default)]
+    pub sliding_expiration: bool,
+    /// 同一 IP 或用户名连续登录失败次数上限，达到后临时锁定，0 表示不限制
+    #[serde(default = "default_max_login_failures")]
+    pub max_login_failures: u32,
+    /// 登录锁定时长(秒)，同时作为失败次数的统计窗口
+    #[serde(default = "default_lockout_secs")]
+    pub lockout_secs: u64,
+    /// 会话绑定 User-Agent，其他浏览器使用同一 token 时视为无效
+    #[serde(default)]
+    pub bind_user_agent: bool,
+    /// 会话绑定客户端 IP，经反向代理访问管理界面时不宜开启
+    #[serde(default)]
+    pub bind_ip: bool,
+    /// OIDC 单点登录，配置后登录页提供 SSO 入口
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            username: "admin".to_string(),
+            password: "admin123".to_string(),
+            session_ttl_secs: default_session_ttl(),
+            sliding_expiration: false,
+            max_login_failures: default_max_login_failures(),
+            lockout_secs: default_lockout_secs(),
+            bind_user_agent: false,
+            bind_ip: false,
+            oidc: None,
+        }
+    }
+}
+
+/// 管理界面角色，viewer 只能查看
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "viewer" => Some(Self::Viewer),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// OIDC 授权码登录配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OidcConfig {
+    /// 身份提供方地址，通过 {issuer}/.well-known/openid-configuration 发现端点
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// 回调地址，需在身份提供方登记，如 https://admin.example.com/api/oidc/callback
+    pub redirect_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// ID Token 中的用户组声明
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    /// 用户组到角色的映射，属于多个组时取最高角色
+    #[serde(default)]
+    pub group_roles: HashMap<String, Role>,
+    /// 不属于任何已映射组时的角色，不配置则拒绝登录
+    #[serde(default)]
+    pub default_role: Option<Role>,
+    /// 禁用本地用户名密码登录
+    #[serde(default)]
+    pub disable_password_login: bool,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "profile".into(), "email".into()]
+}
+
+fn default_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_session_ttl() -> u64 {
+    24 * 3600
+}
+
+fn default_max_login_failures() -> u32 {
+    5
+}
+
+fn default_lockout_secs() -> u64 {
+    15 * 60
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatabaseConfig {
+    #[serde(default = "default_db_path")]
+    pub path: String,
+    #[serde(default)]
+    pub backup: DatabaseBackupConfig,
+}
+
+/// 定时备份：使用 SQLite 在线备份接口复制数据库，运行中的连接不受影响
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatabaseBackupConfig {
+    /// 备份文件目录；为空时不启用
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(default = "default_db_backup_interval")]
+    pub interval_secs: u64,
+    /// 保留最近的备份个数，不设置时不清理旧备份
+    #[serde(default)]
+    pub retention: Option<usize>,
+}
+
+impl Default for DatabaseBackupConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            interval_secs: default_db_backup_interval(),
+            retention: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoggingConfig {
+    pub directory: String,
+    pub max_size_bytes: u64,
+    pub retention_days: u32,
+    /// 写入应用日志与访问日志前脱敏的请求数据
+    #[serde(default)]
+    pub redact: RedactConfig,
+}
+
+/// 日志脱敏：名称不区分大小写，值替换为 [REDACTED]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RedactConfig {
+    #[serde(default = "default_redact_headers")]
+    pub headers: Vec<String>,
+    /// URL 查询参数名，作用于请求路径、转发目标与 Referer
+    #[serde(default = "default_redact_query_params")]
+    pub query_params: Vec<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            headers: default_redact_headers(),
+            query_params: default_redact_query_params(),
+        }
+    }
+}
+
+fn default_redact_headers() -> Vec<String> {
+    [
+        "authorization",
+        "proxy-authorization",
+        "cookie",
+        "set-cookie",
+        "x-api-key",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_redact_query_params() -> Vec<String> {
+    [
+        "api_key",
+        "apikey",
+        "key",
+        "token",
+        "access_token",
+        "password",
+        "secret",
+        "sig",
+        "signature",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// 生命周期 Webhook 配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LifecycleConfig {
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// 单个 Webhook 请求超时(秒)
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+    /// 收到停止信号后等待在途请求完成的最长时间(秒)
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+    /// 代理、管理与 SOCKS5 端口设置 SO_REUSEPORT，升级时新实例与旧实例同时监听，
+    /// 再通过 `/api/admin/drain` 让旧实例排空退出
+    #[serde(default)]
+    pub reuse_port: bool,
+}
+
+impl Default for LifecycleConfig {
+    fn default() -> Self {
+        Self {
+            webhooks: Vec::new(),
+            timeout_secs: default_hook_timeout(),
+            drain_timeout_secs: default_drain_timeout(),
+            reuse_port: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// 订阅的事件，为空表示全部: on_started, on_reloaded, on_draining, on_stopped
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// 变更审批：开启后规则与系统配置的修改需审批后才生效
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChangeApprovalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 提交人本人审批前需等待的时间(秒)，其他管理员可随时审批
+    #[serde(default = "default_approval_delay")]
+    pub approval_delay_secs: u64,
+}
+
+impl Default for ChangeApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            approval_delay_secs: default_approval_delay(),
+        }
+    }
+}
+
+fn default_approval_delay() -> u64 {
+    300
+}
+
+/// 配置变更 Webhook：规则增删改与系统配置修改时推送，用于同步到 CMDB 等外部系统
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChangeWebhooksConfig {
+    #[serde(default)]
+    pub endpoints: Vec<ChangeWebhookConfig>,
+    /// 单次投递超时(秒)
+    #[serde(default = "default_change_webhook_timeout")]
+    pub timeout_secs: u64,
+    /// 投递失败（连接错误或非 2xx）时的最多尝试次数，间隔按 1、2、4... 秒递增
+    #[serde(default = "default_change_webhook_attempts")]
+    pub max_attempts: u32,
+}
+
+impl Default for ChangeWebhooksConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            timeout_secs: default_change_webhook_timeout(),
+            max_attempts: default_change_webhook_attempts(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChangeWebhookConfig {
+    pub url: String,
+    /// 设置后请求带 `X-Proxy-Signature: sha256=<HMAC-SHA256(secret, body) 十六进制>`
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// 订阅的事件，为空表示全部: rule.created, rule.updated, rule.deleted, rules.imported, config.changed
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+fn default_change_webhook_timeout() -> u64 {
+    10
+}
+
+fn default_change_webhook_attempts() -> u32 {
+    5
+}
+
+/// 告警：上游不可用、错误率超限与证书即将过期时通过各通道发送通知
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertsConfig {
+    #[serde(default)]
+    pub channels: Vec<AlertChannelConfig>,
+    /// 通知中的实例名，默认为主机名
+    #[serde(default)]
+    pub instance: Option<String>,
+    /// 上游被标记为不健康（连续失败）或关键上游探测不可达时告警
+    #[serde(default = "default_true")]
+    pub upstream_down: bool,
+    /// 全局错误率告警，不配置则不检查
+    #[serde(default)]
+    pub error_rate: Option<ErrorRateAlertConfig>,
+    /// 证书剩余有效期少于该天数时告警，0 表示不检查
+    #[serde(default = "default_cert_expiry_days")]
+    pub cert_expiry_days: u64,
+    /// 错误率与证书有效期的检查间隔(秒)
+    #[serde(default = "default_alert_check_interval")]
+    pub check_interval_secs: u64,
+    /// 告警持续期间重复通知的间隔(秒)，0 表示只通知一次
+    #[serde(default = "default_alert_repeat_interval")]
+    pub repeat_interval_secs: u64,
+    /// 告警规则（管理 API 维护）的求值间隔(秒)
+    #[serde(default = "default_alert_evaluation_interval")]
+    pub evaluation_interval_secs: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+            instance: None,
+            upstream_down: true,
+            error_rate: None,
+            cert_expiry_days: default_cert_expiry_days(),
+            check_interval_secs: default_alert_check_interval(),
+            repeat_interval_secs: default_alert_repeat_interval(),
+            evaluation_interval_secs: default_alert_evaluation_interval(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ErrorRateAlertConfig {
+    /// 5xx 与代理错误占比，如 0.05
+    pub threshold: f64,
+    /// 统计窗口(秒)，最长 900
+    #[serde(default = "default_error_rate_window")]
+    pub window_secs: u64,
+    /// 窗口内请求数少于该值时不告警，避免低流量时误报
+    #[serde(default = "default_error_rate_min_requests")]
+    pub min_requests: u64,
+}
+
+/// 告警通道
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AlertChannelConfig {
+    #[serde(flatten)]
+    pub kind: AlertChannelKind,
+    /// 订阅的告警类型，为空表示全部: upstream_down, error_rate, cert_expiry, alert_rule
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AlertChannelKind {
+    /// POST 告警 JSON
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    /// Slack Incoming Webhook
+    Slack {
+        webhook_url: String,
+        #[serde(default)]
+        channel: Option<String>,
+    },
+    Email(SmtpConfig),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// 默认按 security 取 465 / 587 / 25
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// 连接后通过 STARTTLS 升级
+    #[default]
+    Starttls,
+    /// 隐式 TLS（SMTPS）
+    Tls,
+    /// 明文，仅用于内网中继
+    None,
+}
+
+fn default_cert_expiry_days() -> u64 {
+    14
+}
+
+fn default_alert_check_interval() -> u64 {
+    60
+}
+
+fn default_alert_repeat_interval() -> u64 {
+    3600
+}
+
+fn default_alert_evaluation_interval() -> u64 {
+    15
+}
+
+fn default_error_rate_window() -> u64 {
+    300
+}
+
+fn default_error_rate_min_requests() -> u64 {
+    100
+}
+
+/// 主备角色
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HaRole {
+    #[default]
+    Standalone,
+    Primary,
+    Standby,
+}
+
+/// 主备热备配置：备机定期向主机发送心跳并同步规则，主机失联后自动接管
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HaConfig {
+    #[serde(default)]
+    pub role: HaRole,
+    /// 备机使用：主机管理接口地址，如 http://10.0.0.1:8080
+    #[serde(default)]
+    pub peer_url: Option<String>,
+    /// 主备共享的心跳令牌
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval_secs: u64,
+    /// 连续多久未收到主机心跳后接管(秒)
+    #[serde(default = "default_failover_timeout")]
+    pub failover_timeout_secs: u64,
+    /// 备机接管时执行的脚本，如切换 VRRP 虚拟 IP
+    #[serde(default)]
+    pub on_promote: Option<String>,
+    /// 主机恢复、备机退回待机时执行的脚本
+    #[serde(default)]
+    pub on_demote: Option<String>,
+}
+
+/// 集群：多个实例同时处理流量，通过 gossip 交换成员信息，id 最小的存活节点为主节点，
+/// 规则与系统配置只能在主节点修改，其余节点发现与主节点不一致时整体同步
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClusterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 节点 id，默认为 advertise_url
+    #[serde(default)]
+    pub node_id: Option<String>,
+    /// 其他节点访问本节点管理接口的地址，如 http://10.0.0.1:8080
+    #[serde(default)]
+    pub advertise_url: Option<String>,
+    /// 启动时联系的节点管理接口地址，其余成员通过 gossip 发现
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// 节点间共享的令牌
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_heartbeat_interval")]
+    pub gossip_interval_secs: u64,
+    /// 多久未联系上的节点视为离线(秒)
+    #[serde(default = "default_failover_timeout")]
+    pub peer_timeout_secs: u64,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            node_id: None,
+            advertise_url: None,
+            peers: Vec::new(),
+            token: None,
+            gossip_interval_secs: default_heartbeat_interval(),
+            peer_timeout_secs: default_failover_timeout(),
+        }
+    }
+}
+
+/// 直接代理（/{proxy}/https://...）目标访问控制，防止 SSRF
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DirectProxyConfig {
+    /// 允许访问的域名或 IP / CIDR，非空时仅允许列表内的目标
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// 禁止访问的域名或 IP / CIDR，优先于允许列表
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// 禁止访问内网、回环、链路本地等地址段（允许列表中的地址除外）
+    #[serde(default = "default_true")]
+    pub block_private: bool,
+    /// GitHub Release / raw 文件等下载加速缓存
+    #[serde(default)]
+    pub cache: DirectCacheConfig,
+    /// 标准正向代理（绝对地址请求与 CONNECT 隧道），供浏览器、系统代理设置直接使用
+    #[serde(default)]
+    pub forward_proxy: ForwardProxyConfig,
+}
+
+impl Default for DirectProxyConfig {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            block_private: true,
+            cache: DirectCacheConfig::default(),
+            forward_proxy: ForwardProxyConfig::default(),
+        }
+    }
+}
+
+/// 正向代理：目标访问控制与直接代理相同，认证使用代理 API Key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ForwardProxyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 要求 Proxy-Authorization: Basic（密码为未绑定规则的 API Key，用户名任意）
+    #[serde(default = "default_true")]
+    pub require_auth: bool,
+    /// CONNECT 允许的目标端口，为空时不限制
+    #[serde(default = "default_connect_ports")]
+    pub connect_ports: Vec<u16>,
+}
+
+impl Default for ForwardProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            require_auth: true,
+            connect_ports: default_connect_ports(),
+        }
+    }
+}
+
+fn default_connect_ports() -> Vec<u16> {
+    vec![443]
+}
+
+/// SOCKS5 代理：目标访问控制与直接代理相同，认证使用代理 API Key
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SocksConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_socks_host")]
+    pub host: String,
+    #[serde(default = "default_socks_port")]
+    pub port: u16,
+    /// 要求用户名/密码认证（密码为未绑定规则的 API Key，用户名任意）
+    #[serde(default = "default_true")]
+    pub require_auth: bool,
+}
+
+impl Default for SocksConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: default_socks_host(),
+            port: default_socks_port(),
+            require_auth: true,
+        }
+    }
+}
+
+fn default_socks_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_socks_port() -> u16 {
+    1080
+}
+
+/// 直接代理的下载缓存，只缓存列表内主机的 GET 请求
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DirectCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_direct_cache_dir")]
+    pub dir: String,
+    /// 缓存的主机，精确匹配
+    #[serde(default = "default_direct_cache_hosts")]
+    pub hosts: Vec<String>,
+    /// 分支、标签等可变地址的缓存秒数，Release 附件与按提交 sha 访问的文件永久缓存
+    #[serde(default = "default_direct_cache_ttl")]
+    pub ttl_secs: u64,
+    /// 缓存目录容量上限（MB），超过时删除最早缓存（disk 后端为最久未访问）的文件，未设置时不限制
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+}
+
+impl Default for DirectCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_direct_cache_dir(),
+            hosts: default_direct_cache_hosts(),
+            ttl_secs: default_direct_cache_ttl(),
+            max_size_mb: None,
+        }
+    }
+}
+
+fn default_direct_cache_dir() -> String {
+    "./data/direct-cache".to_string()
+}
+
+fn default_direct_cache_hosts() -> Vec<String> {
+    [
+        "github.com",
+        "raw.githubusercontent.com",
+        "objects.githubusercontent.com",
+        "release-assets.githubusercontent.com",
+        "codeload.github.com",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn default_direct_cache_ttl() -> u64 {
+    600
+}
+
+/// 镜像与下载缓存的存储后端
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    /// 保存在各缓存目录下的本地文件
+    #[default]
+    Disk,
+    /// 进程内存，重启后清空
+    Memory,
+    /// S3 兼容的对象存储（AWS S3、MinIO 等），可在多个实例间共享
+    S3,
+}
+
+/// 缓存存储配置，各规则的 cache_dir 作为对象路径的前缀
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheStoreConfig {
+    #[serde(default)]
+    pub backend: CacheBackend,
+    /// memory 后端的容量上限（MB），超过时删除最早缓存的对象
+    #[serde(default = "default_memory_cache_size")]
+    pub memory_max_size_mb: u64,
+    /// disk 后端全部缓存目录的容量上限（MB），超过时删除最久未访问的文件，未设置时不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_max_size_mb: Option<u64>,
+    /// disk 后端的索引文件，记录缓存目录与文件的最近访问时间，启动时据此重建索引
+    #[serde(default = "default_cache_index_path")]
+    pub index_path: String,
+    #[serde(default)]
+    pub s3: S3StoreConfig,
+}
+
+impl Default for CacheStoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::default(),
+            memory_max_size_mb: default_memory_cache_size(),
+            disk_max_size_mb: None,
+            index_path: default_cache_index_path(),
+            s3: S3StoreConfig::default(),
+        }
+    }
+}
+
+fn default_memory_cache_size() -> u64 {
+    512
+}
+
+fn default_cache_index_path() -> String {
+    "./data/cache-index.json".to_string()
+}
+
+/// S3 兼容对象存储
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct S3StoreConfig {
+    /// 服务地址，如 https://s3.us-east-1.amazonaws.com 或 http://minio:9000
+    #[serde(default = "default_s3_endpoint")]
+    pub endpoint: String,
+    #[serde(default)]
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    #[serde(default)]
+    pub access_key: String,
+    #[serde(default)]
+    pub secret_key: String,
+    /// 对象键前缀，多个部署共用存储桶时区分
+    #[serde(default)]
+    pub prefix: String,
+    /// 使用路径风格地址（endpoint/bucket/key），MinIO 等通常需要开启；关闭时使用 bucket.endpoint/key
+    #[serde(default = "default_true")]
+    pub path_style: bool,
+}
+
+impl Default for S3StoreConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: default_s3_endpoint(),
+            bucket: String::new(),
+            region: default_s3_region(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            prefix: String::new(),
+            path_style: true,
+        }
+    }
+}
+
+fn default_s3_endpoint() -> String {
+    "https://s3.amazonaws.com".to_string()
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// WASM 插件模块与单次调用的资源限制
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PluginConfig {
+    /// .wasm 模块路径
+    pub path: String,
+    /// 每次钩子调用可执行的指令预算，耗尽时调用失败
+    #[serde(default = "default_plugin_fuel")]
+    pub fuel: u64,
+    /// 线性内存上限(MB)
+    #[serde(default = "default_plugin_memory")]
+    pub max_memory_mb: u64,
+}
+
+/// 规则文件（GitOps）：监视 YAML 规则列表文件，变化时校验并整体替换规则
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RulesFileConfig {
+    /// 规则列表文件，格式与 `import` 命令相同；为空时不启用
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub mode: RulesFileMode,
+}
+
+/// 规则文件与数据库以哪一方为准
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RulesFileMode {
+    /// 以文件为准：启动时与文件变化时用文件替换数据库中的规则，管理接口不能修改规则
+    #[default]
+    File,
+    /// 以数据库为准：启动时与管理接口修改规则后把规则写回文件，文件变化时同样导入
+    Db,
+}
+
+/// 服务发现：规则目标 `consul://服务名/...`、`etcd://服务名/...` 转发到服务的健康实例
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiscoveryConfig {
+    #[serde(default)]
+    pub consul: Option<ConsulConfig>,
+    #[serde(default)]
+    pub etcd: Option<EtcdConfig>,
+    #[serde(default)]
+    pub kubernetes: KubernetesConfig,
+    /// 单次监视（Consul 阻塞查询、etcd watch）的最长等待时间(秒)，超时后重新查询
+    #[serde(default = "default_discovery_wait")]
+    pub wait_secs: u64,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            consul: None,
+            etcd: None,
+            kubernetes: KubernetesConfig::default(),
+            wait_secs: default_discovery_wait(),
+        }
+    }
+}
+
+fn default_discovery_wait() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConsulConfig {
+    /// Consul HTTP 接口地址，如 http://127.0.0.1:8500
+    pub address: String,
+    /// ACL 令牌
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub datacenter: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EtcdConfig {
+    /// etcd v3 HTTP 网关地址，如 http://127.0.0.1:2379
+    pub address: String,
+    /// 实例注册在 `{prefix}/{服务名}/` 下，值为 `host:port` 或 `{"host": ..., "port": ...}`
+    #[serde(default = "default_etcd_prefix")]
+    pub prefix: String,
+}
+
+/// Kubernetes 服务发现：规则目标 `k8s://命名空间/服务:端口/...` 转发到 Endpoints 中就绪的 Pod
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct KubernetesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// API Server 地址，不设置时使用 Pod 内的集群配置（服务账号令牌与 CA 证书）
+    #[serde(default)]
+    pub api_server: Option<String>,
+}
+
+fn default_etcd_prefix() -> String {
+    "/services".to_string()
 }
 
+/// 上游域名解析：静态主机覆盖、指定的 DNS 服务器与进程内解析缓存
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct AuthConfig {
-    pub username: String,
-    pub password: String,
+pub struct DnsConfig {
+    /// 域名到 IP 的静态映射（单个地址或列表），优先于 DNS 查询，不匹配子域名
+    #[serde(default, deserialize_with = "host_overrides")]
+    pub hosts: HashMap<String, Vec<IpAddr>>,
+    /// DNS 服务器，为空时使用系统配置（/etc/resolv.conf）；支持 `1.1.1.1`、`[::1]:5353`、
+    /// `tcp://8.8.8.8`、`tls://dns.google`（DoT）与 `https://cloudflare-dns.com/dns-query`（DoH），
+    /// DoT / DoH 的服务器域名在启动时通过系统 DNS 解析
+    #[serde(default)]
+    pub nameservers: Vec<String>,
+    /// 解析结果的最短缓存时间(秒)，DNS 返回的 TTL 低于该值时按该值缓存
+    #[serde(default = "default_dns_min_ttl")]
+    pub min_ttl_secs: u64,
+    /// 解析结果的最长缓存时间(秒)
+    #[serde(default = "default_dns_max_ttl")]
+    pub max_ttl_secs: u64,
+    /// 解析失败的缓存时间(秒)，期间同一域名直接返回错误，0 表示不缓存
+    #[serde(default = "default_dns_negative_ttl")]
+    pub negative_ttl_secs: u64,
+    /// 连接上游使用的 IP 地址族，规则可用 ip_family 选项单独设置
+    #[serde(default)]
+    pub ip_family: IpFamily,
+}
+
+/// 连接上游时的 IP 地址族选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    /// 按 DNS 返回顺序，IPv4 与 IPv6 同时存在时以 Happy Eyeballs 竞速
+    #[default]
+    Auto,
+    /// 先尝试 IPv4，未及时连上时再并行尝试 IPv6
+    PreferIpv4,
+    /// 先尝试 IPv6，未及时连上时再并行尝试 IPv4
+    PreferIpv6,
+    /// 只使用 IPv4，忽略 AAAA 记录
+    Ipv4Only,
+    /// 只使用 IPv6
+    Ipv6Only,
+}
+
+impl std::fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IpFamily::Auto => "IP",
+            IpFamily::PreferIpv4 | IpFamily::Ipv4Only => "IPv4",
+            IpFamily::PreferIpv6 | IpFamily::Ipv6Only => "IPv6",
+        })
+    }
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            hosts: HashMap::new(),
+            nameservers: Vec::new(),
+            min_ttl_secs: default_dns_min_ttl(),
+            max_ttl_secs: default_dns_max_ttl(),
+            negative_ttl_secs: default_dns_negative_ttl(),
+            ip_family: IpFamily::default(),
+        }
+    }
+}
+
+fn default_dns_min_ttl() -> u64 {
+    5
+}
+
+fn default_dns_max_ttl() -> u64 {
+    3600
 }
 
+fn default_dns_negative_ttl() -> u64 {
+    10
+}
+
+fn host_overrides<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, Vec<IpAddr>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let hosts = HashMap::<String, OneOrMany<IpAddr>>::deserialize(deserializer)?;
+    Ok(hosts
+        .into_iter()
+        .map(|(host, addrs)| {
+            let addrs = match addrs {
+                OneOrMany::One(addr) => vec![addr],
+                OneOrMany::Many(addrs) => addrs,
+            };
+            (host, addrs)
+        })
+        .collect())
+}
+
+/// 数据库中加密保存的密钥（上游凭据、签名密钥、证书私钥等）
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SecretsConfig {
+    /// 主密钥，32 字节的 base64 编码（如 `openssl rand -base64 32`）
+    #[serde(default)]
+    pub key: Option<String>,
+    /// 未设置 key 时从该文件读取主密钥，文件不存在时自动生成，默认为数据库路径加 .key
+    #[serde(default)]
+    pub key_file: Option<String>,
+}
+
+impl Default for HaConfig {
+    fn default() -> Self {
+        Self {
+            role: HaRole::default(),
+            peer_url: None,
+            token: None,
+            heartbeat_interval_secs: default_heartbeat_interval(),
+            failover_timeout_secs: default_failover_timeout(),
+            on_promote: None,
+            on_demote: None,
+        }
+    }
+}
+
+fn default_heartbeat_interval() -> u64 {
+    2
+}
+
+fn default_failover_timeout() -> u64 {
+    10
+}
+
+/// 代理端口上内置端点（健康检查、指标）配置
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct DatabaseConfig {
-    #[serde(default = "default_db_path")]
-    pub path: String,
+pub struct EndpointsConfig {
+    #[serde(default = "default_true")]
+    pub health_enabled: bool,
+    #[serde(default = "default_health_path")]
+    pub health_path: String,
+    #[serde(default = "default_true")]
+    pub metrics_enabled: bool,
+    #[serde(default = "default_metrics_path")]
+    pub metrics_path: String,
+    /// 有规则匹配内置端点路径时，优先转发到上游而不是返回内置响应
+    #[serde(default)]
+    pub rules_override: bool,
+    /// 允许访问的 IP / CIDR，为空且未设置 token 时不限制
+    #[serde(default)]
+    pub allow_ips: Vec<String>,
+    /// 访问令牌，通过 `Authorization: Bearer <token>` 或 `?token=` 传递
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for EndpointsConfig {
+    fn default() -> Self {
+        Self {
+            health_enabled: true,
+            health_path: default_health_path(),
+            metrics_enabled: true,
+            metrics_path: default_metrics_path(),
+            rules_override: false,
+            allow_ips: Vec::new(),
+            token: None,
+        }
+    }
+}
+
+fn default_health_path() -> String {
+    "/health".to_string()
+}
+
+fn default_metrics_path() -> String {
+    "/metrics".to_string()
 }
 
+/// 访问日志配置，与应用日志分开写入
 #[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct LoggingConfig {
+pub struct AccessLogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_access_log_dir")]
     pub directory: String,
+    #[serde(default)]
+    pub format: AccessLogFormat,
+    #[serde(default = "default_log_max_size")]
     pub max_size_bytes: u64,
+    #[serde(default = "default_log_retention_days")]
     pub retention_days: u32,
 }
 
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: default_access_log_dir(),
+            format: AccessLogFormat::default(),
+            max_size_bytes: default_log_max_size(),
+            retention_days: default_log_retention_days(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    #[default]
+    Json,
+    Combined,
+}
+
+fn default_access_log_dir() -> String {
+    "./logs/access".to_string()
+}
+
+fn default_log_max_size() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_log_retention_days() -> u32 {
+    30
+}
+
+/// OpenTelemetry 链路追踪配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP 导出地址，如 http://localhost:4318/v1/traces，为空则不导出
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// 采样率 0.0 ~ 1.0，已携带 traceparent 的请求遵循上游采样决定
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "proxy-server".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_hook_timeout() -> u64 {
+    5
+}
+
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+fn default_plugin_fuel() -> u64 {
+    10_000_000
+}
+
+fn default_plugin_memory() -> u64 {
+    16
+}
+
 fn default_timeout() -> u64 {
     30
 }
 
+fn default_max_body_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_true() -> bool {
+    true
+}
+
 fn default_db_path() -> String {
     "./proxy.db".to_string()
 }
 
+fn default_db_backup_interval() -> u64 {
+    24 * 3600
+}
+
 impl Config {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = std::fs::read_to_string(path)?;
@@ -74,14 +1445,98 @@ impl Config {
                 self.admin.port = port;
             }
         }
-
-        // Proxy 配置
-        if let Ok(v) = env::var("PROXY_PROXY_HOST") {
-            self.proxy.host = v;
+        if let Ok(v) = env::var("PROXY_ADMIN_TCP_ENABLED") {
+            if let Ok(enabled) = v.parse() {
+                self.admin.tcp_enabled = enabled;
+            }
         }
-        if let Ok(v) = env::var("PROXY_PROXY_PORT") {
-            if let Ok(port) = v.parse() {
-                self.proxy.port = port;
+        if let Ok(v) = env::var("PROXY_ADMIN_SOCKET") {
+            self.admin.unix_socket = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_SOCKET_MODE") {
+            self.admin.unix_socket_mode = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_ALLOWED_CIDRS") {
+            self.admin.allowed_cidrs = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_LOCALHOST_BYPASS") {
+            if let Ok(enabled) = v.parse() {
+                self.admin.localhost_bypass = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_COMPRESSION") {
+            if let Ok(enabled) = v.parse() {
+                self.admin.compression.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_COMPRESSION_MIN_SIZE") {
+            if let Ok(size) = v.parse() {
+                self.admin.compression.min_size_bytes = size;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_COMPRESSION_EXCLUDE") {
+            self.admin.compression.exclude_content_types =
+                v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_ADMIN_COMPRESSION_ZSTD") {
+            if let Ok(enabled) = v.parse() {
+                self.admin.compression.zstd = enabled;
+            }
+        }
+
+        // Proxy 配置，作用于第一个监听器
+        if let Some(proxy) = self.proxy.first_mut() {
+            if let Ok(v) = env::var("PROXY_PROXY_HOST") {
+                proxy.host = v;
+            }
+            if let Ok(v) = env::var("PROXY_PROXY_PORT") {
+                if let Ok(port) = v.parse() {
+                    proxy.port = port;
+                }
+            }
+            if let Ok(v) = env::var("PROXY_PROXY_TCP_ENABLED") {
+                if let Ok(enabled) = v.parse() {
+                    proxy.tcp_enabled = enabled;
+                }
+            }
+            if let Ok(v) = env::var("PROXY_PROXY_PROTOCOL") {
+                if let Ok(enabled) = v.parse() {
+                    proxy.proxy_protocol = enabled;
+                }
+            }
+            if let Ok(v) = env::var("PROXY_PROXY_SOCKET") {
+                proxy.unix_socket = Some(v);
+            }
+            if let Ok(v) = env::var("PROXY_PROXY_SOCKET_MODE") {
+                proxy.unix_socket_mode = Some(v);
+            }
+            if let Ok(v) = env::var("PROXY_TLS_CERT") {
+                proxy.tls.get_or_insert_with(Default::default).cert_path = v;
+            }
+            if let Ok(v) = env::var("PROXY_TLS_KEY") {
+                proxy.tls.get_or_insert_with(Default::default).key_path = v;
+            }
+            if let Ok(v) = env::var("PROXY_TLS_CLIENT_CA") {
+                proxy
+                    .tls
+                    .get_or_insert_with(Default::default)
+                    .client_ca_path = Some(v);
+            }
+            if let Ok(v) = env::var("PROXY_TLS_CLIENT_CERT_REQUIRED") {
+                if let Ok(required) = v.parse() {
+                    proxy
+                        .tls
+                        .get_or_insert_with(Default::default)
+                        .client_cert_required = required;
+                }
+            }
+            if let Some(acme) = proxy.tls.as_mut().and_then(|t| t.acme.as_mut()) {
+                if let Ok(v) = env::var("PROXY_ACME_DIRECTORY") {
+                    acme.directory_url = v;
+                }
+                if let Ok(v) = env::var("PROXY_ACME_STORAGE_DIR") {
+                    acme.storage_dir = v;
+                }
             }
         }
 
@@ -92,11 +1547,55 @@ impl Config {
         if let Ok(v) = env::var("PROXY_PASSWORD") {
             self.auth.password = v;
         }
+        if let Ok(v) = env::var("PROXY_SESSION_TTL_SECS") {
+            if let Ok(ttl) = v.parse() {
+                self.auth.session_ttl_secs = ttl;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_SLIDING_EXPIRATION") {
+            if let Ok(enabled) = v.parse() {
+                self.auth.sliding_expiration = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_SESSION_BIND_USER_AGENT") {
+            if let Ok(enabled) = v.parse() {
+                self.auth.bind_user_agent = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_SESSION_BIND_IP") {
+            if let Ok(enabled) = v.parse() {
+                self.auth.bind_ip = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_MAX_LOGIN_FAILURES") {
+            if let Ok(max) = v.parse() {
+                self.auth.max_login_failures = max;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_LOGIN_LOCKOUT_SECS") {
+            if let Ok(secs) = v.parse() {
+                self.auth.lockout_secs = secs;
+            }
+        }
+        if let (Ok(v), Some(oidc)) = (env::var("PROXY_OIDC_CLIENT_SECRET"), &mut self.auth.oidc) {
+            oidc.client_secret = v;
+        }
 
         // 数据库配置
         if let Ok(v) = env::var("PROXY_DB_PATH") {
             self.database.path = v;
         }
+        if let Ok(v) = env::var("PROXY_DB_BACKUP_DIR") {
+            self.database.backup.directory = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Ok(v) = env::var("PROXY_DB_BACKUP_INTERVAL") {
+            if let Ok(secs) = v.parse() {
+                self.database.backup.interval_secs = secs;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DB_BACKUP_RETENTION") {
+            self.database.backup.retention = v.parse().ok().filter(|n| *n > 0);
+        }
 
         // 日志配置
         if let Ok(v) = env::var("PROXY_LOG_DIR") {
@@ -112,6 +1611,319 @@ impl Config {
                 self.logging.retention_days = days;
             }
         }
+        if let Ok(v) = env::var("PROXY_LOG_REDACT_HEADERS") {
+            self.logging.redact.headers = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_LOG_REDACT_QUERY") {
+            self.logging.redact.query_params = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+
+        // 访问日志
+        if let Ok(v) = env::var("PROXY_ACCESS_LOG") {
+            if let Ok(enabled) = v.parse() {
+                self.access_log.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ACCESS_LOG_DIR") {
+            self.access_log.directory = v;
+        }
+        if let Ok(v) = env::var("PROXY_ACCESS_LOG_FORMAT") {
+            match v.to_ascii_lowercase().as_str() {
+                "json" => self.access_log.format = AccessLogFormat::Json,
+                "combined" => self.access_log.format = AccessLogFormat::Combined,
+                _ => {}
+            }
+        }
+
+        // 内置端点
+        if let Ok(v) = env::var("PROXY_HEALTH_PATH") {
+            self.endpoints.health_path = v;
+        }
+        if let Ok(v) = env::var("PROXY_HEALTH_ENABLED") {
+            if let Ok(enabled) = v.parse() {
+                self.endpoints.health_enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_METRICS_PATH") {
+            self.endpoints.metrics_path = v;
+        }
+        if let Ok(v) = env::var("PROXY_METRICS_ENABLED") {
+            if let Ok(enabled) = v.parse() {
+                self.endpoints.metrics_enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ENDPOINTS_RULES_OVERRIDE") {
+            if let Ok(enabled) = v.parse() {
+                self.endpoints.rules_override = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ENDPOINTS_ALLOW_IPS") {
+            self.endpoints.allow_ips = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_ENDPOINTS_TOKEN") {
+            self.endpoints.token = Some(v);
+        }
+
+        // 变更审批
+        if let Ok(v) = env::var("PROXY_CHANGE_APPROVAL") {
+            if let Ok(enabled) = v.parse() {
+                self.change_approval.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_CHANGE_APPROVAL_DELAY_SECS") {
+            if let Ok(secs) = v.parse() {
+                self.change_approval.approval_delay_secs = secs;
+            }
+        }
+
+        // 主备
+        if let Ok(v) = env::var("PROXY_HA_ROLE") {
+            match v.to_ascii_lowercase().as_str() {
+                "standalone" => self.ha.role = HaRole::Standalone,
+                "primary" => self.ha.role = HaRole::Primary,
+                "standby" => self.ha.role = HaRole::Standby,
+                _ => {}
+            }
+        }
+        if let Ok(v) = env::var("PROXY_HA_PEER_URL") {
+            self.ha.peer_url = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_HA_TOKEN") {
+            self.ha.token = Some(v);
+        }
+
+        // 服务发现
+        if let Ok(v) = env::var("PROXY_CONSUL_ADDR") {
+            match self.discovery.consul {
+                Some(ref mut consul) => consul.address = v,
+                None => {
+                    self.discovery.consul = Some(ConsulConfig {
+                        address: v,
+                        token: None,
+                        datacenter: None,
+                    })
+                }
+            }
+        }
+        if let (Ok(v), Some(consul)) = (env::var("PROXY_CONSUL_TOKEN"), &mut self.discovery.consul)
+        {
+            consul.token = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_K8S_DISCOVERY") {
+            if let Ok(enabled) = v.parse() {
+                self.discovery.kubernetes.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ETCD_ADDR") {
+            match self.discovery.etcd {
+                Some(ref mut etcd) => etcd.address = v,
+                None => {
+                    self.discovery.etcd = Some(EtcdConfig {
+                        address: v,
+                        prefix: default_etcd_prefix(),
+                    })
+                }
+            }
+        }
+
+        // 集群
+        if let Ok(v) = env::var("PROXY_CLUSTER_ENABLED") {
+            if let Ok(enabled) = v.parse() {
+                self.cluster.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_CLUSTER_NODE_ID") {
+            self.cluster.node_id = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Ok(v) = env::var("PROXY_CLUSTER_ADVERTISE_URL") {
+            self.cluster.advertise_url = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_CLUSTER_PEERS") {
+            self.cluster.peers = v
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Ok(v) = env::var("PROXY_CLUSTER_TOKEN") {
+            self.cluster.token = Some(v);
+        }
+
+        // 直接代理目标访问控制
+        if let Ok(v) = env::var("PROXY_DIRECT_ALLOW") {
+            self.direct_proxy.allow = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_DENY") {
+            self.direct_proxy.deny = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_BLOCK_PRIVATE") {
+            if let Ok(block) = v.parse() {
+                self.direct_proxy.block_private = block;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_FORWARD_PROXY") {
+            if let Ok(enabled) = v.parse() {
+                self.direct_proxy.forward_proxy.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_FORWARD_PROXY_AUTH") {
+            if let Ok(required) = v.parse() {
+                self.direct_proxy.forward_proxy.require_auth = required;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_FORWARD_PROXY_CONNECT_PORTS") {
+            self.direct_proxy.forward_proxy.connect_ports =
+                v.split(',').filter_map(|s| s.trim().parse().ok()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_SOCKS") {
+            if let Ok(enabled) = v.parse() {
+                self.socks.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_SOCKS_HOST") {
+            self.socks.host = v;
+        }
+        if let Ok(v) = env::var("PROXY_SOCKS_PORT") {
+            if let Ok(port) = v.parse() {
+                self.socks.port = port;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_SOCKS_AUTH") {
+            if let Ok(required) = v.parse() {
+                self.socks.require_auth = required;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_CACHE") {
+            if let Ok(enabled) = v.parse() {
+                self.direct_proxy.cache.enabled = enabled;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_CACHE_DIR") {
+            self.direct_proxy.cache.dir = v;
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_CACHE_HOSTS") {
+            self.direct_proxy.cache.hosts = v.split(',').map(|s| s.trim().to_string()).collect();
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_CACHE_TTL_SECS") {
+            if let Ok(ttl) = v.parse() {
+                self.direct_proxy.cache.ttl_secs = ttl;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DIRECT_CACHE_MAX_SIZE_MB") {
+            if let Ok(size) = v.parse() {
+                self.direct_proxy.cache.max_size_mb = Some(size);
+            }
+        }
+
+        // 缓存存储后端
+        if let Ok(v) = env::var("PROXY_CACHE_BACKEND") {
+            match v.to_lowercase().as_str() {
+                "disk" => self.cache_store.backend = CacheBackend::Disk,
+                "memory" => self.cache_store.backend = CacheBackend::Memory,
+                "s3" => self.cache_store.backend = CacheBackend::S3,
+                _ => {}
+            }
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_MEMORY_MAX_SIZE_MB") {
+            if let Ok(size) = v.parse() {
+                self.cache_store.memory_max_size_mb = size;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_DISK_MAX_SIZE_MB") {
+            if let Ok(size) = v.parse() {
+                self.cache_store.disk_max_size_mb = Some(size);
+            }
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_INDEX_PATH") {
+            self.cache_store.index_path = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_ENDPOINT") {
+            self.cache_store.s3.endpoint = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_BUCKET") {
+            self.cache_store.s3.bucket = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_REGION") {
+            self.cache_store.s3.region = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_ACCESS_KEY") {
+            self.cache_store.s3.access_key = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_SECRET_KEY") {
+            self.cache_store.s3.secret_key = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_PREFIX") {
+            self.cache_store.s3.prefix = v;
+        }
+        if let Ok(v) = env::var("PROXY_CACHE_S3_PATH_STYLE") {
+            if let Ok(path_style) = v.parse() {
+                self.cache_store.s3.path_style = path_style;
+            }
+        }
+
+        // 上游 DNS
+        if let Ok(v) = env::var("PROXY_DNS_NAMESERVERS") {
+            self.dns.nameservers = v
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Ok(v) = env::var("PROXY_DNS_MIN_TTL_SECS") {
+            if let Ok(secs) = v.parse() {
+                self.dns.min_ttl_secs = secs;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DNS_MAX_TTL_SECS") {
+            if let Ok(secs) = v.parse() {
+                self.dns.max_ttl_secs = secs;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DNS_NEGATIVE_TTL_SECS") {
+            if let Ok(secs) = v.parse() {
+                self.dns.negative_ttl_secs = secs;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_DNS_IP_FAMILY") {
+            match v.to_ascii_lowercase().as_str() {
+                "auto" => self.dns.ip_family = IpFamily::Auto,
+                "prefer_ipv4" => self.dns.ip_family = IpFamily::PreferIpv4,
+                "prefer_ipv6" => self.dns.ip_family = IpFamily::PreferIpv6,
+                "ipv4_only" => self.dns.ip_family = IpFamily::Ipv4Only,
+                "ipv6_only" => self.dns.ip_family = IpFamily::Ipv6Only,
+                _ => {}
+            }
+        }
+        // 格式为 host=ip，逗号分隔，同一域名可出现多次
+        if let Ok(v) = env::var("PROXY_DNS_HOSTS") {
+            let mut hosts: HashMap<String, Vec<IpAddr>> = HashMap::new();
+            for (host, ip) in v.split(',').filter_map(|entry| entry.split_once('=')) {
+                if let Ok(ip) = ip.trim().parse() {
+                    hosts.entry(host.trim().to_string()).or_default().push(ip);
+                }
+            }
+            self.dns.hosts = hosts;
+        }
+
+        // 规则文件
+        if let Ok(v) = env::var("PROXY_RULES_FILE") {
+            self.rules_file.path = Some(v).filter(|v| !v.is_empty());
+        }
+        if let Ok(v) = env::var("PROXY_RULES_FILE_MODE") {
+            match v.to_ascii_lowercase().as_str() {
+                "file" => self.rules_file.mode = RulesFileMode::File,
+                "db" => self.rules_file.mode = RulesFileMode::Db,
+                _ => {}
+            }
+        }
+
+        // 加密密钥存储
+        if let Ok(v) = env::var("PROXY_SECRETS_KEY") {
+            self.secrets.key = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_SECRETS_KEY_FILE") {
+            self.secrets.key_file = Some(v);
+        }
 
         // 默认超时
         if let Ok(v) = env::var("PROXY_DEFAULT_TIMEOUT") {
@@ -119,5 +1931,84 @@ impl Config {
                 self.default_timeout_secs = timeout;
             }
         }
+        if let Ok(v) = env::var("PROXY_MAX_BODY_BYTES") {
+            if let Ok(bytes) = v.parse() {
+                self.max_body_bytes = bytes;
+            }
+        }
+
+        // 生命周期 Webhook
+        if let Ok(v) = env::var("PROXY_LIFECYCLE_WEBHOOK") {
+            self.lifecycle.webhooks.push(WebhookConfig {
+                url: v,
+                events: Vec::new(),
+            });
+        }
+        if let Ok(v) = env::var("PROXY_DRAIN_TIMEOUT") {
+            if let Ok(secs) = v.parse() {
+                self.lifecycle.drain_timeout_secs = secs;
+            }
+        }
+        if let Ok(v) = env::var("PROXY_REUSE_PORT") {
+            if let Ok(enabled) = v.parse() {
+                self.lifecycle.reuse_port = enabled;
+            }
+        }
+
+        // 配置变更 Webhook
+        if let Ok(v) = env::var("PROXY_CHANGE_WEBHOOK") {
+            self.change_webhooks.endpoints.push(ChangeWebhookConfig {
+                url: v,
+                secret: env::var("PROXY_CHANGE_WEBHOOK_SECRET").ok(),
+                events: Vec::new(),
+            });
+        }
+
+        // 告警
+        if let Ok(v) = env::var("PROXY_ALERT_WEBHOOK") {
+            self.alerts.channels.push(AlertChannelConfig {
+                kind: AlertChannelKind::Webhook {
+                    url: v,
+                    headers: HashMap::new(),
+                },
+                events: Vec::new(),
+            });
+        }
+        if let Ok(v) = env::var("PROXY_ALERT_SLACK_WEBHOOK") {
+            self.alerts.channels.push(AlertChannelConfig {
+                kind: AlertChannelKind::Slack {
+                    webhook_url: v,
+                    channel: None,
+                },
+                events: Vec::new(),
+            });
+        }
+        if let Ok(v) = env::var("PROXY_ALERT_ERROR_RATE") {
+            if let Ok(threshold) = v.parse() {
+                self.alerts.error_rate = Some(ErrorRateAlertConfig {
+                    threshold,
+                    window_secs: default_error_rate_window(),
+                    min_requests: default_error_rate_min_requests(),
+                });
+            }
+        }
+        if let Ok(v) = env::var("PROXY_ALERT_CERT_EXPIRY_DAYS") {
+            if let Ok(days) = v.parse() {
+                self.alerts.cert_expiry_days = days;
+            }
+        }
+
+        // 链路追踪
+        if let Ok(v) = env::var("PROXY_OTLP_ENDPOINT") {
+            self.telemetry.otlp_endpoint = Some(v);
+        }
+        if let Ok(v) = env::var("PROXY_OTLP_SERVICE_NAME") {
+            self.telemetry.service_name = v;
+        }
+        if let Ok(v) = env::var("PROXY_OTLP_SAMPLE_RATIO") {
+            if let Ok(ratio) = v.parse() {
+                self.telemetry.sample_ratio = ratio;
+            }
+        }
     }
 }
@@ -0,0 +1,206 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use bytes::Bytes;
+use chrono::Utc;
+use dashmap::DashMap;
+use futures::{stream, StreamExt};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::watch;
+
+use crate::api::ApiResponse;
+use crate::AdminState;
+
+/// 进行中的代理请求，响应体传输完成或客户端断开后移除
+#[derive(Clone, Default)]
+pub struct ActiveRequests {
+    next_id: Arc<AtomicU64>,
+    entries: Arc<DashMap<u64, Arc<ActiveEntry>>>,
+}
+
+impl std::fmt::Debug for ActiveRequests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActiveRequests")
+            .field("active", &self.entries.len())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct ActiveEntry {
+    id: u64,
+    client_ip: String,
+    method: String,
+    path: String,
+    /// 匹配的规则与转发目标，路由完成后写入
+    route: Mutex<(Option<String>, Option<String>)>,
+    started: Instant,
+    started_at: i64,
+    bytes_out: AtomicU64,
+    kill: watch::Sender<bool>,
+}
+
+/// 请求列表中的一项
+#[derive(Debug, Serialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub client_ip: String,
+    pub method: String,
+    pub path: String,
+    pub rule: Option<String>,
+    pub target: Option<String>,
+    pub started_at: i64,
+    pub duration_ms: u64,
+    pub bytes_out: u64,
+}
+
+impl ActiveRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, client_ip: String, method: String, path: String) -> ActiveRequest {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let entry = Arc::new(ActiveEntry {
+            id,
+            client_ip,
+            method,
+            path,
+            route: Mutex::new((None, None)),
+            started: Instant::now(),
+            started_at: Utc::now().timestamp(),
+            bytes_out: AtomicU64::new(0),
+            kill: watch::channel(false).0,
+        });
+        self.entries.insert(id, entry.clone());
+        ActiveRequest {
+            entry,
+            registry: self.clone(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<ConnectionInfo> {
+        let mut list: Vec<ConnectionInfo> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let (rule, target) = entry.route.lock().clone();
+                ConnectionInfo {
+                    id: entry.id,
+                    client_ip: entry.client_ip.clone(),
+                    method: entry.method.clone(),
+                    path: entry.path.clone(),
+                    rule,
+                    target,
+                    started_at: entry.started_at,
+                    duration_ms: entry.started.elapsed().as_millis() as u64,
+                    bytes_out: entry.bytes_out.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        list.sort_by_key(|c| c.id);
+        list
+    }
+
+    /// 进行中的请求数
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 终止指定请求，请求不存在时返回 false
+    pub fn kill(&self, id: u64) -> bool {
+        match self.entries.get(&id) {
+            Some(entry) => {
+                entry.kill.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 单个请求的登记，释放时从列表移除
+#[derive(Debug)]
+pub struct ActiveRequest {
+    entry: Arc<ActiveEntry>,
+    registry: ActiveRequests,
+}
+
+impl ActiveRequest {
+    pub fn set_route(&self, rule: Option<&str>, target: &str) {
+        *self.entry.route.lock() = (rule.map(str::to_string), Some(target.to_string()));
+    }
+
+    /// 被管理接口终止时完成，不借用本登记，可与请求处理并发等待
+    pub fn killed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut kill = self.entry.kill.subscribe();
+        async move {
+            if kill.wait_for(|killed| *killed).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// 统计已发送字节数；被终止时响应体以错误结束，连接随之断开
+    pub fn wrap_response(self, resp: Response) -> Response {
+        let (parts, body) = resp.into_parts();
+        let killed = self.killed();
+        let entry = self.entry.clone();
+        let counted = body.into_data_stream().map(move |chunk| {
+            if let Ok(ref data) = chunk {
+                entry
+                    .bytes_out
+                    .fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+            chunk
+        });
+        let entry = self.entry.clone();
+        let aborted =
+            stream::once(async move { *entry.kill.borrow() }).filter_map(|killed| async move {
+                killed.then(|| {
+                    Err::<Bytes, _>(axum::Error::new(std::io::Error::other("connection killed")))
+                })
+            });
+        let stream = counted.take_until(killed).chain(aborted).map(move |chunk| {
+            // 登记随响应体一起释放
+            let _ = &self;
+            chunk
+        });
+
+        Response::from_parts(parts, Body::from_stream(stream))
+    }
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.registry.entries.remove(&self.entry.id);
+    }
+}
+
+/// 进行中的代理请求列表
+pub async fn list_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<ConnectionInfo>>> {
+    Json(ApiResponse::ok(state.active.snapshot()))
+}
+
+/// 终止指定请求，用于切断异常的长时间下载等
+pub async fn kill_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<u64>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if !state.active.kill(id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::warn!(id, "Proxy request killed");
+    Ok(Json(ApiResponse::ok(())))
+}
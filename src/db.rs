@@ -1,9 +1,29 @@
 use anyhow::Result;
 use r2d2::{Pool, PooledConnection};
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
 use serde::{Deserialize, Serialize};
 
+use crate::access_log::RuleLogging;
+use crate::alerts::{AlertMetric, Comparison, Severity};
+use crate::auth::Session;
+use crate::config::{IpFamily, Role};
+use crate::dns::PoolOptions;
+use crate::experiment::ExperimentOptions;
+use crate::faults::FaultOptions;
+use crate::goproxy::GoProxyOptions;
+use crate::maven::MavenOptions;
+use crate::npm::NpmOptions;
+use crate::packages::PackageCacheOptions;
+use crate::pypi::PypiOptions;
+use crate::registry::RegistryOptions;
+use crate::rule_auth::RuleAuth;
+use crate::secrets::{UpstreamAuth, SYSTEM_PREFIX};
+use crate::simulate::{FixtureRequest, FixtureResponse};
+use crate::stats::RuleStatsSnapshot;
+use crate::tls::ClientCertFormat;
+use crate::upstream_proxy::UpstreamProxyOptions;
+
 /// 代理规则
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyRule {
@@ -13,10 +33,697 @@ pub struct ProxyRule {
     pub target: String,
     pub timeout_secs: u64,
     pub enabled: bool,
+    #[serde(default)]
+    pub options: RuleOptions,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// 规则高级选项，以 JSON 存储在 proxy_rules.options 列
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleOptions {
+    /// 规则标签，配置了 rule_tags 的代理监听器只处理带有其中标签的规则
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 幂等键缓存时间(秒)，设置后对携带 Idempotency-Key 的请求缓存上游响应
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_ttl_secs: Option<u64>,
+    /// 连接池调优：空闲连接数、空闲时间与上游并发上限
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolOptions>,
+    /// 与上游建立连接（含 TLS 握手）的超时(秒)，默认 10 秒
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// 等待上游下一个响应体数据块的超时(秒)，默认与规则超时相同
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_read_timeout_secs: Option<u64>,
+    /// 慢客户端接收单个数据块的超时(秒)，默认不限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_write_timeout_secs: Option<u64>,
+    /// 向上游转发客户端证书信息（X-SSL-Client-*），需启用 mTLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert_headers: Option<ClientCertFormat>,
+    /// 保留请求头原始大小写与顺序转发（仅 HTTP/1，供对大小写敏感的旧上游使用）
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub preserve_header_case: bool,
+    /// 连接上游时先发送 PROXY 协议 v2 头部，携带客户端地址（仅 HTTP/1，每个请求新建连接）
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub upstream_proxy_protocol: bool,
+    /// 经 HTTP / SOCKS5 代理访问上游（企业出口或指定地区出口）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_proxy: Option<UpstreamProxyOptions>,
+    /// 连接上游使用的 IP 地址族，默认使用全局 dns.ip_family
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_family: Option<IpFamily>,
+    /// 请求体下限(字节)，Content-Length 小于该值时跳过本规则，交给后续规则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_request_bytes: Option<u64>,
+    /// 请求体上限(字节)，Content-Length 大于该值时跳过本规则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_request_bytes: Option<u64>,
+    /// 转发时缓冲的请求体上限(字节)，超过时返回 413，默认使用全局 max_body_bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    /// 配置了大小条件但请求体长度未知（如分块传输）时仍匹配本规则，默认跳过
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub match_unknown_length: bool,
+    /// 上游未返回 ETag 时按响应体生成强 ETag，并处理 If-None-Match
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub generate_etag: bool,
+    /// 向上游添加 X-Proxy-Rule / X-Proxy-Target 请求头，标明处理请求的规则
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub annotate_upstream: bool,
+    /// 向客户端响应添加同样的标注头，供调试使用
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub annotate_response: bool,
+    /// 要求已登录管理界面（单点登录）的会话，并向上游注入 X-Auth-User / X-Auth-Groups / X-Auth-Assertion
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub identity_headers: bool,
+    /// 访问本规则需要的认证（Bearer / Basic / API Key），上游本身没有认证时使用，令牌与密码引用密钥名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<RuleAuth>,
+    /// 向上游注入的认证凭据，凭据值引用加密密钥存储中的名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_auth: Option<UpstreamAuth>,
+    /// 作为 Docker Registry v2 镜像，代理处理上游 token 认证并可缓存 blob
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<RegistryOptions>,
+    /// 作为 npm 镜像，包元数据中的 tarball 地址改写为经过代理的地址，并可缓存 tarball
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub npm: Option<NpmOptions>,
+    /// 作为 PyPI simple 索引镜像，文件链接改写为经过代理的地址，并可按 sha256 缓存文件
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pypi: Option<PypiOptions>,
+    /// 作为 Maven 仓库缓存，发布版本永久缓存，maven-metadata.xml 与 SNAPSHOT 短期缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maven: Option<MavenOptions>,
+    /// 作为 Go 模块代理（GOPROXY），具体版本永久缓存，版本列表短期缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goproxy: Option<GoProxyOptions>,
+    /// 作为 APT / YUM 软件包仓库缓存，软件包永久缓存，仓库元数据短期缓存，可限制缓存容量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_cache: Option<PackageCacheOptions>,
+    /// 按请求头或 cookie 确定性分组的 A/B 实验，各分组可转发到不同目标
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<ExperimentOptions>,
+    /// 故障注入：按比例延迟或返回错误，全局开关打开或携带指定请求头时生效
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault: Option<FaultOptions>,
+    /// 转发前执行的 Rhai 脚本，可修改请求或直接返回响应
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    /// 按顺序执行的 WASM 插件名，对应配置文件 `plugins` 中的键
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<String>,
+    /// 关键上游：定期探测可达性，不可达时就绪检查返回 503
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    /// 请求日志：off 不记录，errors 只记录错误，sampled 每 N 个记录 1 个，默认 full 全部记录
+    #[serde(skip_serializing_if = "RuleLogging::is_full")]
+    pub log: RuleLogging,
+}
+
+impl RuleOptions {
+    /// 引用的密钥名
+    pub fn secret_refs(&self) -> impl Iterator<Item = &str> {
+        let auth = self.auth.as_ref().and_then(RuleAuth::secret);
+        let upstream_auth = self.upstream_auth.as_ref().map(|auth| auth.secret());
+        let registry = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.password_secret.as_deref());
+        let upstream_proxy = self
+            .upstream_proxy
+            .as_ref()
+            .and_then(|proxy| proxy.password_secret.as_deref());
+        auth.into_iter()
+            .chain(upstream_auth)
+            .chain(registry)
+            .chain(upstream_proxy)
+    }
+}
+
+/// 规则调试样本，供模拟接口重放
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleFixture {
+    pub id: i64,
+    pub rule_id: i64,
+    pub name: String,
+    pub request: FixtureRequest,
+    pub response: FixtureResponse,
+    pub created_at: String,
+}
+
+/// 自定义告警规则，按 evaluation_interval_secs 对采集的指标求值
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertRule {
+    pub id: i64,
+    pub name: String,
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// 滚动窗口类指标的统计窗口(秒)
+    pub window_secs: u64,
+    /// 条件持续成立该时长(秒)后才告警，0 表示立即告警
+    pub duration_secs: u64,
+    pub severity: Severity,
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRuleInput {
+    pub name: String,
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    #[serde(default = "default_alert_window")]
+    pub window_secs: u64,
+    #[serde(default)]
+    pub duration_secs: u64,
+    #[serde(default = "default_alert_severity")]
+    pub severity: Severity,
+    #[serde(default = "default_import_enabled")]
+    pub enabled: bool,
+}
+
+fn default_alert_window() -> u64 {
+    60
+}
+
+fn default_alert_severity() -> Severity {
+    Severity::Warning
+}
+
+/// 规则访问 API Key，只保存 SHA-256 摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyKey {
+    pub id: i64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    /// 密钥开头几位，便于识别
+    pub prefix: String,
+    /// 限定可访问的规则，为空时适用于所有使用 API Key 认证的规则
+    pub rule_id: Option<i64>,
+    pub created_at: String,
+}
+
+/// 管理员账号，备份与恢复时连同密码哈希一起导出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminUser {
+    pub username: String,
+    pub password_hash: String,
+}
+
+/// 加密保存的密钥，value 为 base64(nonce || 密文)，接口只返回名称
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredSecret {
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub value: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// 规则写入参数
+pub struct RuleInput<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+    pub target: &'a str,
+    pub timeout_secs: u64,
+    /// 为 None 时更新操作保留原有选项
+    pub options: Option<&'a RuleOptions>,
+}
+
+/// 导入的规则，按名称对应已有规则；导出的完整规则（含 id 与时间戳）也可直接导入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleImport {
+    pub name: String,
+    pub source: String,
+    pub target: String,
+    #[serde(default = "default_import_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_import_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub options: RuleOptions,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+fn default_import_timeout() -> u64 {
+    30
+}
+
+fn default_import_enabled() -> bool {
+    true
+}
+
+/// 规则列表查询参数
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleQuery {
+    #[serde(default = "default_page")]
+    pub page: u64,
+    #[serde(default = "default_page_size")]
+    pub size: u64,
+    #[serde(default)]
+    pub search: Option<String>,
+    /// 排序字段，可加 `:desc` 后缀，如 `name:desc`
+    #[serde(default)]
+    pub sort: Option<String>,
+}
+
+/// 规则分页结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RulePage {
+    pub items: Vec<ProxyRule>,
+    pub total: u64,
+    pub page: u64,
+    pub size: u64,
+}
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_page_size() -> u64 {
+    20
+}
+
+const RULE_COLUMNS: &str =
+    "id, name, source, target, timeout_secs, enabled, options, created_at, updated_at";
+
+fn map_rule_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<ProxyRule> {
+    Ok(ProxyRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        source: row.get(2)?,
+        target: row.get(3)?,
+        timeout_secs: row.get::<_, i64>(4)? as u64,
+        enabled: row.get::<_, i64>(5)? == 1,
+        options: row
+            .get::<_, Option<String>>(6)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
+
+/// 当前数据库结构版本，新增表或列时递增并同步更新 SCHEMA
+pub const SCHEMA_VERSION: i64 = 14;
+
+/// 迁移完成后应存在的表及列
+pub const SCHEMA: &[(&str, &[&str])] = &[
+    (
+        "proxy_rules",
+        &[
+            "id",
+            "name",
+            "source",
+            "target",
+            "timeout_secs",
+            "enabled",
+            "options",
+            "created_at",
+            "updated_at",
+        ],
+    ),
+    ("system_config", &["id", "key", "value"]),
+    (
+        "rule_stats",
+        &[
+            "rule_id",
+            "request_count",
+            "error_count",
+            "bytes_in",
+            "bytes_out",
+            "p50_ms",
+            "p95_ms",
+            "updated_at",
+        ],
+    ),
+    (
+        "rule_fixtures",
+        &["id", "rule_id", "name", "request", "response", "created_at"],
+    ),
+    (
+        "admin_users",
+        &["id", "username", "password_hash", "updated_at"],
+    ),
+    (
+        "sessions",
+        &[
+            "token",
+            "username",
+            "role",
+            "groups",
+            "binding",
+            "expires_at",
+            "created_at",
+        ],
+    ),
+    (
+        "login_attempts",
+        &[
+            "id",
+            "username",
+            "client_ip",
+            "success",
+            "reason",
+            "created_at",
+        ],
+    ),
+    (
+        "pending_changes",
+        &[
+            "id",
+            "method",
+            "path",
+            "payload",
+            "requested_by",
+            "requested_at",
+            "approvable_at",
+            "status",
+            "decided_by",
+            "decided_at",
+            "error",
+        ],
+    ),
+    (
+        "proxy_keys",
+        &["id", "name", "key_hash", "prefix", "rule_id", "created_at"],
+    ),
+    (
+        "secrets",
+        &["id", "name", "value", "created_at", "updated_at"],
+    ),
+    (
+        "alert_rules",
+        &[
+            "id",
+            "name",
+            "metric",
+            "comparison",
+            "threshold",
+            "window_secs",
+            "duration_secs",
+            "severity",
+            "enabled",
+            "created_at",
+            "updated_at",
+        ],
+    ),
+];
+
+/// 以给定规则（保留 id）替换全部规则，并清理已删除规则的统计与样本
+fn enabled_rules_in(conn: &rusqlite::Connection) -> Result<Vec<ProxyRule>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM proxy_rules WHERE enabled = 1 ORDER BY id",
+        RULE_COLUMNS
+    ))?;
+    let rules = stmt
+        .query_map([], map_rule_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rules)
+}
+
+fn update_rule_in(
+    tx: &rusqlite::Transaction<'_>,
+    id: i64,
+    rule: &RuleInput<'_>,
+    options: Option<String>,
+    enabled: bool,
+) -> Result<()> {
+    tx.execute(
+        "UPDATE proxy_rules SET name = ?1, source = ?2, target = ?3, timeout_secs = ?4, enabled = ?5, 
+         options = COALESCE(?6, options), updated_at = datetime('now', 'localtime') WHERE id = ?7",
+        params![
+            rule.name,
+            rule.source,
+            rule.target,
+            rule.timeout_secs as i64,
+            enabled as i64,
+            options,
+            id
+        ],
+    )?;
+    Ok(())
+}
+
+fn import_rules_in(tx: &rusqlite::Transaction<'_>, rules: &[RuleImport]) -> Result<ImportSummary> {
+    let mut existing: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT id, name FROM proxy_rules ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut summary = ImportSummary::default();
+    for rule in rules {
+        let options = serde_json::to_string(&rule.options)?;
+        match existing.iter().position(|(_, name)| *name == rule.name) {
+            Some(index) => {
+                let (id, _) = existing.remove(index);
+                tx.execute(
+                    "UPDATE proxy_rules SET source = ?1, target = ?2, timeout_secs = ?3, enabled = ?4,
+                     options = ?5, updated_at = datetime('now', 'localtime') WHERE id = ?6",
+                    params![
+                        rule.source,
+                        rule.target,
+                        rule.timeout_secs as i64,
+                        rule.enabled as i64,
+                        options,
+                        id
+                    ],
+                )?;
+                summary.updated += 1;
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO proxy_rules (name, source, target, timeout_secs, enabled, options)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        rule.name,
+                        rule.source,
+                        rule.target,
+                        rule.timeout_secs as i64,
+                        rule.enabled as i64,
+                        options
+                    ],
+                )?;
+                summary.created += 1;
+            }
+        }
+    }
+    for (id, _) in existing {
+        tx.execute("DELETE FROM proxy_rules WHERE id = ?1", params![id])?;
+        tx.execute("DELETE FROM rule_stats WHERE rule_id = ?1", params![id])?;
+        tx.execute("DELETE FROM rule_fixtures WHERE rule_id = ?1", params![id])?;
+        summary.deleted += 1;
+    }
+    Ok(summary)
+}
+
+fn restore_in(
+    tx: &rusqlite::Transaction<'_>,
+    rules: &[ProxyRule],
+    configs: &[SystemConfig],
+    users: &[AdminUser],
+) -> Result<()> {
+    replace_rules_in(tx, rules)?;
+    tx.execute("DELETE FROM system_config", [])?;
+    for config in configs {
+        tx.execute(
+            "INSERT INTO system_config (key, value) VALUES (?1, ?2)",
+            params![config.key, config.value],
+        )?;
+    }
+    if !users.is_empty() {
+        tx.execute("DELETE FROM admin_users", [])?;
+        for user in users {
+            tx.execute(
+                "INSERT INTO admin_users (username, password_hash) VALUES (?1, ?2)",
+                params![user.username, user.password_hash],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// 替换 API Key 与内部密钥以外的密钥，供集群与主备同步
+fn replace_credentials_in(
+    tx: &rusqlite::Transaction<'_>,
+    secrets: &[(String, String)],
+    keys: &[ProxyKey],
+) -> Result<()> {
+    tx.execute(
+        "DELETE FROM secrets WHERE substr(name, 1, length(?1)) != ?1",
+        params![SYSTEM_PREFIX],
+    )?;
+    for (name, value) in secrets {
+        tx.execute(
+            "INSERT INTO secrets (name, value) VALUES (?1, ?2)",
+            params![name, value],
+        )?;
+    }
+    tx.execute("DELETE FROM proxy_keys", [])?;
+    for key in keys {
+        tx.execute(
+            "INSERT INTO proxy_keys (id, name, key_hash, prefix, rule_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                key.id,
+                key.name,
+                key.key_hash,
+                key.prefix,
+                key.rule_id,
+                key.created_at
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn replace_rules_in(tx: &rusqlite::Transaction<'_>, rules: &[ProxyRule]) -> Result<()> {
+    tx.execute("DELETE FROM proxy_rules", [])?;
+    {
+        let mut stmt = tx.prepare(&format!(
+            "INSERT INTO proxy_rules ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            RULE_COLUMNS
+        ))?;
+        for rule in rules {
+            stmt.execute(params![
+                rule.id,
+                rule.name,
+                rule.source,
+                rule.target,
+                rule.timeout_secs as i64,
+                rule.enabled as i64,
+                serde_json::to_string(&rule.options)?,
+                rule.created_at,
+                rule.updated_at
+            ])?;
+        }
+    }
+    tx.execute(
+        "DELETE FROM rule_stats WHERE rule_id NOT IN (SELECT id FROM proxy_rules)",
+        [],
+    )?;
+    tx.execute(
+        "DELETE FROM rule_fixtures WHERE rule_id NOT IN (SELECT id FROM proxy_rules)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn column_exists(conn: &rusqlite::Connection, table: &str, column: &str) -> Result<bool> {
+    Ok(conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ))?
+        .exists(params![column])?)
+}
+
+fn map_fixture_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RuleFixture> {
+    let request: String = row.get(3)?;
+    let response: String = row.get(4)?;
+    Ok(RuleFixture {
+        id: row.get(0)?,
+        rule_id: row.get(1)?,
+        name: row.get(2)?,
+        request: serde_json::from_str(&request).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        response: serde_json::from_str(&response).unwrap_or_default(),
+        created_at: row.get(5)?,
+    })
+}
+
+fn map_alert_rule_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AlertRule> {
+    Ok(AlertRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        metric: text_enum(row, 2)?,
+        comparison: text_enum(row, 3)?,
+        threshold: row.get(4)?,
+        window_secs: row.get::<_, i64>(5)? as u64,
+        duration_secs: row.get::<_, i64>(6)? as u64,
+        severity: text_enum(row, 7)?,
+        enabled: row.get::<_, i64>(8)? != 0,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+/// 以 serde 名称保存为文本的枚举列
+fn text_enum<T: for<'de> Deserialize<'de>>(
+    row: &rusqlite::Row<'_>,
+    idx: usize,
+) -> rusqlite::Result<T> {
+    let text: String = row.get(idx)?;
+    serde_json::from_value(serde_json::Value::String(text)).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn enum_text<T: Serialize>(value: &T) -> Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(text) => Ok(text),
+        other => anyhow::bail!("unexpected enum value {}", other),
+    }
+}
+
+/// 为已有表补充新增列（SQLite 不支持 ADD COLUMN IF NOT EXISTS）
+fn add_column_if_missing(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// 解析排序参数，只允许白名单字段，避免 SQL 注入
+fn parse_rule_sort(sort: Option<&str>) -> (&'static str, bool) {
+    let Some(sort) = sort else {
+        return ("id", false);
+    };
+    let (field, descending) = match sort.split_once(':') {
+        Some((field, dir)) => (field, dir.eq_ignore_ascii_case("desc")),
+        None => match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort, false),
+        },
+    };
+    let column = match field {
+        "name" => "name",
+        "source" => "source",
+        "target" => "target",
+        "timeout_secs" => "timeout_secs",
+        "enabled" => "enabled",
+        "created_at" => "created_at",
+        "updated_at" => "updated_at",
+        _ => "id",
+    };
+    (column, descending)
+}
+
 /// 系统配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemConfig {
@@ -25,6 +732,54 @@ pub struct SystemConfig {
     pub value: String,
 }
 
+/// 登录尝试记录
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginAttempt {
+    pub id: i64,
+    pub username: String,
+    pub client_ip: String,
+    pub success: bool,
+    /// ok / invalid_credentials / locked
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// 待审批变更，status 为 pending / applying / applied / failed / rejected
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingChange {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    pub payload: serde_json::Value,
+    pub requested_by: String,
+    pub requested_at: i64,
+    /// 提交人本人最早可审批的时间
+    pub approvable_at: i64,
+    pub status: String,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+const CHANGE_COLUMNS: &str = "id, method, path, payload, requested_by, requested_at, approvable_at, status, decided_by, decided_at, error";
+
+fn map_change_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<PendingChange> {
+    let payload: String = row.get(3)?;
+    Ok(PendingChange {
+        id: row.get(0)?,
+        method: row.get(1)?,
+        path: row.get(2)?,
+        payload: serde_json::from_str(&payload).unwrap_or_default(),
+        requested_by: row.get(4)?,
+        requested_at: row.get(5)?,
+        approvable_at: row.get(6)?,
+        status: row.get(7)?,
+        decided_by: row.get(8)?,
+        decided_at: row.get(9)?,
+        error: row.get(10)?,
+    })
+}
+
 /// 数据库连接池管理器
 #[derive(Clone)]
 pub struct Database {
@@ -44,6 +799,38 @@ impl Database {
         Ok(db)
     }
 
+    /// 内存数据库，用于发布前校验迁移
+    pub fn in_memory() -> Result<Self> {
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())?;
+
+        let db = Self { pool };
+        db.init_tables()?;
+        Ok(db)
+    }
+
+    pub fn schema_version(&self) -> Result<i64> {
+        let conn = self.conn()?;
+        Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+
+    /// 就绪检查：在短时间内取得连接并执行一次查询
+    pub fn ping(&self) -> Result<()> {
+        let conn = self.pool.get_timeout(std::time::Duration::from_secs(2))?;
+        conn.query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// 用 SQLite 在线备份接口把数据库复制到 `dest`，分批复制页面，期间其他连接照常读写
+    pub fn backup_to(&self, dest: &std::path::Path) -> Result<()> {
+        let conn = self.conn()?;
+        let mut target = rusqlite::Connection::open(dest)?;
+        let backup = rusqlite::backup::Backup::new(&conn, &mut target)?;
+        backup.run_to_completion(256, std::time::Duration::from_millis(10), None)?;
+        Ok(())
+    }
+
     fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>> {
         Ok(self.pool.get()?)
     }
@@ -61,6 +848,15 @@ impl Database {
         ",
         )?;
 
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            anyhow::bail!(
+                "Database schema version {} is newer than supported version {}",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS proxy_rules (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -69,12 +865,20 @@ impl Database {
                 target TEXT NOT NULL,
                 timeout_secs INTEGER DEFAULT 30,
                 enabled INTEGER DEFAULT 1,
+                options TEXT NOT NULL DEFAULT '{}',
                 created_at TEXT DEFAULT (datetime('now', 'localtime')),
                 updated_at TEXT DEFAULT (datetime('now', 'localtime'))
             )",
             [],
         )?;
 
+        add_column_if_missing(
+            &conn,
+            "proxy_rules",
+            "options",
+            "TEXT NOT NULL DEFAULT '{}'",
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS system_config (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -84,6 +888,124 @@ impl Database {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rule_stats (
+                rule_id INTEGER PRIMARY KEY,
+                request_count INTEGER DEFAULT 0,
+                error_count INTEGER DEFAULT 0,
+                bytes_in INTEGER DEFAULT 0,
+                bytes_out INTEGER DEFAULT 0,
+                p50_ms INTEGER DEFAULT 0,
+                p95_ms INTEGER DEFAULT 0,
+                updated_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rule_fixtures (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                rule_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                request TEXT NOT NULL,
+                response TEXT NOT NULL DEFAULT '{}',
+                created_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS admin_users (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT UNIQUE NOT NULL,
+                password_hash TEXT NOT NULL,
+                updated_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                token TEXT PRIMARY KEY,
+                username TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                created_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+        add_column_if_missing(&conn, "sessions", "role", "TEXT NOT NULL DEFAULT 'admin'")?;
+        add_column_if_missing(&conn, "sessions", "groups", "TEXT NOT NULL DEFAULT '[]'")?;
+        add_column_if_missing(&conn, "sessions", "binding", "TEXT")?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS login_attempts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                username TEXT NOT NULL,
+                client_ip TEXT NOT NULL,
+                success INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                created_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pending_changes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                method TEXT NOT NULL,
+                path TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                requested_by TEXT NOT NULL,
+                requested_at INTEGER NOT NULL,
+                approvable_at INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                decided_by TEXT,
+                decided_at INTEGER,
+                error TEXT
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS proxy_keys (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                key_hash TEXT UNIQUE NOT NULL,
+                prefix TEXT NOT NULL,
+                rule_id INTEGER,
+                created_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS secrets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                value TEXT NOT NULL,
+                created_at TEXT DEFAULT (datetime('now', 'localtime')),
+                updated_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS alert_rules (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT UNIQUE NOT NULL,
+                metric TEXT NOT NULL,
+                comparison TEXT NOT NULL,
+                threshold REAL NOT NULL,
+                window_secs INTEGER NOT NULL DEFAULT 60,
+                duration_secs INTEGER NOT NULL DEFAULT 0,
+                severity TEXT NOT NULL DEFAULT 'warning',
+                enabled INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT DEFAULT (datetime('now', 'localtime')),
+                updated_at TEXT DEFAULT (datetime('now', 'localtime'))
+            )",
+            [],
+        )?;
+
         // 创建索引
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_rules_enabled ON proxy_rules(enabled)",
@@ -93,6 +1015,10 @@ impl Database {
             "CREATE INDEX IF NOT EXISTS idx_config_key ON system_config(key)",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_fixtures_rule ON rule_fixtures(rule_id)",
+            [],
+        )?;
 
         conn.execute(
             "INSERT OR IGNORE INTO system_config (key, value) VALUES ('direct_proxy_path', 'proxy')",
@@ -102,104 +1028,463 @@ impl Database {
             "INSERT OR IGNORE INTO system_config (key, value) VALUES ('proxy_port', '3000')",
             [],
         )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO system_config (key, value) VALUES ('direct_proxy_mode', 'open')",
+            [],
+        )?;
+
+        // 校验迁移是否完整
+        for (table, columns) in SCHEMA {
+            for column in columns.iter() {
+                if !column_exists(&conn, table, column)? {
+                    anyhow::bail!(
+                        "Migration incomplete: column {}.{} is missing",
+                        table,
+                        column
+                    );
+                }
+            }
+        }
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
 
         Ok(())
     }
 
     pub fn get_all_rules(&self) -> Result<Vec<ProxyRule>> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare_cached(
-            "SELECT id, name, source, target, timeout_secs, enabled, created_at, updated_at 
-             FROM proxy_rules ORDER BY id",
-        )?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM proxy_rules ORDER BY id",
+            RULE_COLUMNS
+        ))?;
 
         let rules = stmt
-            .query_map([], |row| {
-                Ok(ProxyRule {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    source: row.get(2)?,
-                    target: row.get(3)?,
-                    timeout_secs: row.get::<_, i64>(4)? as u64,
-                    enabled: row.get::<_, i64>(5)? == 1,
-                    created_at: row.get(6)?,
-                    updated_at: row.get(7)?,
-                })
-            })?
+            .query_map([], map_rule_row)?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(rules)
     }
 
+    pub fn get_rule(&self, id: i64) -> Result<Option<ProxyRule>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM proxy_rules WHERE id = ?1",
+            RULE_COLUMNS
+        ))?;
+        Ok(stmt.query_row(params![id], map_rule_row).optional()?)
+    }
+
     pub fn get_enabled_rules(&self) -> Result<Vec<ProxyRule>> {
+        enabled_rules_in(&*self.conn()?)
+    }
+
+    /// 在事务中修改规则，提交前用 check 校验修改后启用的规则集，校验失败时回滚
+    fn write_rules<T>(
+        &self,
+        write: impl FnOnce(&rusqlite::Transaction<'_>) -> Result<T>,
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<T> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let value = write(&tx)?;
+        check(&enabled_rules_in(&tx)?)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// 分页查询规则，支持按名称/源路径/目标地址模糊搜索和排序
+    pub fn query_rules(&self, query: &RuleQuery) -> Result<RulePage> {
+        let conn = self.conn()?;
+        let page = query.page.max(1);
+        let size = query.size.clamp(1, 500);
+
+        let search = query
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                format!(
+                    "%{}%",
+                    s.replace('\\', "\\\\")
+                        .replace('%', "\\%")
+                        .replace('_', "\\_")
+                )
+            });
+        let where_clause = if search.is_some() {
+            "WHERE name LIKE ?1 ESCAPE '\\' OR source LIKE ?1 ESCAPE '\\' OR target LIKE ?1 ESCAPE '\\'"
+        } else {
+            ""
+        };
+
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM proxy_rules {}", where_clause),
+            rusqlite::params_from_iter(search.iter()),
+            |row| row.get(0),
+        )?;
+
+        let (column, descending) = parse_rule_sort(query.sort.as_deref());
+        let sql = format!(
+            "SELECT {} FROM proxy_rules {} ORDER BY {} {}, id LIMIT {} OFFSET {}",
+            RULE_COLUMNS,
+            where_clause,
+            column,
+            if descending { "DESC" } else { "ASC" },
+            size,
+            // 页码由客户端传入，过大时按 SQLite 的最大偏移处理，返回空页
+            ((page - 1).saturating_mul(size)).min(i64::MAX as u64)
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let items = stmt
+            .query_map(rusqlite::params_from_iter(search.iter()), map_rule_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RulePage {
+            items,
+            total: total as u64,
+            page,
+            size,
+        })
+    }
+
+    /// 创建规则，check 校验创建后的启用规则集，未通过时不写入
+    pub fn create_rule(
+        &self,
+        rule: &RuleInput<'_>,
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<i64> {
+        let options = serde_json::to_string(&rule.options.cloned().unwrap_or_default())?;
+        self.write_rules(
+            |tx| {
+                tx.execute(
+                    "INSERT INTO proxy_rules (name, source, target, timeout_secs, options) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![rule.name, rule.source, rule.target, rule.timeout_secs as i64, options],
+                )?;
+                Ok(tx.last_insert_rowid())
+            },
+            check,
+        )
+    }
+
+    pub fn update_rule(
+        &self,
+        id: i64,
+        rule: &RuleInput<'_>,
+        enabled: bool,
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<()> {
+        let options = rule.options.map(serde_json::to_string).transpose()?;
+        self.write_rules(|tx| update_rule_in(tx, id, rule, options, enabled), check)
+    }
+
+    pub fn delete_rule(&self, id: i64) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM proxy_rules WHERE id = ?1", params![id])?;
+        conn.execute("DELETE FROM rule_stats WHERE rule_id = ?1", params![id])?;
+        conn.execute("DELETE FROM rule_fixtures WHERE rule_id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// 备机同步：用主机的规则（保留 id 与时间戳）、API Key 与内部密钥以外的密钥整体替换本地数据，
+    /// 提交前用 check 校验规则集
+    pub fn replace_replicated(
+        &self,
+        rules: &[ProxyRule],
+        secrets: &[(String, String)],
+        keys: &[ProxyKey],
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<()> {
+        self.write_rules(
+            |tx| {
+                replace_rules_in(tx, rules)?;
+                replace_credentials_in(tx, secrets, keys)
+            },
+            check,
+        )
+    }
+
+    /// 以导入的规则为准整体同步：同名规则原地更新（保留 id 与统计），新名称创建，
+    /// 不在导入列表中的规则删除；同名规则有多条时按 id 顺序一一对应
+    pub fn import_rules(
+        &self,
+        rules: &[RuleImport],
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<ImportSummary> {
+        self.write_rules(|tx| import_rules_in(tx, rules), check)
+    }
+
+    pub fn toggle_rule(
+        &self,
+        id: i64,
+        enabled: bool,
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<()> {
+        self.write_rules(
+            |tx| {
+                tx.execute(
+                    "UPDATE proxy_rules SET enabled = ?1, updated_at = datetime('now', 'localtime') WHERE id = ?2",
+                    params![enabled as i64, id],
+                )?;
+                Ok(())
+            },
+            check,
+        )
+    }
+
+    pub fn get_rule_stats(&self, rule_id: i64) -> Result<Option<RuleStatsSnapshot>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare_cached(
-            "SELECT id, name, source, target, timeout_secs, enabled, created_at, updated_at 
-             FROM proxy_rules WHERE enabled = 1 ORDER BY id",
+            "SELECT rule_id, request_count, error_count, bytes_in, bytes_out, p50_ms, p95_ms
+             FROM rule_stats WHERE rule_id = ?1",
         )?;
+        let stats = stmt
+            .query_row(params![rule_id], |row| {
+                Ok(RuleStatsSnapshot {
+                    rule_id: row.get(0)?,
+                    request_count: row.get::<_, i64>(1)? as u64,
+                    error_count: row.get::<_, i64>(2)? as u64,
+                    bytes_in: row.get::<_, i64>(3)? as u64,
+                    bytes_out: row.get::<_, i64>(4)? as u64,
+                    p50_ms: row.get::<_, i64>(5)? as u64,
+                    p95_ms: row.get::<_, i64>(6)? as u64,
+                })
+            })
+            .optional()?;
+        Ok(stats)
+    }
 
-        let rules = stmt
+    /// 累加规则统计增量，延迟分位数直接覆盖为最新窗口值
+    pub fn add_rule_stats(&self, delta: &RuleStatsSnapshot) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO rule_stats (rule_id, request_count, error_count, bytes_in, bytes_out, p50_ms, p95_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(rule_id) DO UPDATE SET
+                request_count = request_count + excluded.request_count,
+                error_count = error_count + excluded.error_count,
+                bytes_in = bytes_in + excluded.bytes_in,
+                bytes_out = bytes_out + excluded.bytes_out,
+                p50_ms = excluded.p50_ms,
+                p95_ms = excluded.p95_ms,
+                updated_at = datetime('now', 'localtime')",
+            params![
+                delta.rule_id,
+                delta.request_count as i64,
+                delta.error_count as i64,
+                delta.bytes_in as i64,
+                delta.bytes_out as i64,
+                delta.p50_ms as i64,
+                delta.p95_ms as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_fixtures(&self, rule_id: i64) -> Result<Vec<RuleFixture>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, rule_id, name, request, response, created_at
+             FROM rule_fixtures WHERE rule_id = ?1 ORDER BY id",
+        )?;
+        let fixtures = stmt
+            .query_map(params![rule_id], map_fixture_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(fixtures)
+    }
+
+    pub fn get_fixture(&self, rule_id: i64, id: i64) -> Result<Option<RuleFixture>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, rule_id, name, request, response, created_at
+             FROM rule_fixtures WHERE rule_id = ?1 AND id = ?2",
+        )?;
+        Ok(stmt
+            .query_row(params![rule_id, id], map_fixture_row)
+            .optional()?)
+    }
+
+    pub fn create_fixture(
+        &self,
+        rule_id: i64,
+        name: &str,
+        request: &FixtureRequest,
+        response: &FixtureResponse,
+    ) -> Result<i64> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO rule_fixtures (rule_id, name, request, response) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                rule_id,
+                name,
+                serde_json::to_string(request)?,
+                serde_json::to_string(response)?
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn delete_fixture(&self, rule_id: i64, id: i64) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM rule_fixtures WHERE rule_id = ?1 AND id = ?2",
+            params![rule_id, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_proxy_keys(&self) -> Result<Vec<ProxyKey>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, name, key_hash, prefix, rule_id, created_at FROM proxy_keys ORDER BY id",
+        )?;
+        let keys = stmt
             .query_map([], |row| {
-                Ok(ProxyRule {
+                Ok(ProxyKey {
                     id: row.get(0)?,
                     name: row.get(1)?,
-                    source: row.get(2)?,
-                    target: row.get(3)?,
-                    timeout_secs: row.get::<_, i64>(4)? as u64,
-                    enabled: row.get::<_, i64>(5)? == 1,
-                    created_at: row.get(6)?,
-                    updated_at: row.get(7)?,
+                    key_hash: row.get(2)?,
+                    prefix: row.get(3)?,
+                    rule_id: row.get(4)?,
+                    created_at: row.get(5)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(rules)
+        Ok(keys)
     }
 
-    pub fn create_rule(
+    pub fn create_proxy_key(
         &self,
         name: &str,
-        source: &str,
-        target: &str,
-        timeout_secs: u64,
+        key_hash: &str,
+        prefix: &str,
+        rule_id: Option<i64>,
     ) -> Result<i64> {
         let conn = self.conn()?;
         conn.execute(
-            "INSERT INTO proxy_rules (name, source, target, timeout_secs) VALUES (?1, ?2, ?3, ?4)",
-            params![name, source, target, timeout_secs as i64],
+            "INSERT INTO proxy_keys (name, key_hash, prefix, rule_id) VALUES (?1, ?2, ?3, ?4)",
+            params![name, key_hash, prefix, rule_id],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
-    pub fn update_rule(
-        &self,
-        id: i64,
-        name: &str,
-        source: &str,
-        target: &str,
-        timeout_secs: u64,
-        enabled: bool,
-    ) -> Result<()> {
+    /// 返回是否删除了记录
+    pub fn delete_proxy_key(&self, id: i64) -> Result<bool> {
+        let conn = self.conn()?;
+        Ok(conn.execute("DELETE FROM proxy_keys WHERE id = ?1", params![id])? > 0)
+    }
+
+    pub fn list_alert_rules(&self) -> Result<Vec<AlertRule>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, name, metric, comparison, threshold, window_secs, duration_secs, severity,
+                    enabled, created_at, updated_at
+             FROM alert_rules ORDER BY id",
+        )?;
+        let rules = stmt
+            .query_map([], map_alert_rule_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rules)
+    }
+
+    pub fn create_alert_rule(&self, rule: &AlertRuleInput) -> Result<i64> {
         let conn = self.conn()?;
         conn.execute(
-            "UPDATE proxy_rules SET name = ?1, source = ?2, target = ?3, timeout_secs = ?4, enabled = ?5, 
-             updated_at = datetime('now', 'localtime') WHERE id = ?6",
-            params![name, source, target, timeout_secs as i64, enabled as i64, id],
+            "INSERT INTO alert_rules
+                (name, metric, comparison, threshold, window_secs, duration_secs, severity, enabled)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                rule.name,
+                enum_text(&rule.metric)?,
+                enum_text(&rule.comparison)?,
+                rule.threshold,
+                rule.window_secs as i64,
+                rule.duration_secs as i64,
+                enum_text(&rule.severity)?,
+                rule.enabled as i64
+            ],
         )?;
-        Ok(())
+        Ok(conn.last_insert_rowid())
     }
 
-    pub fn delete_rule(&self, id: i64) -> Result<()> {
+    /// 规则不存在时返回 false
+    pub fn update_alert_rule(&self, id: i64, rule: &AlertRuleInput) -> Result<bool> {
         let conn = self.conn()?;
-        conn.execute("DELETE FROM proxy_rules WHERE id = ?1", params![id])?;
+        let updated = conn.execute(
+            "UPDATE alert_rules SET name = ?1, metric = ?2, comparison = ?3, threshold = ?4,
+                window_secs = ?5, duration_secs = ?6, severity = ?7, enabled = ?8,
+                updated_at = datetime('now', 'localtime')
+             WHERE id = ?9",
+            params![
+                rule.name,
+                enum_text(&rule.metric)?,
+                enum_text(&rule.comparison)?,
+                rule.threshold,
+                rule.window_secs as i64,
+                rule.duration_secs as i64,
+                enum_text(&rule.severity)?,
+                rule.enabled as i64,
+                id
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn delete_alert_rule(&self, id: i64) -> Result<bool> {
+        let conn = self.conn()?;
+        Ok(conn.execute("DELETE FROM alert_rules WHERE id = ?1", params![id])? > 0)
+    }
+
+    pub fn list_secrets(&self) -> Result<Vec<StoredSecret>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT name, value, created_at, updated_at FROM secrets ORDER BY name",
+        )?;
+        let secrets = stmt
+            .query_map([], |row| {
+                Ok(StoredSecret {
+                    name: row.get(0)?,
+                    value: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(secrets)
+    }
+
+    pub fn upsert_secret(&self, name: &str, value: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO secrets (name, value) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET
+                value = excluded.value,
+                updated_at = datetime('now', 'localtime')",
+            params![name, value],
+        )?;
         Ok(())
     }
 
-    pub fn toggle_rule(&self, id: i64, enabled: bool) -> Result<()> {
+    /// 返回是否删除了记录
+    pub fn delete_secret(&self, name: &str) -> Result<bool> {
+        let conn = self.conn()?;
+        Ok(conn.execute("DELETE FROM secrets WHERE name = ?1", params![name])? > 0)
+    }
+
+    /// 规则选项原始 JSON，供迁移旧格式使用
+    pub fn list_rule_options(&self) -> Result<Vec<(i64, String)>> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare("SELECT id, options FROM proxy_rules WHERE options IS NOT NULL")?;
+        let options = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(options)
+    }
+
+    pub fn set_rule_options(&self, id: i64, options: &str) -> Result<()> {
         let conn = self.conn()?;
         conn.execute(
-            "UPDATE proxy_rules SET enabled = ?1, updated_at = datetime('now', 'localtime') WHERE id = ?2",
-            params![enabled as i64, id],
+            "UPDATE proxy_rules SET options = ?2 WHERE id = ?1",
+            params![id, options],
         )?;
         Ok(())
     }
@@ -220,6 +1505,257 @@ impl Database {
         Ok(())
     }
 
+    pub fn delete_config(&self, key: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM system_config WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    /// 获取管理员密码哈希（PHC 格式）
+    pub fn get_password_hash(&self, username: &str) -> Result<Option<String>> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare_cached("SELECT password_hash FROM admin_users WHERE username = ?1")?;
+        Ok(stmt
+            .query_row(params![username], |row| row.get(0))
+            .optional()?)
+    }
+
+    pub fn set_password_hash(&self, username: &str, hash: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO admin_users (username, password_hash) VALUES (?1, ?2)
+             ON CONFLICT(username) DO UPDATE SET
+                password_hash = excluded.password_hash,
+                updated_at = datetime('now', 'localtime')",
+            params![username, hash],
+        )?;
+        Ok(())
+    }
+
+    /// 加载未过期的 session
+    pub fn load_sessions(&self, now: i64) -> Result<Vec<(String, Session)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT token, username, role, groups, expires_at, binding FROM sessions WHERE expires_at > ?1",
+        )?;
+        let sessions = stmt
+            .query_map(params![now], |row| {
+                let role: String = row.get(2)?;
+                let groups: String = row.get(3)?;
+                Ok((
+                    row.get(0)?,
+                    Session {
+                        username: row.get(1)?,
+                        role: Role::parse(&role).unwrap_or(Role::Viewer),
+                        groups: serde_json::from_str(&groups).unwrap_or_default(),
+                        expires_at: row.get(4)?,
+                        binding: row.get(5)?,
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(sessions)
+    }
+
+    pub fn save_session(&self, token: &str, session: &Session) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO sessions (token, username, role, groups, expires_at, binding) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                token,
+                session.username,
+                session.role.as_str(),
+                serde_json::to_string(&session.groups)?,
+                session.expires_at,
+                session.binding
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_session(&self, token: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute("DELETE FROM sessions WHERE token = ?1", params![token])?;
+        Ok(())
+    }
+
+    /// 删除除指定 token 外的所有 session
+    pub fn delete_other_sessions(&self, keep_token: &str) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM sessions WHERE token != ?1",
+            params![keep_token],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_expired_sessions(&self, now: i64) -> Result<usize> {
+        let conn = self.conn()?;
+        Ok(conn.execute("DELETE FROM sessions WHERE expires_at <= ?1", params![now])?)
+    }
+
+    pub fn record_login_attempt(
+        &self,
+        username: &str,
+        client_ip: &str,
+        success: bool,
+        reason: &str,
+    ) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO login_attempts (username, client_ip, success, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![username, client_ip, success, reason],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_login_attempts(&self, limit: u32) -> Result<Vec<LoginAttempt>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, username, client_ip, success, reason, created_at
+             FROM login_attempts ORDER BY id DESC LIMIT ?1",
+        )?;
+        let attempts = stmt
+            .query_map(params![limit], |row| {
+                Ok(LoginAttempt {
+                    id: row.get(0)?,
+                    username: row.get(1)?,
+                    client_ip: row.get(2)?,
+                    success: row.get(3)?,
+                    reason: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(attempts)
+    }
+
+    /// 删除超过保留天数的登录尝试记录
+    pub fn prune_login_attempts(&self, retention_days: u32) -> Result<usize> {
+        let conn = self.conn()?;
+        Ok(conn.execute(
+            "DELETE FROM login_attempts WHERE created_at < datetime('now', 'localtime', ?1)",
+            params![format!("-{} days", retention_days)],
+        )?)
+    }
+
+    pub fn create_pending_change(
+        &self,
+        method: &str,
+        path: &str,
+        payload: &serde_json::Value,
+        requested_by: &str,
+        requested_at: i64,
+        approvable_at: i64,
+    ) -> Result<PendingChange> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO pending_changes (method, path, payload, requested_by, requested_at, approvable_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                method,
+                path,
+                payload.to_string(),
+                requested_by,
+                requested_at,
+                approvable_at
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM pending_changes WHERE id = ?1",
+            CHANGE_COLUMNS
+        ))?;
+        Ok(stmt.query_row(params![id], map_change_row)?)
+    }
+
+    pub fn get_pending_change(&self, id: i64) -> Result<Option<PendingChange>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM pending_changes WHERE id = ?1",
+            CHANGE_COLUMNS
+        ))?;
+        Ok(stmt.query_row(params![id], map_change_row).optional()?)
+    }
+
+    /// 最近 200 条变更，可按状态过滤
+    pub fn list_pending_changes(&self, status: Option<&str>) -> Result<Vec<PendingChange>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {} FROM pending_changes WHERE ?1 IS NULL OR status = ?1 ORDER BY id DESC LIMIT 200",
+            CHANGE_COLUMNS
+        ))?;
+        let changes = stmt
+            .query_map(params![status], map_change_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(changes)
+    }
+
+    /// 将变更从 `from` 状态迁移到 `to`，当前状态不符时返回 false
+    pub fn transition_pending_change(
+        &self,
+        id: i64,
+        from: &str,
+        to: &str,
+        decided_by: &str,
+        error: Option<&str>,
+        decided_at: i64,
+    ) -> Result<bool> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE pending_changes SET status = ?3, decided_by = ?4, error = ?5, decided_at = ?6
+             WHERE id = ?1 AND status = ?2",
+            params![id, from, to, decided_by, error, decided_at],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn list_admin_users(&self) -> Result<Vec<AdminUser>> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare_cached("SELECT username, password_hash FROM admin_users ORDER BY id")?;
+        let users = stmt
+            .query_map([], |row| {
+                Ok(AdminUser {
+                    username: row.get(0)?,
+                    password_hash: row.get(1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(users)
+    }
+
+    /// 在一个事务中整体替换规则、系统配置与管理员账号，规则保留备份中的 id
+    pub fn restore(
+        &self,
+        rules: &[ProxyRule],
+        configs: &[SystemConfig],
+        users: &[AdminUser],
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<()> {
+        self.write_rules(|tx| restore_in(tx, rules, configs, users), check)
+    }
+
+    /// 集群同步：与 restore 相同地替换规则与系统配置，并替换 API Key 与内部密钥以外的密钥
+    /// （secrets 为名称与加密值），提交前用 check 校验规则集
+    pub fn restore_cluster(
+        &self,
+        rules: &[ProxyRule],
+        configs: &[SystemConfig],
+        secrets: &[(String, String)],
+        keys: &[ProxyKey],
+        check: impl FnOnce(&[ProxyRule]) -> Result<()>,
+    ) -> Result<()> {
+        self.write_rules(
+            |tx| {
+                restore_in(tx, rules, configs, &[])?;
+                replace_credentials_in(tx, secrets, keys)
+            },
+            check,
+        )
+    }
+
     pub fn get_all_configs(&self) -> Result<Vec<SystemConfig>> {
         let conn = self.conn()?;
         let mut stmt = conn.prepare_cached("SELECT id, key, value FROM system_config")?;
@@ -235,3 +1771,52 @@ impl Database {
         Ok(configs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(page: u64, size: u64) -> RuleQuery {
+        RuleQuery {
+            page,
+            size,
+            search: None,
+            sort: None,
+        }
+    }
+
+    #[test]
+    fn query_rules_pages_and_clamps() {
+        let db = Database::in_memory().unwrap();
+        for i in 0..3 {
+            let source = format!("/r{}/{{*path}}", i);
+            db.create_rule(
+                &RuleInput {
+                    name: &format!("r{}", i),
+                    source: &source,
+                    target: "http://upstream/{*path}",
+                    timeout_secs: 30,
+                    options: None,
+                },
+                |_| Ok(()),
+            )
+            .unwrap();
+        }
+
+        let page = db.query_rules(&query(2, 2)).unwrap();
+        assert_eq!((page.total, page.page, page.size), (3, 2, 2));
+        assert_eq!(page.items.len(), 1);
+        let page = db.query_rules(&query(0, 0)).unwrap();
+        assert_eq!((page.page, page.size, page.items.len()), (1, 1, 1));
+    }
+
+    #[test]
+    fn query_rules_huge_page_is_empty() {
+        let db = Database::in_memory().unwrap();
+        for page in [u64::MAX, u64::MAX / 2, i64::MAX as u64] {
+            let result = db.query_rules(&query(page, 500)).unwrap();
+            assert!(result.items.is_empty());
+            assert_eq!(result.page, page);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    response::Response,
+    Json,
+};
+use dashmap::DashMap;
+use ring::digest;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::ApiResponse;
+use crate::config::DirectCacheConfig;
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{upstream_response_headers, ForwardTimeouts, ProxyState};
+use crate::stats::RuleCounters;
+use crate::AdminState;
+
+/// 直接代理的下载缓存（GitHub Release、raw 文件等）与按主机的命中统计
+#[derive(Clone)]
+pub struct DirectCache {
+    config: Arc<DirectCacheConfig>,
+    hosts: Arc<DashMap<String, Arc<HostCounters>>>,
+}
+
+#[derive(Default)]
+struct HostCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// 从缓存返回的字节数
+    hit_bytes: Arc<RuleCounters>,
+    /// 未命中时从上游返回的字节数
+    miss_bytes: Arc<RuleCounters>,
+}
+
+/// 单个主机的缓存统计
+#[derive(Debug, Serialize)]
+pub struct HostCacheStats {
+    pub host: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_bytes: u64,
+    pub miss_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DirectCacheStatus {
+    pub enabled: bool,
+    pub dir: String,
+    pub hosts: Vec<HostCacheStats>,
+}
+
+/// 可缓存的直接代理请求
+pub struct CacheEntry {
+    file: CacheFile,
+    counters: Arc<HostCounters>,
+}
+
+impl DirectCache {
+    pub fn new(config: &DirectCacheConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            hosts: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// 缓存开启且目标主机在列表内的 GET / HEAD 请求返回缓存项，
+    /// 缓存文件按完整地址的 sha256 保存在 <dir>/<主机>/ 下（使用配置的缓存存储）
+    pub fn entry(
+        &self,
+        method: &Method,
+        target_url: &str,
+        state: &ProxyState,
+    ) -> Option<CacheEntry> {
+        if !self.config.enabled || (method != Method::GET && method != Method::HEAD) {
+            return None;
+        }
+        let url = reqwest::Url::parse(target_url).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+        if !self
+            .config
+            .hosts
+            .iter()
+            .any(|h| h.eq_ignore_ascii_case(&host))
+        {
+            return None;
+        }
+
+        let dir = Path::new(&self.config.dir);
+        let key: String = digest::digest(&digest::SHA256, url.as_str().as_bytes())
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let mut file = CacheFile::new(&state.cache_store, dir, &Path::new(&host).join(key), None);
+        if let Some(max_size_mb) = self.config.max_size_mb {
+            file = file.with_quota(&state.cache_quotas, max_size_mb * 1024 * 1024);
+        }
+        if host.starts_with("raw.") {
+            file = file.with_content_type("text/plain; charset=utf-8");
+        }
+        if !is_immutable(url.path()) {
+            if self.config.ttl_secs == 0 {
+                return None;
+            }
+            file = file.with_max_age(Duration::from_secs(self.config.ttl_secs));
+        }
+
+        let counters = self.hosts.entry(host).or_default().clone();
+        Some(CacheEntry { file, counters })
+    }
+
+    pub fn status(&self) -> DirectCacheStatus {
+        let mut hosts: Vec<HostCacheStats> = self
+            .hosts
+            .iter()
+            .map(|entry| {
+                let counters = entry.value();
+                HostCacheStats {
+                    host: entry.key().clone(),
+                    hits: counters.hits.load(Ordering::Relaxed),
+                    misses: counters.misses.load(Ordering::Relaxed),
+                    hit_bytes: counters.hit_bytes.bytes_out(),
+                    miss_bytes: counters.miss_bytes.bytes_out(),
+                }
+            })
+            .collect();
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+        DirectCacheStatus {
+            enabled: self.config.enabled,
+            dir: self.config.dir.clone(),
+            hosts,
+        }
+    }
+}
+
+/// Release 附件与按 40 位提交 sha 访问的文件内容不会变化，分支、标签等地址按 TTL 缓存
+fn is_immutable(path: &str) -> bool {
+    path.contains("/releases/download/")
+        || path
+            .split('/')
+            .any(|segment| segment.len() == 40 && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// 命中缓存时直接返回（支持 Range），否则经直接代理客户端请求上游；
+/// 带 Range 的请求与 HEAD 不写入缓存，完整的 GET 200 响应边转发边写入
+pub async fn forward(
+    state: &ProxyState,
+    entry: CacheEntry,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+) -> Result<Response, StatusCode> {
+    let CacheEntry { file, counters } = entry;
+    if let Some(resp) =
+        mirror::serve_cached(&file, req.method(), req.headers(), &counters.hit_bytes).await
+    {
+        counters.hits.fetch_add(1, Ordering::Relaxed);
+        return Ok(resp);
+    }
+    counters.misses.fetch_add(1, Ordering::Relaxed);
+
+    let cache = Some(file)
+        .filter(|_| req.method() == Method::GET && !req.headers().contains_key(header::RANGE));
+    let timeouts = ForwardTimeouts::uniform(state.default_timeout);
+    let resp = mirror::send(
+        mirror::cache_request(
+            &state.direct_client,
+            &req,
+            target_url,
+            client_ip,
+            cache.is_some(),
+        ),
+        timeouts,
+    )
+    .await?;
+    let response_headers = upstream_response_headers(resp.headers());
+    Ok(mirror::relay(
+        resp,
+        response_headers,
+        timeouts,
+        counters.miss_bytes.clone(),
+        cache,
+    )
+    .await)
+}
+
+/// 直接代理缓存的配置与按主机统计
+pub async fn status_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<DirectCacheStatus>> {
+    Json(ApiResponse::ok(state.direct_cache.status()))
+}
@@ -0,0 +1,464 @@
+use arc_swap::ArcSwap;
+use axum::{extract::State, http::StatusCode, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Local;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::api::ApiResponse;
+use crate::config::{ConsulConfig, DiscoveryConfig, EtcdConfig};
+use crate::kubernetes::{KubeClient, KubeTarget};
+use crate::AdminState;
+
+/// 首个请求等待初次查询的最长时间
+const FIRST_LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// 查询失败后的重试间隔
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// 多久没有请求的服务停止监视
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    Consul,
+    Etcd,
+    Kubernetes,
+}
+
+/// 读取实例后继续监视的位置
+enum Resume {
+    Etcd(i64),
+    Kubernetes(KubeTarget, String),
+}
+
+/// 一个被监视的服务：最近一次查询到的健康实例（host:port）
+struct Service {
+    instances: ArcSwap<Vec<String>>,
+    next: AtomicUsize,
+    last_used: Mutex<Instant>,
+    last_error: Mutex<Option<String>>,
+    updated_at: Mutex<Option<String>>,
+    /// 完成首次查询（无论成功与否）后置为 true
+    loaded: watch::Sender<bool>,
+}
+
+/// 服务发现：按需监视规则目标引用的服务，请求在健康实例间轮询
+#[derive(Clone)]
+pub struct ServiceDiscovery {
+    config: Arc<DiscoveryConfig>,
+    client: Client,
+    kube: Option<Arc<KubeClient>>,
+    services: Arc<DashMap<(Backend, String), Arc<Service>>>,
+}
+
+impl ServiceDiscovery {
+    pub fn new(config: &DiscoveryConfig, client: Client) -> anyhow::Result<Self> {
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            client,
+            kube: KubeClient::from_config(&config.kubernetes)?.map(Arc::new),
+            services: Arc::new(DashMap::new()),
+        })
+    }
+
+    /// 把服务发现目标改写为一个健康实例的地址，其他目标不变；服务没有健康实例时返回 503
+    pub async fn resolve(&self, target: &mut String) -> Result<(), StatusCode> {
+        let Some((scheme_end, backend, https)) = parse_scheme(target) else {
+            return Ok(());
+        };
+        let rest = &target[scheme_end + 3..];
+        // Kubernetes 服务名包含命名空间：k8s://命名空间/服务:端口/路径
+        let skip = match backend {
+            Backend::Kubernetes => rest.find('/').map_or(0, |i| i + 1),
+            _ => 0,
+        };
+        let name_end = rest[skip..]
+            .find(['/', '?', '#'])
+            .map_or(rest.len(), |i| skip + i);
+        let name = &rest[..name_end];
+        let valid = match backend {
+            Backend::Kubernetes => KubeTarget::parse(name).is_some(),
+            _ => !name.is_empty(),
+        };
+        if !valid {
+            tracing::error!(target = %target, "Invalid service discovery target");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        let configured = match backend {
+            Backend::Consul => self.config.consul.is_some(),
+            Backend::Etcd => self.config.etcd.is_some(),
+            Backend::Kubernetes => self.kube.is_some(),
+        };
+        if !configured {
+            tracing::error!(target = %target, "Service discovery backend is not configured");
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+
+        let service = self.service(backend, name);
+        *service.last_used.lock() = Instant::now();
+        let mut loaded = service.loaded.subscribe();
+        let _ = tokio::time::timeout(FIRST_LOOKUP_TIMEOUT, loaded.wait_for(|loaded| *loaded)).await;
+
+        let instances = service.instances.load();
+        if instances.is_empty() {
+            tracing::warn!(service = %name, backend = ?backend, "No healthy instances");
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        let instance = &instances[service.next.fetch_add(1, Ordering::Relaxed) % instances.len()];
+        *target = format!(
+            "{}://{}{}",
+            if https { "https" } else { "http" },
+            instance,
+            &rest[name_end..]
+        );
+        Ok(())
+    }
+
+    fn service(&self, backend: Backend, name: &str) -> Arc<Service> {
+        let key = (backend, name.to_string());
+        if let Some(service) = self.services.get(&key) {
+            return service.clone();
+        }
+        let service = self
+            .services
+            .entry(key)
+            .or_insert_with(|| {
+                let service = Arc::new(Service {
+                    instances: ArcSwap::from_pointee(Vec::new()),
+                    next: AtomicUsize::new(0),
+                    last_used: Mutex::new(Instant::now()),
+                    last_error: Mutex::new(None),
+                    updated_at: Mutex::new(None),
+                    loaded: watch::channel(false).0,
+                });
+                tokio::spawn(
+                    self.clone()
+                        .watch(backend, name.to_string(), service.clone()),
+                );
+                service
+            })
+            .clone();
+        service
+    }
+
+    /// 持续监视服务实例，服务长时间没有请求时停止并移除
+    async fn watch(self, backend: Backend, name: String, service: Arc<Service>) {
+        tracing::info!(service = %name, backend = ?backend, "Watching service");
+        let wait = Duration::from_secs(self.config.wait_secs.max(1));
+        let mut consul_index = 0;
+        loop {
+            if service.last_used.lock().elapsed() > IDLE_TIMEOUT {
+                self.services.remove(&(backend, name.clone()));
+                tracing::info!(service = %name, backend = ?backend, "Stopped watching idle service");
+                return;
+            }
+            let result = match (backend, &self.config.consul, &self.config.etcd) {
+                (Backend::Consul, Some(consul), _) => {
+                    consul_health(&self.client, consul, &name, consul_index, wait)
+                        .await
+                        .map(|(instances, index)| {
+                            // 索引回退（如 Consul 重建）时重新开始阻塞查询
+                            consul_index = if index < consul_index { 0 } else { index };
+                            (instances, None)
+                        })
+                }
+                (Backend::Etcd, _, Some(etcd)) => etcd_range(&self.client, etcd, &name)
+                    .await
+                    .map(|(instances, revision)| (instances, Some(Resume::Etcd(revision)))),
+                (Backend::Kubernetes, _, _) => {
+                    let (Some(kube), Some(target)) = (&self.kube, KubeTarget::parse(&name)) else {
+                        return;
+                    };
+                    kube.endpoints(&target).await.map(|(instances, version)| {
+                        (instances, Some(Resume::Kubernetes(target, version)))
+                    })
+                }
+                _ => return,
+            };
+            match result {
+                Ok((instances, resume)) => {
+                    if **service.instances.load() != instances {
+                        tracing::info!(service = %name, instances = ?instances, "Service instances changed");
+                    }
+                    service.instances.store(Arc::new(instances));
+                    *service.last_error.lock() = None;
+                    *service.updated_at.lock() =
+                        Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+                    service.loaded.send_replace(true);
+                    let watched = match (resume, &self.config.etcd, &self.kube) {
+                        (Some(Resume::Etcd(revision)), Some(etcd), _) => {
+                            etcd_watch(&self.client, etcd, &name, revision, wait).await
+                        }
+                        (Some(Resume::Kubernetes(target, version)), _, Some(kube)) => {
+                            kube.watch(&target, &version, wait).await
+                        }
+                        _ => Ok(()),
+                    };
+                    if let Err(e) = watched {
+                        tracing::warn!(service = %name, backend = ?backend, "Service watch failed: {}", e);
+                        tokio::time::sleep(RETRY_INTERVAL).await;
+                    }
+                }
+                Err(e) => {
+                    // 查询失败时保留上次的实例
+                    tracing::warn!(service = %name, backend = ?backend, "Service lookup failed: {}", e);
+                    *service.last_error.lock() = Some(e.to_string());
+                    service.loaded.send_replace(true);
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                }
+            }
+        }
+    }
+}
+
+/// 识别服务发现目标，返回 (`://` 的位置, 后端, 是否以 HTTPS 访问实例)
+fn parse_scheme(target: &str) -> Option<(usize, Backend, bool)> {
+    let scheme_end = target.find("://")?;
+    let (backend, https) = match &target[..scheme_end] {
+        "consul" => (Backend::Consul, false),
+        "consul+https" => (Backend::Consul, true),
+        "etcd" => (Backend::Etcd, false),
+        "etcd+https" => (Backend::Etcd, true),
+        "k8s" => (Backend::Kubernetes, false),
+        "k8s+https" => (Backend::Kubernetes, true),
+        _ => return None,
+    };
+    Some((scheme_end, backend, https))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulEntry {
+    node: ConsulNode,
+    service: ConsulService,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulNode {
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulService {
+    #[serde(default)]
+    address: String,
+    port: u16,
+}
+
+/// Consul 阻塞查询：index 之后有变化或等待超时时返回全部通过健康检查的实例与新的索引
+async fn consul_health(
+    client: &Client,
+    consul: &ConsulConfig,
+    name: &str,
+    index: u64,
+    wait: Duration,
+) -> anyhow::Result<(Vec<String>, u64)> {
+    let mut request = client
+        .get(format!(
+            "{}/v1/health/service/{}",
+            consul.address.trim_end_matches('/'),
+            name
+        ))
+        .query(&[
+            ("passing", "true".to_string()),
+            ("index", index.to_string()),
+            ("wait", format!("{}s", wait.as_secs())),
+        ])
+        .timeout(wait + Duration::from_secs(10));
+    if let Some(ref dc) = consul.datacenter {
+        request = request.query(&[("dc", dc)]);
+    }
+    if let Some(ref token) = consul.token {
+        request = request.header("X-Consul-Token", token);
+    }
+    let resp = request.send().await?.error_for_status()?;
+    let index = resp
+        .headers()
+        .get("x-consul-index")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let entries: Vec<ConsulEntry> = resp.json().await?;
+    let mut instances: Vec<String> = entries
+        .into_iter()
+        .map(|entry| {
+            let host = if entry.service.address.is_empty() {
+                entry.node.address
+            } else {
+                entry.service.address
+            };
+            join_host_port(&host, entry.service.port)
+        })
+        .collect();
+    instances.sort();
+    Ok((instances, index))
+}
+
+/// 服务在 etcd 中的键前缀与范围终点（前缀最后一个字节加一）
+fn etcd_range_keys(etcd: &EtcdConfig, name: &str) -> (String, String) {
+    let prefix = format!("{}/{}/", etcd.prefix.trim_end_matches('/'), name);
+    let mut end = prefix.clone().into_bytes();
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+    (STANDARD.encode(&prefix), STANDARD.encode(end))
+}
+
+#[derive(Deserialize)]
+struct EtcdRange {
+    header: EtcdHeader,
+    #[serde(default)]
+    kvs: Vec<EtcdKv>,
+}
+
+#[derive(Deserialize)]
+struct EtcdHeader {
+    /// v3 HTTP 网关把 int64 编码为字符串
+    revision: String,
+}
+
+#[derive(Deserialize)]
+struct EtcdKv {
+    #[serde(default)]
+    value: String,
+}
+
+/// 读取服务前缀下的全部实例，返回实例与当前的 revision
+async fn etcd_range(
+    client: &Client,
+    etcd: &EtcdConfig,
+    name: &str,
+) -> anyhow::Result<(Vec<String>, i64)> {
+    let (key, range_end) = etcd_range_keys(etcd, name);
+    let range: EtcdRange = client
+        .post(format!(
+            "{}/v3/kv/range",
+            etcd.address.trim_end_matches('/')
+        ))
+        .json(&serde_json::json!({ "key": key, "range_end": range_end }))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let mut instances = Vec::new();
+    for kv in range.kvs {
+        let value = String::from_utf8(STANDARD.decode(&kv.value)?)?;
+        match parse_etcd_instance(&value) {
+            Some(instance) => instances.push(instance),
+            None => {
+                tracing::warn!(service = %name, value = %value, "Invalid etcd service instance")
+            }
+        }
+    }
+    instances.sort();
+    Ok((instances, range.header.revision.parse()?))
+}
+
+/// 实例值为 `host:port` 或 `{"host": ..., "port": ...}`（也接受 `address`）
+fn parse_etcd_instance(value: &str) -> Option<String> {
+    let value = value.trim();
+    if let Ok(Value::Object(object)) = serde_json::from_str::<Value>(value) {
+        let host = object
+            .get("host")
+            .or_else(|| object.get("address"))?
+            .as_str()?;
+        let port = object.get("port")?.as_u64()?;
+        return Some(join_host_port(host, u16::try_from(port).ok()?));
+    }
+    let (host, port) = value.rsplit_once(':')?;
+    port.parse::<u16>().ok()?;
+    (!host.is_empty()).then(|| value.to_string())
+}
+
+/// 等待 revision 之后服务前缀下的第一个变化，超时或连接断开时返回，由调用方重新读取
+async fn etcd_watch(
+    client: &Client,
+    etcd: &EtcdConfig,
+    name: &str,
+    revision: i64,
+    wait: Duration,
+) -> anyhow::Result<()> {
+    let (key, range_end) = etcd_range_keys(etcd, name);
+    let request = serde_json::json!({
+        "create_request": {
+            "key": key,
+            "range_end": range_end,
+            "start_revision": (revision + 1).to_string(),
+        }
+    });
+    let watching = async {
+        let mut resp = client
+            .post(format!("{}/v3/watch", etcd.address.trim_end_matches('/')))
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?;
+        // 网关逐条输出 JSON，首条为创建确认，之后带 events 的消息表示有变化
+        let mut received = Vec::new();
+        while let Some(chunk) = resp.chunk().await? {
+            received.extend_from_slice(&chunk);
+            if String::from_utf8_lossy(&received).contains("\"events\"") {
+                break;
+            }
+        }
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(wait, watching).await {
+        Ok(result) => result,
+        Err(_) => Ok(()),
+    }
+}
+
+fn join_host_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceStatus {
+    pub backend: Backend,
+    pub service: String,
+    pub instances: Vec<String>,
+    pub last_error: Option<String>,
+    pub updated_at: Option<String>,
+    /// 距最近一次请求的秒数，超过 10 分钟停止监视
+    pub idle_secs: u64,
+}
+
+/// 正在监视的服务与实例
+pub async fn list_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<ServiceStatus>>> {
+    let mut services: Vec<ServiceStatus> = state
+        .discovery
+        .services
+        .iter()
+        .map(|entry| {
+            let ((backend, name), service) = (entry.key(), entry.value());
+            ServiceStatus {
+                backend: *backend,
+                service: name.clone(),
+                instances: service.instances.load().to_vec(),
+                last_error: service.last_error.lock().clone(),
+                updated_at: service.updated_at.lock().clone(),
+                idle_secs: service.last_used.lock().elapsed().as_secs(),
+            }
+        })
+        .collect();
+    services.sort_by(|a, b| a.service.cmp(&b.service));
+    Json(ApiResponse::ok(services))
+}
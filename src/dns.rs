@@ -0,0 +1,476 @@
+use dashmap::DashMap;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfig, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::proto::xfer::Protocol;
+use hickory_resolver::TokioResolver;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::config::{DnsConfig, IpFamily};
+use crate::tasks::TaskRegistry;
+
+/// 检查上游域名解析结果是否过期的间隔
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 上游域名最近一次解析结果，`addrs` 为空表示解析失败的否定缓存
+struct Record {
+    addrs: Vec<IpAddr>,
+    error: Option<String>,
+    valid_until: Instant,
+}
+
+/// 同一域名并发的缓存未命中共用一次查询
+type PendingLookup = Shared<BoxFuture<'static, Result<Vec<IpAddr>, String>>>;
+
+/// 解析缓存计数
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    misses: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// 上游 DNS 解析：按解析顺序返回全部地址，连接失败时依次尝试下一个；
+/// 解析结果按 TTL（限制在 min / max 之间）缓存在进程内，过期后由后台任务重新解析
+#[derive(Clone)]
+pub struct UpstreamDns {
+    resolver: Arc<TokioResolver>,
+    records: Arc<DashMap<String, Record>>,
+    pending: Arc<DashMap<String, PendingLookup>>,
+    /// 静态主机覆盖，键为小写且不带末尾的点
+    hosts: Arc<HashMap<String, Vec<IpAddr>>>,
+    min_ttl: Duration,
+    max_ttl: Duration,
+    negative_ttl: Duration,
+    /// 返回给连接器的地址族与顺序
+    family: IpFamily,
+    counters: Arc<CacheCounters>,
+}
+
+impl UpstreamDns {
+    /// 未配置 DNS 服务器时读取系统 DNS 配置（/etc/resolv.conf），同时查询 IPv4 与 IPv6 地址
+    pub fn new(config: &DnsConfig) -> anyhow::Result<Self> {
+        let mut builder = if config.nameservers.is_empty() {
+            TokioResolver::builder_tokio()?
+        } else {
+            let mut resolver_config = ResolverConfig::new();
+            for entry in &config.nameservers {
+                for nameserver in parse_nameserver(entry)
+                    .map_err(|e| anyhow::anyhow!("Invalid DNS nameserver '{}': {}", entry, e))?
+                {
+                    resolver_config.add_name_server(nameserver);
+                }
+            }
+            TokioResolver::builder_with_config(resolver_config, TokioConnectionProvider::default())
+        };
+        builder.options_mut().ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+        let hosts = config
+            .hosts
+            .iter()
+            .filter(|(_, addrs)| !addrs.is_empty())
+            .map(|(host, addrs)| (normalize(host), addrs.clone()))
+            .collect();
+        Ok(Self {
+            resolver: Arc::new(builder.build()),
+            records: Arc::new(DashMap::new()),
+            pending: Arc::new(DashMap::new()),
+            hosts: Arc::new(hosts),
+            min_ttl: Duration::from_secs(config.min_ttl_secs),
+            max_ttl: Duration::from_secs(config.max_ttl_secs.max(config.min_ttl_secs)),
+            negative_ttl: Duration::from_secs(config.negative_ttl_secs),
+            family: config.ip_family,
+            counters: Arc::new(CacheCounters::default()),
+        })
+    }
+
+    /// 共用解析缓存，按指定地址族返回地址
+    pub fn with_family(&self, family: IpFamily) -> Self {
+        Self {
+            family,
+            ..self.clone()
+        }
+    }
+
+    /// 解析并按地址族筛选、排序，筛选后没有地址时返回错误
+    async fn resolve_addrs(&self, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+        let addrs = order_addrs(self.family, self.resolve_host(host).await?);
+        if addrs.is_empty() {
+            anyhow::bail!("{} has no {} address", host, self.family);
+        }
+        Ok(addrs)
+    }
+
+    /// 依次查静态主机覆盖、解析缓存，都未命中时查询 DNS；
+    /// 覆盖的域名不记录 TTL，也不参与定期重新解析
+    async fn resolve_host(&self, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+        let host = normalize(host);
+        if let Some(addrs) = self.hosts.get(&host) {
+            return Ok(addrs.clone());
+        }
+        if let Some(record) = self.records.get(&host) {
+            if record.valid_until > Instant::now() {
+                return match &record.error {
+                    None => {
+                        self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                        Ok(record.addrs.clone())
+                    }
+                    Some(error) => {
+                        self.counters.negative_hits.fetch_add(1, Ordering::Relaxed);
+                        Err(anyhow::anyhow!("{} (cached)", error))
+                    }
+                };
+            }
+        }
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+
+        let pending = self
+            .pending
+            .entry(host.clone())
+            .or_insert_with(|| {
+                let dns = self.clone();
+                let host = host.clone();
+                async move {
+                    let result = dns.lookup(&host).await.map_err(|e| e.to_string());
+                    dns.pending.remove(&host);
+                    result
+                }
+                .boxed()
+                .shared()
+            })
+            .clone();
+        pending.await.map_err(anyhow::Error::msg)
+    }
+
+    /// 查询 DNS 并写入缓存，失败时按 negative_ttl 缓存错误
+    async fn lookup(&self, host: &str) -> anyhow::Result<Vec<IpAddr>> {
+        match self.resolver.lookup_ip(host).await {
+            Ok(lookup) => {
+                let addrs: Vec<IpAddr> = lookup.iter().collect();
+                let now = Instant::now();
+                let ttl = lookup
+                    .valid_until()
+                    .saturating_duration_since(now)
+                    .clamp(self.min_ttl, self.max_ttl);
+                self.records.insert(
+                    host.to_string(),
+                    Record {
+                        addrs: addrs.clone(),
+                        error: None,
+                        valid_until: now + ttl,
+                    },
+                );
+                Ok(addrs)
+            }
+            Err(e) => {
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                if self.negative_ttl.is_zero() {
+                    self.records.remove(host);
+                } else {
+                    self.records.insert(
+                        host.to_string(),
+                        Record {
+                            addrs: Vec::new(),
+                            error: Some(e.to_string()),
+                            valid_until: Instant::now() + self.negative_ttl,
+                        },
+                    );
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// 解析缓存指标
+    pub fn render_prometheus(&self, out: &mut String) {
+        let counters = [
+            (
+                "proxy_dns_cache_hits_total",
+                "Upstream DNS lookups answered from the cache",
+                &self.counters.hits,
+            ),
+            (
+                "proxy_dns_cache_negative_hits_total",
+                "Upstream DNS lookups answered from the negative cache",
+                &self.counters.negative_hits,
+            ),
+            (
+                "proxy_dns_cache_misses_total",
+                "Upstream DNS lookups not found in the cache",
+                &self.counters.misses,
+            ),
+            (
+                "proxy_dns_lookup_failures_total",
+                "Failed upstream DNS queries",
+                &self.counters.failures,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+        let _ = writeln!(
+            out,
+            "# HELP proxy_dns_cache_entries Cached upstream DNS results, including negative entries"
+        );
+        let _ = writeln!(out, "# TYPE proxy_dns_cache_entries gauge");
+        let _ = writeln!(out, "proxy_dns_cache_entries {}", self.records.len());
+    }
+
+    /// 重新解析 TTL 已过期的域名，返回解析结果发生变化的域名
+    async fn refresh_expired(&self) -> Vec<String> {
+        let now = Instant::now();
+        // 过期的否定缓存直接丢弃，下次请求时再查询
+        self.records
+            .retain(|_, record| record.error.is_none() || record.valid_until > now);
+        let expired: Vec<(String, Vec<IpAddr>)> = self
+            .records
+            .iter()
+            .filter(|record| record.valid_until <= now)
+            .map(|record| (record.key().clone(), record.addrs.clone()))
+            .collect();
+
+        let mut changed = Vec::new();
+        for (host, old) in expired {
+            match self.lookup(&host).await {
+                Ok(new) if same_addrs(&old, &new) => {}
+                Ok(new) => {
+                    tracing::info!(host = %host, old = ?old, new = ?new, "Upstream DNS changed");
+                    changed.push(host);
+                }
+                Err(e) => {
+                    // 解析失败时保留现有连接，下次新建连接时再解析
+                    tracing::warn!(host = %host, error = %e, "Failed to re-resolve upstream");
+                    self.records.remove(&host);
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl Resolve for UpstreamDns {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = self.clone();
+        Box::pin(async move {
+            let addrs = dns.resolve_addrs(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter().map(|ip| SocketAddr::new(ip, 0))) as Addrs)
+        })
+    }
+}
+
+/// 底层 HTTP/1 客户端（preserve_header_case 规则）使用的解析器，与规则代理客户端共用解析结果
+impl tower::Service<hyper_util::client::legacy::connect::dns::Name> for UpstreamDns {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: hyper_util::client::legacy::connect::dns::Name) -> Self::Future {
+        let dns = self.clone();
+        Box::pin(async move {
+            let addrs = dns.resolve_addrs(name.as_str()).await?;
+            Ok(addrs
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .collect::<Vec<_>>()
+                .into_iter())
+        })
+    }
+}
+
+/// 解析 DNS 服务器地址：不带协议时同时使用 UDP 与 TCP（默认端口 53），
+/// tls:// 默认端口 853，https:// 默认端口 443、路径 /dns-query
+fn parse_nameserver(entry: &str) -> anyhow::Result<Vec<NameServerConfig>> {
+    let entry = entry.trim();
+    if !entry.contains("://") {
+        let addr = entry
+            .parse::<SocketAddr>()
+            .or_else(|_| entry.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))?;
+        return Ok(vec![
+            NameServerConfig::new(addr, Protocol::Udp),
+            NameServerConfig::new(addr, Protocol::Tcp),
+        ]);
+    }
+
+    let url = Url::parse(entry)?;
+    let protocol = match url.scheme() {
+        "udp" => Protocol::Udp,
+        "tcp" => Protocol::Tcp,
+        "tls" => Protocol::Tls,
+        "https" => Protocol::Https,
+        scheme => anyhow::bail!("unsupported scheme {}", scheme),
+    };
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("missing host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port().unwrap_or(match protocol {
+        Protocol::Tls => 853,
+        Protocol::Https => 443,
+        _ => 53,
+    });
+    // 服务器地址为域名时用系统 DNS 解析一次，避免解析 DNS 服务器自身时依赖它
+    let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => (host, port).to_socket_addrs()?.collect(),
+    };
+    let encrypted = matches!(protocol, Protocol::Tls | Protocol::Https);
+    let http_endpoint =
+        (protocol == Protocol::Https && url.path() != "/").then(|| url.path().to_string());
+    Ok(addrs
+        .into_iter()
+        .map(|addr| {
+            let mut nameserver = NameServerConfig::new(addr, protocol);
+            if encrypted {
+                nameserver.tls_dns_name = Some(host.to_string());
+            }
+            nameserver.http_endpoint = http_endpoint.clone();
+            nameserver
+        })
+        .collect())
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// 轮询 DNS 每次返回的顺序可能不同，按地址集合比较
+fn same_addrs(old: &[IpAddr], new: &[IpAddr]) -> bool {
+    let mut old = old.to_vec();
+    let mut new = new.to_vec();
+    old.sort();
+    new.sort();
+    old == new
+}
+
+/// 规则的连接池调优，未设置的项使用默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoolOptions {
+    /// 每个上游主机保留的空闲连接数，默认 200
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_idle_per_host: Option<usize>,
+    /// 空闲连接的保留时间(秒)，默认 90
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_timeout_secs: Option<u64>,
+    /// 每个上游主机同时进行的请求数上限，超出时排队，等待超过规则超时返回 503
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+}
+
+/// 规则对连接池的设置，设置相同的规则共用一个连接池
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct PoolSettings {
+    /// 为空时使用全局地址族
+    pub family: Option<IpFamily>,
+    /// 为空时使用默认的 10 秒
+    pub connect_timeout: Option<Duration>,
+    pub max_idle_per_host: Option<usize>,
+    pub idle_timeout: Option<Duration>,
+}
+
+/// 创建使用指定解析器与连接池设置的规则代理客户端
+type BuildClient = dyn Fn(UpstreamDns, &PoolSettings) -> reqwest::Result<Client> + Send + Sync;
+
+/// 规则代理客户端：每组连接池设置一个连接池，未单独设置的规则共用全局地址族的连接池
+#[derive(Clone)]
+pub struct UpstreamClients {
+    dns: UpstreamDns,
+    clients: Arc<DashMap<PoolSettings, Arc<Client>>>,
+    build: Arc<BuildClient>,
+}
+
+impl UpstreamClients {
+    pub fn new<F>(dns: UpstreamDns, build: F) -> reqwest::Result<Self>
+    where
+        F: Fn(UpstreamDns, &PoolSettings) -> reqwest::Result<Client> + Send + Sync + 'static,
+    {
+        let clients = Self {
+            dns,
+            clients: Arc::new(DashMap::new()),
+            build: Arc::new(build),
+        };
+        clients.get(PoolSettings::default())?;
+        Ok(clients)
+    }
+
+    /// 按连接池设置取连接池，首次使用时创建
+    pub fn get(&self, settings: PoolSettings) -> reqwest::Result<Arc<Client>> {
+        let settings = PoolSettings {
+            family: Some(settings.family.unwrap_or(self.dns.family)),
+            ..settings
+        };
+        if let Some(client) = self.clients.get(&settings) {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(self.build(&settings)?);
+        Ok(self.clients.entry(settings).or_insert(client).clone())
+    }
+
+    fn build(&self, settings: &PoolSettings) -> reqwest::Result<Client> {
+        let family = settings.family.unwrap_or(self.dns.family);
+        (self.build)(self.dns.with_family(family), settings)
+    }
+
+    /// 重建全部已创建的连接池
+    fn rebuild(&self) -> reqwest::Result<()> {
+        let keys: Vec<PoolSettings> = self.clients.iter().map(|entry| *entry.key()).collect();
+        for settings in keys {
+            let client = self.build(&settings)?;
+            self.clients.insert(settings, Arc::new(client));
+        }
+        Ok(())
+    }
+}
+
+/// 定期重新解析过期的上游域名，解析结果变化时重建连接池，避免长连接一直固定在旧地址上；
+/// 旧连接池在进行中的请求结束后释放
+pub fn start_refresh_task(tasks: &TaskRegistry, clients: UpstreamClients) {
+    tasks.spawn_periodic("upstream_dns_refresh", REFRESH_INTERVAL, move || {
+        let clients = clients.clone();
+        async move {
+            let changed = clients.dns.refresh_expired().await;
+            if !changed.is_empty() {
+                clients.rebuild()?;
+                tracing::info!(hosts = ?changed, "Upstream connection pool rebuilt after DNS change");
+            }
+            Ok(())
+        }
+    });
+}
+
+/// 按地址族筛选并排序解析结果；auto 保持 DNS 返回的顺序，
+/// 连接时由 Happy Eyeballs 先尝试第一个地址的地址族，300ms 未连上再并行尝试另一地址族
+fn order_addrs(family: IpFamily, addrs: Vec<IpAddr>) -> Vec<IpAddr> {
+    match family {
+        IpFamily::Auto => addrs,
+        IpFamily::PreferIpv4 => {
+            let (mut v4, v6): (Vec<IpAddr>, Vec<IpAddr>) =
+                addrs.into_iter().partition(IpAddr::is_ipv4);
+            v4.extend(v6);
+            v4
+        }
+        IpFamily::PreferIpv6 => {
+            let (mut v6, v4): (Vec<IpAddr>, Vec<IpAddr>) =
+                addrs.into_iter().partition(IpAddr::is_ipv6);
+            v6.extend(v4);
+            v6
+        }
+        IpFamily::Ipv4Only => addrs.into_iter().filter(IpAddr::is_ipv4).collect(),
+        IpFamily::Ipv6Only => addrs.into_iter().filter(IpAddr::is_ipv6).collect(),
+    }
+}
@@ -0,0 +1,430 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use chrono::Utc;
+use reqwest::Client;
+use ring::{digest, hmac, rand::SecureRandom};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::config::DnsProviderConfig;
+
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+const ROUTE53_HOST: &str = "route53.amazonaws.com";
+/// Route53 为全局服务，签名固定使用 us-east-1
+const ROUTE53_REGION: &str = "us-east-1";
+/// 验证记录的 TTL(秒)
+const TXT_TTL: u32 = 60;
+
+/// DNS-01 验证使用的 DNS 服务商
+///
+/// 同一个记录名可能同时需要多个值（如 example.com 与 *.example.com），
+/// 因此按记录名一次写入或删除全部值
+pub enum DnsProvider {
+    Cloudflare(Cloudflare),
+    Route53(Route53),
+    Rfc2136(Rfc2136),
+}
+
+pub struct Cloudflare {
+    client: Client,
+    api_token: String,
+    zone_id: Option<String>,
+}
+
+pub struct Route53 {
+    client: Client,
+    access_key_id: String,
+    secret_access_key: String,
+    hosted_zone_id: String,
+    session_token: Option<String>,
+}
+
+pub struct Rfc2136 {
+    server: String,
+    zone: String,
+    tsig: Option<Tsig>,
+}
+
+/// RFC 8945 TSIG 密钥
+pub struct Tsig {
+    name: String,
+    algorithm: &'static str,
+    key: hmac::Key,
+}
+
+impl DnsProvider {
+    pub fn from_config(config: &DnsProviderConfig, client: Client) -> Result<Self> {
+        Ok(match config {
+            DnsProviderConfig::Cloudflare { api_token, zone_id } => Self::Cloudflare(Cloudflare {
+                client,
+                api_token: api_token.clone(),
+                zone_id: zone_id.clone(),
+            }),
+            DnsProviderConfig::Route53 {
+                access_key_id,
+                secret_access_key,
+                hosted_zone_id,
+                session_token,
+            } => Self::Route53(Route53 {
+                client,
+                access_key_id: access_key_id.clone(),
+                secret_access_key: secret_access_key.clone(),
+                hosted_zone_id: hosted_zone_id
+                    .trim_start_matches("/hostedzone/")
+                    .to_string(),
+                session_token: session_token.clone(),
+            }),
+            DnsProviderConfig::Rfc2136 {
+                server,
+                zone,
+                tsig_key_name,
+                tsig_secret,
+                tsig_algorithm,
+            } => {
+                let tsig = match (tsig_key_name, tsig_secret) {
+                    (Some(name), Some(secret)) => Some(Tsig::new(name, secret, tsig_algorithm)?),
+                    (None, None) => None,
+                    _ => bail!("rfc2136: tsig_key_name and tsig_secret must be set together"),
+                };
+                Self::Rfc2136(Rfc2136 {
+                    server: server.clone(),
+                    zone: zone.clone(),
+                    tsig,
+                })
+            }
+        })
+    }
+
+    /// 写入 TXT 记录，name 为完整域名（不带结尾的点）
+    pub async fn set_txt(&self, name: &str, values: &[String]) -> Result<()> {
+        match self {
+            Self::Cloudflare(p) => p.set_txt(name, values).await,
+            Self::Route53(p) => p.change("UPSERT", name, values).await,
+            Self::Rfc2136(p) => p.update(name, values, false).await,
+        }
+    }
+
+    /// 删除 set_txt 写入的记录
+    pub async fn clear_txt(&self, name: &str, values: &[String]) -> Result<()> {
+        match self {
+            Self::Cloudflare(p) => p.clear_txt(name, values).await,
+            Self::Route53(p) => p.change("DELETE", name, values).await,
+            Self::Rfc2136(p) => p.update(name, values, true).await,
+        }
+    }
+}
+
+impl Cloudflare {
+    async fn set_txt(&self, name: &str, values: &[String]) -> Result<()> {
+        let zone = self.zone(name).await?;
+        for value in values {
+            let resp = self
+                .client
+                .post(format!("{}/zones/{}/dns_records", CLOUDFLARE_API, zone))
+                .bearer_auth(&self.api_token)
+                .json(&json!({
+                    "type": "TXT",
+                    "name": name,
+                    "content": value,
+                    "ttl": TXT_TTL,
+                }))
+                .send()
+                .await?;
+            cloudflare_result(resp).await?;
+        }
+        Ok(())
+    }
+
+    async fn clear_txt(&self, name: &str, values: &[String]) -> Result<()> {
+        let zone = self.zone(name).await?;
+        for value in values {
+            let resp = self
+                .client
+                .get(format!("{}/zones/{}/dns_records", CLOUDFLARE_API, zone))
+                .bearer_auth(&self.api_token)
+                .query(&[("type", "TXT"), ("name", name), ("content", value)])
+                .send()
+                .await?;
+            let records = cloudflare_result(resp).await?;
+            for id in records
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|r| r["id"].as_str())
+            {
+                let resp = self
+                    .client
+                    .delete(format!(
+                        "{}/zones/{}/dns_records/{}",
+                        CLOUDFLARE_API, zone, id
+                    ))
+                    .bearer_auth(&self.api_token)
+                    .send()
+                    .await?;
+                cloudflare_result(resp).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn zone(&self, name: &str) -> Result<String> {
+        if let Some(ref id) = self.zone_id {
+            return Ok(id.clone());
+        }
+        // 从最长的父域名开始查找所属 zone
+        let labels: Vec<&str> = name.split('.').collect();
+        for i in 1..labels.len().saturating_sub(1) {
+            let candidate = labels[i..].join(".");
+            let resp = self
+                .client
+                .get(format!("{}/zones", CLOUDFLARE_API))
+                .bearer_auth(&self.api_token)
+                .query(&[("name", candidate.as_str())])
+                .send()
+                .await?;
+            if let Some(id) = cloudflare_result(resp).await?[0]["id"].as_str() {
+                return Ok(id.to_string());
+            }
+        }
+        bail!("cloudflare: no zone found for {}", name)
+    }
+}
+
+impl Route53 {
+    async fn change(&self, action: &str, name: &str, values: &[String]) -> Result<()> {
+        let records: String = values
+            .iter()
+            .map(|v| format!("<ResourceRecord><Value>\"{}\"</Value></ResourceRecord>", v))
+            .collect();
+        let body = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                r#"<ChangeResourceRecordSetsRequest xmlns="https://route53.amazonaws.com/doc/2013-04-01/">"#,
+                "<ChangeBatch><Changes><Change><Action>{}</Action><ResourceRecordSet>",
+                "<Name>{}.</Name><Type>TXT</Type><TTL>{}</TTL><ResourceRecords>{}</ResourceRecords>",
+                "</ResourceRecordSet></Change></Changes></ChangeBatch></ChangeResourceRecordSetsRequest>"
+            ),
+            action, name, TXT_TTL, records
+        );
+        let path = format!("/2013-04-01/hostedzone/{}/rrset/", self.hosted_zone_id);
+
+        // AWS Signature Version 4
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let mut headers = vec![
+            ("content-type", "text/xml".to_string()),
+            ("host", ROUTE53_HOST.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(ref token) = self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n{}\n\n{}\n{}\n{}",
+            path,
+            canonical_headers,
+            signed_headers,
+            hex(digest::digest(&digest::SHA256, body.as_bytes()).as_ref())
+        );
+        let scope = format!("{}/{}/route53/aws4_request", date, ROUTE53_REGION);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let mut key = format!("AWS4{}", self.secret_access_key).into_bytes();
+        for part in [date.as_str(), ROUTE53_REGION, "route53", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        let mut request = self
+            .client
+            .post(format!("https://{}{}", ROUTE53_HOST, path))
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                    self.access_key_id, scope, signed_headers, signature
+                ),
+            )
+            .body(body);
+        for (k, v) in headers.into_iter().filter(|(k, _)| *k != "host") {
+            request = request.header(k, v);
+        }
+
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            bail!(
+                "route53 {} {} failed: {} {}",
+                action,
+                name,
+                status,
+                resp.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn cloudflare_result(resp: reqwest::Response) -> Result<Value> {
+    let status = resp.status();
+    let body: Value = resp.json().await.context("cloudflare: invalid response")?;
+    if !status.is_success() || body["success"] != json!(true) {
+        bail!("cloudflare: {} {}", status, body["errors"]);
+    }
+    Ok(body["result"].clone())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Tsig {
+    fn new(name: &str, secret: &str, algorithm: &str) -> Result<Self> {
+        let secret = base64::engine::general_purpose::STANDARD
+            .decode(secret.trim())
+            .context("rfc2136: tsig_secret is not valid base64")?;
+        let (algorithm, hmac_alg) = match algorithm.trim_end_matches('.') {
+            "hmac-sha256" => ("hmac-sha256", hmac::HMAC_SHA256),
+            "hmac-sha512" => ("hmac-sha512", hmac::HMAC_SHA512),
+            other => bail!("rfc2136: unsupported tsig algorithm {}", other),
+        };
+        Ok(Self {
+            name: name.trim_end_matches('.').to_ascii_lowercase(),
+            algorithm,
+            key: hmac::Key::new(hmac_alg, &secret),
+        })
+    }
+
+    /// 在消息末尾追加 TSIG 记录并更新 ARCOUNT
+    fn sign(&self, msg: &mut Vec<u8>) {
+        let time_signed = Utc::now().timestamp() as u64;
+        let fudge: u16 = 300;
+
+        let mut signed = msg.clone();
+        put_name(&mut signed, &self.name);
+        signed.extend_from_slice(&255u16.to_be_bytes()); // CLASS ANY
+        signed.extend_from_slice(&0u32.to_be_bytes()); // TTL
+        put_name(&mut signed, self.algorithm);
+        signed.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        signed.extend_from_slice(&fudge.to_be_bytes());
+        signed.extend_from_slice(&0u16.to_be_bytes()); // error
+        signed.extend_from_slice(&0u16.to_be_bytes()); // other len
+        let mac = hmac::sign(&self.key, &signed);
+
+        let mut rdata = Vec::new();
+        put_name(&mut rdata, self.algorithm);
+        rdata.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+        rdata.extend_from_slice(&fudge.to_be_bytes());
+        rdata.extend_from_slice(&(mac.as_ref().len() as u16).to_be_bytes());
+        rdata.extend_from_slice(mac.as_ref());
+        rdata.extend_from_slice(&msg[0..2]); // original id
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+
+        put_name(msg, &self.name);
+        msg.extend_from_slice(&250u16.to_be_bytes()); // TYPE TSIG
+        msg.extend_from_slice(&255u16.to_be_bytes());
+        msg.extend_from_slice(&0u32.to_be_bytes());
+        msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&rdata);
+
+        let arcount = u16::from_be_bytes([msg[10], msg[11]]) + 1;
+        msg[10..12].copy_from_slice(&arcount.to_be_bytes());
+    }
+}
+
+fn put_name(buf: &mut Vec<u8>, name: &str) {
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+    {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+}
+
+impl Rfc2136 {
+    /// 发送 UPDATE 消息，delete 为 true 时删除指定的记录值
+    async fn update(&self, name: &str, values: &[String], delete: bool) -> Result<()> {
+        let (server, zone) = (&self.server, &self.zone);
+        let mut id = [0u8; 2];
+        ring::rand::SystemRandom::new()
+            .fill(&mut id)
+            .map_err(|_| anyhow::anyhow!("rfc2136: failed to generate message id"))?;
+
+        let mut msg = Vec::with_capacity(512);
+        msg.extend_from_slice(&id);
+        msg.extend_from_slice(&0x2800u16.to_be_bytes()); // opcode UPDATE
+        msg.extend_from_slice(&1u16.to_be_bytes()); // ZOCOUNT
+        msg.extend_from_slice(&0u16.to_be_bytes()); // PRCOUNT
+        msg.extend_from_slice(&(values.len() as u16).to_be_bytes()); // UPCOUNT
+        msg.extend_from_slice(&0u16.to_be_bytes()); // ADCOUNT
+
+        put_name(&mut msg, zone);
+        msg.extend_from_slice(&6u16.to_be_bytes()); // SOA
+        msg.extend_from_slice(&1u16.to_be_bytes()); // IN
+
+        for value in values {
+            put_name(&mut msg, name);
+            msg.extend_from_slice(&16u16.to_be_bytes()); // TXT
+                                                         // 删除单条记录时 CLASS 为 NONE、TTL 为 0
+            let (class, ttl) = if delete { (254u16, 0) } else { (1u16, TXT_TTL) };
+            msg.extend_from_slice(&class.to_be_bytes());
+            msg.extend_from_slice(&ttl.to_be_bytes());
+            msg.extend_from_slice(&(value.len() as u16 + 1).to_be_bytes());
+            msg.push(value.len() as u8);
+            msg.extend_from_slice(value.as_bytes());
+        }
+
+        if let Some(ref tsig) = self.tsig {
+            tsig.sign(&mut msg);
+        }
+
+        let addr = tokio::net::lookup_host(server)
+            .await?
+            .next()
+            .with_context(|| format!("rfc2136: cannot resolve {}", server))?;
+        let bind = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = tokio::net::UdpSocket::bind(bind).await?;
+        socket.connect(addr).await?;
+        socket.send(&msg).await?;
+
+        let mut buf = [0u8; 1232];
+        let len = tokio::time::timeout(Duration::from_secs(10), socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow::anyhow!("rfc2136: no response from {}", server))??;
+        if len < 12 || buf[0..2] != id {
+            bail!("rfc2136: invalid response from {}", server);
+        }
+        match buf[3] & 0x0f {
+            0 => Ok(()),
+            rcode => bail!("rfc2136: update {} rejected with rcode {}", name, rcode),
+        }
+    }
+}
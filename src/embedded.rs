@@ -0,0 +1,58 @@
+use crate::db::{Database, SCHEMA, SCHEMA_VERSION};
+use crate::static_files::StaticAssets;
+
+/// 管理界面运行必需的静态资源
+const REQUIRED_ASSETS: &[&str] = &["index.html", "login.html"];
+
+/// 打印内置静态资源与数据库结构版本，并在内存数据库上执行一次迁移校验，
+/// 用于发布前确认打包产物完整
+pub fn print_embedded() -> anyhow::Result<()> {
+    println!(
+        "proxy-server {} ({})",
+        env!("CARGO_PKG_VERSION"),
+        env!("BUILD_TARGET")
+    );
+
+    println!("\nEmbedded assets:");
+    let mut names: Vec<_> = StaticAssets::iter().collect();
+    names.sort();
+    for name in &names {
+        if let Some(file) = StaticAssets::get(name) {
+            let hash: String = file
+                .metadata
+                .sha256_hash()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            println!(
+                "  {:<24} {:>8} bytes  sha256:{}",
+                name,
+                file.data.len(),
+                hash
+            );
+        }
+    }
+
+    for required in REQUIRED_ASSETS {
+        if StaticAssets::get(required).is_none() {
+            anyhow::bail!("Required asset {} is not embedded", required);
+        }
+    }
+
+    let db = Database::in_memory()?;
+    let version = db.schema_version()?;
+    if version != SCHEMA_VERSION {
+        anyhow::bail!(
+            "Schema version mismatch: migrated to {}, expected {}",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+
+    println!("\nSchema version: {}", version);
+    for (table, columns) in SCHEMA {
+        println!("  {} ({})", table, columns.join(", "));
+    }
+
+    Ok(())
+}
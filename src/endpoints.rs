@@ -0,0 +1,191 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::acl::IpAcl;
+use crate::config::EndpointsConfig;
+use crate::proxy::{rule_proxy_handler, ProxyState};
+use crate::upstreams::RequiredUpstream;
+
+/// 内置端点访问控制：IP 白名单或令牌，满足其一即可
+#[derive(Debug, Clone)]
+pub struct EndpointGuard {
+    acl: IpAcl,
+    token: Option<String>,
+}
+
+impl EndpointGuard {
+    pub fn from_config(config: &EndpointsConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            acl: IpAcl::parse(&config.allow_ips)?,
+            token: config.token.clone().filter(|t| !t.is_empty()),
+        })
+    }
+
+    fn check<B>(&self, client: SocketAddr, req: &Request<B>) -> Result<(), StatusCode> {
+        if self.acl.is_empty() && self.token.is_none() {
+            return Ok(());
+        }
+        if !self.acl.is_empty() && self.acl.contains(client.ip()) {
+            return Ok(());
+        }
+        if let Some(ref expected) = self.token {
+            if request_token(req)
+                .is_some_and(|t| constant_time_eq(t.as_bytes(), expected.as_bytes()))
+            {
+                return Ok(());
+            }
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+pub async fn guard_middleware(
+    State(guard): State<Arc<EndpointGuard>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match guard.check(client, &req) {
+        Ok(()) => next.run(req).await,
+        Err(status) => {
+            tracing::warn!(client_ip = %client.ip(), path = %req.uri().path(), "Endpoint access denied");
+            status.into_response()
+        }
+    }
+}
+
+/// 内置端点被规则覆盖时的转发状态
+#[derive(Clone)]
+pub struct RulesOverride {
+    pub proxy: ProxyState,
+    pub paths: Arc<Vec<String>>,
+}
+
+/// 请求内置端点路径且有规则匹配时，直接走规则代理
+pub async fn rules_override_middleware(
+    State(o): State<RulesOverride>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    if o.paths.iter().any(|p| p == path)
+        && o.proxy
+            .rules
+            .load()
+            .candidates(path)
+            .any(|r| r.matches(path))
+    {
+        return rule_proxy_handler(State(o.proxy), ConnectInfo(client), req)
+            .await
+            .into_response();
+    }
+    next.run(req).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct Liveness {
+    pub status: &'static str,
+    pub pid: u32,
+}
+
+/// 存活检查：进程能处理请求即返回 200，不检查依赖，避免依赖故障时被编排系统反复重启
+pub async fn live_handler() -> Json<Liveness> {
+    Json(Liveness {
+        status: "ok",
+        pid: std::process::id(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct RulesReadiness {
+    /// 规则已成功加载
+    pub loaded: bool,
+    pub count: usize,
+    /// 最近一次重载编译失败或冲突的规则数
+    pub failed: usize,
+    pub last_reload: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: &'static str,
+    /// 主备模式下是否处理流量
+    pub active: bool,
+    /// 数据库可用时为 null，否则为错误信息
+    pub database: Option<String>,
+    pub rules: RulesReadiness,
+    /// `required` 规则的上游，任一不可达时返回 503
+    pub upstreams: Vec<RequiredUpstream>,
+}
+
+/// 就绪检查：数据库可用、规则已加载、关键上游可达且本实例处理流量时返回 200，否则返回 503 与原因，便于负载均衡摘除
+pub async fn ready_handler(State(state): State<ProxyState>) -> (StatusCode, Json<Readiness>) {
+    let db = state.db.clone();
+    let database = match tokio::task::spawn_blocking(move || db.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(e) => Some(e.to_string()),
+    };
+    let last = state.reloads.recent(1).pop();
+    let rules = RulesReadiness {
+        loaded: state.rules_ready.load(Ordering::Acquire),
+        count: state.rules.load().len(),
+        failed: last.as_ref().map_or(0, |reload| reload.failures.len()),
+        last_reload: last.as_ref().map(|reload| reload.time.clone()),
+        last_error: last.and_then(|reload| reload.error),
+    };
+    let upstreams = state.upstream_probes.required(&state.rules.load());
+    let active = state.ha.is_active();
+    let (code, status) = if !active {
+        (StatusCode::SERVICE_UNAVAILABLE, "standby")
+    } else if !rules.loaded {
+        (StatusCode::SERVICE_UNAVAILABLE, "starting")
+    } else if database.is_some() {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    } else if upstreams.iter().any(|u| u.reachable == Some(false)) {
+        (StatusCode::SERVICE_UNAVAILABLE, "upstream_unreachable")
+    } else {
+        (StatusCode::OK, "ok")
+    };
+    (
+        code,
+        Json(Readiness {
+            status,
+            active,
+            database,
+            rules,
+            upstreams,
+        }),
+    )
+}
+
+fn request_token<B>(req: &Request<B>) -> Option<&str> {
+    if let Some(token) = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+    {
+        return Some(token);
+    }
+    req.uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
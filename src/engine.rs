@@ -0,0 +1,403 @@
+//! 可嵌入的代理引擎：不启动管理界面，由调用方提供规则并挂载代理路由
+//!
+//! 独立监听端口：
+//!
+//! ```no_run
+//! use proxy_server::engine::{ProxyRule, ProxyServer};
+//!
+//! # async fn run(rules: Vec<ProxyRule>) -> anyhow::Result<()> {
+//! ProxyServer::builder()
+//!     .with_rules(rules)
+//!     .bind("0.0.0.0:3000")
+//!     .serve()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! 挂载到已有的 Axum 应用：
+//!
+//! ```no_run
+//! use axum::{routing::get, Router};
+//! use proxy_server::engine::{ProxyRule, ProxyServer};
+//! use std::net::SocketAddr;
+//!
+//! # async fn run(rules: Vec<ProxyRule>) -> anyhow::Result<()> {
+//! let proxy = ProxyServer::builder().with_rules(rules).build()?;
+//! let app = Router::new()
+//!     .route("/hello", get(|| async { "hello" }))
+//!     .nest_service("/proxy", proxy.router());
+//! let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+//! axum::serve(
+//!     listener,
+//!     app.into_make_service_with_connect_info::<SocketAddr>(),
+//! )
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use arc_swap::ArcSwap;
+use axum::{routing::any, Router};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::{self, AuthState};
+use crate::cache_store;
+use crate::config::{AuthConfig, DiscoveryConfig, HaConfig, PluginConfig, SecretsConfig};
+use crate::connections::ActiveRequests;
+use crate::db::Database;
+use crate::direct_cache::DirectCache;
+use crate::discovery::ServiceDiscovery;
+use crate::dns::UpstreamDns;
+use crate::events::AdminEvents;
+use crate::faults::FaultSwitch;
+use crate::ha::HaState;
+use crate::hooks::ProxyHooks;
+use crate::idempotency::IdempotencyCache;
+use crate::identity::IdentityAssertions;
+use crate::lifecycle;
+use crate::mirror::CacheQuotas;
+use crate::plugins::Plugins;
+use crate::pool_stats::ConnectionStats;
+use crate::proxy::{build_direct_client, build_upstream_client, RawClients};
+use crate::proxy_protocol::UpstreamConnector;
+use crate::registry::RegistryMirror;
+use crate::reloads::ReloadHistory;
+use crate::rolling::RollingStats;
+use crate::router::{self, RuleSet};
+use crate::rule_auth::{self, ProxyKeys};
+use crate::secrets::Secrets;
+use crate::signed_urls::SignedUrls;
+use crate::stats::RuleStats;
+use crate::target_guard::TargetGuard;
+use crate::tasks::TaskRegistry;
+use crate::traffic::TrafficTail;
+use crate::upstream_proxy::UpstreamProxies;
+use crate::upstreams::{UpstreamHealth, UpstreamLimits, UpstreamProbes};
+
+pub use crate::config::{
+    CacheBackend, CacheStoreConfig, DirectCacheConfig, DirectProxyConfig, DnsConfig, IpFamily,
+    S3StoreConfig,
+};
+pub use crate::db::{ProxyRule, RuleOptions};
+pub use crate::goproxy::GoProxyOptions;
+pub use crate::maven::MavenOptions;
+pub use crate::npm::NpmOptions;
+pub use crate::packages::PackageCacheOptions;
+pub use crate::proxy::{rule_proxy_handler, CompiledProxyRule, ProxyState};
+pub use crate::pypi::PypiOptions;
+pub use crate::registry::RegistryOptions;
+pub use crate::rule_auth::RuleAuth;
+pub use crate::secrets::UpstreamAuth;
+pub use crate::upstream_proxy::UpstreamProxyOptions;
+
+/// [`ProxyState`] 构建器，未设置的项与独立运行时的默认值相同
+pub struct ProxyStateBuilder {
+    rules: Vec<ProxyRule>,
+    database_path: Option<String>,
+    direct_proxy_path: String,
+    direct_proxy: DirectProxyConfig,
+    cache_store: CacheStoreConfig,
+    dns: DnsConfig,
+    default_timeout: Duration,
+    max_body_bytes: u64,
+    secrets_key: Option<String>,
+    hooks: ProxyHooks,
+    plugins: HashMap<String, PluginConfig>,
+}
+
+impl Default for ProxyStateBuilder {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            database_path: None,
+            direct_proxy_path: "proxy".to_string(),
+            direct_proxy: DirectProxyConfig::default(),
+            cache_store: CacheStoreConfig::default(),
+            dns: DnsConfig::default(),
+            default_timeout: Duration::from_secs(30),
+            max_body_bytes: 100 * 1024 * 1024,
+            secrets_key: None,
+            hooks: ProxyHooks::default(),
+            plugins: HashMap::new(),
+        }
+    }
+}
+
+impl ProxyStateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 初始规则，之后可通过 [`ProxyState::set_rules`] 替换
+    pub fn rules(mut self, rules: Vec<ProxyRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// 保存签名密钥、会话等数据的 SQLite 路径，默认使用内存数据库（重启后密钥重新生成）
+    pub fn database_path(mut self, path: impl Into<String>) -> Self {
+        self.database_path = Some(path.into());
+        self
+    }
+
+    /// 直接代理路径前缀，即 /{path}/https://...
+    pub fn direct_proxy_path(mut self, path: impl Into<String>) -> Self {
+        self.direct_proxy_path = path.into();
+        self
+    }
+
+    /// 直接代理目标访问控制
+    pub fn direct_proxy(mut self, config: DirectProxyConfig) -> Self {
+        self.direct_proxy = config;
+        self
+    }
+
+    /// 镜像与下载缓存的存储后端，默认保存在本地缓存目录
+    pub fn cache_store(mut self, config: CacheStoreConfig) -> Self {
+        self.cache_store = config;
+        self
+    }
+
+    /// 规则上游的静态主机覆盖与 DNS 服务器，默认使用系统 DNS 配置
+    pub fn dns(mut self, config: DnsConfig) -> Self {
+        self.dns = config;
+        self
+    }
+
+    /// 直接代理的默认超时
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// 缓冲转发的请求体上限(字节)，默认 100MB，超过时返回 413
+    pub fn max_body_bytes(mut self, bytes: u64) -> Self {
+        self.max_body_bytes = bytes;
+        self
+    }
+
+    /// 加密密钥存储的主密钥（32 字节的 base64 编码），未设置时使用数据库路径加 .key 的密钥文件，
+    /// 内存数据库使用临时密钥
+    pub fn secrets_key(mut self, key: impl Into<String>) -> Self {
+        self.secrets_key = Some(key.into());
+        self
+    }
+
+    pub fn hooks(mut self, hooks: ProxyHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// 规则 `plugins` 选项可引用的 WASM 插件，键为插件名
+    pub fn plugins(mut self, plugins: HashMap<String, PluginConfig>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// 编译规则并创建客户端，需在 tokio 运行时中调用（会启动后台 DNS 刷新任务）
+    pub fn build(self) -> anyhow::Result<ProxyState> {
+        let db = match &self.database_path {
+            Some(path) => Database::new(path)?,
+            None => Database::in_memory()?,
+        };
+        let secrets = Secrets::load(
+            &db,
+            &SecretsConfig {
+                key: self.secrets_key,
+                key_file: None,
+            },
+            self.database_path.as_deref(),
+        )?;
+        rule_auth::migrate_plaintext(&db, &secrets)?;
+        let tasks = TaskRegistry::new();
+        let upstream_dns = UpstreamDns::new(&self.dns)?;
+        let cache_store = cache_store::build(&self.cache_store, &tasks)?;
+        let direct_guard = Arc::new(TargetGuard::from_config(&self.direct_proxy)?);
+
+        // 嵌入模式没有管理界面，管理员密码随机生成，仅用于满足会话存储的初始化
+        let auth_config = AuthConfig {
+            password: auth::generate_token()?,
+            ..AuthConfig::default()
+        };
+
+        let state = ProxyState {
+            client: build_upstream_client(&tasks, &upstream_dns, &ConnectionStats::new())?,
+            raw_clients: RawClients::new(&upstream_dns),
+            upstream_dns,
+            proxy_protocol: UpstreamConnector::new()?,
+            upstream_proxies: UpstreamProxies::default(),
+            direct_client: build_direct_client(&direct_guard)?,
+            direct_guard,
+            hooks: self.hooks,
+            rules: Arc::new(ArcSwap::from_pointee(RuleSet::default())),
+            direct_proxy_path: Arc::new(ArcSwap::from_pointee(self.direct_proxy_path)),
+            db: db.clone(),
+            default_timeout: self.default_timeout,
+            max_body_bytes: self.max_body_bytes,
+            stats: RuleStats::new(),
+            idempotency: IdempotencyCache::new(),
+            tasks,
+            access_log: None,
+            traffic: TrafficTail::new(),
+            rolling: RollingStats::new(),
+            reloads: ReloadHistory::new(),
+            upstreams: UpstreamHealth::new(AdminEvents::new()),
+            upstream_probes: UpstreamProbes::new(AdminEvents::new()),
+            upstream_limits: UpstreamLimits::default(),
+            discovery: ServiceDiscovery::new(&DiscoveryConfig::default(), Client::new())?,
+            signed_urls: SignedUrls::load(&db, &secrets)?,
+            ha: HaState::new(&HaConfig::default())?,
+            auth: AuthState::new(db.clone(), &auth_config)?,
+            identity: IdentityAssertions::load(&secrets)?,
+            proxy_keys: ProxyKeys::load(&db)?,
+            active: ActiveRequests::new(),
+            secrets,
+            registry: RegistryMirror::new(),
+            cache_store,
+            cache_quotas: CacheQuotas::new(),
+            direct_cache: DirectCache::new(&self.direct_proxy.cache),
+            plugins: Plugins::load(&self.plugins)?,
+            faults: FaultSwitch::default(),
+            rules_ready: Arc::new(AtomicBool::new(false)),
+        };
+        state.set_rules(&self.rules)?;
+        Ok(state)
+    }
+}
+
+impl ProxyState {
+    /// 编译并原子替换全部规则，任一规则编译失败或被前面的规则遮蔽时保留原规则；只加载已启用的规则
+    pub fn set_rules(&self, rules: &[ProxyRule]) -> anyhow::Result<()> {
+        let compiled = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .map(|rule| {
+                CompiledProxyRule::from_db_rule(rule)
+                    .map_err(|e| anyhow::anyhow!("Failed to compile rule '{}': {}", rule.name, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if let Some((later, earlier)) = router::shadowed(&compiled).first() {
+            anyhow::bail!(
+                "Rule '{}' is never matched: earlier rule '{}' has the same source",
+                compiled[*later].name,
+                compiled[*earlier].name
+            );
+        }
+        self.rules.store(Arc::new(RuleSet::new(compiled)));
+        self.rules_ready.store(true, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// 代理路由：所有请求交给 [`rule_proxy_handler`]，服务需使用
+/// `into_make_service_with_connect_info::<SocketAddr>()` 提供客户端地址
+pub fn proxy_router(state: ProxyState) -> Router {
+    Router::new()
+        .fallback(any(rule_proxy_handler))
+        .with_state(state)
+}
+
+/// 代理服务：在 [`ProxyState`] 之上提供监听与优雅停机，也可取出路由挂载到调用方的 Axum 应用
+pub struct ProxyServer {
+    state: ProxyState,
+    addr: String,
+}
+
+impl ProxyServer {
+    pub fn builder() -> ProxyServerBuilder {
+        ProxyServerBuilder::default()
+    }
+
+    /// 代理状态，可用于 [`ProxyState::set_rules`] 运行时替换规则
+    pub fn state(&self) -> &ProxyState {
+        &self.state
+    }
+
+    /// 代理路由，挂载时外层服务同样需要提供 `ConnectInfo<SocketAddr>`；
+    /// 通过 `nest_service` 挂载到子路径时，规则按去掉前缀后的路径匹配
+    pub fn router(&self) -> Router {
+        proxy_router(self.state.clone())
+    }
+
+    /// 监听构建时指定的地址，收到 SIGINT / SIGTERM 后停止接受新连接并等待在途请求完成
+    pub async fn serve(self) -> anyhow::Result<()> {
+        let listener = tokio::net::TcpListener::bind(&self.addr).await?;
+        self.serve_with_shutdown(listener, lifecycle::shutdown_signal())
+            .await
+    }
+
+    /// 在调用方提供的监听器上服务，`shutdown` 完成后优雅停止
+    pub async fn serve_with_shutdown(
+        self,
+        listener: tokio::net::TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        tracing::info!("Proxy: http://{}", listener.local_addr()?);
+        axum::serve(
+            listener,
+            self.router()
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+        Ok(())
+    }
+}
+
+/// [`ProxyServer`] 构建器，代理行为的设置见 [`ProxyStateBuilder`]
+pub struct ProxyServerBuilder {
+    state: ProxyStateBuilder,
+    addr: String,
+}
+
+impl Default for ProxyServerBuilder {
+    fn default() -> Self {
+        Self {
+            state: ProxyStateBuilder::default(),
+            addr: "0.0.0.0:3000".to_string(),
+        }
+    }
+}
+
+impl ProxyServerBuilder {
+    pub fn with_rules(mut self, rules: Vec<ProxyRule>) -> Self {
+        self.state = self.state.rules(rules);
+        self
+    }
+
+    pub fn with_hooks(mut self, hooks: ProxyHooks) -> Self {
+        self.state = self.state.hooks(hooks);
+        self
+    }
+
+    /// 修改其余代理设置，如 `.configure(|b| b.dns(dns).max_body_bytes(1 << 20))`
+    pub fn configure(mut self, f: impl FnOnce(ProxyStateBuilder) -> ProxyStateBuilder) -> Self {
+        self.state = f(self.state);
+        self
+    }
+
+    /// [`ProxyServer::serve`] 的监听地址，默认 0.0.0.0:3000
+    pub fn bind(mut self, addr: impl Into<String>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    /// 需在 tokio 运行时中调用，见 [`ProxyStateBuilder::build`]
+    pub fn build(self) -> anyhow::Result<ProxyServer> {
+        Ok(ProxyServer {
+            state: self.state.build()?,
+            addr: self.addr,
+        })
+    }
+
+    pub async fn serve(self) -> anyhow::Result<()> {
+        self.build()?.serve().await
+    }
+}
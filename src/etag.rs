@@ -0,0 +1,110 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use bytes::Bytes;
+use futures::StreamExt;
+use ring::digest;
+
+use crate::proxy;
+
+/// 生成 ETag 的最大响应体，超过时原样透传
+const MAX_ETAG_BODY: usize = 10 * 1024 * 1024;
+
+/// 需要生成 ETag 的请求，记录客户端携带的 If-None-Match
+pub struct EtagRequest {
+    if_none_match: Option<String>,
+}
+
+impl EtagRequest {
+    /// 仅对 GET 请求生成 ETag
+    pub fn from_request(method: &Method, headers: &HeaderMap) -> Option<Self> {
+        (method == Method::GET).then(|| Self {
+            if_none_match: headers
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        })
+    }
+
+    /// If-None-Match 是否命中，按弱比较忽略 `W/` 前缀
+    fn matches(&self, etag: &str) -> bool {
+        self.if_none_match.as_deref().is_some_and(|value| {
+            value.split(',').map(str::trim).any(|candidate| {
+                candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+            })
+        })
+    }
+}
+
+/// 上游未提供校验器的可缓存响应：按响应体计算强 ETag，命中 If-None-Match 时返回 304
+pub async fn apply(
+    result: Result<Response, StatusCode>,
+    req: EtagRequest,
+) -> Result<Response, StatusCode> {
+    let resp = match result {
+        Ok(resp) if is_cacheable(resp.headers(), resp.status()) => resp,
+        other => return other,
+    };
+
+    let (mut parts, body) = resp.into_parts();
+    let mut stream = body.into_data_stream();
+    let mut chunks = Vec::new();
+    let mut total = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| {
+            tracing::warn!("Failed to buffer response for ETag: {}", e);
+            StatusCode::BAD_GATEWAY
+        })?;
+        total += chunk.len();
+        chunks.push(Ok(chunk));
+        if total > MAX_ETAG_BODY {
+            // 超过上限，已读取的数据块与剩余部分拼接后继续流式返回
+            let body = Body::from_stream(futures::stream::iter(chunks).chain(stream));
+            return Ok(Response::from_parts(parts, body));
+        }
+    }
+
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    let mut bytes = Vec::with_capacity(total);
+    for chunk in chunks.into_iter().flatten() {
+        ctx.update(&chunk);
+        bytes.extend_from_slice(&chunk);
+    }
+    let etag = format!("\"{}\"", URL_SAFE_NO_PAD.encode(ctx.finish().as_ref()));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+
+    if req.matches(&etag) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Ok(Response::from_parts(parts, Body::empty()));
+    }
+    Ok(Response::from_parts(parts, Body::from(Bytes::from(bytes))))
+}
+
+/// 仅处理 200 响应，且上游未返回 ETag、未禁止缓存、已知长度不超过上限；
+/// Git 的流式响应不缓冲
+fn is_cacheable(headers: &HeaderMap, status: StatusCode) -> bool {
+    if status != StatusCode::OK
+        || headers.contains_key(header::ETAG)
+        || proxy::is_git_content_type(headers)
+    {
+        return false;
+    }
+    let no_store = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-store"));
+    let too_large = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > MAX_ETAG_BODY);
+    !no_store && !too_large
+}
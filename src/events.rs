@@ -0,0 +1,100 @@
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+
+use crate::AdminState;
+
+/// 广播缓冲区大小，订阅者落后超过该数量时丢弃旧事件
+const CHANNEL_CAPACITY: usize = 256;
+
+/// 管理端状态变化事件
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminEvent {
+    pub time: String,
+    /// 事件类型，同时作为 SSE 的 event 字段
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub data: Value,
+}
+
+/// 规则重载、配置修改、上游健康变化与证书续期等事件的广播，供管理前端实时刷新
+#[derive(Clone)]
+pub struct AdminEvents {
+    tx: broadcast::Sender<AdminEvent>,
+}
+
+impl Default for AdminEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdminEvents {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    pub fn publish(&self, kind: &'static str, data: Value) {
+        let _ = self.tx.send(AdminEvent {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            kind,
+            data,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AdminEvent> {
+        self.tx.subscribe()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventFilter {
+    /// 逗号分隔的事件类型，为空时推送全部事件
+    pub types: Option<String>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &AdminEvent) -> bool {
+        match self.types {
+            Some(ref types) => types.split(',').any(|kind| kind.trim() == event.kind),
+            None => true,
+        }
+    }
+}
+
+/// 管理端事件 SSE 推送
+pub async fn stream_handler(
+    State(state): State<AdminState>,
+    Query(filter): Query<EventFilter>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.events.subscribe();
+
+    let stream = futures::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) if filter.matches(&event) => {
+                    let sse = Event::default()
+                        .event(event.kind)
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok(sse), (rx, filter)));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let sse = Event::default().event("lagged").data(skipped.to_string());
+                    return Some((Ok(sse), (rx, filter)));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,97 @@
+use axum::http::{header, HeaderMap};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+fn default_response_header() -> String {
+    "X-Experiment-Bucket".to_string()
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// 规则的 A/B 实验：按请求头或 cookie 的值确定性分组，同一用户始终落在同一分组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentOptions {
+    /// 实验名，参与哈希，不同实验的分组相互独立
+    pub name: String,
+    /// 分组依据的请求头
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// 分组依据的 cookie，与 `header` 二选一
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<String>,
+    /// 分组，请求不带分组依据时使用第一个
+    pub variants: Vec<ExperimentVariant>,
+    /// 返回分组名的响应头
+    #[serde(default = "default_response_header")]
+    pub response_header: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExperimentVariant {
+    pub name: String,
+    /// 目标地址模板，可使用规则的路径参数，为空时使用规则本身的目标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+impl ExperimentOptions {
+    /// 校验分组设置，保存规则时调用
+    pub fn validate(&self) -> Result<(), String> {
+        if self.header.is_some() == self.cookie.is_some() {
+            return Err("experiment needs exactly one of header or cookie".to_string());
+        }
+        if self.variants.is_empty() {
+            return Err("experiment has no variants".to_string());
+        }
+        if self.variants.iter().all(|v| v.weight == 0) {
+            return Err("experiment variants have no weight".to_string());
+        }
+        if let Err(e) = axum::http::HeaderName::try_from(self.response_header.as_str()) {
+            return Err(format!("invalid response_header: {}", e));
+        }
+        Ok(())
+    }
+
+    /// 按分组依据选择分组：实验名与键值的 SHA-256 前 8 字节按权重取模
+    pub fn select(&self, headers: &HeaderMap) -> &ExperimentVariant {
+        let Some(key) = self.key(headers) else {
+            return &self.variants[0];
+        };
+        let hash = digest::digest(&digest::SHA256, format!("{}:{}", self.name, key).as_bytes());
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash.as_ref()[..8]);
+        let total: u64 = self.variants.iter().map(|v| u64::from(v.weight)).sum();
+        let mut point = u64::from_be_bytes(bytes) % total;
+        for variant in &self.variants {
+            let weight = u64::from(variant.weight);
+            if point < weight {
+                return variant;
+            }
+            point -= weight;
+        }
+        &self.variants[0]
+    }
+
+    fn key<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        if let Some(ref name) = self.header {
+            return headers
+                .get(name.as_str())
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty());
+        }
+        let cookie = self.cookie.as_deref()?;
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == cookie)
+            .map(|(_, value)| value)
+            .filter(|v| !v.is_empty())
+    }
+}
@@ -0,0 +1,153 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderName, StatusCode},
+    response::Response,
+    Json,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::ApiResponse;
+use crate::AdminState;
+
+fn default_percent() -> f64 {
+    100.0
+}
+
+/// 规则的故障注入，用于测试客户端的重试与超时行为
+///
+/// 全局开关打开，或请求携带 `header` 指定的请求头时生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultOptions {
+    /// 转发前增加的延迟(毫秒)
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 增加延迟的请求比例(%)
+    #[serde(default = "default_percent")]
+    pub delay_percent: f64,
+    /// 直接返回的状态码，如 503
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abort_status: Option<u16>,
+    /// 直接返回的请求比例(%)
+    #[serde(default = "default_percent")]
+    pub abort_percent: f64,
+    /// 携带该请求头的请求不受全局开关限制，请求头不转发给上游
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+impl FaultOptions {
+    /// 校验比例、状态码与请求头名，保存规则时调用
+    pub fn validate(&self) -> Result<(), String> {
+        for percent in [self.delay_percent, self.abort_percent] {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("fault percent {} out of range 0-100", percent));
+            }
+        }
+        if let Some(status) = self.abort_status {
+            StatusCode::from_u16(status).map_err(|e| e.to_string())?;
+        }
+        if let Some(ref header) = self.header {
+            HeaderName::try_from(header.as_str()).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// 注入故障：按比例延迟，按比例返回错误响应；未生效时直接返回 None
+    pub async fn inject(
+        &self,
+        switch: &FaultSwitch,
+        req: &mut Request,
+        rule: &str,
+    ) -> Option<Response> {
+        let by_header = self
+            .header
+            .as_deref()
+            .is_some_and(|name| req.headers_mut().remove(name).is_some());
+        if !by_header && !switch.is_enabled() {
+            return None;
+        }
+        if self.delay_ms > 0 && roll(self.delay_percent) {
+            tracing::debug!(rule = %rule, delay_ms = self.delay_ms, "Injecting fault delay");
+            tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+        }
+        let status = self
+            .abort_status
+            .filter(|_| roll(self.abort_percent))
+            .and_then(|status| StatusCode::from_u16(status).ok())?;
+        tracing::debug!(rule = %rule, status = %status, "Injecting fault response");
+        let mut resp = Response::new(Body::from("Injected fault"));
+        *resp.status_mut() = status;
+        Some(resp)
+    }
+}
+
+/// 按百分比随机命中
+fn roll(percent: f64) -> bool {
+    if percent >= 100.0 {
+        return true;
+    }
+    let mut bytes = [0u8; 4];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return false;
+    }
+    f64::from(u32::from_be_bytes(bytes)) / f64::from(u32::MAX) * 100.0 < percent
+}
+
+/// 故障注入全局开关，启动时关闭，通过 /api/faults 切换
+#[derive(Clone, Default)]
+pub struct FaultSwitch {
+    enabled: Arc<AtomicBool>,
+}
+
+impl FaultSwitch {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FaultStatus {
+    pub enabled: bool,
+    /// 配置了故障注入的规则名
+    #[serde(default, skip_deserializing)]
+    pub rules: Vec<String>,
+}
+
+fn status(state: &AdminState) -> FaultStatus {
+    FaultStatus {
+        enabled: state.faults.is_enabled(),
+        rules: state
+            .rules
+            .load()
+            .iter()
+            .filter(|rule| rule.options.fault.is_some())
+            .map(|rule| rule.name.clone())
+            .collect(),
+    }
+}
+
+pub async fn status_handler(State(state): State<AdminState>) -> Json<ApiResponse<FaultStatus>> {
+    Json(ApiResponse::ok(status(&state)))
+}
+
+/// 打开或关闭全局故障注入
+pub async fn update_handler(
+    State(state): State<AdminState>,
+    Json(req): Json<FaultStatus>,
+) -> Json<ApiResponse<FaultStatus>> {
+    state.faults.set(req.enabled);
+    tracing::warn!(
+        enabled = req.enabled,
+        "Fault injection switched via admin API"
+    );
+    Json(ApiResponse::ok(status(&state)))
+}
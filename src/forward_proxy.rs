@@ -0,0 +1,197 @@
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Version},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use hyper_util::rt::TokioIo;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+use crate::config::ForwardProxyConfig;
+use crate::proxy::ListenerScope;
+use crate::redact;
+use crate::rule_auth::{self, ProxyKeys};
+use crate::target_guard::TargetGuard;
+
+/// 连接隧道目标的超时，与其他上游客户端一致
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 已通过认证的绝对地址请求，按直接代理转发且不校验签名
+#[derive(Debug, Clone, Copy)]
+pub struct ForwardProxied;
+
+/// 正向代理：绝对地址请求交给直接代理转发，CONNECT 建立 TCP 隧道
+#[derive(Clone)]
+pub struct ForwardProxy {
+    config: Arc<ForwardProxyConfig>,
+    keys: ProxyKeys,
+    guard: Arc<TargetGuard>,
+}
+
+impl ForwardProxy {
+    pub fn new(config: &ForwardProxyConfig, keys: ProxyKeys, guard: Arc<TargetGuard>) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            keys,
+            guard,
+        }
+    }
+
+    /// 校验 Proxy-Authorization，返回认证主体
+    fn authenticate(&self, headers: &HeaderMap) -> Option<String> {
+        if !self.config.require_auth {
+            return Some("anonymous".to_string());
+        }
+        let (_, key) = rule_auth::basic_header_credentials(headers, header::PROXY_AUTHORIZATION)?;
+        self.keys
+            .verify_unscoped(&key)
+            .map(|name| format!("key:{}", name))
+    }
+
+    /// 校验目标后连接，成功时返回 200 并在连接升级后双向转发数据
+    async fn connect(&self, req: Request, client: SocketAddr, principal: String) -> Response {
+        let Some(authority) = req.uri().authority().cloned() else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let Some(port) = authority.port_u16() else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let target = authority.as_str();
+        let host = authority
+            .host()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+
+        if !self.config.connect_ports.is_empty() && !self.config.connect_ports.contains(&port) {
+            tracing::warn!(target = %target, client_ip = %client.ip(), "Forward proxy port not allowed");
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        let mut upstream = match connect_target(&self.guard, host, port).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!(target = %target, client_ip = %client.ip(), "Forward proxy tunnel rejected: {}", e);
+                return e.status().into_response();
+            }
+        };
+
+        tracing::info!(target = %target, client_ip = %client.ip(), principal = %principal, "Forward proxy tunnel");
+        let target = target.to_string();
+        tokio::spawn(async move {
+            let upgraded = match hyper::upgrade::on(req).await {
+                Ok(upgraded) => upgraded,
+                Err(e) => {
+                    tracing::debug!(target = %target, "Forward proxy upgrade failed: {}", e);
+                    return;
+                }
+            };
+            match tokio::io::copy_bidirectional(&mut TokioIo::new(upgraded), &mut upstream).await {
+                Ok((sent, received)) => {
+                    tracing::debug!(target = %target, sent, received, "Forward proxy tunnel closed")
+                }
+                Err(e) => tracing::debug!(target = %target, "Forward proxy tunnel error: {}", e),
+            }
+        });
+        // 空 Body 会被 axum 补上 content-length: 0，而 hyper 拒绝在 CONNECT 的 2xx 响应中发送该头，
+        // 因此使用长度未知的空流
+        Body::from_stream(futures::stream::empty::<Result<Bytes, std::io::Error>>()).into_response()
+    }
+}
+
+/// 隧道目标无法连接的原因
+#[derive(Debug)]
+pub enum TunnelError {
+    /// 目标被访问控制禁止
+    Blocked(String),
+    /// 解析失败或解析出的地址全部被禁止
+    Resolve(String),
+    Connect(std::io::Error),
+    Timeout,
+}
+
+impl TunnelError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::Blocked(_) => StatusCode::FORBIDDEN,
+            Self::Resolve(_) | Self::Connect(_) => StatusCode::BAD_GATEWAY,
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+impl std::fmt::Display for TunnelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Blocked(reason) => write!(f, "target blocked: {}", reason),
+            Self::Resolve(e) => write!(f, "resolve failed: {}", e),
+            Self::Connect(e) => write!(f, "connect failed: {}", e),
+            Self::Timeout => write!(f, "connect timed out"),
+        }
+    }
+}
+
+/// 按直接代理的访问控制校验并连接隧道目标（不带方括号的主机名），CONNECT 与 SOCKS5 共用；
+/// 解析结果中被禁止的地址已丢弃
+pub async fn connect_target(
+    guard: &TargetGuard,
+    host: &str,
+    port: u16,
+) -> Result<TcpStream, TunnelError> {
+    guard.check_host(host).map_err(TunnelError::Blocked)?;
+    let addrs = guard
+        .resolve(host, port)
+        .await
+        .map_err(|e| TunnelError::Resolve(e.to_string()))?;
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addrs[..]))
+        .await
+        .map_err(|_| TunnelError::Timeout)?
+        .map_err(TunnelError::Connect)?;
+    let _ = stream.set_nodelay(true);
+    Ok(stream)
+}
+
+/// 绝对地址（`GET http://host/path`）与 CONNECT 请求按正向代理处理，其他请求原样交给后续路由；
+/// 监听器关闭直接代理时不处理正向代理请求
+pub async fn middleware(
+    State(proxy): State<ForwardProxy>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let enabled = req
+        .extensions()
+        .get::<ListenerScope>()
+        .is_none_or(ListenerScope::direct_proxy);
+    if !enabled || !is_forward_request(&req) {
+        return next.run(req).await;
+    }
+
+    let Some(principal) = proxy.authenticate(req.headers()) else {
+        tracing::warn!(target = %redact::url(&req.uri().to_string()), client_ip = %client.ip(), "Forward proxy authentication failed");
+        let mut resp = StatusCode::PROXY_AUTHENTICATION_REQUIRED.into_response();
+        resp.headers_mut().insert(
+            header::PROXY_AUTHENTICATE,
+            HeaderValue::from_static("Basic realm=\"proxy\""),
+        );
+        return resp;
+    };
+    req.headers_mut().remove(header::PROXY_AUTHORIZATION);
+    req.headers_mut().remove("proxy-connection");
+
+    if req.method() == Method::CONNECT {
+        return proxy.connect(req, client, principal).await;
+    }
+    req.extensions_mut().insert(ForwardProxied);
+    next.run(req).await
+}
+
+/// HTTP/2 请求总是带有 scheme 与 authority，只有 HTTP/1 的绝对地址表示正向代理请求
+fn is_forward_request(req: &Request) -> bool {
+    req.method() == Method::CONNECT
+        || (matches!(req.version(), Version::HTTP_10 | Version::HTTP_11)
+            && req.uri().scheme().is_some())
+}
@@ -0,0 +1,101 @@
+use axum::{extract::Request, http::StatusCode, response::Response};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{CompiledProxyRule, ProxyState};
+use crate::stats::RuleCounters;
+
+/// @v/list 与 @latest 的默认缓存时间
+const DEFAULT_LIST_TTL_SECS: u64 = 60;
+
+/// Go 模块代理（GOPROXY）规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GoProxyOptions {
+    /// 缓存目录，按模块路径保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// @v/list 与 @latest 的缓存秒数，默认 60，0 表示不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_ttl_secs: Option<u64>,
+}
+
+/// GOPROXY 协议中的请求类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GoRequest {
+    /// 具体版本的 .info / .mod / .zip，内容不会变化
+    Version(&'static str),
+    /// 版本列表、@latest 或分支名等非规范版本的查询，结果随发布变化
+    Query(&'static str),
+}
+
+impl GoRequest {
+    /// 按 <模块>/@v/<版本>.<扩展名>、<模块>/@v/list、<模块>/@latest 识别，其他路径（如 sumdb）返回 None
+    fn of(path: &str) -> Option<Self> {
+        if path.ends_with("/@latest") {
+            return Some(Self::Query("application/json"));
+        }
+        let (_, file) = path.rsplit_once("/@v/")?;
+        if file == "list" {
+            return Some(Self::Query("text/plain; charset=utf-8"));
+        }
+        let (version, ext) = file.rsplit_once('.')?;
+        let content_type = match ext {
+            "info" => "application/json",
+            "mod" => "text/plain; charset=utf-8",
+            "zip" => "application/zip",
+            _ => return None,
+        };
+        // 规范版本（含伪版本）以 v 加数字开头，分支名等查询结果会变化
+        let canonical = version
+            .strip_prefix('v')
+            .is_some_and(|v| v.starts_with(|c: char| c.is_ascii_digit()));
+        Some(if canonical {
+            Self::Version(content_type)
+        } else {
+            Self::Query(content_type)
+        })
+    }
+}
+
+/// 镜像规则的 GET / HEAD 请求：具体版本的 .info / .mod / .zip 永久缓存，
+/// 版本列表与 @latest 按 TTL 缓存，sumdb 等其他路径直接转发
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &GoProxyOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    let path = req.uri().path();
+    let cache = mirror::source_prefix(rule, path, target_url)
+        .and_then(|prefix| cache_file(state, options, &path[prefix.len()..]));
+    mirror::forward_cached(state, rule, req, target_url, client_ip, counters, cache).await
+}
+
+/// 请求对应的缓存文件，不可缓存时返回 None；模块路径中的大写字母已按协议转义为 !小写
+fn cache_file(state: &ProxyState, options: &GoProxyOptions, relative: &str) -> Option<CacheFile> {
+    let dir = options.cache_dir.as_deref()?;
+    let kind = GoRequest::of(relative)?;
+    let file = CacheFile::new(
+        &state.cache_store,
+        Path::new(dir),
+        &mirror::relative_path(relative)?,
+        None,
+    );
+    match kind {
+        GoRequest::Version(content_type) => Some(file.with_content_type(content_type)),
+        GoRequest::Query(content_type) => {
+            let ttl = options.list_ttl_secs.unwrap_or(DEFAULT_LIST_TTL_SECS);
+            (ttl > 0).then(|| {
+                file.with_content_type(content_type)
+                    .with_max_age(Duration::from_secs(ttl))
+            })
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use parking_lot::Mutex;
+use reqwest::Client;
+use ring::digest;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api::ApiResponse;
+use crate::cluster::{self, SyncedKey, SyncedSecret};
+use crate::config::{HaConfig, HaRole};
+use crate::db::ProxyRule;
+use crate::endpoints::constant_time_eq;
+use crate::reloads;
+use crate::tasks::TaskRegistry;
+use crate::AdminState;
+
+/// 心跳令牌请求头
+const TOKEN_HEADER: &str = "x-ha-token";
+
+/// 主机返回的心跳
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub role: HaRole,
+    pub active: bool,
+    /// 规则、密钥与 API Key 的摘要，备机据此判断是否需要同步
+    #[serde(alias = "rules_hash")]
+    pub state_hash: String,
+}
+
+/// 主机的规则、密钥与 API Key，备机整体同步；规则的认证与上游凭据以密钥名称引用，
+/// 只同步规则时接管后这些规则无法使用
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HaSnapshot {
+    pub rules: Vec<ProxyRule>,
+    /// 不含各实例自己的内部密钥（system.），value 保持加密，主备需配置相同的 secrets.key
+    #[serde(default)]
+    pub secrets: Vec<SyncedSecret>,
+    #[serde(default)]
+    pub proxy_keys: Vec<SyncedKey>,
+}
+
+impl HaSnapshot {
+    fn load(admin: &AdminState) -> anyhow::Result<Self> {
+        Ok(Self {
+            rules: admin.db.get_all_rules()?,
+            secrets: cluster::synced_secrets(&admin.db)?,
+            proxy_keys: cluster::synced_keys(&admin.db)?,
+        })
+    }
+
+    /// 密钥比较加密值，同步后与主机一致
+    fn hash(&self) -> anyhow::Result<String> {
+        let json = serde_json::to_vec(self)?;
+        Ok(URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, &json).as_ref()))
+    }
+
+    /// 在一个事务中写入，规则集校验未通过或主密钥不一致时不写入
+    fn apply(&self, admin: &AdminState) -> anyhow::Result<()> {
+        let (secrets, keys) = cluster::credentials(admin, &self.secrets, &self.proxy_keys)?;
+        let before = admin.db.get_all_rules()?;
+        admin
+            .db
+            .replace_replicated(&self.rules, &secrets, &keys, reloads::check)?;
+        cluster::synced(admin, before, &self.rules)?;
+        admin.reload_rules("ha_sync")
+    }
+}
+
+#[derive(Debug, Default)]
+struct PeerStatus {
+    last_heartbeat: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// 主备状态：standalone 与 primary 始终处理流量，standby 仅在接管后处理
+#[derive(Clone)]
+pub struct HaState {
+    config: Arc<HaConfig>,
+    active: Arc<AtomicBool>,
+    peer: Arc<Mutex<PeerStatus>>,
+}
+
+impl HaState {
+    pub fn new(config: &HaConfig) -> anyhow::Result<Self> {
+        if config.role != HaRole::Standalone && config.token.as_deref().unwrap_or("").is_empty() {
+            anyhow::bail!("ha.token is required when ha.role is {:?}", config.role);
+        }
+        if config.role == HaRole::Standby && config.peer_url.is_none() {
+            anyhow::bail!("ha.peer_url is required when ha.role is standby");
+        }
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            active: Arc::new(AtomicBool::new(config.role != HaRole::Standby)),
+            // 启动后至少等待一个故障转移周期再接管
+            peer: Arc::new(Mutex::new(PeerStatus {
+                last_heartbeat: Some(Instant::now()),
+                last_error: None,
+            })),
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        match (self.config.token.as_deref(), headers.get(TOKEN_HEADER)) {
+            (Some(expected), Some(token)) if !expected.is_empty() => {
+                constant_time_eq(token.as_bytes(), expected.as_bytes())
+            }
+            _ => false,
+        }
+    }
+
+    /// 备机定期向主机发送心跳、同步规则、密钥与 API Key，主机失联超时后接管
+    pub fn start_standby_task(&self, tasks: &TaskRegistry, client: Client, admin: AdminState) {
+        if self.config.role != HaRole::Standby {
+            return;
+        }
+        let ha = self.clone();
+        let interval = Duration::from_secs(self.config.heartbeat_interval_secs.max(1));
+        tasks.spawn_periodic("ha_heartbeat", interval, move || {
+            let ha = ha.clone();
+            let client = client.clone();
+            let admin = admin.clone();
+            async move { ha.heartbeat(&client, &admin, interval).await }
+        });
+    }
+
+    async fn heartbeat(
+        &self,
+        client: &Client,
+        admin: &AdminState,
+        timeout: Duration,
+    ) -> anyhow::Result<()> {
+        let result = self
+            .fetch::<Heartbeat>(client, "/api/ha/heartbeat", timeout)
+            .await;
+        let heartbeat = match result {
+            Ok(heartbeat) => heartbeat,
+            Err(e) => {
+                let mut peer = self.peer.lock();
+                peer.last_error = Some(e.to_string());
+                let silent = peer.last_heartbeat.map(|t| t.elapsed()).unwrap_or_default();
+                drop(peer);
+                if !self.is_active()
+                    && silent >= Duration::from_secs(self.config.failover_timeout_secs)
+                {
+                    tracing::warn!(
+                        silent_secs = silent.as_secs(),
+                        "Primary unreachable, taking over"
+                    );
+                    self.set_active(true);
+                }
+                return Err(e);
+            }
+        };
+
+        *self.peer.lock() = PeerStatus {
+            last_heartbeat: Some(Instant::now()),
+            last_error: None,
+        };
+        if heartbeat.active && self.is_active() {
+            tracing::warn!("Primary is back, returning to standby");
+            self.set_active(false);
+        }
+
+        if HaSnapshot::load(admin)?.hash()? != heartbeat.state_hash {
+            let snapshot = self
+                .fetch::<HaSnapshot>(client, "/api/ha/state", timeout)
+                .await?;
+            snapshot.apply(admin)?;
+            tracing::info!(
+                rules = snapshot.rules.len(),
+                secrets = snapshot.secrets.len(),
+                proxy_keys = snapshot.proxy_keys.len(),
+                "Synced rules, secrets and API keys from primary"
+            );
+        }
+        Ok(())
+    }
+
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        client: &Client,
+        path: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<T> {
+        let url = format!(
+            "{}{}",
+            self.config
+                .peer_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/'),
+            path
+        );
+        let resp: ApiResponse<T> = client
+            .get(&url)
+            .header(
+                TOKEN_HEADER,
+                self.config.token.as_deref().unwrap_or_default(),
+            )
+            .timeout(timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        resp.data
+            .ok_or_else(|| anyhow::anyhow!("Empty response from {}", url))
+    }
+
+    fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::Relaxed);
+        let (event, script) = if active {
+            ("promote", self.config.on_promote.clone())
+        } else {
+            ("demote", self.config.on_demote.clone())
+        };
+        if let Some(script) = script {
+            tokio::spawn(run_hook(event, script));
+        }
+    }
+}
+
+/// 执行主备切换脚本，通过 PROXY_HA_EVENT 环境变量传入事件名
+async fn run_hook(event: &'static str, script: String) {
+    let status = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&script)
+        .env("PROXY_HA_EVENT", event)
+        .status()
+        .await;
+    match status {
+        Ok(status) if status.success() => tracing::info!(event, "HA hook completed"),
+        Ok(status) => tracing::warn!(event, status = %status, "HA hook failed"),
+        Err(e) => tracing::error!("Failed to run HA hook: {}", e),
+    }
+}
+
+/// 心跳接口，使用共享令牌认证
+pub async fn heartbeat_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Heartbeat>>, StatusCode> {
+    if !state.ha.authorized(&headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let state_hash = HaSnapshot::load(&state)
+        .and_then(|snapshot| snapshot.hash())
+        .map_err(|e| {
+            tracing::error!("Failed to build HA snapshot: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(ApiResponse::ok(Heartbeat {
+        role: state.ha.config.role,
+        active: state.ha.is_active(),
+        state_hash,
+    })))
+}
+
+/// 供备机同步的规则、密钥与 API Key，使用共享令牌认证
+pub async fn state_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<HaSnapshot>>, StatusCode> {
+    if !state.ha.authorized(&headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    HaSnapshot::load(&state)
+        .map(|snapshot| Json(ApiResponse::ok(snapshot)))
+        .map_err(|e| {
+            tracing::error!("Failed to build HA snapshot: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 完整规则列表，使用共享令牌认证，供旧版本备机同步
+pub async fn rules_handler(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<ProxyRule>>>, StatusCode> {
+    if !state.ha.authorized(&headers) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    state
+        .db
+        .get_all_rules()
+        .map(|rules| Json(ApiResponse::ok(rules)))
+        .map_err(|e| {
+            tracing::error!("Failed to get rules: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+#[derive(Debug, Serialize)]
+pub struct HaStatus {
+    pub role: HaRole,
+    pub active: bool,
+    pub peer_url: Option<String>,
+    /// 距上次收到主机心跳的秒数，仅备机
+    pub last_heartbeat_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+pub async fn status_handler(State(state): State<AdminState>) -> Json<ApiResponse<HaStatus>> {
+    let ha = &state.ha;
+    let standby = ha.config.role == HaRole::Standby;
+    let peer = ha.peer.lock();
+    Json(ApiResponse::ok(HaStatus {
+        role: ha.config.role,
+        active: ha.is_active(),
+        peer_url: ha.config.peer_url.clone(),
+        last_heartbeat_secs: peer
+            .last_heartbeat
+            .filter(|_| standby)
+            .map(|t| t.elapsed().as_secs()),
+        last_error: peer.last_error.clone(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{RuleInput, RuleOptions};
+    use crate::rule_auth::{self, CreateKeyRequest, RuleAuth};
+    use crate::testing;
+
+    /// 主备使用相同的主密钥
+    fn pair() -> (AdminState, AdminState) {
+        let mut config = testing::config();
+        config.secrets.key = Some(base64::engine::general_purpose::STANDARD.encode([7u8; 32]));
+        (testing::admin_state(&config), testing::admin_state(&config))
+    }
+
+    fn create_rule(state: &AdminState, source: &str, options: &RuleOptions) {
+        state
+            .db
+            .create_rule(
+                &RuleInput {
+                    name: source,
+                    source,
+                    target: "http://upstream/{*path}",
+                    timeout_secs: 30,
+                    options: Some(options),
+                },
+                reloads::check,
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn standby_syncs_secrets_and_api_keys() {
+        let (primary, standby) = pair();
+        primary.secrets.set("api-token", "s3cret").unwrap();
+        let options = RuleOptions {
+            auth: Some(RuleAuth::Bearer {
+                token_secret: "api-token".to_string(),
+            }),
+            ..Default::default()
+        };
+        create_rule(&primary, "/api/{*path}", &options);
+        let key = rule_auth::create_key(
+            State(primary.clone()),
+            Json(CreateKeyRequest {
+                name: "ci".to_string(),
+                rule_id: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+        .data
+        .unwrap()
+        .key;
+
+        let snapshot = HaSnapshot::load(&primary).unwrap();
+        assert_ne!(
+            snapshot.hash().unwrap(),
+            HaSnapshot::load(&standby).unwrap().hash().unwrap()
+        );
+        snapshot.apply(&standby).unwrap();
+
+        assert_eq!(standby.secrets.get("api-token").as_deref(), Some("s3cret"));
+        assert_eq!(
+            standby.proxy_keys.verify_unscoped(&key).as_deref(),
+            Some("ci")
+        );
+        assert_eq!(standby.rules.load().len(), 1);
+        assert_eq!(
+            snapshot.hash().unwrap(),
+            HaSnapshot::load(&standby).unwrap().hash().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_snapshot_is_not_written() {
+        let (primary, standby) = pair();
+        create_rule(&standby, "/old/{*path}", &RuleOptions::default());
+        primary.secrets.set("api-token", "s3cret").unwrap();
+        let mut snapshot = HaSnapshot::load(&primary).unwrap();
+        let mut rule = standby.db.get_all_rules().unwrap().remove(0);
+        rule.source = "/api/{*path}".to_string();
+        snapshot.rules = vec![
+            rule.clone(),
+            ProxyRule {
+                id: rule.id + 1,
+                ..rule
+            },
+        ];
+
+        assert!(snapshot.apply(&standby).is_err());
+        let rules = standby.db.get_all_rules().unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].source, "/old/{*path}");
+        assert!(!standby.secrets.contains("api-token"));
+    }
+
+    #[tokio::test]
+    async fn secrets_sealed_with_another_key_are_not_written() {
+        let primary = testing::admin_state(&testing::config());
+        let standby = testing::admin_state(&testing::config());
+        primary.secrets.set("api-token", "s3cret").unwrap();
+
+        assert!(HaSnapshot::load(&primary).unwrap().apply(&standby).is_err());
+        assert!(!standby.secrets.contains("api-token"));
+    }
+}
@@ -0,0 +1,75 @@
+use axum::{
+    extract::Request,
+    http::{Method, StatusCode},
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// 钩子调用时的请求信息
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub client_addr: SocketAddr,
+    pub method: Method,
+    pub path: String,
+    /// 匹配的规则名，直接代理时为空
+    pub rule: Option<String>,
+    /// 转发目标地址（查询参数已按日志脱敏配置处理），未匹配到规则时为空
+    pub target: Option<String>,
+}
+
+/// 代理请求钩子，嵌入方实现后通过 [`ProxyHooks::register`] 注册，按注册顺序调用
+pub trait ProxyHook: Send + Sync + 'static {
+    /// 转发到上游前调用，可修改请求；返回响应时不再转发，直接返回给客户端
+    fn on_request(&self, _ctx: &HookContext, _req: &mut Request) -> Option<Response> {
+        None
+    }
+
+    /// 返回响应前调用，可修改状态码与响应头
+    fn on_response(&self, _ctx: &HookContext, _resp: &mut Response) {}
+
+    /// 请求处理失败（未匹配规则、上游不可达、超时等）时调用
+    fn on_error(&self, _ctx: &HookContext, _status: StatusCode) {}
+}
+
+/// 已注册的钩子列表
+#[derive(Clone, Default)]
+pub struct ProxyHooks {
+    hooks: Arc<Vec<Arc<dyn ProxyHook>>>,
+}
+
+impl ProxyHooks {
+    pub fn register(mut self, hook: impl ProxyHook) -> Self {
+        Arc::make_mut(&mut self.hooks).push(Arc::new(hook));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub(crate) fn on_request(&self, ctx: &HookContext, req: &mut Request) -> Option<Response> {
+        self.hooks.iter().find_map(|hook| hook.on_request(ctx, req))
+    }
+
+    pub(crate) fn finish(
+        &self,
+        ctx: &HookContext,
+        result: Result<Response, StatusCode>,
+    ) -> Result<Response, StatusCode> {
+        match result {
+            Ok(mut resp) => {
+                for hook in self.hooks.iter() {
+                    hook.on_response(ctx, &mut resp);
+                }
+                Ok(resp)
+            }
+            Err(status) => {
+                for hook in self.hooks.iter() {
+                    hook.on_error(ctx, status);
+                }
+                Err(status)
+            }
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 可缓存的最大响应体
+const MAX_CACHED_BODY: usize = 10 * 1024 * 1024;
+
+/// 幂等键最大长度
+const MAX_KEY_LEN: usize = 255;
+
+enum CacheEntry {
+    InFlight,
+    Done {
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+        expires_at: Instant,
+    },
+    /// 上游已处理但响应体过大未缓存，TTL 内重试不再转发也无法重放
+    Completed {
+        expires_at: Instant,
+    },
+}
+
+pub enum Lookup {
+    /// 首次请求，需转发到上游，完成后通过 guard 写入缓存
+    Miss(PendingKey),
+    /// 相同幂等键的请求仍在处理中
+    InFlight,
+    /// 相同幂等键的请求已处理，响应未缓存
+    NotReplayable,
+    /// 命中缓存，直接返回
+    Hit(Response),
+}
+
+/// 基于 Idempotency-Key 的上游响应缓存，保证同一键在 TTL 内至多转发一次
+#[derive(Clone, Default)]
+pub struct IdempotencyCache {
+    entries: Arc<DashMap<String, CacheEntry>>,
+}
+
+impl IdempotencyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从请求头提取幂等键，按规则、请求方法与路径隔离，同一个键不能重放到其他端点
+    pub fn key_for(
+        rule_id: i64,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Option<String> {
+        headers
+            .get("idempotency-key")
+            .and_then(|v| v.to_str().ok())
+            .filter(|k| !k.is_empty() && k.len() <= MAX_KEY_LEN)
+            .map(|k| format!("{}:{}:{}:{}", rule_id, method, path, k))
+    }
+
+    pub fn begin(&self, key: String) -> Lookup {
+        match self.entries.entry(key.clone()) {
+            Entry::Occupied(mut occupied) => match occupied.get() {
+                CacheEntry::InFlight => Lookup::InFlight,
+                CacheEntry::Completed { expires_at } => {
+                    if *expires_at > Instant::now() {
+                        Lookup::NotReplayable
+                    } else {
+                        occupied.insert(CacheEntry::InFlight);
+                        Lookup::Miss(PendingKey::new(self.clone(), key))
+                    }
+                }
+                CacheEntry::Done {
+                    status,
+                    headers,
+                    body,
+                    expires_at,
+                } => {
+                    if *expires_at > Instant::now() {
+                        Lookup::Hit(build_response(*status, headers.clone(), body.clone(), true))
+                    } else {
+                        occupied.insert(CacheEntry::InFlight);
+                        Lookup::Miss(PendingKey::new(self.clone(), key))
+                    }
+                }
+            },
+            Entry::Vacant(vacant) => {
+                vacant.insert(CacheEntry::InFlight);
+                Lookup::Miss(PendingKey::new(self.clone(), key))
+            }
+        }
+    }
+
+    /// 清理过期缓存
+    pub fn cleanup_expired(&self) {
+        let now = Instant::now();
+        self.entries.retain(|_, entry| match entry {
+            CacheEntry::InFlight => true,
+            CacheEntry::Done { expires_at, .. } | CacheEntry::Completed { expires_at } => {
+                *expires_at > now
+            }
+        });
+    }
+}
+
+/// 正在处理的幂等键，未完成就被丢弃（如客户端断开）时自动释放
+pub struct PendingKey {
+    cache: IdempotencyCache,
+    key: Option<String>,
+}
+
+impl PendingKey {
+    fn new(cache: IdempotencyCache, key: String) -> Self {
+        Self {
+            cache,
+            key: Some(key),
+        }
+    }
+
+    /// 缓存上游响应；5xx 与转发失败不缓存，允许客户端重试。
+    /// 响应体超过上限时原样转发，只记录已处理，重试返回 422 而不是再次转发
+    pub async fn complete(
+        mut self,
+        result: Result<Response, StatusCode>,
+        ttl: Duration,
+    ) -> Result<Response, StatusCode> {
+        let resp = match result {
+            Ok(resp) if !resp.status().is_server_error() => resp,
+            other => return other,
+        };
+
+        let (parts, body) = resp.into_parts();
+        let mut stream = body.into_data_stream();
+        let mut chunks = Vec::new();
+        let mut size = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    tracing::error!("Failed to buffer idempotent response: {}", e);
+                    self.mark_completed(ttl);
+                    return Err(StatusCode::BAD_GATEWAY);
+                }
+            };
+            size += chunk.len();
+            chunks.push(chunk);
+            if size > MAX_CACHED_BODY {
+                tracing::debug!("Idempotent response too large to cache, streaming through");
+                self.mark_completed(ttl);
+                let body = futures::stream::iter(chunks.into_iter().map(Ok)).chain(stream);
+                return Ok(Response::from_parts(parts, Body::from_stream(body)));
+            }
+        }
+        let body = Bytes::from(chunks.concat());
+
+        if let Some(key) = self.key.take() {
+            self.cache.entries.insert(
+                key,
+                CacheEntry::Done {
+                    status: parts.status,
+                    headers: parts.headers.clone(),
+                    body: body.clone(),
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+
+        Ok(build_response(parts.status, parts.headers, body, false))
+    }
+}
+
+impl PendingKey {
+    fn mark_completed(&mut self, ttl: Duration) {
+        if let Some(key) = self.key.take() {
+            self.cache.entries.insert(
+                key,
+                CacheEntry::Completed {
+                    expires_at: Instant::now() + ttl,
+                },
+            );
+        }
+    }
+}
+
+impl Drop for PendingKey {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.cache
+                .entries
+                .remove_if(&key, |_, entry| matches!(entry, CacheEntry::InFlight));
+        }
+    }
+}
+
+fn build_response(status: StatusCode, headers: HeaderMap, body: Bytes, replayed: bool) -> Response {
+    let mut resp = Response::new(Body::from(body));
+    *resp.status_mut() = status;
+    *resp.headers_mut() = headers;
+    if replayed {
+        resp.headers_mut()
+            .insert("idempotent-replayed", HeaderValue::from_static("true"));
+    }
+    resp
+}
@@ -0,0 +1,134 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue},
+    Json,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use ring::digest;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::auth::Session;
+use crate::secrets::Secrets;
+use crate::AdminState;
+
+/// 旧版本保存签名私钥的 system_config 键名，启动时迁移到加密密钥存储
+const LEGACY_SIGNING_KEY: &str = "identity_signing_key";
+
+/// 身份断言签名私钥（PKCS#8）在加密密钥存储中的名称
+const SIGNING_KEY_NAME: &str = "system.identity_signing_key";
+
+/// 断言签发方
+const ISSUER: &str = "rust-proxy";
+
+/// 断言有效期（秒），每个请求重新签发
+const ASSERTION_TTL_SECS: i64 = 60;
+
+/// 注入的身份请求头，转发前先移除客户端伪造的同名头
+const IDENTITY_HEADERS: [&str; 3] = ["x-auth-user", "x-auth-groups", "x-auth-assertion"];
+
+/// 向上游签发 ES256 身份断言，上游通过 JWKS 公钥校验
+#[derive(Clone)]
+pub struct IdentityAssertions {
+    key_pair: Arc<EcdsaKeyPair>,
+    kid: String,
+}
+
+impl IdentityAssertions {
+    /// 从加密密钥存储加载签名私钥，首次启动时生成
+    pub fn load(secrets: &Secrets) -> anyhow::Result<Self> {
+        let rng = SystemRandom::new();
+        let pkcs8 = secrets.get_or_init(SIGNING_KEY_NAME, LEGACY_SIGNING_KEY, || {
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                .map_err(|_| anyhow::anyhow!("Failed to generate identity signing key"))?;
+            tracing::info!("Generated identity assertion signing key");
+            Ok(URL_SAFE_NO_PAD.encode(pkcs8.as_ref()))
+        })?;
+        let pkcs8 = URL_SAFE_NO_PAD.decode(pkcs8)?;
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, &rng)
+            .map_err(|e| anyhow::anyhow!("Invalid identity signing key: {}", e))?;
+        let kid = URL_SAFE_NO_PAD.encode(
+            &digest::digest(&digest::SHA256, key_pair.public_key().as_ref()).as_ref()[..12],
+        );
+        Ok(Self {
+            key_pair: Arc::new(key_pair),
+            kid,
+        })
+    }
+
+    /// JWK 格式的公钥，未压缩点格式为 0x04 || x || y
+    fn jwk(&self) -> Value {
+        let point = self.key_pair.public_key().as_ref();
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "alg": "ES256",
+            "use": "sig",
+            "kid": self.kid,
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        })
+    }
+
+    /// 写入 X-Auth-User、X-Auth-Groups 与签名断言 X-Auth-Assertion，受众为规则名
+    pub fn apply(&self, headers: &mut HeaderMap, session: &Session, audience: &str) {
+        strip_identity_headers(headers);
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "iss": ISSUER,
+            "aud": audience,
+            "sub": session.username,
+            "groups": session.groups,
+            "role": session.role,
+            "iat": now,
+            "exp": now + ASSERTION_TTL_SECS,
+        });
+        let header = json!({ "alg": "ES256", "typ": "JWT", "kid": self.kid });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = match self
+            .key_pair
+            .sign(&SystemRandom::new(), signing_input.as_bytes())
+        {
+            Ok(signature) => signature,
+            Err(_) => {
+                tracing::error!("Failed to sign identity assertion");
+                return;
+            }
+        };
+        let assertion = format!(
+            "{}.{}",
+            signing_input,
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        );
+
+        // 用户名与用户组可能包含非 ASCII 字符，无法写入请求头时只保留断言
+        let values = [
+            session.username.clone(),
+            session.groups.join(","),
+            assertion,
+        ];
+        for (name, value) in IDENTITY_HEADERS.iter().zip(values) {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(*name, value);
+            }
+        }
+    }
+}
+
+fn strip_identity_headers(headers: &mut HeaderMap) {
+    for name in IDENTITY_HEADERS {
+        headers.remove(name);
+    }
+}
+
+/// 身份断言公钥（JWKS），供上游应用校验 X-Auth-Assertion
+pub async fn jwks_handler(State(state): State<AdminState>) -> Json<Value> {
+    Json(json!({ "keys": [state.identity.jwk()] }))
+}
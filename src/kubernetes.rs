@@ -0,0 +1,244 @@
+use anyhow::Context;
+use reqwest::{Certificate, Client};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::KubernetesConfig;
+
+/// Pod 内的服务账号目录
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+/// 规则目标中的服务：`命名空间/服务:端口`，端口为服务端口号或端口名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KubeTarget {
+    pub namespace: String,
+    pub service: String,
+    pub port: String,
+}
+
+impl KubeTarget {
+    pub fn parse(name: &str) -> Option<Self> {
+        let (namespace, service) = name.split_once('/')?;
+        let (service, port) = service.split_once(':')?;
+        if namespace.is_empty() || service.is_empty() || port.is_empty() {
+            return None;
+        }
+        Some(Self {
+            namespace: namespace.to_string(),
+            service: service.to_string(),
+            port: port.to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct Endpoints {
+    metadata: ObjectMeta,
+    #[serde(default)]
+    subsets: Vec<EndpointSubset>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectMeta {
+    #[serde(default)]
+    resource_version: String,
+}
+
+/// 只使用就绪的地址，notReadyAddresses 忽略
+#[derive(Deserialize)]
+struct EndpointSubset {
+    #[serde(default)]
+    addresses: Vec<EndpointAddress>,
+    #[serde(default)]
+    ports: Vec<EndpointPort>,
+}
+
+#[derive(Deserialize)]
+struct EndpointAddress {
+    ip: String,
+}
+
+#[derive(Deserialize)]
+struct EndpointPort {
+    #[serde(default)]
+    name: String,
+    port: u16,
+}
+
+#[derive(Deserialize)]
+struct Service {
+    spec: ServiceSpec,
+}
+
+#[derive(Deserialize)]
+struct ServiceSpec {
+    #[serde(default)]
+    ports: Vec<ServicePort>,
+}
+
+#[derive(Deserialize)]
+struct ServicePort {
+    #[serde(default)]
+    name: String,
+    port: u16,
+}
+
+/// 访问 Kubernetes API 的最小客户端，只读取 Endpoints 与 Service
+pub struct KubeClient {
+    api_server: String,
+    client: Client,
+    /// 服务账号令牌会定期轮换，每次请求时重新读取
+    token_file: Option<String>,
+}
+
+impl KubeClient {
+    /// 未开启时返回 None；未指定 API Server 时使用 Pod 内的集群配置
+    pub fn from_config(config: &KubernetesConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let token_file = format!("{}/token", SERVICE_ACCOUNT_DIR);
+        let token_file = Path::new(&token_file).exists().then_some(token_file);
+        let mut builder = Client::builder().connect_timeout(Duration::from_secs(10));
+        let api_server = match config.api_server {
+            Some(ref server) => server.trim_end_matches('/').to_string(),
+            None => {
+                let host = std::env::var("KUBERNETES_SERVICE_HOST")
+                    .context("KUBERNETES_SERVICE_HOST is not set, not running in a cluster")?;
+                let port =
+                    std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".into());
+                let ca = std::fs::read(format!("{}/ca.crt", SERVICE_ACCOUNT_DIR))
+                    .context("failed to read the service account CA certificate")?;
+                builder = builder.add_root_certificate(Certificate::from_pem(&ca)?);
+                if host.contains(':') {
+                    format!("https://[{}]:{}", host, port)
+                } else {
+                    format!("https://{}:{}", host, port)
+                }
+            }
+        };
+        tracing::info!(api_server = %api_server, "Kubernetes service discovery enabled");
+        Ok(Some(Self {
+            api_server,
+            client: builder.build()?,
+            token_file,
+        }))
+    }
+
+    fn get(&self, path: &str) -> anyhow::Result<reqwest::RequestBuilder> {
+        let mut request = self.client.get(format!("{}{}", self.api_server, path));
+        if let Some(ref file) = self.token_file {
+            let token = std::fs::read_to_string(file)?;
+            request = request.bearer_auth(token.trim());
+        }
+        Ok(request)
+    }
+
+    /// 就绪 Pod 的 ip:port 与 Endpoints 的 resourceVersion
+    pub async fn endpoints(&self, target: &KubeTarget) -> anyhow::Result<(Vec<String>, String)> {
+        let endpoints: Endpoints = self
+            .get(&format!(
+                "/api/v1/namespaces/{}/endpoints/{}",
+                target.namespace, target.service
+            ))?
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let port_name = self.port_name(target).await;
+
+        let mut instances = Vec::new();
+        for subset in &endpoints.subsets {
+            let port = subset.ports.iter().find(|port| match port_name {
+                Some(ref name) => &port.name == name,
+                None => port.port.to_string() == target.port,
+            });
+            // 单端口服务的端口可以不命名
+            let port = port.or_else(|| subset.ports.first().filter(|_| subset.ports.len() == 1));
+            let Some(port) = port else { continue };
+            for address in &subset.addresses {
+                instances.push(if address.ip.contains(':') {
+                    format!("[{}]:{}", address.ip, port.port)
+                } else {
+                    format!("{}:{}", address.ip, port.port)
+                });
+            }
+        }
+        instances.sort();
+        Ok((instances, endpoints.metadata.resource_version))
+    }
+
+    /// 目标端口对应的端口名：端口名原样使用，端口号通过 Service 查找，查不到时按 Pod 端口号匹配
+    async fn port_name(&self, target: &KubeTarget) -> Option<String> {
+        let number: u16 = match target.port.parse() {
+            Ok(number) => number,
+            Err(_) => return Some(target.port.clone()),
+        };
+        let path = format!(
+            "/api/v1/namespaces/{}/services/{}",
+            target.namespace, target.service
+        );
+        let service: Service = async {
+            self.get(&path)?
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Service>()
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        .await
+        .map_err(|e| tracing::debug!(service = %target.service, "Failed to get service: {}", e))
+        .ok()?;
+        service
+            .spec
+            .ports
+            .into_iter()
+            .find(|port| port.port == number)
+            .map(|port| port.name)
+    }
+
+    /// 等待 resourceVersion 之后 Endpoints 的第一个变化，超时或连接断开时返回，由调用方重新读取
+    pub async fn watch(
+        &self,
+        target: &KubeTarget,
+        resource_version: &str,
+        wait: Duration,
+    ) -> anyhow::Result<()> {
+        let watching = async {
+            let mut resp = self
+                .get(&format!(
+                    "/api/v1/namespaces/{}/endpoints",
+                    target.namespace
+                ))?
+                .query(&[
+                    ("watch", "true"),
+                    (
+                        "fieldSelector",
+                        &format!("metadata.name={}", target.service),
+                    ),
+                    ("resourceVersion", resource_version),
+                    ("timeoutSeconds", &wait.as_secs().to_string()),
+                ])
+                .send()
+                .await?
+                .error_for_status()?;
+            // 每个事件为一行 JSON，收到任意事件即返回
+            while let Some(chunk) = resp.chunk().await? {
+                if chunk.contains(&b'\n') {
+                    break;
+                }
+            }
+            anyhow::Ok(())
+        };
+        match tokio::time::timeout(wait + Duration::from_secs(5), watching).await {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,1018 @@
+//! 动态反向代理服务，可作为库嵌入，通过 [`hooks::ProxyHook`] 扩展请求处理
+
+mod access_log;
+mod acl;
+mod acme;
+mod alerts;
+mod api;
+mod auth;
+mod backup;
+mod cache_objects;
+mod cache_store;
+mod changes;
+mod cli;
+mod cluster;
+mod config;
+mod connections;
+mod db;
+mod direct_cache;
+mod discovery;
+mod dns;
+mod dns01;
+mod embedded;
+mod endpoints;
+pub mod engine;
+mod etag;
+mod events;
+mod experiment;
+mod faults;
+mod forward_proxy;
+mod goproxy;
+mod ha;
+pub mod hooks;
+mod idempotency;
+mod identity;
+mod kubernetes;
+mod lifecycle;
+mod listener;
+mod listeners;
+mod logger;
+mod login_limit;
+mod maven;
+mod metrics;
+mod mirror;
+mod npm;
+mod oidc;
+mod packages;
+mod pipeline;
+mod plugins;
+mod pool_stats;
+mod proxy;
+mod proxy_protocol;
+mod pypi;
+mod redact;
+mod registry;
+mod reloads;
+mod rolling;
+mod router;
+mod rule_auth;
+mod rules_file;
+mod s3_store;
+mod scripts;
+mod secrets;
+mod signed_urls;
+mod simulate;
+mod sni;
+mod socks;
+mod static_files;
+mod stats;
+mod target_guard;
+mod tasks;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod tls;
+mod traffic;
+mod upstream_proxy;
+mod upstreams;
+mod webhooks;
+
+use arc_swap::ArcSwap;
+use axum::{
+    http::{header, Extensions, HeaderMap, StatusCode, Version},
+    middleware,
+    routing::{any, delete, get, post, put},
+    Router,
+};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    trace::TraceLayer,
+};
+use tracing_subscriber::{
+    fmt::time::FormatTime, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
+};
+
+use crate::access_log::AccessLogger;
+use crate::acl::AdminAcl;
+use crate::acme::AcmeManager;
+use crate::alerts::{Alerts, MetricSources};
+use crate::auth::AuthState;
+use crate::changes::ChangeControl;
+use crate::cli::Cli;
+use crate::cluster::Cluster;
+use crate::config::{AdminCompressionConfig, Config};
+use crate::connections::ActiveRequests;
+use crate::db::Database;
+use crate::direct_cache::DirectCache;
+use crate::discovery::ServiceDiscovery;
+use crate::dns::UpstreamDns;
+use crate::endpoints::EndpointGuard;
+use crate::events::AdminEvents;
+use crate::faults::FaultSwitch;
+use crate::forward_proxy::ForwardProxy;
+use crate::ha::HaState;
+use crate::hooks::ProxyHooks;
+use crate::idempotency::IdempotencyCache;
+use crate::identity::IdentityAssertions;
+use crate::lifecycle::{DrainTrigger, LifecycleEvent, LifecycleHooks};
+use crate::listeners::ListenerManager;
+use crate::logger::{start_cleanup_task, RollingFileWriter};
+use crate::mirror::CacheQuotas;
+use crate::oidc::OidcClient;
+use crate::plugins::Plugins;
+use crate::pool_stats::ConnectionStats;
+use crate::proxy::{
+    build_direct_client, build_upstream_client, http_client_builder, rule_proxy_handler,
+    ProxyState, RawClients,
+};
+use crate::proxy_protocol::UpstreamConnector;
+use crate::registry::RegistryMirror;
+use crate::reloads::{ReloadHistory, ReloadSummary, RulesRejected};
+use crate::rolling::RollingStats;
+use crate::router::RuleSet;
+use crate::rule_auth::ProxyKeys;
+use crate::rules_file::RulesFile;
+use crate::secrets::Secrets;
+use crate::signed_urls::SignedUrls;
+use crate::socks::SocksServer;
+use crate::stats::RuleStats;
+use crate::target_guard::TargetGuard;
+use crate::tasks::TaskRegistry;
+use crate::tls::CertStore;
+use crate::traffic::TrafficTail;
+use crate::upstream_proxy::UpstreamProxies;
+use crate::upstreams::{UpstreamHealth, UpstreamLimits, UpstreamProbes};
+use crate::webhooks::ChangeWebhooks;
+
+/// 启动时规则加载失败后的重试间隔
+const RULES_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+struct CustomTimer;
+
+impl FormatTime for CustomTimer {
+    fn format_time(&self, w: &mut tracing_subscriber::fmt::format::Writer<'_>) -> std::fmt::Result {
+        write!(w, "{}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"))
+    }
+}
+
+/// 管理界面状态
+#[derive(Clone)]
+pub struct AdminState {
+    pub db: Database,
+    pub rules: Arc<ArcSwap<RuleSet>>,
+    pub direct_proxy_path: Arc<ArcSwap<String>>,
+    pub proxy_port: Arc<AtomicU16>,
+    pub auth: AuthState,
+    pub stats: RuleStats,
+    pub lifecycle: LifecycleHooks,
+    pub tasks: TaskRegistry,
+    pub traffic: TrafficTail,
+    pub rolling: RollingStats,
+    pub reloads: ReloadHistory,
+    pub upstreams: UpstreamHealth,
+    pub signed_urls: SignedUrls,
+    pub changes: ChangeControl,
+    pub ha: HaState,
+    pub oidc: Option<OidcClient>,
+    pub identity: IdentityAssertions,
+    pub proxy_keys: ProxyKeys,
+    pub active: ActiveRequests,
+    pub secrets: Secrets,
+    pub direct_cache: DirectCache,
+    pub rules_ready: Arc<AtomicBool>,
+    pub listeners: ListenerManager,
+    pub drain: DrainTrigger,
+    pub plugins: Plugins,
+    pub faults: FaultSwitch,
+    /// 配置了 rules_file 时的规则文件
+    pub rules_file: Option<RulesFile>,
+    /// 开启集群时的成员与主节点状态
+    pub cluster: Option<Cluster>,
+    pub pool_stats: ConnectionStats,
+    pub upstream_limits: UpstreamLimits,
+    pub discovery: ServiceDiscovery,
+    /// 推送给管理前端的状态变化事件
+    pub events: AdminEvents,
+    /// 规则与系统配置变更的外部通知
+    pub webhooks: ChangeWebhooks,
+    pub alerts: Alerts,
+}
+
+impl AdminState {
+    pub fn reload_rules(&self, trigger: &'static str) -> anyhow::Result<()> {
+        let result = self.load_rules(trigger);
+        if let Some(summary) = self.reloads.recent(1).pop() {
+            self.events
+                .publish("rules_reloaded", serde_json::json!(summary));
+        }
+        result?;
+        // 以数据库为准时生效的规则写回规则文件，重载被拒绝时不写回；由文件触发的重载不再写回
+        if let Some(file) = self.rules_file.as_ref().filter(|_| trigger != "rules_file") {
+            if let Err(e) = file.write_back(self) {
+                tracing::error!("Failed to write rules file: {:#}", e);
+            }
+        }
+        if let Some(cluster) = self.cluster.as_ref().filter(|_| trigger != "cluster_sync") {
+            cluster.changed();
+        }
+        self.lifecycle.emit_background(
+            LifecycleEvent::Reloaded,
+            serde_json::json!({ "rules_count": self.rules.load().len() }),
+        );
+        Ok(())
+    }
+
+    /// 通过管理接口修改系统配置后推送给管理前端并投递配置变更 Webhook，value 为 None 表示不便展示的结构化配置
+    pub fn config_changed(&self, key: &str, value: Option<&str>) {
+        let data = serde_json::json!({ "key": key, "value": value });
+        self.events.publish("config_changed", data.clone());
+        self.webhooks.notify("config.changed", data);
+    }
+
+    fn load_rules(&self, trigger: &'static str) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let mut summary = ReloadSummary::new(trigger);
+
+        let db_rules = match self.db.get_enabled_rules() {
+            Ok(rules) => rules,
+            Err(e) => {
+                summary.duration_ms = start.elapsed().as_millis() as u64;
+                summary.error = Some(e.to_string());
+                self.reloads.record(summary);
+                return Err(e);
+            }
+        };
+
+        // 先完整编译新规则集，全部通过后才替换
+        let (compiled, failures) = reloads::compile(&db_rules);
+        summary.failures = failures;
+        summary.rules_failed = summary.failures.len();
+
+        // 已有生效规则时拒绝替换；首次加载时没有可保留的规则，加载能编译的规则以免代理整体不可用
+        if !summary.failures.is_empty() && self.rules_ready.load(Ordering::Acquire) {
+            let rejected = RulesRejected {
+                failures: summary.failures.clone(),
+            };
+            summary.duration_ms = start.elapsed().as_millis() as u64;
+            summary.error = Some(rejected.to_string());
+            self.reloads.record(summary);
+            return Err(rejected.into());
+        }
+
+        let diff = self.reloads.diff(&db_rules);
+        summary.rules_compiled = compiled.len();
+        summary.added = diff.added;
+        summary.removed = diff.removed;
+        summary.changed = diff.changed;
+
+        self.rules.store(Arc::new(RuleSet::new(compiled)));
+        self.rules_ready.store(true, Ordering::Release);
+        summary.duration_ms = start.elapsed().as_millis() as u64;
+        self.reloads.record(summary);
+        Ok(())
+    }
+}
+
+/// 启动管理界面与代理服务，直到收到停机信号；`hooks` 为嵌入方注册的请求钩子
+pub async fn run(hooks: ProxyHooks) -> anyhow::Result<()> {
+    if std::env::args().any(|arg| arg == "--print-embedded") {
+        return embedded::print_embedded();
+    }
+    if let Some(cli) = Cli::parse(std::env::args().skip(1))? {
+        return cli.run().await;
+    }
+
+    let config = Config::load("config.yaml").expect("Failed to load config.yaml");
+
+    // 日志初始化
+    redact::init(&config.logging.redact);
+    let file_writer =
+        RollingFileWriter::new(&config.logging.directory, config.logging.max_size_bytes)?;
+
+    let tracer_provider = telemetry::init_tracer_provider(&config.telemetry)?;
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::new("info,hyper=warn,reqwest=warn"))
+        .with(tracer_provider.as_ref().map(telemetry::layer))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(file_writer)
+                .with_ansi(false)
+                .with_timer(CustomTimer)
+                .with_target(false)
+                .with_file(true)
+                .with_line_number(true),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stdout)
+                .with_timer(CustomTimer)
+                .with_target(false),
+        )
+        .init();
+
+    tracing::info!("Starting proxy server...");
+
+    let access_log = if config.access_log.enabled {
+        let writer = RollingFileWriter::new(
+            &config.access_log.directory,
+            config.access_log.max_size_bytes,
+        )?;
+        tracing::info!("Access log: {}", config.access_log.directory);
+        Some(AccessLogger::new(writer, config.access_log.format))
+    } else {
+        None
+    };
+    if let Some(ref endpoint) = config.telemetry.otlp_endpoint {
+        tracing::info!("OTLP trace export: {}", endpoint);
+    }
+
+    let tasks = TaskRegistry::new();
+
+    start_cleanup_task(
+        &tasks,
+        config.logging.directory.clone(),
+        config.logging.retention_days,
+    );
+    if access_log.is_some() {
+        logger::start_named_cleanup_task(
+            &tasks,
+            "access_log_cleanup",
+            config.access_log.directory.clone(),
+            config.access_log.retention_days,
+        );
+    }
+
+    // 数据库连接池
+    let db = Database::new(&config.database.path)?;
+    tracing::info!("Database initialized: {}", config.database.path);
+
+    let direct_proxy_path = db
+        .get_config("direct_proxy_path")?
+        .unwrap_or_else(|| "proxy".to_string());
+    let secrets = Secrets::load(&db, &config.secrets, Some(&config.database.path))?;
+    rule_auth::migrate_plaintext(&db, &secrets)?;
+    let signed_urls = SignedUrls::load(&db, &secrets)?;
+
+    let events = AdminEvents::new();
+
+    // 高性能 HTTP 客户端
+    let client = http_client_builder().build()?;
+    let direct_guard = Arc::new(TargetGuard::from_config(&config.direct_proxy)?);
+    let direct_client = build_direct_client(&direct_guard)?;
+    let upstream_dns = UpstreamDns::new(&config.dns)?;
+    let pool_stats = ConnectionStats::new();
+    let discovery = ServiceDiscovery::new(&config.discovery, client.clone())?;
+    let upstream_client = build_upstream_client(&tasks, &upstream_dns, &pool_stats)?;
+
+    if config.proxy.is_empty() {
+        anyhow::bail!("At least one proxy listener must be configured");
+    }
+
+    // TLS 证书：各监听器配置文件中的证书与 ACME 签发的证书，ACME 只能在一个监听器上配置
+    let tls_certs = config
+        .proxy
+        .iter()
+        .map(|listener| {
+            listener
+                .tls
+                .as_ref()
+                .map(CertStore::from_config)
+                .transpose()
+                .map(|certs| certs.map(Arc::new))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut acme_listeners = config
+        .proxy
+        .iter()
+        .zip(&tls_certs)
+        .filter_map(|(listener, certs)| {
+            let acme_config = listener.tls.as_ref()?.acme.clone()?;
+            Some((acme_config, certs.clone()?))
+        });
+    let acme = match acme_listeners.next() {
+        Some((acme_config, certs)) => {
+            if acme_listeners.next().is_some() {
+                anyhow::bail!("ACME can only be configured on one proxy listener");
+            }
+            let manager = AcmeManager::new(
+                acme_config,
+                client.clone(),
+                certs,
+                secrets.clone(),
+                events.clone(),
+            )?;
+            manager.load_saved();
+            manager.start_renewal_task(&tasks);
+            Some(manager)
+        }
+        None => None,
+    };
+
+    // 使用 ArcSwap 实现无锁读取
+    let rules = Arc::new(ArcSwap::from_pointee(RuleSet::default()));
+    let direct_path = Arc::new(ArcSwap::from_pointee(direct_proxy_path.clone()));
+    let proxy_port = Arc::new(AtomicU16::new(config.proxy[0].port));
+    let rules_ready = Arc::new(AtomicBool::new(false));
+
+    let stats = RuleStats::new();
+    stats::start_flush_task(&tasks, stats.clone(), db.clone());
+    backup::start_snapshot_task(&tasks, db.clone(), &config.database.backup);
+
+    let traffic = TrafficTail::new();
+    let rolling = RollingStats::new();
+    let alerts = Alerts::new(client.clone(), &config.alerts)?;
+    alerts.load_rules(&db)?;
+    let reloads = ReloadHistory::new();
+    let upstreams = UpstreamHealth::new(events.clone());
+    let upstream_probes = UpstreamProbes::new(events.clone());
+    upstreams::start_probe_task(
+        &tasks,
+        upstream_probes.clone(),
+        rules.clone(),
+        client.clone(),
+    );
+    let auth_state = AuthState::new(db.clone(), &config.auth)?;
+    let lifecycle = LifecycleHooks::new(client.clone(), &config.lifecycle);
+    let ha = HaState::new(&config.ha)?;
+    let identity = IdentityAssertions::load(&secrets)?;
+    let proxy_keys = ProxyKeys::load(&db)?;
+    let active = ActiveRequests::new();
+    alerts::start(
+        &alerts,
+        &tasks,
+        &config.alerts,
+        &events,
+        MetricSources {
+            rolling: rolling.clone(),
+            active: active.clone(),
+            upstreams: upstreams.clone(),
+        },
+        tls_certs.iter().flatten().cloned().collect(),
+    );
+    let direct_cache = DirectCache::new(&config.direct_proxy.cache);
+    let plugins = Plugins::load(&config.plugins)?;
+    let faults = FaultSwitch::default();
+    let upstream_limits = UpstreamLimits::default();
+
+    // 关闭信号，各监听器收到后停止接受新连接并等待在途请求完成
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+    let listeners =
+        ListenerManager::new(db.clone(), shutdown_rx.clone(), config.lifecycle.reuse_port);
+    let drain = DrainTrigger::new();
+
+    let admin_state = AdminState {
+        db: db.clone(),
+        rules: rules.clone(),
+        direct_proxy_path: direct_path.clone(),
+        proxy_port: proxy_port.clone(),
+        auth: auth_state.clone(),
+        stats: stats.clone(),
+        lifecycle: lifecycle.clone(),
+        tasks: tasks.clone(),
+        traffic: traffic.clone(),
+        rolling: rolling.clone(),
+        reloads: reloads.clone(),
+        upstreams: upstreams.clone(),
+        signed_urls: signed_urls.clone(),
+        changes: ChangeControl {
+            enabled: config.change_approval.enabled,
+            approval_delay_secs: config.change_approval.approval_delay_secs,
+        },
+        ha: ha.clone(),
+        oidc: config
+            .auth
+            .oidc
+            .as_ref()
+            .map(|oidc| OidcClient::new(oidc, client.clone())),
+        identity: identity.clone(),
+        proxy_keys: proxy_keys.clone(),
+        active: active.clone(),
+        secrets: secrets.clone(),
+        direct_cache: direct_cache.clone(),
+        rules_ready: rules_ready.clone(),
+        listeners: listeners.clone(),
+        drain: drain.clone(),
+        plugins: plugins.clone(),
+        faults: faults.clone(),
+        rules_file: RulesFile::new(&config.rules_file),
+        cluster: Cluster::new(&config.cluster)?,
+        discovery: discovery.clone(),
+        pool_stats,
+        upstream_limits: upstream_limits.clone(),
+        events,
+        webhooks: ChangeWebhooks::new(client.clone(), &config.change_webhooks),
+        alerts,
+    };
+
+    let cache_store = cache_store::build(&config.cache_store, &tasks)?;
+    let proxy_state = ProxyState {
+        client: upstream_client,
+        raw_clients: RawClients::new(&upstream_dns),
+        upstream_dns,
+        proxy_protocol: UpstreamConnector::new()?,
+        upstream_proxies: UpstreamProxies::default(),
+        direct_client,
+        direct_guard: direct_guard.clone(),
+        hooks,
+        rules: rules.clone(),
+        direct_proxy_path: direct_path.clone(),
+        db: db.clone(),
+        default_timeout: Duration::from_secs(config.default_timeout_secs),
+        max_body_bytes: config.max_body_bytes,
+        stats,
+        idempotency: IdempotencyCache::new(),
+        tasks: tasks.clone(),
+        access_log,
+        traffic,
+        rolling,
+        reloads,
+        upstreams,
+        upstream_probes,
+        upstream_limits,
+        discovery,
+        signed_urls,
+        ha: ha.clone(),
+        auth: auth_state.clone(),
+        identity,
+        proxy_keys,
+        active,
+        secrets,
+        registry: RegistryMirror::new(),
+        cache_store: cache_store.clone(),
+        cache_quotas: CacheQuotas::new(),
+        direct_cache,
+        plugins,
+        faults,
+        rules_ready,
+    };
+
+    // 规则文件：先与数据库同步，加载规则后开始监视
+    if let Some(ref file) = admin_state.rules_file {
+        if let Err(e) = file.sync_on_startup(&admin_state) {
+            tracing::error!(
+                "Failed to sync rules file, using rules from the database: {:#}",
+                e
+            );
+        }
+        file.start_watcher(admin_state.clone())?;
+    }
+
+    // 加载规则，失败时代理端口返回 503 并在后台重试，直到首次加载成功
+    if let Err(e) = admin_state.load_rules("startup") {
+        tracing::error!("Failed to load rules, retrying in background: {}", e);
+        let admin = admin_state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RULES_RETRY_INTERVAL).await;
+                match admin.load_rules("startup_retry") {
+                    Ok(()) => {
+                        tracing::info!("Rules loaded, proxy is ready");
+                        break;
+                    }
+                    Err(e) => tracing::warn!("Failed to load rules: {}", e),
+                }
+            }
+        });
+    }
+    ha.start_standby_task(&tasks, client.clone(), admin_state.clone());
+    if let Some(ref cluster) = admin_state.cluster {
+        cluster.start(&tasks, client.clone(), admin_state.clone());
+    }
+
+    // 启动 session 清理任务
+    let auth_cleanup = auth_state.clone();
+    tasks.spawn_periodic("session_cleanup", Duration::from_secs(3600), move || {
+        let result = auth_cleanup.cleanup_expired();
+        async { result }
+    });
+
+    // 启动幂等缓存清理任务
+    let idempotency_cleanup = proxy_state.idempotency.clone();
+    tasks.spawn_periodic("idempotency_cleanup", Duration::from_secs(60), move || {
+        idempotency_cleanup.cleanup_expired();
+        async { Ok(()) }
+    });
+
+    // 管理界面路由 (带压缩)
+    let admin_app = Router::new()
+        .route("/", get(static_files::index_handler))
+        .route("/login", get(static_files::login_page))
+        .route("/api/login", post(auth::login_handler))
+        .route("/api/logout", post(auth::logout_handler))
+        .route("/api/session", get(auth::check_session_handler))
+        .route("/api/password", post(auth::change_password_handler))
+        .route("/api/login/methods", get(auth::login_methods_handler))
+        .route("/api/oidc/login", get(oidc::login_handler))
+        .route("/api/oidc/callback", get(oidc::callback_handler))
+        .route(
+            "/api/login/attempts",
+            get(login_limit::list_attempts_handler),
+        )
+        .route("/api/ratelimits", get(login_limit::list_handler))
+        .route("/api/ratelimits/:key", delete(login_limit::reset_handler))
+        .route(
+            "/api/proxy-keys",
+            get(rule_auth::list_keys).post(rule_auth::create_key),
+        )
+        .route("/api/proxy-keys/:id", delete(rule_auth::delete_key))
+        .route("/api/connections", get(connections::list_handler))
+        .route("/api/connections/:id", delete(connections::kill_handler))
+        .route("/api/secrets", get(secrets::list_handler))
+        .route(
+            "/api/secrets/:name",
+            put(secrets::set_handler).delete(secrets::delete_handler),
+        )
+        .route("/api/rules", get(api::list_rules))
+        .route("/api/rules", post(api::create_rule))
+        .route("/api/rules/export", get(api::export_rules))
+        .route("/api/rules/import", post(api::import_rules))
+        .route("/api/backup", get(backup::backup_handler))
+        .route("/api/restore", post(backup::restore_handler))
+        .route("/api/rules/:id", put(api::update_rule))
+        .route("/api/rules/:id", delete(api::delete_rule))
+        .route("/api/rules/:id/toggle", post(api::toggle_rule))
+        .route("/api/rules/:id/stats", get(api::get_rule_stats))
+        .route("/api/rules/:id/simulate", post(simulate::simulate_handler))
+        .route(
+            "/api/rules/:id/fixtures",
+            get(api::list_fixtures).post(api::create_fixture),
+        )
+        .route(
+            "/api/rules/:id/fixtures/:fixture_id",
+            delete(api::delete_fixture),
+        )
+        .route("/api/configs", get(api::get_configs))
+        .route("/api/configs/:key", put(api::update_config))
+        .route("/api/direct/sign", post(signed_urls::sign_handler))
+        .route("/api/status", get(api::get_proxy_status))
+        .route("/api/status/detail", get(api::get_status_detail))
+        .route("/api/status/connections", get(pool_stats::status_handler))
+        .route("/api/tasks", get(api::list_tasks))
+        .route("/api/reloads", get(api::list_reloads))
+        .route("/api/upstreams", get(upstreams::list_handler))
+        .route("/api/discovery", get(discovery::list_handler))
+        .route("/api/direct-cache", get(direct_cache::status_handler))
+        .merge(
+            Router::new()
+                .route(
+                    "/api/cache/objects",
+                    get(cache_objects::list_handler).post(cache_objects::prewarm_handler),
+                )
+                .route(
+                    "/api/cache/objects/*path",
+                    get(cache_objects::inspect_handler)
+                        .patch(cache_objects::pin_handler)
+                        .delete(cache_objects::purge_handler),
+                )
+                .with_state(proxy_state.clone()),
+        )
+        .route(
+            "/api/faults",
+            get(faults::status_handler).put(faults::update_handler),
+        )
+        .route(
+            "/api/listeners",
+            get(listeners::list_handler).post(listeners::create_handler),
+        )
+        .route(
+            "/api/listeners/:name",
+            put(listeners::update_handler).delete(listeners::delete_handler),
+        )
+        .route("/api/logs/stream", get(traffic::stream_handler))
+        .route("/api/events", get(events::stream_handler))
+        .route(
+            "/api/webhooks/deliveries",
+            get(webhooks::deliveries_handler),
+        )
+        .route("/api/alerts", get(alerts::list_handler))
+        .route(
+            "/api/alerts/rules",
+            get(alerts::rules_handler).post(alerts::create_rule_handler),
+        )
+        .route(
+            "/api/alerts/rules/:id",
+            put(alerts::update_rule_handler).delete(alerts::delete_rule_handler),
+        )
+        .route("/api/alerts/test", post(alerts::test_handler))
+        .route("/api/ha/heartbeat", get(ha::heartbeat_handler))
+        .route("/api/ha/rules", get(ha::rules_handler))
+        .route("/api/ha/state", get(ha::state_handler))
+        .route("/api/ha/status", get(ha::status_handler))
+        .route("/api/cluster", get(cluster::status_handler))
+        .route("/api/cluster/gossip", post(cluster::gossip_handler))
+        .route("/api/cluster/state", get(cluster::state_handler))
+        .route("/api/identity/jwks", get(identity::jwks_handler))
+        .route("/api/changes", get(changes::list_handler))
+        .route("/api/changes/:id/approve", post(changes::approve_handler))
+        .route("/api/changes/:id/reject", post(changes::reject_handler))
+        .route("/api/admin/drain", post(lifecycle::drain_handler))
+        .route("/static/*path", get(static_files::serve_static))
+        .layer(middleware::from_fn_with_state(
+            admin_state.clone(),
+            changes::approval_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            admin_state.clone(),
+            cluster::leader_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            admin_state.clone(),
+            auth::auth_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            Arc::new(AdminAcl::from_config(&config.admin)?),
+            acl::admin_acl_middleware,
+        ))
+        .layer(admin_compression(&config.admin.compression))
+        .layer(TraceLayer::new_for_http())
+        .with_state(admin_state.clone());
+
+    // 代理服务路由 - 使用 fallback 处理所有请求，支持动态路径
+    let endpoint_guard = Arc::new(EndpointGuard::from_config(&config.endpoints)?);
+    let mut builtin = Router::new();
+    let mut builtin_paths = Vec::new();
+    if config.endpoints.health_enabled {
+        let health = config.endpoints.health_path.trim_end_matches('/');
+        let live = format!("{}/live", health);
+        let ready = format!("{}/ready", health);
+        builtin = builtin
+            .route(&config.endpoints.health_path, get(endpoints::ready_handler))
+            .route(&live, get(endpoints::live_handler))
+            .route(&ready, get(endpoints::ready_handler));
+        builtin_paths.extend([config.endpoints.health_path.clone(), live, ready]);
+    }
+    if config.endpoints.metrics_enabled {
+        builtin = builtin.route(
+            &config.endpoints.metrics_path,
+            get(metrics::metrics_handler),
+        );
+        builtin_paths.push(config.endpoints.metrics_path.clone());
+    }
+    if !builtin_paths.is_empty() {
+        builtin = builtin.route_layer(middleware::from_fn_with_state(
+            endpoint_guard,
+            endpoints::guard_middleware,
+        ));
+    }
+
+    // ACME HTTP-01 验证不受内置端点访问控制限制
+    if let Some(ref acme) = acme {
+        if acme.uses_http01() {
+            builtin = builtin.merge(
+                Router::new()
+                    .route(acme::HTTP01_PATH, get(acme::http01_handler))
+                    .with_state(acme.http01_tokens()),
+            );
+        }
+    }
+
+    let mut proxy_app = builtin
+        .fallback(any(rule_proxy_handler))
+        .with_state(proxy_state.clone());
+    if config.endpoints.rules_override && !builtin_paths.is_empty() {
+        proxy_app = proxy_app.layer(middleware::from_fn_with_state(
+            endpoints::RulesOverride {
+                proxy: proxy_state,
+                paths: Arc::new(builtin_paths),
+            },
+            endpoints::rules_override_middleware,
+        ));
+    }
+    if config.direct_proxy.forward_proxy.enabled {
+        proxy_app = proxy_app.layer(middleware::from_fn_with_state(
+            ForwardProxy::new(
+                &config.direct_proxy.forward_proxy,
+                admin_state.proxy_keys.clone(),
+                direct_guard.clone(),
+            ),
+            forward_proxy::middleware,
+        ));
+    }
+
+    let admin_addr = format!("{}:{}", config.admin.host, config.admin.port);
+    let proxy_addrs: Vec<String> = config
+        .proxy
+        .iter()
+        .map(
+            |listener| match (listener.tcp_enabled, &listener.unix_socket) {
+                (false, Some(socket)) => format!("unix:{}", socket),
+                _ => format!("{}:{}", listener.host, listener.port),
+            },
+        )
+        .collect();
+
+    if config.admin.tcp_enabled {
+        tracing::info!("Admin: http://{}", admin_addr);
+    }
+    if let Some(ref socket) = config.admin.unix_socket {
+        tracing::info!("Admin: unix:{}", socket);
+    }
+    if !config.admin.tcp_enabled && config.admin.unix_socket.is_none() {
+        tracing::warn!("Admin interface has no listener configured");
+    }
+    for listener in &config.proxy {
+        let scheme = if listener.tls.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+        if listener.tcp_enabled {
+            tracing::info!(
+                rule_tags = ?listener.rule_tags,
+                direct_proxy = listener.direct_proxy,
+                "Proxy: {}://{}:{}",
+                scheme,
+                listener.host,
+                listener.port
+            );
+        }
+        if let Some(ref socket) = listener.unix_socket {
+            tracing::info!(
+                rule_tags = ?listener.rule_tags,
+                direct_proxy = listener.direct_proxy,
+                "Proxy: unix:{}",
+                socket
+            );
+        }
+    }
+    if config.socks.enabled {
+        tracing::info!(
+            require_auth = config.socks.require_auth,
+            "SOCKS5: {}:{}",
+            config.socks.host,
+            config.socks.port
+        );
+    }
+    tracing::info!(
+        "Direct proxy path from DB: '{}', use: /{}/https://...",
+        direct_proxy_path,
+        direct_proxy_path
+    );
+
+    let admin_listener = if config.admin.tcp_enabled {
+        Some(listener::bind_tcp(&admin_addr, config.lifecycle.reuse_port).await?)
+    } else {
+        None
+    };
+    // 管理界面可同时监听 TCP 与 Unix 套接字，未启用的监听器直接返回
+    let admin_tcp = {
+        let app = admin_app.clone();
+        let shutdown = lifecycle::wait_shutdown(shutdown_rx.clone());
+        async move {
+            match admin_listener {
+                Some(listener) => axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown)
+                .await
+                .map_err(anyhow::Error::from),
+                None => Ok(()),
+            }
+        }
+    };
+    let admin_unix = {
+        let shutdown = lifecycle::wait_shutdown(shutdown_rx.clone());
+        let unix_socket = config.admin.unix_socket.clone();
+        let unix_socket_mode = config.admin.unix_socket_mode.clone();
+        async move {
+            match unix_socket {
+                Some(path) => {
+                    listener::serve_unix(&path, unix_socket_mode.as_deref(), admin_app, shutdown)
+                        .await
+                }
+                None => Ok(()),
+            }
+        }
+    };
+
+    // 配置文件中的监听器与管理接口添加的监听器
+    let acceptors = config
+        .proxy
+        .iter()
+        .zip(tls_certs)
+        .map(|(listener, certs)| match (listener.tls.as_ref(), certs) {
+            (Some(tls_config), Some(certs)) => tls::build_acceptor(tls_config, certs).map(Some),
+            _ => Ok(None),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    listeners.start(proxy_app, &config.proxy, acceptors).await?;
+    let proxy_server = async move {
+        listeners.wait().await;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let socks_listener = if config.socks.enabled {
+        let addr = format!("{}:{}", config.socks.host, config.socks.port);
+        Some(listener::bind_tcp(&addr, config.lifecycle.reuse_port).await?)
+    } else {
+        None
+    };
+    let socks_server = {
+        let server = SocksServer::new(&config.socks, admin_state.proxy_keys.clone(), direct_guard);
+        let shutdown = lifecycle::wait_shutdown(shutdown_rx.clone());
+        async move {
+            match socks_listener {
+                Some(listener) => server.serve(listener, shutdown).await,
+                None => Ok(()),
+            }
+        }
+    };
+
+    let servers =
+        async move { tokio::try_join!(admin_tcp, admin_unix, proxy_server, socks_server) };
+    tokio::pin!(servers);
+
+    lifecycle
+        .emit(
+            LifecycleEvent::Started,
+            serde_json::json!({
+                "admin": admin_addr,
+                "proxy": proxy_addrs[0],
+                "proxy_listeners": proxy_addrs,
+                "rules_count": rules.load().len(),
+            }),
+        )
+        .await;
+
+    let shutdown = async {
+        tokio::select! {
+            _ = lifecycle::shutdown_signal() => {
+                tracing::info!("Shutdown signal received, draining connections...");
+            }
+            _ = drain.wait() => {
+                tracing::info!("Drain requested, draining connections...");
+            }
+        }
+    };
+
+    tokio::select! {
+        r = &mut servers => { r?; }
+        _ = shutdown => {
+            lifecycle.emit(LifecycleEvent::Draining, serde_json::json!({})).await;
+            let _ = shutdown_tx.send(true);
+
+            let drain = Duration::from_secs(config.lifecycle.drain_timeout_secs);
+            match tokio::time::timeout(drain, &mut servers).await {
+                Ok(r) => { r?; }
+                Err(_) => tracing::warn!("Drain timeout after {:?}, forcing shutdown", drain),
+            }
+        }
+    }
+
+    if let Err(e) = admin_state.stats.flush(&admin_state.db) {
+        tracing::error!("Failed to flush rule stats: {}", e);
+    }
+    if let Err(e) = cache_store.flush() {
+        tracing::error!("Failed to save cache index: {}", e);
+    }
+    lifecycle
+        .emit(LifecycleEvent::Stopped, serde_json::json!({}))
+        .await;
+    tracing::info!("Proxy server stopped");
+
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// 管理接口压缩层：按大小阈值与内容类型决定是否压缩
+fn admin_compression(config: &AdminCompressionConfig) -> CompressionLayer<impl Predicate> {
+    let enabled = config.enabled;
+    let excluded: Arc<[String]> = config
+        .exclude_content_types
+        .iter()
+        .filter(|prefix| !prefix.is_empty())
+        .map(|prefix| prefix.to_ascii_lowercase())
+        .collect();
+    let allowed = move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        enabled
+            && !excluded
+                .iter()
+                .any(|prefix| content_type.starts_with(prefix))
+    };
+    CompressionLayer::new().zstd(config.zstd).compress_when(
+        SizeAbove::new(config.min_size_bytes)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE)
+            .and(allowed),
+    )
+}
@@ -0,0 +1,189 @@
+use axum::{extract::State, http::StatusCode, Json};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+
+use crate::api::ApiResponse;
+use crate::config::{LifecycleConfig, WebhookConfig};
+use crate::AdminState;
+
+/// 生命周期事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Started,
+    Reloaded,
+    Draining,
+    Stopped,
+}
+
+impl LifecycleEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Started => "on_started",
+            Self::Reloaded => "on_reloaded",
+            Self::Draining => "on_draining",
+            Self::Stopped => "on_stopped",
+        }
+    }
+}
+
+/// 生命周期 Webhook 通知，供外部系统（负载均衡注册、服务发现等）感知进程状态
+#[derive(Clone)]
+pub struct LifecycleHooks {
+    client: Client,
+    webhooks: Arc<Vec<WebhookConfig>>,
+    timeout: Duration,
+}
+
+impl LifecycleHooks {
+    pub fn new(client: Client, config: &LifecycleConfig) -> Self {
+        Self {
+            client,
+            webhooks: Arc::new(config.webhooks.clone()),
+            timeout: Duration::from_secs(config.timeout_secs),
+        }
+    }
+
+    /// 发送事件并等待所有 Webhook 完成（失败只记录日志）
+    pub async fn emit(&self, event: LifecycleEvent, detail: Value) {
+        let payload = json!({
+            "event": event.as_str(),
+            "timestamp": chrono::Local::now().to_rfc3339(),
+            "pid": std::process::id(),
+            "detail": detail,
+        });
+
+        let sends = self
+            .webhooks
+            .iter()
+            .filter(|hook| hook.events.is_empty() || hook.events.iter().any(|e| e == event.as_str()))
+            .map(|hook| {
+                let req = self
+                    .client
+                    .post(&hook.url)
+                    .timeout(self.timeout)
+                    .json(&payload);
+                async move {
+                    match req.send().await {
+                        Ok(resp) if resp.status().is_success() => {
+                            tracing::debug!(url = %hook.url, event = event.as_str(), "Lifecycle hook sent");
+                        }
+                        Ok(resp) => {
+                            tracing::warn!(url = %hook.url, event = event.as_str(), status = %resp.status(), "Lifecycle hook rejected");
+                        }
+                        Err(e) => {
+                            tracing::warn!(url = %hook.url, event = event.as_str(), error = %e, "Lifecycle hook failed");
+                        }
+                    }
+                }
+            });
+
+        futures::future::join_all(sends).await;
+    }
+
+    /// 后台发送事件，不阻塞调用方
+    pub fn emit_background(&self, event: LifecycleEvent, detail: Value) {
+        if self.webhooks.is_empty() {
+            return;
+        }
+        let hooks = self.clone();
+        tokio::spawn(async move { hooks.emit(event, detail).await });
+    }
+}
+
+/// 等待 SIGINT / SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// 等待关闭通知，用于各监听器的优雅退出
+pub async fn wait_shutdown(mut rx: watch::Receiver<bool>) {
+    let _ = rx.wait_for(|stopped| *stopped).await;
+}
+
+/// 管理接口触发的排空，与收到 SIGTERM 走同一停机流程，用于新旧实例交接
+#[derive(Clone, Default)]
+pub struct DrainTrigger {
+    requested: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl DrainTrigger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求排空，已请求过时返回 false
+    pub fn trigger(&self) -> bool {
+        if self.requested.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        self.notify.notify_one();
+        true
+    }
+
+    /// 等待排空请求
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DrainRequest {
+    /// 只排空指定进程；开启 `reuse_port` 时管理端口由新旧实例共享，请求可能落到任一实例
+    pub pid: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DrainStatus {
+    pub pid: u32,
+    /// 排空开始时进行中的代理请求数
+    pub in_flight: usize,
+    /// 此前已开始排空
+    pub already_draining: bool,
+}
+
+/// 停止接受新连接并等待在途请求完成后退出；`pid` 与本进程不符时返回 409，调用方重试即可
+pub async fn drain_handler(
+    State(state): State<AdminState>,
+    body: Option<Json<DrainRequest>>,
+) -> Result<Json<ApiResponse<DrainStatus>>, StatusCode> {
+    let pid = std::process::id();
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    if req.pid.is_some_and(|expected| expected != pid) {
+        return Err(StatusCode::CONFLICT);
+    }
+    let already_draining = !state.drain.trigger();
+    if !already_draining {
+        tracing::warn!(pid, "Drain requested via admin API");
+    }
+    Ok(Json(ApiResponse::ok(DrainStatus {
+        pid,
+        in_flight: state.active.snapshot().len(),
+        already_draining,
+    })))
+}
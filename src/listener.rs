@@ -0,0 +1,437 @@
+use axum::{
+    extract::{ConnectInfo, Request},
+    Router,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+
+use crate::proxy_protocol;
+use crate::sni::{self, Peeked, SniRouter};
+use crate::tls::ClientCert;
+
+/// 代理端口的连接构建器：记录 HTTP/1 请求头的原始大小写，供 `preserve_header_case` 规则原样转发
+fn proxy_builder() -> auto::Builder<TokioExecutor> {
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder.http1().preserve_header_case(true);
+    builder
+}
+
+/// 开启 PROXY 协议时读取连接开头的协议头并替换客户端地址，协议头无效时关闭连接
+async fn accept_proxy_protocol(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    enabled: bool,
+) -> Option<(TcpStream, SocketAddr)> {
+    if !enabled {
+        return Some((stream, addr));
+    }
+    match proxy_protocol::read_header(&mut stream, addr).await {
+        Ok(client) => Some((stream, client)),
+        Err(e) => {
+            tracing::debug!("PROXY protocol header rejected from {}: {}", addr, e);
+            None
+        }
+    }
+}
+
+/// 配置了 SNI 透传时查看 ClientHello：转发到后端的连接在此处理完毕，返回 None；
+/// 其余连接交给监听器处理，`terminates_tls` 为 false 时未匹配后端的 TLS 连接直接关闭
+async fn accept_sni_passthrough(
+    stream: TcpStream,
+    addr: SocketAddr,
+    sni: Option<&SniRouter>,
+    terminates_tls: bool,
+) -> Option<TcpStream> {
+    let Some(sni) = sni else {
+        return Some(stream);
+    };
+    let server_name = match sni::peek_client_hello(&stream).await {
+        Ok(Peeked::NotTls) => return Some(stream),
+        Ok(Peeked::Tls(server_name)) => server_name,
+        Err(e) => {
+            tracing::debug!("SNI peek failed from {}: {}", addr, e);
+            return None;
+        }
+    };
+    match sni.backend(server_name.as_deref()) {
+        Some(backend) => {
+            tracing::debug!(server_name = ?server_name, backend = %backend, client_ip = %addr.ip(), "SNI passthrough");
+            if let Err(e) = sni.forward(stream, addr, backend).await {
+                tracing::debug!(backend = %backend, "SNI passthrough error: {}", e);
+            }
+            None
+        }
+        None if terminates_tls => Some(stream),
+        None => {
+            tracing::debug!(server_name = ?server_name, "No SNI passthrough route from {}", addr);
+            None
+        }
+    }
+}
+
+/// 绑定 TCP 监听地址；`reuse_port` 开启时设置 SO_REUSEPORT，新旧实例可同时监听同一端口，
+/// 由内核在两者之间分配新连接，旧实例排空退出后全部交给新实例（仅 Unix 平台支持）
+pub async fn bind_tcp(addr: &str, reuse_port: bool) -> std::io::Result<TcpListener> {
+    if !reuse_port {
+        return TcpListener::bind(addr).await;
+    }
+    let mut last_err = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        match bind_reuse_port(addr) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+fn bind_reuse_port(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    #[cfg(unix)]
+    {
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+/// 在 TCP 监听器上提供 HTTP 服务
+///
+/// 每个请求附带 `ConnectInfo<SocketAddr>`（`proxy_protocol` 开启时为 PROXY 协议头中的客户端地址）；
+/// 配置 `sni` 时 TLS 连接按服务器名称透传到后端；收到关闭信号后停止接受新连接，并等待已有连接处理完在途请求
+pub async fn serve_tcp(
+    listener: TcpListener,
+    app: Router,
+    proxy_protocol: bool,
+    sni: Option<Arc<SniRouter>>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let graceful = GracefulShutdown::new();
+
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, addr) = tokio::select! {
+            r = listener.accept() => match r {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let app = app.clone();
+        let sni = sni.clone();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let Some((stream, addr)) = accept_proxy_protocol(stream, addr, proxy_protocol).await
+            else {
+                return;
+            };
+            let Some(stream) = accept_sni_passthrough(stream, addr, sni.as_deref(), false).await
+            else {
+                return;
+            };
+            let service = hyper::service::service_fn(move |mut req: Request<Incoming>| {
+                req.extensions_mut().insert(ConnectInfo(addr));
+                app.clone().call(req)
+            });
+
+            let conn = proxy_builder()
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .into_owned();
+            if let Err(e) = watcher.watch(conn).await {
+                tracing::debug!("Connection error: {}", e);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// 在 TCP 监听器上提供 HTTPS 服务
+///
+/// 每个请求附带 `ConnectInfo<SocketAddr>`，客户端出示证书时附带 `ClientCert`；
+/// PROXY 协议头与 SNI 透传在 TLS 握手之前处理；收到关闭信号后停止接受新连接，并等待已有连接处理完在途请求
+pub async fn serve_tls(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+    proxy_protocol: bool,
+    sni: Option<Arc<SniRouter>>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let graceful = GracefulShutdown::new();
+
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, addr) = tokio::select! {
+            r = listener.accept() => match r {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let sni = sni.clone();
+        let watcher = graceful.watcher();
+
+        tokio::spawn(async move {
+            let Some((stream, addr)) = accept_proxy_protocol(stream, addr, proxy_protocol).await
+            else {
+                return;
+            };
+            let Some(stream) = accept_sni_passthrough(stream, addr, sni.as_deref(), true).await
+            else {
+                return;
+            };
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::debug!("TLS handshake failed from {}: {}", addr, e);
+                    return;
+                }
+            };
+            let client_cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| ClientCert::parse(cert));
+
+            let service = hyper::service::service_fn(move |mut req: Request<Incoming>| {
+                req.extensions_mut().insert(ConnectInfo(addr));
+                if let Some(ref cert) = client_cert {
+                    req.extensions_mut().insert(cert.clone());
+                }
+                app.clone().call(req)
+            });
+
+            let conn = proxy_builder()
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .into_owned();
+            if let Err(e) = watcher.watch(conn).await {
+                tracing::debug!("TLS connection error: {}", e);
+            }
+        });
+    }
+
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// 在 Unix 域套接字上提供 HTTP 服务
+pub async fn serve_unix(
+    path: &str,
+    mode: Option<&str>,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    UnixSocket::bind(path, mode)?.serve(app, shutdown).await
+}
+
+/// 在同目录下权限为 700 的临时目录中绑定并设置权限，再改名到目标路径，
+/// 套接字出现在目标路径之前已是配置的权限，不会有按 umask 默认权限可连接的时间窗口
+#[cfg(unix)]
+fn bind_with_mode(path: &std::path::Path, mode: u32) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let parent = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."));
+    let dir = parent.join(format!(
+        ".sock-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    // 上次异常退出残留的同名目录
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
+    }
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let tmp = dir.join("s");
+    let result = tokio::net::UnixListener::bind(&tmp)
+        .and_then(|listener| {
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(mode))?;
+            std::fs::rename(&tmp, path)?;
+            Ok(listener)
+        })
+        .map_err(anyhow::Error::from);
+    let _ = std::fs::remove_file(&tmp);
+    let _ = std::fs::remove_dir(&dir);
+    result
+}
+
+/// 已绑定的 Unix 域套接字，停止服务时删除套接字文件
+pub struct UnixSocket {
+    path: String,
+    #[cfg(unix)]
+    listener: tokio::net::UnixListener,
+    /// 绑定时套接字文件的 (设备号, inode)，新实例接管同一路径后旧实例不删除其文件
+    #[cfg(unix)]
+    inode: (u64, u64),
+}
+
+impl UnixSocket {
+    /// 绑定前会删除残留的套接字文件，`mode` 为八进制文件权限（如 `660`）
+    #[cfg(unix)]
+    pub fn bind(path: &str, mode: Option<&str>) -> anyhow::Result<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let mode = mode
+            .map(|mode| {
+                u32::from_str_radix(mode, 8)
+                    .map_err(|_| anyhow::anyhow!("Invalid unix socket mode: {}", mode))
+            })
+            .transpose()?;
+        if std::path::Path::new(path).exists() {
+            std::fs::remove_file(path)?;
+        }
+        let listener = match mode {
+            Some(mode) => bind_with_mode(std::path::Path::new(path), mode)?,
+            None => tokio::net::UnixListener::bind(path)?,
+        };
+        let meta = std::fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            listener,
+            inode: (meta.dev(), meta.ino()),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(_path: &str, _mode: Option<&str>) -> anyhow::Result<Self> {
+        anyhow::bail!("Unix domain sockets are not supported on this platform")
+    }
+
+    /// 收到关闭信号后停止接受新连接，并等待已有连接处理完在途请求
+    #[cfg(unix)]
+    pub async fn serve(
+        self,
+        app: Router,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        let graceful = GracefulShutdown::new();
+
+        tokio::pin!(shutdown);
+        let result = loop {
+            let stream = tokio::select! {
+                r = self.listener.accept() => match r {
+                    Ok((stream, _)) => stream,
+                    Err(e) => break Err(e.into()),
+                },
+                _ = &mut shutdown => break Ok(()),
+            };
+            let app = app.clone();
+            let watcher = graceful.watcher();
+
+            tokio::spawn(async move {
+                let service =
+                    hyper::service::service_fn(move |req: Request<Incoming>| app.clone().call(req));
+
+                let conn = proxy_builder()
+                    .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                    .into_owned();
+                if let Err(e) = watcher.watch(conn).await {
+                    tracing::debug!("Unix socket connection error: {}", e);
+                }
+            });
+        };
+
+        self.remove_file();
+        graceful.shutdown().await;
+        result
+    }
+
+    #[cfg(unix)]
+    fn remove_file(&self) {
+        use std::os::unix::fs::MetadataExt;
+
+        match std::fs::metadata(&self.path) {
+            Ok(meta) if (meta.dev(), meta.ino()) == self.inode => {
+                let _ = std::fs::remove_file(&self.path);
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub async fn serve(
+        self,
+        _app: Router,
+        _shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "Unix domain sockets are not supported on this platform: {}",
+            self.path
+        )
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("listener-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn unix_socket_is_created_with_mode() {
+        let dir = test_dir("mode");
+        let path = dir.join("admin.sock");
+        let socket = UnixSocket::bind(path.to_str().unwrap(), Some("600")).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+        // 临时目录已删除，只留下套接字
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        socket.remove_file();
+        assert!(!path.exists());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_mode_is_rejected_before_binding() {
+        let dir = test_dir("invalid");
+        let path = dir.join("admin.sock");
+        assert!(UnixSocket::bind(path.to_str().unwrap(), Some("rw-rw----")).is_err());
+        assert!(!path.exists());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,684 @@
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    Extension, Json, Router,
+};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+
+use crate::api::ApiResponse;
+use crate::config::ProxyConfig;
+use crate::db::Database;
+use crate::lifecycle;
+use crate::listener;
+use crate::proxy::ListenerScope;
+use crate::sni::SniRouter;
+use crate::tls::{self, CertStore};
+use crate::AdminState;
+
+/// system_config 中保存管理接口添加的监听器的键
+pub const LISTENERS_KEY: &str = "proxy_listeners";
+
+/// 配置文件中的监听器名称前缀，按配置顺序编号
+const CONFIG_PREFIX: &str = "config-";
+
+/// 管理接口添加的监听器定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListenerDef {
+    pub name: String,
+    #[serde(flatten)]
+    pub config: ProxyConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerSource {
+    /// 配置文件中的监听器，只能通过 proxy_port 修改第一个监听器的端口
+    Config,
+    /// 管理接口添加的监听器，保存在 system_config 中，启动时重新绑定
+    Api,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListenerStatus {
+    pub name: String,
+    pub source: ListenerSource,
+    pub tcp_enabled: bool,
+    pub host: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<String>,
+    pub tls: bool,
+    /// 配置了 SNI 透传路由
+    pub sni_passthrough: bool,
+    pub rule_tags: Vec<String>,
+    pub direct_proxy: bool,
+    pub running: bool,
+    /// 启动时绑定失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum ListenerError {
+    NotFound,
+    /// 名称已存在
+    Conflict,
+    /// 配置文件中的监听器不能通过管理接口修改
+    ReadOnly,
+    Invalid(anyhow::Error),
+    Bind(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl ListenerError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict | Self::Bind(_) => StatusCode::CONFLICT,
+            Self::ReadOnly => StatusCode::FORBIDDEN,
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for ListenerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "listener not found"),
+            Self::Conflict => write!(f, "listener already exists"),
+            Self::ReadOnly => write!(f, "listener is defined in the config file"),
+            Self::Invalid(e) => write!(f, "invalid listener: {}", e),
+            Self::Bind(e) => write!(f, "failed to bind: {}", e),
+            Self::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// 代理监听器：配置文件中的监听器与管理接口添加的监听器，运行时绑定与停止
+#[derive(Clone)]
+pub struct ListenerManager {
+    inner: Arc<ManagerInner>,
+}
+
+struct ManagerInner {
+    db: Database,
+    /// 代理路由，各监听器附加自己的 ListenerScope
+    app: OnceLock<Router>,
+    shutdown: watch::Receiver<bool>,
+    /// TCP 端口设置 SO_REUSEPORT，供新旧实例交接
+    reuse_port: bool,
+    listeners: Mutex<BTreeMap<String, Listener>>,
+}
+
+struct Listener {
+    config: ProxyConfig,
+    source: ListenerSource,
+    scope: ListenerScope,
+    acceptor: Option<TlsAcceptor>,
+    /// TCP 端口上的服务，未启用或绑定失败时为 None
+    tcp: Option<Server>,
+    /// Unix 套接字上的服务，未配置或绑定失败时为 None
+    unix: Option<Server>,
+    error: Option<String>,
+}
+
+impl Listener {
+    fn stop(self) {
+        self.tcp.into_iter().chain(self.unix).for_each(Server::stop);
+    }
+}
+
+/// 运行中的服务任务
+struct Server {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    /// 停止接受新连接，已有连接在后台处理完在途请求
+    fn stop(self) {
+        let _ = self.stop.send(true);
+    }
+}
+
+impl ListenerManager {
+    pub fn new(db: Database, shutdown: watch::Receiver<bool>, reuse_port: bool) -> Self {
+        Self {
+            inner: Arc::new(ManagerInner {
+                db,
+                app: OnceLock::new(),
+                shutdown,
+                reuse_port,
+                listeners: Mutex::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// 绑定配置文件中的监听器（失败时返回错误）与保存的监听器（失败时记录原因，其余继续）
+    pub async fn start(
+        &self,
+        app: Router,
+        configs: &[ProxyConfig],
+        acceptors: Vec<Option<TlsAcceptor>>,
+    ) -> anyhow::Result<()> {
+        let _ = self.inner.app.set(app);
+        let mut listeners = self.inner.listeners.lock().await;
+        for (index, (config, acceptor)) in configs.iter().zip(acceptors).enumerate() {
+            validate(config)?;
+            let scope = ListenerScope::new(config);
+            let (tcp, unix) = self.bind(config, acceptor.clone(), &scope).await?;
+            listeners.insert(
+                format!("{}{}", CONFIG_PREFIX, index),
+                Listener {
+                    config: config.clone(),
+                    source: ListenerSource::Config,
+                    scope,
+                    acceptor,
+                    tcp,
+                    unix,
+                    error: None,
+                },
+            );
+        }
+
+        for def in self.load()? {
+            let scope = ListenerScope::new(&def.config);
+            let mut listener = Listener {
+                config: def.config,
+                source: ListenerSource::Api,
+                scope,
+                acceptor: None,
+                tcp: None,
+                unix: None,
+                error: None,
+            };
+            let bound = match validate(&listener.config)
+                .map_err(ListenerError::Invalid)
+                .and_then(|()| api_acceptor(&listener.config))
+            {
+                Ok(acceptor) => self
+                    .bind(&listener.config, acceptor.clone(), &listener.scope)
+                    .await
+                    .map(|servers| (acceptor, servers))
+                    .map_err(ListenerError::Bind),
+                Err(e) => Err(e),
+            };
+            match bound {
+                Ok((acceptor, (tcp, unix))) => {
+                    listener.acceptor = acceptor;
+                    listener.tcp = tcp;
+                    listener.unix = unix;
+                }
+                Err(e) => {
+                    tracing::error!(name = %def.name, "Failed to start proxy listener: {}", e);
+                    listener.error = Some(e.to_string());
+                }
+            }
+            listeners.insert(def.name, listener);
+        }
+        Ok(())
+    }
+
+    /// 等待关闭信号后等待全部监听器处理完在途请求
+    pub async fn wait(&self) {
+        lifecycle::wait_shutdown(self.inner.shutdown.clone()).await;
+        let tasks: Vec<JoinHandle<()>> = {
+            let mut listeners = self.inner.listeners.lock().await;
+            listeners
+                .values_mut()
+                .flat_map(|listener| listener.tcp.take().into_iter().chain(listener.unix.take()))
+                .map(|server| server.task)
+                .collect()
+        };
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+
+    pub async fn status(&self) -> Vec<ListenerStatus> {
+        let listeners = self.inner.listeners.lock().await;
+        listeners
+            .iter()
+            .map(|(name, listener)| ListenerStatus {
+                name: name.clone(),
+                source: listener.source,
+                tcp_enabled: listener.config.tcp_enabled,
+                host: listener.config.host.clone(),
+                port: listener.config.port,
+                unix_socket: listener.config.unix_socket.clone(),
+                tls: listener.config.tls.is_some(),
+                sni_passthrough: listener.config.sni_passthrough.is_some(),
+                rule_tags: listener.config.rule_tags.clone(),
+                direct_proxy: listener.config.direct_proxy,
+                running: listener.tcp.is_some() || listener.unix.is_some(),
+                error: listener.error.clone(),
+            })
+            .collect()
+    }
+
+    /// 添加并绑定监听器
+    pub async fn add(&self, def: ListenerDef) -> Result<(), ListenerError> {
+        if !valid_name(&def.name) {
+            return Err(ListenerError::Invalid(anyhow::anyhow!(
+                "name must be letters, digits, '-' or '_' and not start with '{}'",
+                CONFIG_PREFIX
+            )));
+        }
+        validate(&def.config).map_err(ListenerError::Invalid)?;
+        let mut listeners = self.inner.listeners.lock().await;
+        if listeners.contains_key(&def.name) {
+            return Err(ListenerError::Conflict);
+        }
+        self.check_addr(&listeners, &def.name, &def.config)?;
+        let acceptor = api_acceptor(&def.config)?;
+        let scope = ListenerScope::new(&def.config);
+        let (tcp, unix) = self
+            .bind(&def.config, acceptor.clone(), &scope)
+            .await
+            .map_err(ListenerError::Bind)?;
+        listeners.insert(
+            def.name.clone(),
+            Listener {
+                config: def.config,
+                source: ListenerSource::Api,
+                scope,
+                acceptor,
+                tcp,
+                unix,
+                error: None,
+            },
+        );
+        self.save(&listeners)?;
+        tracing::info!(name = %def.name, "Proxy listener added");
+        Ok(())
+    }
+
+    /// 修改监听器：只修改规则范围时立即生效；地址或套接字变化时先绑定新地址再停止旧地址，
+    /// 同一地址不能修改 TLS、PROXY 协议与 SNI 透传设置、同一套接字不能修改文件权限（需删除后重新添加）
+    pub async fn update(&self, name: &str, config: ProxyConfig) -> Result<(), ListenerError> {
+        validate(&config).map_err(ListenerError::Invalid)?;
+        let mut listeners = self.inner.listeners.lock().await;
+        self.check_addr(&listeners, name, &config)?;
+        let listener = listeners.get_mut(name).ok_or(ListenerError::NotFound)?;
+        if listener.source == ListenerSource::Config {
+            return Err(ListenerError::ReadOnly);
+        }
+
+        let old = &listener.config;
+        let same_addr = old.tcp_enabled == config.tcp_enabled
+            && old.host == config.host
+            && old.port == config.port;
+        let same_tls = serde_json::to_value(&old.tls).ok()
+            == serde_json::to_value(&config.tls).ok()
+            && old.proxy_protocol == config.proxy_protocol
+            && serde_json::to_value(&old.sni_passthrough).ok()
+                == serde_json::to_value(&config.sni_passthrough).ok();
+        let same_socket = old.unix_socket == config.unix_socket;
+        if same_addr && !same_tls && listener.tcp.is_some() {
+            return Err(ListenerError::Invalid(anyhow::anyhow!(
+                "TLS, PROXY protocol or SNI passthrough settings cannot be changed on the same address, remove the listener first"
+            )));
+        }
+        if same_socket && old.unix_socket_mode != config.unix_socket_mode && listener.unix.is_some()
+        {
+            return Err(ListenerError::Invalid(anyhow::anyhow!(
+                "socket mode cannot be changed on the same socket, remove the listener first"
+            )));
+        }
+
+        let rebind_tcp = config.tcp_enabled && !(same_addr && listener.tcp.is_some());
+        let rebind_unix = config.unix_socket.is_some() && !(same_socket && listener.unix.is_some());
+        let acceptor = if rebind_tcp {
+            api_acceptor(&config)?
+        } else {
+            listener.acceptor.clone()
+        };
+        let tcp = match rebind_tcp {
+            true => Some(
+                self.bind_tcp(&config, acceptor.clone(), &listener.scope)
+                    .await
+                    .map_err(ListenerError::Bind)?,
+            ),
+            false => None,
+        };
+        let unix = match rebind_unix {
+            true => match self.bind_unix(&config, &listener.scope) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    tcp.into_iter().for_each(Server::stop);
+                    return Err(ListenerError::Bind(e));
+                }
+            },
+            false => None,
+        };
+
+        // 新绑定的服务替换旧服务，关闭的 TCP 端口或套接字停止旧服务
+        if rebind_tcp || !config.tcp_enabled {
+            if let Some(old) = std::mem::replace(&mut listener.tcp, tcp) {
+                old.stop();
+            }
+        }
+        if rebind_unix || config.unix_socket.is_none() {
+            if let Some(old) = std::mem::replace(&mut listener.unix, unix) {
+                old.stop();
+            }
+        }
+        listener.acceptor = acceptor;
+        listener.error = None;
+        listener.scope.update(&config);
+        listener.config = config;
+        self.save(&listeners)?;
+        tracing::info!(name = %name, "Proxy listener updated");
+        Ok(())
+    }
+
+    /// 停止并删除监听器，已有连接处理完在途请求后关闭
+    pub async fn remove(&self, name: &str) -> Result<(), ListenerError> {
+        let mut listeners = self.inner.listeners.lock().await;
+        match listeners.get(name) {
+            None => return Err(ListenerError::NotFound),
+            Some(listener) if listener.source == ListenerSource::Config => {
+                return Err(ListenerError::ReadOnly)
+            }
+            Some(_) => {}
+        }
+        if let Some(listener) = listeners.remove(name) {
+            listener.stop();
+        }
+        self.save(&listeners)?;
+        tracing::info!(name = %name, "Proxy listener removed");
+        Ok(())
+    }
+
+    /// 第一个配置文件监听器改用新端口：先绑定新端口，成功后停止旧端口
+    pub async fn set_primary_port(&self, port: u16) -> Result<(), ListenerError> {
+        let mut listeners = self.inner.listeners.lock().await;
+        let name = format!("{}0", CONFIG_PREFIX);
+        let listener = listeners.get_mut(&name).ok_or(ListenerError::NotFound)?;
+        if !listener.config.tcp_enabled {
+            return Err(ListenerError::Invalid(anyhow::anyhow!(
+                "the first proxy listener has no TCP port"
+            )));
+        }
+        if listener.config.port == port {
+            return Ok(());
+        }
+        let mut config = listener.config.clone();
+        config.port = port;
+        let server = self
+            .bind_tcp(&config, listener.acceptor.clone(), &listener.scope)
+            .await
+            .map_err(ListenerError::Bind)?;
+        if let Some(old) = listener.tcp.replace(server) {
+            old.stop();
+        }
+        tracing::info!(old = listener.config.port, new = port, "Proxy port changed");
+        listener.config = config;
+        Ok(())
+    }
+
+    /// 绑定 TCP 端口与 Unix 套接字，任一失败时停止已启动的服务
+    async fn bind(
+        &self,
+        config: &ProxyConfig,
+        acceptor: Option<TlsAcceptor>,
+        scope: &ListenerScope,
+    ) -> anyhow::Result<(Option<Server>, Option<Server>)> {
+        let tcp = match config.tcp_enabled {
+            true => Some(self.bind_tcp(config, acceptor, scope).await?),
+            false => None,
+        };
+        let unix = match config.unix_socket {
+            Some(_) => match self.bind_unix(config, scope) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    tcp.into_iter().for_each(Server::stop);
+                    return Err(e);
+                }
+            },
+            None => None,
+        };
+        Ok((tcp, unix))
+    }
+
+    /// 开启 SO_REUSEPORT 后本进程内重复绑定同一端口不会失败，需自行检查其他监听器是否已占用该地址
+    fn check_addr(
+        &self,
+        listeners: &BTreeMap<String, Listener>,
+        name: &str,
+        config: &ProxyConfig,
+    ) -> Result<(), ListenerError> {
+        let taken = self.inner.reuse_port
+            && config.tcp_enabled
+            && listeners.iter().any(|(other, listener)| {
+                other != name
+                    && listener.tcp.is_some()
+                    && listener.config.host == config.host
+                    && listener.config.port == config.port
+            });
+        if taken {
+            return Err(ListenerError::Bind(anyhow::anyhow!(
+                "address already in use: {}:{}",
+                config.host,
+                config.port
+            )));
+        }
+        Ok(())
+    }
+
+    async fn bind_tcp(
+        &self,
+        config: &ProxyConfig,
+        acceptor: Option<TlsAcceptor>,
+        scope: &ListenerScope,
+    ) -> anyhow::Result<Server> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let tcp = listener::bind_tcp(&addr, self.inner.reuse_port).await?;
+        let app = self.app(scope);
+        let proxy_protocol = config.proxy_protocol;
+        let sni = config
+            .sni_passthrough
+            .as_ref()
+            .map(SniRouter::from_config)
+            .transpose()?
+            .map(Arc::new);
+        Ok(self.spawn(addr, move |shutdown| async move {
+            match acceptor {
+                Some(acceptor) => {
+                    listener::serve_tls(tcp, acceptor, app, proxy_protocol, sni, shutdown).await
+                }
+                None => listener::serve_tcp(tcp, app, proxy_protocol, sni, shutdown).await,
+            }
+        }))
+    }
+
+    /// Unix 套接字上的请求没有对端地址，客户端地址记为 127.0.0.1，
+    /// 真实地址由前端通过 X-Forwarded-For 传递
+    fn bind_unix(&self, config: &ProxyConfig, scope: &ListenerScope) -> anyhow::Result<Server> {
+        let path = config.unix_socket.clone().unwrap_or_default();
+        let socket = listener::UnixSocket::bind(&path, config.unix_socket_mode.as_deref())?;
+        let app = self
+            .app(scope)
+            .layer(Extension(ConnectInfo(SocketAddr::from((
+                [127, 0, 0, 1],
+                0,
+            )))));
+        Ok(self.spawn(format!("unix:{}", path), move |shutdown| {
+            socket.serve(app, shutdown)
+        }))
+    }
+
+    fn app(&self, scope: &ListenerScope) -> Router {
+        self.inner
+            .app
+            .get()
+            .cloned()
+            .unwrap_or_default()
+            .layer(Extension(scope.clone()))
+    }
+
+    /// 启动服务任务，关闭信号或单独停止时结束
+    fn spawn<F, Fut>(&self, addr: String, serve: F) -> Server
+    where
+        F: FnOnce(BoxFuture<'static, ()>) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let (stop, mut stopped) = watch::channel(false);
+        let global = lifecycle::wait_shutdown(self.inner.shutdown.clone());
+        let shutdown = async move {
+            tokio::select! {
+                _ = global => {}
+                _ = stopped.wait_for(|stopped| *stopped) => {}
+            }
+        };
+        let serving = serve(Box::pin(shutdown));
+        let task = tokio::spawn(async move {
+            match serving.await {
+                Ok(()) => tracing::info!("Proxy listener stopped: {}", addr),
+                Err(e) => tracing::error!("Proxy listener {} failed: {}", addr, e),
+            }
+        });
+        Server { stop, task }
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<ListenerDef>> {
+        match self.inner.db.get_config(LISTENERS_KEY)? {
+            Some(value) => Ok(serde_json::from_str(&value)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn save(&self, listeners: &BTreeMap<String, Listener>) -> Result<(), ListenerError> {
+        let defs: Vec<ListenerDef> = listeners
+            .iter()
+            .filter(|(_, listener)| listener.source == ListenerSource::Api)
+            .map(|(name, listener)| ListenerDef {
+                name: name.clone(),
+                config: listener.config.clone(),
+            })
+            .collect();
+        let value = serde_json::to_string(&defs).map_err(|e| ListenerError::Internal(e.into()))?;
+        self.inner
+            .db
+            .set_config(LISTENERS_KEY, &value)
+            .map_err(ListenerError::Internal)
+    }
+}
+
+/// 监听器至少需要 TCP 端口或 Unix 套接字之一，SNI 透传的后端需为 host:port
+fn validate(config: &ProxyConfig) -> anyhow::Result<()> {
+    if !config.tcp_enabled && config.unix_socket.is_none() {
+        anyhow::bail!(
+            "proxy listener {}:{} has tcp_enabled: false but no unix_socket",
+            config.host,
+            config.port
+        );
+    }
+    if let Some(ref sni) = config.sni_passthrough {
+        SniRouter::from_config(sni)?;
+    }
+    Ok(())
+}
+
+/// 管理接口添加的监听器使用证书文件，不支持 ACME
+fn api_acceptor(config: &ProxyConfig) -> Result<Option<TlsAcceptor>, ListenerError> {
+    let Some(ref tls_config) = config.tls else {
+        return Ok(None);
+    };
+    if tls_config.acme.is_some() {
+        return Err(ListenerError::Invalid(anyhow::anyhow!(
+            "ACME is only supported on listeners in the config file"
+        )));
+    }
+    let certs = CertStore::from_config(tls_config).map_err(ListenerError::Invalid)?;
+    tls::build_acceptor(tls_config, Arc::new(certs))
+        .map(Some)
+        .map_err(ListenerError::Invalid)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with(CONFIG_PREFIX)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn error_status(e: ListenerError) -> StatusCode {
+    match e {
+        ListenerError::Internal(_) => tracing::error!("Listener operation failed: {}", e),
+        _ => tracing::warn!("Listener operation rejected: {}", e),
+    }
+    e.status()
+}
+
+/// 监听器定义保存在 `listeners` 配置项中，变化时按配置修改通知
+fn listeners_changed(state: &AdminState) {
+    state.config_changed(LISTENERS_KEY, None);
+}
+
+/// 全部代理监听器
+pub async fn list_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<ListenerStatus>>> {
+    Json(ApiResponse::ok(state.listeners.status().await))
+}
+
+/// 添加监听器，名称已存在或地址被占用时返回 409
+pub async fn create_handler(
+    State(state): State<AdminState>,
+    Json(def): Json<ListenerDef>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    state.listeners.add(def).await.map_err(error_status)?;
+    listeners_changed(&state);
+    Ok(Json(ApiResponse::ok(())))
+}
+
+/// 修改管理接口添加的监听器，配置文件中的监听器返回 403
+pub async fn update_handler(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(config): Json<ProxyConfig>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    state
+        .listeners
+        .update(&name, config)
+        .await
+        .map_err(error_status)?;
+    listeners_changed(&state);
+    Ok(Json(ApiResponse::ok(())))
+}
+
+/// 删除管理接口添加的监听器
+pub async fn delete_handler(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    state.listeners.remove(&name).await.map_err(error_status)?;
+    listeners_changed(&state);
+    Ok(Json(ApiResponse::ok(())))
+}
+
+/// 修改 proxy_port 配置时重新绑定第一个监听器
+pub async fn apply_proxy_port(state: &AdminState, value: &str) -> Result<(), StatusCode> {
+    let port: u16 = value.trim().parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    state
+        .listeners
+        .set_primary_port(port)
+        .await
+        .map_err(error_status)?;
+    state
+        .proxy_port
+        .store(port, std::sync::atomic::Ordering::Relaxed);
+    Ok(())
+}
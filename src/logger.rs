@@ -6,6 +6,8 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tracing_subscriber::fmt::MakeWriter;
 
+use crate::tasks::TaskRegistry;
+
 /// 自定义日志写入器，支持按日期和大小滚动切割
 pub struct RollingFileWriter {
     inner: Arc<Mutex<RollingFileWriterInner>>,
@@ -188,12 +190,21 @@ pub async fn cleanup_old_logs(directory: impl AsRef<Path>, retention_days: u32)
 }
 
 /// 启动定时清理任务
-pub fn start_cleanup_task(directory: String, retention_days: u32) {
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(86400));
-        loop {
-            interval.tick().await;
+pub fn start_cleanup_task(tasks: &TaskRegistry, directory: String, retention_days: u32) {
+    start_named_cleanup_task(tasks, "log_cleanup", directory, retention_days);
+}
+
+pub fn start_named_cleanup_task(
+    tasks: &TaskRegistry,
+    name: &'static str,
+    directory: String,
+    retention_days: u32,
+) {
+    tasks.spawn_periodic(name, std::time::Duration::from_secs(86400), move || {
+        let directory = directory.clone();
+        async move {
             cleanup_old_logs(&directory, retention_days).await;
+            Ok(())
         }
     });
 }
@@ -0,0 +1,328 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    Json,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api::ApiResponse;
+use crate::db::LoginAttempt;
+use crate::AdminState;
+
+#[derive(Debug, Clone, Copy)]
+struct FailureWindow {
+    failures: u32,
+    first_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// 登录失败限流：同一 IP 或同一用户名在锁定时长内连续失败达到上限后临时锁定
+#[derive(Clone)]
+pub struct LoginLimiter {
+    windows: Arc<DashMap<String, FailureWindow>>,
+    max_failures: u32,
+    lockout: Duration,
+}
+
+impl LoginLimiter {
+    pub fn new(max_failures: u32, lockout: Duration) -> Self {
+        Self {
+            windows: Arc::new(DashMap::new()),
+            max_failures,
+            lockout,
+        }
+    }
+
+    fn keys(ip: &str, username: &str) -> [String; 2] {
+        [format!("ip:{}", ip), format!("user:{}", username)]
+    }
+
+    /// 处于锁定期时返回剩余锁定时间
+    pub fn check(&self, ip: &str, username: &str) -> Option<Duration> {
+        if self.max_failures == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        Self::keys(ip, username)
+            .iter()
+            .filter_map(|key| self.windows.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .max()
+            .map(|until| until - now)
+    }
+
+    /// 记录一次失败，返回是否因此触发锁定
+    pub fn record_failure(&self, ip: &str, username: &str) -> bool {
+        if self.max_failures == 0 {
+            return false;
+        }
+        let now = Instant::now();
+        let mut locked = false;
+        for key in Self::keys(ip, username) {
+            let mut window = self.windows.entry(key).or_insert(FailureWindow {
+                failures: 0,
+                first_failure: now,
+                locked_until: None,
+            });
+            // 统计窗口与锁定时长相同，窗口过期后重新计数
+            if now.duration_since(window.first_failure) >= self.lockout {
+                *window = FailureWindow {
+                    failures: 0,
+                    first_failure: now,
+                    locked_until: None,
+                };
+            }
+            window.failures += 1;
+            if window.failures >= self.max_failures {
+                window.locked_until = Some(now + self.lockout);
+                locked = true;
+            }
+        }
+        locked
+    }
+
+    /// 本次登录的剩余失败次数，取 IP 与用户名中更严格的一个；未开启限流时返回 None
+    pub fn quota(&self, ip: &str, username: &str) -> Option<Quota> {
+        if self.max_failures == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let quota = Self::keys(ip, username)
+            .iter()
+            .filter_map(|key| self.windows.get(key).map(|w| self.status(key, &w, now)))
+            .min_by_key(|status| (status.remaining, std::cmp::Reverse(status.reset_secs)))
+            .map(|status| Quota {
+                limit: self.max_failures,
+                remaining: status.remaining,
+                reset_secs: status.reset_secs,
+            });
+        Some(quota.unwrap_or(Quota {
+            limit: self.max_failures,
+            remaining: self.max_failures,
+            reset_secs: 0,
+        }))
+    }
+
+    fn status(&self, key: &str, window: &FailureWindow, now: Instant) -> LimitStatus {
+        let expired = now.duration_since(window.first_failure) >= self.lockout;
+        let locked_until = window.locked_until.filter(|until| *until > now);
+        let failures = if expired && locked_until.is_none() {
+            0
+        } else {
+            window.failures
+        };
+        let reset_at = locked_until.unwrap_or(window.first_failure + self.lockout);
+        LimitStatus {
+            key: key.to_string(),
+            failures,
+            remaining: self.max_failures.saturating_sub(failures),
+            locked: locked_until.is_some(),
+            reset_secs: (reset_at.saturating_duration_since(now).as_millis() as u64).div_ceil(1000),
+        }
+    }
+
+    /// 当前所有计数，锁定中的排在前面
+    pub fn snapshot(&self) -> Vec<LimitStatus> {
+        let now = Instant::now();
+        let mut list: Vec<LimitStatus> = self
+            .windows
+            .iter()
+            .map(|entry| self.status(entry.key(), entry.value(), now))
+            .filter(|status| status.failures > 0)
+            .collect();
+        list.sort_by(|a, b| b.locked.cmp(&a.locked).then_with(|| a.key.cmp(&b.key)));
+        list
+    }
+
+    /// 清除指定键的计数与锁定，返回是否存在
+    pub fn reset(&self, key: &str) -> bool {
+        self.windows.remove(key).is_some()
+    }
+
+    pub fn record_success(&self, ip: &str, username: &str) {
+        for key in Self::keys(ip, username) {
+            self.windows.remove(&key);
+        }
+    }
+
+    /// 清理已过期的失败记录
+    pub fn cleanup_expired(&self) {
+        let now = Instant::now();
+        self.windows.retain(|_, w| {
+            now.duration_since(w.first_failure) < self.lockout
+                || w.locked_until.is_some_and(|until| until > now)
+        });
+    }
+}
+
+/// 登录限流额度，以 RateLimit-* 响应头返回
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+    /// 距计数窗口或锁定结束的秒数
+    pub reset_secs: u64,
+}
+
+impl Quota {
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in [
+            ("ratelimit-limit", self.limit as u64),
+            ("ratelimit-remaining", self.remaining as u64),
+            ("ratelimit-reset", self.reset_secs),
+        ] {
+            headers.insert(name, HeaderValue::from(value));
+        }
+    }
+}
+
+/// 单个键（`ip:<地址>` 或 `user:<用户名>`）的失败计数
+#[derive(Debug, Serialize)]
+pub struct LimitStatus {
+    pub key: String,
+    pub failures: u32,
+    pub remaining: u32,
+    pub locked: bool,
+    pub reset_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttemptsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+fn default_limit() -> u32 {
+    100
+}
+
+/// 最近的登录尝试记录
+pub async fn list_attempts_handler(
+    State(state): State<AdminState>,
+    Query(query): Query<AttemptsQuery>,
+) -> Result<Json<ApiResponse<Vec<LoginAttempt>>>, StatusCode> {
+    state
+        .db
+        .list_login_attempts(query.limit.min(1000))
+        .map(|attempts| Json(ApiResponse::ok(attempts)))
+        .map_err(|e| {
+            tracing::error!("Failed to list login attempts: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 当前登录限流计数
+pub async fn list_handler(State(state): State<AdminState>) -> Json<ApiResponse<Vec<LimitStatus>>> {
+    Json(ApiResponse::ok(state.auth.limiter.snapshot()))
+}
+
+/// 重置指定键的计数，解除锁定
+pub async fn reset_handler(
+    State(state): State<AdminState>,
+    Path(key): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if !state.auth.limiter.reset(&key) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::info!(key = %key, "Login rate limit reset");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{login_handler, LoginRequest};
+    use crate::testing;
+    use axum::extract::ConnectInfo;
+    use axum::http::header;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn locks_after_max_failures() {
+        let limiter = LoginLimiter::new(3, Duration::from_secs(60));
+        assert!(!limiter.record_failure("10.0.0.1", "alice"));
+        assert!(!limiter.record_failure("10.0.0.1", "alice"));
+        assert!(limiter.check("10.0.0.1", "alice").is_none());
+        assert!(limiter.record_failure("10.0.0.1", "alice"));
+
+        let remaining = limiter.check("10.0.0.1", "alice").unwrap();
+        assert!(remaining > Duration::from_secs(55) && remaining <= Duration::from_secs(60));
+        // IP 与用户名分别计数，换 IP 或换用户名都仍被锁定
+        assert!(limiter.check("10.0.0.2", "alice").is_some());
+        assert!(limiter.check("10.0.0.1", "bob").is_some());
+        assert!(limiter.check("10.0.0.2", "bob").is_none());
+    }
+
+    #[test]
+    fn success_and_reset_clear_failures() {
+        let limiter = LoginLimiter::new(2, Duration::from_secs(60));
+        limiter.record_failure("10.0.0.1", "alice");
+        limiter.record_success("10.0.0.1", "alice");
+        assert!(!limiter.record_failure("10.0.0.1", "alice"));
+
+        assert!(limiter.record_failure("10.0.0.1", "alice"));
+        assert!(limiter.reset("user:alice"));
+        assert!(limiter.reset("ip:10.0.0.1"));
+        assert!(!limiter.reset("ip:10.0.0.1"));
+        assert!(limiter.check("10.0.0.1", "alice").is_none());
+    }
+
+    #[test]
+    fn lockout_expires() {
+        let limiter = LoginLimiter::new(1, Duration::from_millis(50));
+        assert!(limiter.record_failure("10.0.0.1", "alice"));
+        assert!(limiter.check("10.0.0.1", "alice").is_some());
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.check("10.0.0.1", "alice").is_none());
+        limiter.cleanup_expired();
+        assert!(limiter.snapshot().is_empty());
+    }
+
+    #[test]
+    fn zero_max_failures_disables_limit() {
+        let limiter = LoginLimiter::new(0, Duration::from_secs(60));
+        for _ in 0..10 {
+            assert!(!limiter.record_failure("10.0.0.1", "alice"));
+        }
+        assert!(limiter.check("10.0.0.1", "alice").is_none());
+        assert!(limiter.quota("10.0.0.1", "alice").is_none());
+    }
+
+    #[tokio::test]
+    async fn locked_login_is_rejected_even_with_valid_password() {
+        let mut config = testing::config();
+        config.auth.max_login_failures = 2;
+        config.auth.lockout_secs = 60;
+        let state = testing::admin_state(&config);
+        let addr: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        let login = |password: &str| {
+            login_handler(
+                State(state.clone()),
+                Some(ConnectInfo(addr)),
+                HeaderMap::new(),
+                Json(LoginRequest {
+                    username: config.auth.username.clone(),
+                    password: password.to_string(),
+                }),
+            )
+        };
+
+        let resp = login("wrong").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["ratelimit-remaining"], "1");
+        login("wrong").await;
+
+        let resp = login(&config.auth.password).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry: u64 = resp.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry > 0 && retry <= 60);
+        assert_eq!(resp.headers()["ratelimit-remaining"], "0");
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{extract::Request, http::StatusCode, response::Response};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{CompiledProxyRule, ProxyState};
+use crate::stats::RuleCounters;
+
+/// maven-metadata.xml 与 SNAPSHOT 的默认缓存时间
+const DEFAULT_METADATA_TTL_SECS: u64 = 300;
+
+/// Maven 仓库缓存规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MavenOptions {
+    /// 缓存目录，按仓库路径保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// maven-metadata.xml 与 SNAPSHOT 版本的缓存秒数，默认 300，0 表示不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_ttl_secs: Option<u64>,
+}
+
+/// 仓库中的文件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArtifactKind {
+    /// 发布版本，内容不会变化
+    Release,
+    /// maven-metadata.xml 或 SNAPSHOT 版本，会随发布更新
+    Mutable,
+}
+
+impl ArtifactKind {
+    fn of(path: &str) -> Self {
+        let file = path.rsplit('/').next().unwrap_or(path);
+        if file.starts_with("maven-metadata") || path.contains("-SNAPSHOT/") {
+            Self::Mutable
+        } else {
+            Self::Release
+        }
+    }
+}
+
+/// 镜像规则的 GET / HEAD 请求：发布版本永久缓存，元数据与 SNAPSHOT 按 TTL 缓存，
+/// 校验文件（.sha1 / .md5 等）与构件同样处理，未命中缓存时上游的 X-Checksum-* 响应头原样返回
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &MavenOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    let path = req.uri().path();
+    let cache = mirror::source_prefix(rule, path, target_url)
+        .and_then(|prefix| cache_file(state, options, &path[prefix.len()..]));
+    mirror::forward_cached(state, rule, req, target_url, client_ip, counters, cache).await
+}
+
+/// 请求对应的缓存文件，不可缓存（路径不安全或 TTL 为 0）时返回 None
+fn cache_file(state: &ProxyState, options: &MavenOptions, relative: &str) -> Option<CacheFile> {
+    let dir = options.cache_dir.as_deref()?;
+    let file = CacheFile::new(
+        &state.cache_store,
+        Path::new(dir),
+        &mirror::relative_path(relative)?,
+        None,
+    )
+    .with_content_type(content_type(relative));
+    match ArtifactKind::of(relative) {
+        ArtifactKind::Release => Some(file),
+        ArtifactKind::Mutable => {
+            let ttl = options
+                .metadata_ttl_secs
+                .unwrap_or(DEFAULT_METADATA_TTL_SECS);
+            (ttl > 0).then(|| file.with_max_age(Duration::from_secs(ttl)))
+        }
+    }
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("pom" | "xml") => "text/xml",
+        Some("jar") => "application/java-archive",
+        Some("sha1" | "md5" | "sha256" | "sha512" | "asc") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,25 @@
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::proxy::ProxyState;
+
+/// Prometheus 指标端点
+pub async fn metrics_handler(State(state): State<ProxyState>) -> Response {
+    let mut out = String::new();
+    state.tasks.render_prometheus(&mut out);
+    state.reloads.render_prometheus(&mut out);
+    state.cache_store.render_prometheus(&mut out);
+    state.upstream_dns.render_prometheus(&mut out);
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        out,
+    )
+        .into_response()
+}
@@ -0,0 +1,645 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use bytes::Bytes;
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::{stream, Stream, StreamExt};
+use ring::digest;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+
+use crate::cache_store::CacheStore;
+use crate::proxy::{
+    forward_headers, upstream_body_stream, upstream_response_headers, CompiledProxyRule,
+    ForwardTimeouts, ProxyState,
+};
+use crate::redact;
+use crate::stats::RuleCounters;
+use crate::telemetry::TraceParent;
+
+/// 缓存命中时返回的响应头
+const CACHE_HEADER: &str = "x-proxy-cache";
+
+/// 同一缓存目录两次容量检查的最小间隔
+const QUOTA_SCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 镜像对外地址（scheme://host），未配置时按请求的 Host 与 X-Forwarded-Proto 生成
+pub fn public_base(public_url: Option<&str>, headers: &HeaderMap) -> Option<String> {
+    if let Some(url) = public_url {
+        return Some(url.trim_end_matches('/').to_string());
+    }
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    let proto = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    Some(format!("{}://{}", proto, host))
+}
+
+/// 模板中第一个参数之前的固定部分，即上游地址前缀
+pub fn template_prefix(template: &str) -> &str {
+    template.find('{').map_or(template, |idx| &template[..idx])
+}
+
+/// 请求路径中对应上游地址前缀的部分，如规则 /npm/{*path} -> https://registry.npmjs.org/{*path}
+/// 时请求 /npm/lodash 对应 /npm/
+pub fn source_prefix<'a>(
+    rule: &CompiledProxyRule,
+    path: &'a str,
+    target_url: &str,
+) -> Option<&'a str> {
+    let target = target_url.split('?').next().unwrap_or(target_url);
+    let tail = target.strip_prefix(template_prefix(&rule.target_template))?;
+    path.strip_suffix(tail)
+}
+
+/// 地址以上游前缀开头时返回其余部分，比较时忽略 http / https 差异
+pub fn strip_upstream<'a>(url: &'a str, upstream_prefix: &str) -> Option<&'a str> {
+    fn strip_scheme(url: &str) -> Option<&str> {
+        url.strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+    }
+    strip_scheme(url)?.strip_prefix(strip_scheme(upstream_prefix)?)
+}
+
+/// 请求路径转换为缓存目录中的相对路径，含空段、以点开头的段或反斜杠时返回 None
+pub fn relative_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/') {
+        if segment.is_empty() || segment.starts_with('.') || segment.contains(['\\', ':']) {
+            return None;
+        }
+        relative.push(segment);
+    }
+    Some(relative)
+}
+
+/// 发送上游请求，连接错误返回 502，等待响应头超时返回 504
+pub async fn send(
+    request: reqwest::RequestBuilder,
+    timeouts: ForwardTimeouts,
+) -> Result<reqwest::Response, StatusCode> {
+    match tokio::time::timeout(timeouts.response, request.send()).await {
+        Ok(Ok(resp)) => Ok(resp),
+        Ok(Err(e)) => {
+            tracing::error!("Mirror proxy error: {}", redact::url(&e.to_string()));
+            Err(StatusCode::BAD_GATEWAY)
+        }
+        Err(_) => {
+            tracing::error!("Upstream response timeout after {:?}", timeouts.response);
+            Err(StatusCode::GATEWAY_TIMEOUT)
+        }
+    }
+}
+
+/// 读取完整的上游响应体，用于改写元数据
+pub async fn read_body(
+    resp: reqwest::Response,
+    timeouts: ForwardTimeouts,
+) -> Result<Bytes, StatusCode> {
+    match tokio::time::timeout(timeouts.upstream_read, resp.bytes()).await {
+        Ok(Ok(body)) => Ok(body),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to read mirror metadata: {}", e);
+            Err(StatusCode::BAD_GATEWAY)
+        }
+        Err(_) => Err(StatusCode::GATEWAY_TIMEOUT),
+    }
+}
+
+/// 返回改写后的完整响应体，重新设置 Content-Length
+pub fn buffered(
+    status: StatusCode,
+    mut headers: HeaderMap,
+    body: Bytes,
+    counters: &RuleCounters,
+) -> Response {
+    counters.add_bytes_out(body.len() as u64);
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    let mut out = Response::new(Body::from(body));
+    *out.status_mut() = status;
+    *out.headers_mut() = headers;
+    out
+}
+
+/// 流式返回上游响应并统计流量；给定缓存文件且状态为 200 时同时写入缓存
+pub async fn relay(
+    resp: reqwest::Response,
+    headers: HeaderMap,
+    timeouts: ForwardTimeouts,
+    counters: Arc<RuleCounters>,
+    cache: Option<CacheFile>,
+) -> Response {
+    let status = resp.status();
+    let content_length = resp.content_length();
+    let body =
+        upstream_body_stream(resp.bytes_stream(), timeouts.upstream_read).map(move |result| {
+            if let Ok(ref chunk) = result {
+                counters.add_bytes_out(chunk.len() as u64);
+            }
+            result
+        });
+    let body = match cache.filter(|_| status == StatusCode::OK) {
+        Some(cache) => cache.tee(body.boxed(), content_length).await,
+        None => Body::from_stream(body),
+    };
+
+    let mut out = Response::new(body);
+    *out.status_mut() = status;
+    *out.headers_mut() = headers;
+    out
+}
+
+/// 按文件缓存的 GET / HEAD 转发：命中缓存时直接返回（带 X-Proxy-Cache: HIT），
+/// 否则转发到上游，GET 的 200 响应同时写入缓存
+pub async fn forward_cached(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+    cache: Option<CacheFile>,
+) -> Result<Response, StatusCode> {
+    if let Some(ref cache) = cache {
+        if let Some(resp) = serve_cached(cache, req.method(), req.headers(), &counters).await {
+            return Ok(resp);
+        }
+    }
+
+    let client = state.rule_client(rule)?;
+    let timeouts = ForwardTimeouts::for_rule(rule);
+    let cache = cache.filter(|_| req.method() == Method::GET);
+    let resp = send(
+        cache_request(&client, &req, target_url, client_ip, cache.is_some()),
+        timeouts,
+    )
+    .await?;
+    let response_headers = upstream_response_headers(resp.headers());
+    Ok(relay(resp, response_headers, timeouts, counters, cache).await)
+}
+
+/// 从缓存返回 GET / HEAD 请求，支持单个 Range，响应带 X-Proxy-Cache: HIT
+pub async fn serve_cached(
+    cache: &CacheFile,
+    method: &Method,
+    headers: &HeaderMap,
+    counters: &Arc<RuleCounters>,
+) -> Option<Response> {
+    let mut resp = cache
+        .serve(headers, method == Method::GET, counters)
+        .await?;
+    resp.headers_mut()
+        .insert(CACHE_HEADER, HeaderValue::from_static("HIT"));
+    tracing::debug!(path = %cache.path.display(), "Mirror file served from cache");
+    Some(resp)
+}
+
+/// 缓存未命中时的上游 GET / HEAD 请求；需要写入缓存时由客户端库协商压缩并解压，
+/// 避免缓存压缩后的内容；带 Range 的请求保持 identity，不压缩部分内容
+pub fn cache_request(
+    client: &reqwest::Client,
+    req: &Request,
+    target_url: &str,
+    client_ip: &str,
+    caching: bool,
+) -> reqwest::RequestBuilder {
+    let mut headers = forward_headers(
+        req.headers(),
+        target_url,
+        client_ip,
+        req.extensions().get::<TraceParent>().copied(),
+    );
+    if caching && !headers.contains_key(header::RANGE) {
+        headers.remove(header::ACCEPT_ENCODING);
+    }
+    let method = if req.method() == Method::GET {
+        reqwest::Method::GET
+    } else {
+        reqwest::Method::HEAD
+    };
+    client.request(method, target_url).headers(headers)
+}
+
+/// 镜像的缓存文件，保存在配置的缓存存储中，设置 sha256 时写入前校验摘要
+pub struct CacheFile {
+    store: Arc<dyn CacheStore>,
+    /// 缓存目录
+    root: PathBuf,
+    path: PathBuf,
+    /// 小写十六进制
+    sha256: Option<String>,
+    /// 缓存有效期（按文件修改时间），None 表示永久有效
+    max_age: Option<Duration>,
+    content_type: &'static str,
+    quota: Option<Quota>,
+}
+
+impl CacheFile {
+    /// 缓存目录 root 下的 relative 文件
+    pub fn new(
+        store: &Arc<dyn CacheStore>,
+        root: &Path,
+        relative: &Path,
+        sha256: Option<String>,
+    ) -> Self {
+        store.track(root);
+        Self {
+            store: store.clone(),
+            root: root.to_path_buf(),
+            path: root.join(relative),
+            sha256,
+            max_age: None,
+            content_type: "application/octet-stream",
+            quota: None,
+        }
+    }
+
+    /// 写入后检查缓存目录的容量，超过 max_bytes 时清理
+    pub fn with_quota(mut self, quotas: &CacheQuotas, max_bytes: u64) -> Self {
+        self.quota = Some(Quota {
+            quotas: quotas.clone(),
+            store: self.store.clone(),
+            root: self.root.clone(),
+            max_bytes,
+        });
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: &'static str) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// 缓存命中时返回响应，HEAD 请求只返回长度；支持单个字节范围（返回 206，
+    /// 超出文件长度时返回 416），多个范围时返回完整文件。响应带 ETag 与 Last-Modified，
+    /// If-Range 与之不符（文件已重新缓存）时返回完整文件，客户端不会拼接出损坏的下载
+    pub async fn serve(
+        &self,
+        request_headers: &HeaderMap,
+        with_body: bool,
+        counters: &Arc<RuleCounters>,
+    ) -> Option<Response> {
+        let metadata = match self.store.head(&self.path).await {
+            Ok(metadata) => metadata?,
+            Err(e) => {
+                tracing::warn!(path = %self.path.display(), "Failed to read mirror cache: {}", e);
+                return None;
+            }
+        };
+        if let Some(max_age) = self.max_age {
+            if metadata.modified.elapsed().unwrap_or_default() > max_age {
+                return None;
+            }
+        }
+        let len = metadata.len;
+        let modified = chrono::DateTime::<chrono::Utc>::from(metadata.modified);
+        let etag = format!("\"{:x}-{:x}\"", len, modified.timestamp());
+        let last_modified = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let range = request_headers
+            .get(header::RANGE)
+            .and_then(|v| v.to_str().ok())
+            .filter(|_| {
+                request_headers
+                    .get(header::IF_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .is_none_or(|v| v.trim() == etag || v.trim() == last_modified)
+            });
+
+        let mut resp = Response::new(Body::empty());
+        let (start, end) = match range.and_then(|range| byte_range(range, len)) {
+            None => (0, len),
+            Some(Some((start, end))) => {
+                *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+                let content_range = format!("bytes {}-{}/{}", start, end - 1, len);
+                resp.headers_mut().insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&content_range).ok()?,
+                );
+                (start, end)
+            }
+            Some(None) => {
+                *resp.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                let content_range = format!("bytes */{}", len);
+                resp.headers_mut().insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&content_range).ok()?,
+                );
+                return Some(resp);
+            }
+        };
+        if with_body {
+            let body = match self.store.read(&self.path, start, end).await {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!(path = %self.path.display(), "Failed to read mirror cache: {}", e);
+                    return None;
+                }
+            };
+            let counters = counters.clone();
+            *resp.body_mut() = Body::from_stream(body.map(move |result| {
+                if let Ok(ref chunk) = result {
+                    counters.add_bytes_out(chunk.len() as u64);
+                }
+                result
+            }));
+        }
+
+        let headers = resp.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(self.content_type),
+        );
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(end - start));
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        headers.insert(header::ETAG, HeaderValue::from_str(&etag).ok()?);
+        headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&last_modified).ok()?,
+        );
+        Some(resp)
+    }
+
+    /// 边转发边写入缓存，无法创建缓存文件时只转发
+    pub async fn tee<S>(&self, body: S, expected_len: Option<u64>) -> Body
+    where
+        S: Stream<Item = std::io::Result<Bytes>> + Send + Unpin + 'static,
+    {
+        match CacheWriter::create(self, expected_len).await {
+            Ok(writer) => Body::from_stream(tee_to_cache(body, writer)),
+            Err(e) => {
+                tracing::warn!(path = %self.path.display(), "Failed to create mirror cache file: {}", e);
+                Body::from_stream(body)
+            }
+        }
+    }
+}
+
+/// Range 请求头中的单个字节范围，返回 [start, end)；格式无法识别或包含多个范围时返回 None，
+/// 范围超出文件长度时返回 Some(None)
+fn byte_range(value: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let (first, last) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+    if last.contains(',') {
+        return None;
+    }
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        // bytes=-N：最后 N 个字节
+        let suffix: u64 = last.parse().ok()?;
+        (len.saturating_sub(suffix), len)
+    } else {
+        let start: u64 = first.parse().ok()?;
+        let end = if last.is_empty() {
+            len
+        } else {
+            let last: u64 = last.parse().ok()?;
+            if last < start {
+                return None;
+            }
+            last.saturating_add(1).min(len)
+        };
+        (start, end)
+    };
+    Some(Some(range).filter(|(start, end)| start < end))
+}
+
+/// 写入本地暂存文件，长度与摘要校验通过后提交到缓存存储；结束时删除暂存文件
+struct CacheWriter {
+    store: Arc<dyn CacheStore>,
+    file: tokio::fs::File,
+    /// 上游返回的 Content-Length，写满后立即完成（客户端收到完整响应后不一定继续拉取流的结尾）
+    expected_len: Option<u64>,
+    written: u64,
+    tmp: PathBuf,
+    dest: PathBuf,
+    sha256: Option<String>,
+    context: digest::Context,
+    quota: Option<Quota>,
+}
+
+impl CacheWriter {
+    async fn create(cache: &CacheFile, expected_len: Option<u64>) -> std::io::Result<Self> {
+        static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+
+        let dir = cache.store.staging_dir(&cache.path);
+        tokio::fs::create_dir_all(&dir).await?;
+        let name = cache.path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = dir.join(format!(
+            "{}.{}.{}.tmp",
+            name,
+            std::process::id(),
+            NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        Ok(Self {
+            store: cache.store.clone(),
+            file: tokio::fs::File::create(&tmp).await?,
+            expected_len,
+            written: 0,
+            tmp,
+            dest: cache.path.clone(),
+            sha256: cache.sha256.clone(),
+            context: digest::Context::new(&digest::SHA256),
+            quota: cache.quota.clone(),
+        })
+    }
+
+    async fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.context.update(chunk);
+        self.written += chunk.len() as u64;
+        self.file.write_all(chunk).await
+    }
+
+    async fn finish(mut self) {
+        let path = self.dest.display().to_string();
+        if self.expected_len.is_some_and(|len| len != self.written) {
+            tracing::warn!(path = %path, "Mirror response is incomplete, not cached");
+            return;
+        }
+        let actual: String = self
+            .context
+            .clone()
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if self
+            .sha256
+            .as_ref()
+            .is_some_and(|expected| *expected != actual)
+        {
+            tracing::warn!(path = %path, "Mirror cache digest mismatch, not cached");
+            return;
+        }
+        let result = match self.file.flush().await {
+            Ok(()) => {
+                self.store
+                    .commit(&self.dest, &self.tmp, self.written, &actual)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                tracing::info!(path = %path, "Mirror file cached");
+                if let Some(ref quota) = self.quota {
+                    quota.check();
+                }
+            }
+            Err(e) => tracing::warn!(path = %path, "Failed to cache mirror file: {}", e),
+        }
+    }
+}
+
+impl Drop for CacheWriter {
+    fn drop(&mut self) {
+        // 本地文件存储提交时已移动暂存文件
+        let _ = std::fs::remove_file(&self.tmp);
+    }
+}
+
+fn tee_to_cache<S>(
+    body: S,
+    writer: CacheWriter,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + Unpin + 'static,
+{
+    stream::unfold((body, Some(writer)), |(mut body, mut writer)| async move {
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                if let Some(mut w) = writer.take() {
+                    match w.write(&chunk).await {
+                        Ok(()) if w.expected_len == Some(w.written) => w.finish().await,
+                        Ok(()) => writer = Some(w),
+                        Err(e) => {
+                            tracing::warn!(path = %w.dest.display(), "Failed to write mirror cache file: {}", e)
+                        }
+                    }
+                }
+                Some((Ok(chunk), (body, writer)))
+            }
+            // 上游出错时丢弃未完成的缓存
+            Some(Err(e)) => Some((Err(e), (body, None))),
+            None => {
+                if let Some(w) = writer {
+                    w.finish().await;
+                }
+                None
+            }
+        }
+    })
+}
+
+/// 缓存目录的容量限制
+#[derive(Clone)]
+struct Quota {
+    quotas: CacheQuotas,
+    store: Arc<dyn CacheStore>,
+    root: PathBuf,
+    max_bytes: u64,
+}
+
+impl Quota {
+    /// 在后台检查容量，同一目录检查进行中或间隔过短时跳过
+    fn check(&self) {
+        let scans = self.quotas.scans.clone();
+        match scans.entry(self.root.clone()) {
+            Entry::Occupied(mut entry) => match *entry.get() {
+                None => return,
+                Some(at) if at.elapsed() < QUOTA_SCAN_INTERVAL => return,
+                Some(_) => {
+                    entry.insert(None);
+                }
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(None);
+            }
+        }
+        let (store, root, max_bytes) = (self.store.clone(), self.root.clone(), self.max_bytes);
+        tokio::spawn(async move {
+            match store.evict(&root, max_bytes).await {
+                Ok(0) => {}
+                Ok(removed) => {
+                    tracing::info!(path = %root.display(), removed, "Mirror cache exceeded its size limit, evicted oldest files")
+                }
+                Err(e) => {
+                    tracing::warn!(path = %root.display(), "Failed to enforce mirror cache size limit: {}", e)
+                }
+            }
+            scans.insert(root, Some(Instant::now()));
+        });
+    }
+}
+
+/// 各缓存目录的容量检查状态
+#[derive(Clone, Default)]
+pub struct CacheQuotas {
+    /// 缓存目录 -> 上次检查完成时间，检查进行中时为 None
+    scans: Arc<DashMap<PathBuf, Option<Instant>>>,
+}
+
+impl CacheQuotas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_range_parses_single_ranges() {
+        assert_eq!(byte_range("bytes=0-99", 1000), Some(Some((0, 100))));
+        assert_eq!(byte_range(" bytes=100- ", 1000), Some(Some((100, 1000))));
+        // 结束位置超出文件长度时截断
+        assert_eq!(byte_range("bytes=900-2000", 1000), Some(Some((900, 1000))));
+    }
+
+    #[test]
+    fn byte_range_suffix() {
+        assert_eq!(byte_range("bytes=-100", 1000), Some(Some((900, 1000))));
+        assert_eq!(byte_range("bytes=-5000", 1000), Some(Some((0, 1000))));
+        assert_eq!(byte_range("bytes=-0", 1000), Some(None));
+    }
+
+    #[test]
+    fn byte_range_unsatisfiable() {
+        assert_eq!(byte_range("bytes=1000-", 1000), Some(None));
+        assert_eq!(byte_range("bytes=2000-3000", 1000), Some(None));
+        assert_eq!(byte_range("bytes=0-", 0), Some(None));
+    }
+
+    #[test]
+    fn byte_range_ignores_invalid_or_multiple_ranges() {
+        assert_eq!(byte_range("bytes=5-1", 1000), None);
+        assert_eq!(byte_range("bytes=a-b", 1000), None);
+        assert_eq!(byte_range("items=0-10", 1000), None);
+        assert_eq!(byte_range("bytes=0-10,20-30", 1000), None);
+        assert_eq!(byte_range("bytes=", 1000), None);
+    }
+
+    #[test]
+    fn relative_path_rejects_traversal() {
+        assert_eq!(
+            relative_path("org/x/1.0/x.jar"),
+            Some(PathBuf::from("org/x/1.0/x.jar"))
+        );
+        assert_eq!(relative_path("org/../etc/passwd"), None);
+        assert_eq!(relative_path("org//x"), None);
+        assert_eq!(relative_path("org/.hidden"), None);
+        assert_eq!(relative_path("c:\\windows"), None);
+    }
+}
@@ -0,0 +1,149 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{
+    forward_headers, upstream_response_headers, CompiledProxyRule, ForwardTimeouts, ProxyState,
+};
+use crate::stats::RuleCounters;
+use crate::telemetry::TraceParent;
+
+/// npm 镜像规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NpmOptions {
+    /// tarball 缓存目录，按包名与文件名保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// 镜像对外地址（如 https://mirror.example.com），用于改写 tarball 地址，
+    /// 未设置时按请求的 Host 与 X-Forwarded-Proto 生成
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+/// 镜像规则的 GET 请求：包元数据中的 tarball 地址改写为经过镜像的地址，
+/// 配置缓存目录时 tarball 保存到本地；其他方法按普通规则转发
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &NpmOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    let path = req.uri().path();
+    let tarball = tarball_path(path);
+    let cache = options
+        .cache_dir
+        .as_deref()
+        .zip(tarball.as_ref())
+        .map(|(dir, relative)| CacheFile::new(&state.cache_store, Path::new(dir), relative, None));
+    if let Some(ref cache) = cache {
+        if let Some(resp) =
+            mirror::serve_cached(cache, req.method(), req.headers(), &counters).await
+        {
+            return Ok(resp);
+        }
+    }
+
+    let mut headers = forward_headers(
+        req.headers(),
+        target_url,
+        client_ip,
+        req.extensions().get::<TraceParent>().copied(),
+    );
+    if tarball.is_none() {
+        // 元数据需要解析改写，由客户端库协商压缩并解压；部分内容无法改写，始终请求完整文档
+        headers.remove(header::ACCEPT_ENCODING);
+        headers.remove(header::RANGE);
+        headers.remove(header::IF_RANGE);
+    }
+    let client = state.rule_client(rule)?;
+    let timeouts = ForwardTimeouts::for_rule(rule);
+    let resp = mirror::send(client.get(target_url).headers(headers), timeouts).await?;
+
+    let status = resp.status();
+    let response_headers = upstream_response_headers(resp.headers());
+    let is_json = response_headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+
+    if tarball.is_none() && status == StatusCode::OK && is_json {
+        let mirror_prefix = mirror::public_base(options.public_url.as_deref(), req.headers())
+            .zip(mirror::source_prefix(rule, path, target_url))
+            .map(|(base, prefix)| format!("{}{}", base, prefix));
+        if let Some(mirror_prefix) = mirror_prefix {
+            let body = mirror::read_body(resp, timeouts).await?;
+            let body = match serde_json::from_slice::<Value>(&body) {
+                Ok(mut doc) => {
+                    let upstream_prefix = mirror::template_prefix(&rule.target_template);
+                    if rewrite_tarballs(&mut doc, upstream_prefix, &mirror_prefix) > 0 {
+                        serde_json::to_vec(&doc).map(Into::into).unwrap_or(body)
+                    } else {
+                        body
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path, "Failed to parse npm metadata: {}", e);
+                    body
+                }
+            };
+            return Ok(mirror::buffered(status, response_headers, body, &counters));
+        }
+    }
+
+    Ok(mirror::relay(resp, response_headers, timeouts, counters, cache).await)
+}
+
+/// tarball 请求（<包名>/-/<文件名>.tgz）在缓存目录中的相对路径，路径不安全时返回 None
+fn tarball_path(path: &str) -> Option<PathBuf> {
+    let (package, file) = path.rsplit_once("/-/")?;
+    if !file.ends_with(".tgz") || file.contains('/') {
+        return None;
+    }
+    // 作用域包为 @scope/name，取最后两段
+    let mut segments = package.rsplit('/');
+    let name = segments.next()?;
+    let scope = segments.next().filter(|s| s.starts_with('@'));
+    let valid = |segment: &str| {
+        !segment.is_empty()
+            && !segment.starts_with('.')
+            && segment.chars().all(|c| {
+                c.is_ascii_alphanumeric() || matches!(c, '@' | '-' | '_' | '.' | '~' | '%')
+            })
+    };
+    let mut relative = PathBuf::new();
+    for segment in scope.into_iter().chain([name, file]) {
+        if !valid(segment) {
+            return None;
+        }
+        relative.push(segment);
+    }
+    Some(relative)
+}
+
+/// 改写 versions.*.dist.tarball 中指向上游的地址，返回改写数量
+fn rewrite_tarballs(doc: &mut Value, upstream_prefix: &str, mirror_prefix: &str) -> usize {
+    let Some(versions) = doc.get_mut("versions").and_then(Value::as_object_mut) else {
+        return 0;
+    };
+    let mut rewritten = 0;
+    for version in versions.values_mut() {
+        if let Some(Value::String(url)) = version.pointer_mut("/dist/tarball") {
+            if let Some(rest) = mirror::strip_upstream(url, upstream_prefix) {
+                *url = format!("{}{}", mirror_prefix, rest);
+                rewritten += 1;
+            }
+        }
+    }
+    rewritten
+}
@@ -0,0 +1,410 @@
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use reqwest::{Client, Url};
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+use crate::auth::session_cookies;
+use crate::config::{OidcConfig, Role};
+use crate::AdminState;
+
+/// 登录流程（state 参数）有效期
+const LOGIN_TTL: Duration = Duration::from_secs(600);
+
+/// 校验 ID Token 过期时间时允许的时钟偏差(秒)
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// 请求身份提供方的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+struct PendingLogin {
+    nonce: String,
+    verifier: String,
+    created: Instant,
+}
+
+/// 登录用户，role 为 None 表示不属于任何已授权的用户组
+struct Identity {
+    username: String,
+    role: Option<Role>,
+    groups: Vec<String>,
+}
+
+/// OIDC 授权码流程（PKCE），提供方端点在首次登录时发现并缓存
+#[derive(Clone)]
+pub struct OidcClient {
+    config: Arc<OidcConfig>,
+    client: Client,
+    metadata: Arc<OnceCell<ProviderMetadata>>,
+    jwks: Arc<RwLock<Vec<Jwk>>>,
+    pending: Arc<DashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    pub fn new(config: &OidcConfig, client: Client) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            client,
+            metadata: Arc::new(OnceCell::new()),
+            jwks: Arc::new(RwLock::new(Vec::new())),
+            pending: Arc::new(DashMap::new()),
+        }
+    }
+
+    async fn metadata(&self) -> anyhow::Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let issuer = self.config.issuer.trim_end_matches('/');
+                let url = format!("{}/.well-known/openid-configuration", issuer);
+                let metadata: ProviderMetadata = self
+                    .client
+                    .get(&url)
+                    .timeout(REQUEST_TIMEOUT)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                if metadata.issuer.trim_end_matches('/') != issuer {
+                    anyhow::bail!("OIDC issuer mismatch: {}", metadata.issuer);
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// 生成授权地址，并记录本次登录的 state、nonce 与 PKCE verifier
+    async fn authorize_url(&self) -> anyhow::Result<Url> {
+        let metadata = self.metadata().await?;
+        let state = random_token()?;
+        let nonce = random_token()?;
+        let verifier = random_token()?;
+        let challenge =
+            URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, verifier.as_bytes()));
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.config.client_id),
+                ("redirect_uri", &self.config.redirect_url),
+                ("scope", &self.config.scopes.join(" ")),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+
+        self.pending
+            .retain(|_, login| login.created.elapsed() < LOGIN_TTL);
+        self.pending.insert(
+            state,
+            PendingLogin {
+                nonce,
+                verifier,
+                created: Instant::now(),
+            },
+        );
+        Ok(url)
+    }
+
+    /// 用授权码换取 ID Token，校验后按用户组映射角色
+    async fn exchange(&self, code: &str, state: &str) -> anyhow::Result<Identity> {
+        let (_, login) = self
+            .pending
+            .remove(state)
+            .filter(|(_, login)| login.created.elapsed() < LOGIN_TTL)
+            .ok_or_else(|| anyhow::anyhow!("Unknown or expired OIDC login state"))?;
+        let metadata = self.metadata().await?;
+
+        let tokens: TokenResponse = self
+            .client
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("code_verifier", &login.verifier),
+            ])
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let claims = self.verify_id_token(&tokens.id_token, metadata).await?;
+        if claims["nonce"].as_str() != Some(login.nonce.as_str()) {
+            anyhow::bail!("ID token nonce mismatch");
+        }
+
+        let username = ["preferred_username", "email", "sub"]
+            .iter()
+            .find_map(|claim| claims[*claim].as_str())
+            .ok_or_else(|| anyhow::anyhow!("ID token has no subject"))?
+            .to_string();
+        let groups = groups(&claims[&self.config.groups_claim]);
+        Ok(Identity {
+            username,
+            role: self.role_for(&groups),
+            groups,
+        })
+    }
+
+    /// 校验 ID Token 签名（RS256 / ES256）、签发方、受众与有效期
+    async fn verify_id_token(
+        &self,
+        token: &str,
+        metadata: &ProviderMetadata,
+    ) -> anyhow::Result<Value> {
+        let (message, sig) = token
+            .rsplit_once('.')
+            .ok_or_else(|| anyhow::anyhow!("Malformed ID token"))?;
+        let (header, payload) = message
+            .split_once('.')
+            .ok_or_else(|| anyhow::anyhow!("Malformed ID token"))?;
+        let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        let alg = header["alg"].as_str().unwrap_or_default();
+        let key = self.find_key(header["kid"].as_str(), metadata).await?;
+        verify_signature(alg, &key, message.as_bytes(), &URL_SAFE_NO_PAD.decode(sig)?)?;
+
+        let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+        if claims["iss"].as_str() != Some(metadata.issuer.as_str()) {
+            anyhow::bail!("ID token issuer mismatch");
+        }
+        let client_id = self.config.client_id.as_str();
+        let audience_ok = match &claims["aud"] {
+            Value::String(aud) => aud == client_id,
+            Value::Array(auds) => auds.iter().any(|aud| aud.as_str() == Some(client_id)),
+            _ => false,
+        };
+        if !audience_ok {
+            anyhow::bail!("ID token audience mismatch");
+        }
+        let exp = claims["exp"].as_i64().unwrap_or_default();
+        if exp + CLOCK_SKEW_SECS < chrono::Utc::now().timestamp() {
+            anyhow::bail!("ID token expired");
+        }
+        Ok(claims)
+    }
+
+    /// 按 kid 查找签名公钥，未命中时重新获取 JWKS（提供方可能已轮换密钥）
+    async fn find_key(
+        &self,
+        kid: Option<&str>,
+        metadata: &ProviderMetadata,
+    ) -> anyhow::Result<Jwk> {
+        let lookup = |keys: &[Jwk]| match kid {
+            Some(kid) => keys.iter().find(|k| k.kid.as_deref() == Some(kid)).cloned(),
+            None if keys.len() == 1 => keys.first().cloned(),
+            None => None,
+        };
+        if let Some(key) = lookup(&self.jwks.read()) {
+            return Ok(key);
+        }
+
+        let set: JwkSet = self
+            .client
+            .get(&metadata.jwks_uri)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let key = lookup(&set.keys);
+        *self.jwks.write() = set.keys;
+        key.ok_or_else(|| anyhow::anyhow!("No signing key found for kid {:?}", kid))
+    }
+
+    /// 取所属用户组映射到的最高角色，未匹配时使用默认角色
+    fn role_for(&self, groups: &[String]) -> Option<Role> {
+        groups
+            .iter()
+            .filter_map(|group| self.config.group_roles.get(group).copied())
+            .max()
+            .or(self.config.default_role)
+    }
+}
+
+/// 用户组声明可以是字符串数组或单个字符串
+fn groups(claim: &Value) -> Vec<String> {
+    match claim {
+        Value::Array(groups) => groups
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Value::String(group) => vec![group.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn verify_signature(alg: &str, key: &Jwk, message: &[u8], sig: &[u8]) -> anyhow::Result<()> {
+    let decode = |v: &Option<String>| URL_SAFE_NO_PAD.decode(v.as_deref().unwrap_or_default());
+    let result = match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => {
+            let (n, e) = (decode(&key.n)?, decode(&key.e)?);
+            RsaPublicKeyComponents { n: &n, e: &e }.verify(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                message,
+                sig,
+            )
+        }
+        ("ES256", "EC") if key.crv.as_deref() == Some("P-256") => {
+            // 未压缩格式的椭圆曲线点: 0x04 || x || y
+            let mut point = vec![0x04];
+            point.extend(decode(&key.x)?);
+            point.extend(decode(&key.y)?);
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point).verify(message, sig)
+        }
+        _ => anyhow::bail!("Unsupported ID token algorithm: {}", alg),
+    };
+    result.map_err(|_| anyhow::anyhow!("Invalid ID token signature"))
+}
+
+fn random_token() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("Failed to generate random token"))?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// 跳转到身份提供方登录
+pub async fn login_handler(State(state): State<AdminState>) -> Response {
+    let Some(ref oidc) = state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match oidc.authorize_url().await {
+        Ok(url) => Redirect::to(url.as_str()).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to start OIDC login: {}", e);
+            Redirect::to("/login#error=sso_failed").into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 身份提供方回调：登录成功后通过 URL 片段把 token 交给登录页
+pub async fn callback_handler(
+    State(state): State<AdminState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Response {
+    let Some(ref oidc) = state.oidc else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let client_addr = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let client_ip = client_addr.map_or_else(|| "unix".to_string(), |ip| ip.to_string());
+
+    let result = match (query.code, query.state, query.error) {
+        (Some(code), Some(login_state), None) => oidc.exchange(&code, &login_state).await,
+        (_, _, error) => Err(anyhow::anyhow!(
+            "OIDC provider returned error: {}",
+            error.unwrap_or_else(|| "missing code".to_string())
+        )),
+    };
+    match result {
+        Ok(Identity {
+            username,
+            role: Some(role),
+            groups,
+        }) => {
+            state
+                .auth
+                .record_attempt(&username, &client_ip, true, "oidc");
+            let token =
+                match state
+                    .auth
+                    .create_session(&username, role, groups, &headers, client_addr)
+                {
+                    Ok(token) => token,
+                    Err(e) => {
+                        tracing::error!("Failed to create session: {}", e);
+                        return Redirect::to("/login#error=sso_failed").into_response();
+                    }
+                };
+            tracing::info!(username = %username, role = role.as_str(), "OIDC login");
+            (
+                session_cookies(&token),
+                Redirect::to(&format!("/login#token={}", token)),
+            )
+                .into_response()
+        }
+        Ok(Identity {
+            username,
+            role: None,
+            ..
+        }) => {
+            state
+                .auth
+                .record_attempt(&username, &client_ip, false, "oidc_denied");
+            tracing::warn!(username = %username, "OIDC login denied: no matching group");
+            Redirect::to("/login#error=sso_denied").into_response()
+        }
+        Err(e) => {
+            tracing::warn!(client_ip = %client_ip, "OIDC login failed: {}", e);
+            state
+                .auth
+                .record_attempt("", &client_ip, false, "oidc_failed");
+            Redirect::to("/login#error=sso_failed").into_response()
+        }
+    }
+}
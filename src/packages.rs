@@ -0,0 +1,95 @@
+use axum::{extract::Request, http::StatusCode, response::Response};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{CompiledProxyRule, ProxyState};
+use crate::stats::RuleCounters;
+
+/// Release / repomd.xml 等元数据的默认缓存时间
+const DEFAULT_METADATA_TTL_SECS: u64 = 60;
+
+/// 软件包仓库（APT / YUM）缓存规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PackageCacheOptions {
+    /// 缓存目录，按仓库路径保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// 元数据（Release、Packages、repomd.xml 等）的缓存秒数，默认 60，0 表示不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_ttl_secs: Option<u64>,
+    /// 缓存目录容量上限（MB），超过时删除最早缓存（disk 后端为最久未访问）的文件，未设置时不限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+}
+
+/// 仓库中的文件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PackageFile {
+    /// 软件包或按摘要命名的索引（APT by-hash），内容不会变化
+    Immutable(&'static str),
+    /// 仓库元数据，随仓库更新变化
+    Metadata,
+}
+
+impl PackageFile {
+    fn of(path: &str) -> Self {
+        if path.contains("/by-hash/") {
+            return Self::Immutable("application/octet-stream");
+        }
+        match path.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("deb" | "udeb" | "ddeb") => {
+                Self::Immutable("application/vnd.debian.binary-package")
+            }
+            Some("rpm" | "drpm") => Self::Immutable("application/x-rpm"),
+            _ => Self::Metadata,
+        }
+    }
+}
+
+/// 镜像规则的 GET / HEAD 请求：.deb / .rpm 与 by-hash 索引永久缓存，
+/// 其他元数据按 TTL 缓存，配置容量上限时写入后清理最久未访问的文件
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &PackageCacheOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    let path = req.uri().path();
+    let cache = mirror::source_prefix(rule, path, target_url)
+        .and_then(|prefix| cache_file(state, options, &path[prefix.len()..]));
+    mirror::forward_cached(state, rule, req, target_url, client_ip, counters, cache).await
+}
+
+/// 请求对应的缓存文件，不可缓存（路径不安全或 TTL 为 0）时返回 None
+fn cache_file(
+    state: &ProxyState,
+    options: &PackageCacheOptions,
+    relative: &str,
+) -> Option<CacheFile> {
+    let dir = Path::new(options.cache_dir.as_deref()?);
+    let mut file = CacheFile::new(
+        &state.cache_store,
+        dir,
+        &mirror::relative_path(relative)?,
+        None,
+    );
+    if let Some(max_size_mb) = options.max_size_mb {
+        file = file.with_quota(&state.cache_quotas, max_size_mb * 1024 * 1024);
+    }
+    match PackageFile::of(relative) {
+        PackageFile::Immutable(content_type) => Some(file.with_content_type(content_type)),
+        PackageFile::Metadata => {
+            let ttl = options
+                .metadata_ttl_secs
+                .unwrap_or(DEFAULT_METADATA_TTL_SECS);
+            (ttl > 0).then(|| file.with_max_age(Duration::from_secs(ttl)))
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::auth;
+use crate::db::RuleOptions;
+use crate::etag::{self, EtagRequest};
+use crate::experiment::ExperimentOptions;
+use crate::faults::FaultOptions;
+use crate::hooks::HookContext;
+use crate::idempotency::{IdempotencyCache, Lookup, PendingKey};
+use crate::proxy::{annotate_headers, CompiledProxyRule, ProxyState};
+use crate::rule_auth::RuleAuth;
+use crate::scripts::RuleScript;
+use crate::secrets::UpstreamAuth;
+use crate::tls::{self, ClientCert, ClientCertFormat};
+
+/// 规则的请求处理步骤，编译规则时按选项生成，转发前依次执行
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// A/B 实验分组，改写上游地址
+    Experiment(ExperimentOptions),
+    /// 规则访问认证，先于幂等缓存执行，凭据不转发给上游
+    Auth(RuleAuth),
+    /// 幂等键：命中缓存直接返回，处理中的重复请求返回 409
+    Idempotency(Duration),
+    /// 为 GET 响应生成 ETag
+    Etag,
+    /// 向上游添加 X-Proxy-Rule / X-Proxy-Target
+    AnnotateUpstream,
+    /// 身份透传：未登录时拒绝，会话 cookie 不转发给上游
+    Identity,
+    /// 转发客户端证书信息
+    ClientCert(ClientCertFormat),
+    /// 嵌入方注册的请求钩子
+    Hooks,
+    /// WASM 插件
+    Plugins(Vec<String>),
+    /// Rhai 请求脚本
+    Script(RuleScript),
+    /// 上游凭据在钩子、插件与脚本之后注入，它们都看不到密钥值
+    UpstreamAuth(UpstreamAuth),
+    /// 故障注入，放在转发前最后执行，前面的步骤照常生效
+    Fault(FaultOptions),
+    /// 在返回给客户端的响应中添加标注头
+    AnnotateResponse,
+}
+
+/// 规则的处理管线
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+/// 执行步骤时的请求信息
+pub struct StepContext<'a> {
+    pub state: &'a ProxyState,
+    pub rule: &'a CompiledProxyRule,
+    pub client_addr: SocketAddr,
+    pub client_ip: &'a str,
+}
+
+/// 请求阶段的结果
+pub enum Flow {
+    /// 继续转发，响应交给 [`ResponsePhase`] 处理
+    Forward(ResponsePhase),
+    /// 某个步骤直接返回，不再转发
+    Respond(Result<Response, StatusCode>),
+}
+
+/// 请求阶段登记的响应处理，按步骤顺序执行
+#[derive(Default)]
+pub struct ResponsePhase {
+    actions: Vec<ResponseAction>,
+}
+
+enum ResponseAction {
+    Idempotency(PendingKey, Duration),
+    Etag(EtagRequest),
+    /// 插件只修改请求头，方法与路径留给 on_response 使用
+    Plugins {
+        names: Vec<String>,
+        method: String,
+        path: String,
+    },
+    Annotate,
+    /// 添加响应头，如实验分组
+    Header(String, String),
+}
+
+impl Pipeline {
+    /// 按固定顺序为规则选项生成步骤，实验或故障注入设置无效、脚本无法编译时返回错误
+    pub fn build(options: &RuleOptions) -> anyhow::Result<Self> {
+        let mut steps = Vec::new();
+        if let Some(ref experiment) = options.experiment {
+            experiment
+                .validate()
+                .map_err(|e| anyhow::anyhow!("invalid experiment: {}", e))?;
+            steps.push(Step::Experiment(experiment.clone()));
+        }
+        if let Some(ref rule_auth) = options.auth {
+            steps.push(Step::Auth(rule_auth.clone()));
+        }
+        if let Some(ttl) = options.idempotency_ttl_secs {
+            steps.push(Step::Idempotency(Duration::from_secs(ttl)));
+        }
+        if options.generate_etag {
+            steps.push(Step::Etag);
+        }
+        if options.annotate_upstream {
+            steps.push(Step::AnnotateUpstream);
+        }
+        if options.identity_headers {
+            steps.push(Step::Identity);
+        }
+        if let Some(format) = options.client_cert_headers {
+            steps.push(Step::ClientCert(format));
+        }
+        steps.push(Step::Hooks);
+        if !options.plugins.is_empty() {
+            steps.push(Step::Plugins(options.plugins.clone()));
+        }
+        if let Some(ref script) = options.script {
+            let script = RuleScript::compile(script)
+                .map_err(|e| anyhow::anyhow!("invalid script: {}", e))?;
+            steps.push(Step::Script(script));
+        }
+        if let Some(ref upstream_auth) = options.upstream_auth {
+            steps.push(Step::UpstreamAuth(upstream_auth.clone()));
+        }
+        if let Some(ref fault) = options.fault {
+            fault
+                .validate()
+                .map_err(|e| anyhow::anyhow!("invalid fault: {}", e))?;
+            steps.push(Step::Fault(fault.clone()));
+        }
+        if options.annotate_response {
+            steps.push(Step::AnnotateResponse);
+        }
+        Ok(Self { steps })
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// 依次执行请求阶段，步骤可修改请求与上游地址
+    pub async fn on_request(
+        &self,
+        ctx: &StepContext<'_>,
+        req: &mut Request,
+        target_url: &mut String,
+    ) -> Flow {
+        let mut phase = ResponsePhase::default();
+        for step in &self.steps {
+            match step.on_request(ctx, req, target_url, &mut phase).await {
+                Ok(None) => {}
+                Ok(Some(resp)) => return Flow::Respond(Ok(resp)),
+                Err(status) => return Flow::Respond(Err(status)),
+            }
+        }
+        Flow::Forward(phase)
+    }
+}
+
+impl Step {
+    /// 步骤名，用于日志与规则模拟
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Experiment(_) => "experiment",
+            Self::Auth(_) => "auth",
+            Self::Idempotency(_) => "idempotency",
+            Self::Etag => "etag",
+            Self::AnnotateUpstream => "annotate_upstream",
+            Self::Identity => "identity",
+            Self::ClientCert(_) => "client_cert",
+            Self::Hooks => "hooks",
+            Self::Plugins(_) => "plugins",
+            Self::Script(_) => "script",
+            Self::UpstreamAuth(_) => "upstream_auth",
+            Self::Fault(_) => "fault",
+            Self::AnnotateResponse => "annotate_response",
+        }
+    }
+
+    async fn on_request(
+        &self,
+        ctx: &StepContext<'_>,
+        req: &mut Request,
+        target_url: &mut String,
+        phase: &mut ResponsePhase,
+    ) -> Result<Option<Response>, StatusCode> {
+        let rule = ctx.rule;
+        match self {
+            Self::Experiment(experiment) => {
+                let variant = experiment.select(req.headers());
+                if let Some(ref template) = variant.target {
+                    let Some(mut target) = rule.build_target(req.uri().path(), template) else {
+                        return Err(StatusCode::NOT_FOUND);
+                    };
+                    if let Some(q) = req.uri().query() {
+                        target.push('?');
+                        target.push_str(q);
+                    }
+                    *target_url = target;
+                }
+                tracing::debug!(rule = %rule.name, experiment = %experiment.name, bucket = %variant.name, "Experiment bucket selected");
+                phase.actions.push(ResponseAction::Header(
+                    experiment.response_header.clone(),
+                    variant.name.clone(),
+                ));
+            }
+            Self::Auth(rule_auth) => {
+                match rule_auth.authenticate(
+                    req.headers_mut(),
+                    rule.id,
+                    &ctx.state.proxy_keys,
+                    &ctx.state.secrets,
+                ) {
+                    Some(principal) => {
+                        tracing::debug!(rule = %rule.name, principal = %principal, "Rule authentication passed")
+                    }
+                    None => {
+                        tracing::warn!(rule = %rule.name, client_ip = %ctx.client_ip, "Rule authentication failed");
+                        return Ok(Some(rule_auth.challenge(&rule.name)));
+                    }
+                }
+            }
+            Self::Idempotency(ttl) => {
+                if let Some(key) = IdempotencyCache::key_for(
+                    rule.id,
+                    req.method(),
+                    req.uri().path(),
+                    req.headers(),
+                ) {
+                    match ctx.state.idempotency.begin(key) {
+                        Lookup::Hit(resp) => return Ok(Some(resp)),
+                        Lookup::InFlight => return Err(StatusCode::CONFLICT),
+                        Lookup::NotReplayable => return Err(StatusCode::UNPROCESSABLE_ENTITY),
+                        Lookup::Miss(pending) => phase
+                            .actions
+                            .push(ResponseAction::Idempotency(pending, *ttl)),
+                    }
+                }
+            }
+            Self::Etag => {
+                if let Some(etag_request) = EtagRequest::from_request(req.method(), req.headers()) {
+                    phase.actions.push(ResponseAction::Etag(etag_request));
+                }
+            }
+            Self::AnnotateUpstream => annotate_headers(req.headers_mut(), rule, target_url),
+            Self::Identity => {
+                let Some(session) = ctx
+                    .state
+                    .auth
+                    .cookie_session(req.headers(), Some(ctx.client_addr.ip()))
+                else {
+                    tracing::warn!(rule = %rule.name, client_ip = %ctx.client_ip, "Identity-aware rule requires a session");
+                    return Err(StatusCode::UNAUTHORIZED);
+                };
+                auth::strip_session_cookies(req.headers_mut());
+                ctx.state
+                    .identity
+                    .apply(req.headers_mut(), &session, &rule.name);
+            }
+            Self::ClientCert(format) => {
+                let cert = req.extensions().get::<ClientCert>().cloned();
+                tls::apply_client_cert_headers(req.headers_mut(), cert.as_ref(), *format);
+            }
+            Self::Hooks => {
+                if !ctx.state.hooks.is_empty() {
+                    let hook_ctx = HookContext {
+                        client_addr: ctx.client_addr,
+                        method: req.method().clone(),
+                        path: req.uri().path().to_string(),
+                        rule: Some(rule.name.clone()),
+                        target: Some(target_url.clone()),
+                    };
+                    return Ok(ctx.state.hooks.on_request(&hook_ctx, req));
+                }
+            }
+            Self::Plugins(names) => {
+                if let Some(resp) =
+                    ctx.state
+                        .plugins
+                        .on_request(names, &rule.name, ctx.client_ip, req)?
+                {
+                    return Ok(Some(resp));
+                }
+                phase.actions.push(ResponseAction::Plugins {
+                    names: names.clone(),
+                    method: req.method().to_string(),
+                    path: req.uri().path().to_string(),
+                });
+            }
+            Self::Script(script) => {
+                return script.run(&rule.name, ctx.client_ip, req, target_url).await;
+            }
+            Self::UpstreamAuth(upstream_auth) => {
+                if let Err(e) = upstream_auth.apply(req.headers_mut(), &ctx.state.secrets) {
+                    tracing::error!(rule = %rule.name, "Failed to apply upstream credentials: {}", e);
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            }
+            Self::Fault(fault) => {
+                return Ok(fault.inject(&ctx.state.faults, req, &rule.name).await);
+            }
+            Self::AnnotateResponse => phase.actions.push(ResponseAction::Annotate),
+        }
+        Ok(None)
+    }
+}
+
+impl ResponsePhase {
+    /// 按请求阶段登记的顺序处理上游响应
+    pub async fn apply(
+        self,
+        ctx: &StepContext<'_>,
+        target_url: &str,
+        mut result: Result<Response, StatusCode>,
+    ) -> Result<Response, StatusCode> {
+        let rule = ctx.rule;
+        for action in self.actions {
+            result = match action {
+                ResponseAction::Idempotency(pending, ttl) => pending.complete(result, ttl).await,
+                ResponseAction::Etag(etag_request) => etag::apply(result, etag_request).await,
+                ResponseAction::Plugins {
+                    names,
+                    method,
+                    path,
+                } => result.and_then(|mut resp| {
+                    ctx.state
+                        .plugins
+                        .on_response(&names, &rule.name, &method, &path, &mut resp)
+                        .map(|()| resp)
+                }),
+                ResponseAction::Annotate => result.map(|mut resp| {
+                    annotate_headers(resp.headers_mut(), rule, target_url);
+                    resp
+                }),
+                ResponseAction::Header(name, value) => result.map(|mut resp| {
+                    if let (Ok(name), Ok(value)) =
+                        (HeaderName::try_from(name), HeaderValue::try_from(value))
+                    {
+                        resp.headers_mut().insert(name, value);
+                    }
+                    resp
+                }),
+            };
+        }
+        result
+    }
+}
@@ -0,0 +1,312 @@
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use wasmtime::{Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::config::PluginConfig;
+
+const HOOK_REQUEST: &str = "on_request";
+const HOOK_RESPONSE: &str = "on_response";
+
+/// 规则可引用的 WASM 插件
+///
+/// 模块不能有导入，需导出 `memory` 与 `alloc(len: i32) -> i32`，以及钩子
+/// `on_request(ptr: i32, len: i32) -> i64` / `on_response(ptr: i32, len: i32) -> i64` 中的任意几个。
+/// 代理把 JSON 输入写入 `alloc` 分配的内存后调用钩子，钩子返回 0 表示不做修改，
+/// 否则返回 `(ptr << 32) | len` 指向输出的 JSON。每次调用使用新的实例，受指令预算与内存上限限制
+#[derive(Clone, Default)]
+pub struct Plugins {
+    plugins: Arc<HashMap<String, Plugin>>,
+}
+
+struct Plugin {
+    pre: InstancePre<StoreLimits>,
+    fuel: u64,
+    max_memory: usize,
+    on_request: bool,
+    on_response: bool,
+}
+
+/// 钩子输入中的请求信息，同名请求头以 ", " 合并
+#[derive(Serialize)]
+struct RequestInput<'a> {
+    rule: &'a str,
+    client_ip: &'a str,
+    method: &'a str,
+    path: &'a str,
+    query: Option<&'a str>,
+    headers: BTreeMap<&'a str, String>,
+}
+
+#[derive(Serialize)]
+struct ResponseInput<'a> {
+    rule: &'a str,
+    method: &'a str,
+    path: &'a str,
+    status: u16,
+    headers: BTreeMap<&'a str, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RequestOutput {
+    set_headers: HashMap<String, String>,
+    remove_headers: Vec<String>,
+    /// 直接返回的响应，不再转发
+    response: Option<ShortCircuit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShortCircuit {
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ResponseOutput {
+    status: Option<u16>,
+    set_headers: HashMap<String, String>,
+    remove_headers: Vec<String>,
+}
+
+impl Plugins {
+    /// 编译配置中的全部模块，任一模块无效时返回错误
+    pub fn load(configs: &HashMap<String, PluginConfig>) -> anyhow::Result<Self> {
+        if configs.is_empty() {
+            return Ok(Self::default());
+        }
+        let mut engine_config = wasmtime::Config::new();
+        engine_config.consume_fuel(true);
+        let engine = Engine::new(&engine_config)?;
+        let linker = Linker::new(&engine);
+
+        let mut plugins = HashMap::new();
+        for (name, config) in configs {
+            let module = Module::from_file(&engine, &config.path)
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("failed to load plugin {} from {}", name, config.path))?;
+            for export in ["memory", "alloc"] {
+                if module.get_export(export).is_none() {
+                    anyhow::bail!("plugin {} does not export {}", name, export);
+                }
+            }
+            let on_request = module.get_export(HOOK_REQUEST).is_some();
+            let on_response = module.get_export(HOOK_RESPONSE).is_some();
+            let pre = linker
+                .instantiate_pre(&module)
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("plugin {} must not have imports", name))?;
+            tracing::info!(plugin = %name, on_request, on_response, "WASM plugin loaded");
+            plugins.insert(
+                name.clone(),
+                Plugin {
+                    pre,
+                    fuel: config.fuel,
+                    max_memory: (config.max_memory_mb * 1024 * 1024) as usize,
+                    on_request,
+                    on_response,
+                },
+            );
+        }
+        Ok(Self {
+            plugins: Arc::new(plugins),
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.plugins.contains_key(name)
+    }
+
+    /// 按顺序执行规则插件的 `on_request`，修改请求头；插件返回响应时不再执行后续插件与转发。
+    /// 插件不存在或执行失败时返回 500
+    pub fn on_request(
+        &self,
+        names: &[String],
+        rule: &str,
+        client_ip: &str,
+        req: &mut Request,
+    ) -> Result<Option<Response>, StatusCode> {
+        for name in names {
+            let plugin = self.get(name)?;
+            if !plugin.on_request {
+                continue;
+            }
+            let input = RequestInput {
+                rule,
+                client_ip,
+                method: req.method().as_str(),
+                path: req.uri().path(),
+                query: req.uri().query(),
+                headers: header_map(req.headers()),
+            };
+            let output: Option<RequestOutput> = plugin
+                .call(HOOK_REQUEST, &input)
+                .map_err(|e| failed(name, HOOK_REQUEST, e))?;
+            let Some(output) = output else {
+                continue;
+            };
+            if let Some(resp) = output.response {
+                tracing::debug!(plugin = %name, rule = %rule, status = resp.status, "Plugin short-circuited request");
+                return resp
+                    .into_response()
+                    .map(Some)
+                    .map_err(|e| failed(name, HOOK_REQUEST, e));
+            }
+            apply_headers(
+                req.headers_mut(),
+                &output.set_headers,
+                &output.remove_headers,
+            )
+            .map_err(|e| failed(name, HOOK_REQUEST, e))?;
+        }
+        Ok(None)
+    }
+
+    /// 按顺序执行规则插件的 `on_response`，可修改状态码与响应头
+    pub fn on_response(
+        &self,
+        names: &[String],
+        rule: &str,
+        method: &str,
+        path: &str,
+        resp: &mut Response,
+    ) -> Result<(), StatusCode> {
+        for name in names {
+            let plugin = self.get(name)?;
+            if !plugin.on_response {
+                continue;
+            }
+            let input = ResponseInput {
+                rule,
+                method,
+                path,
+                status: resp.status().as_u16(),
+                headers: header_map(resp.headers()),
+            };
+            let output: Option<ResponseOutput> = plugin
+                .call(HOOK_RESPONSE, &input)
+                .map_err(|e| failed(name, HOOK_RESPONSE, e))?;
+            let Some(output) = output else {
+                continue;
+            };
+            if let Some(status) = output.status {
+                *resp.status_mut() = StatusCode::from_u16(status)
+                    .map_err(|e| failed(name, HOOK_RESPONSE, e.into()))?;
+            }
+            apply_headers(
+                resp.headers_mut(),
+                &output.set_headers,
+                &output.remove_headers,
+            )
+            .map_err(|e| failed(name, HOOK_RESPONSE, e))?;
+        }
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Result<&Plugin, StatusCode> {
+        self.plugins.get(name).ok_or_else(|| {
+            tracing::error!(plugin = %name, "Rule references an unknown plugin");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+}
+
+impl Plugin {
+    /// 新建实例执行一次钩子，返回 0 时为 None
+    fn call<I: Serialize, O: for<'de> Deserialize<'de>>(
+        &self,
+        hook: &str,
+        input: &I,
+    ) -> anyhow::Result<Option<O>> {
+        let input = serde_json::to_vec(input)?;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory)
+            .instances(1)
+            .build();
+        let mut store = Store::new(self.pre.module().engine(), limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel)?;
+
+        let instance = self.pre.instantiate(&mut store)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("memory export is not a memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let hook = instance.get_typed_func::<(i32, i32), i64>(&mut store, hook)?;
+
+        let len = i32::try_from(input.len()).context("input too large")?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as u32 as usize, &input)?;
+        let packed = hook.call(&mut store, (ptr, len))? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let output = memory
+            .data(&store)
+            .get(ptr..ptr.saturating_add(len))
+            .context("output out of bounds")?;
+        Ok(Some(
+            serde_json::from_slice(output).context("invalid output JSON")?,
+        ))
+    }
+}
+
+impl ShortCircuit {
+    fn into_response(self) -> anyhow::Result<Response> {
+        let mut resp = Response::new(Body::from(self.body));
+        *resp.status_mut() = StatusCode::from_u16(self.status)?;
+        apply_headers(resp.headers_mut(), &self.headers, &[])?;
+        Ok(resp)
+    }
+}
+
+fn header_map(headers: &HeaderMap) -> BTreeMap<&str, String> {
+    let mut map: BTreeMap<&str, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        map.entry(name.as_str())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+fn apply_headers(
+    headers: &mut HeaderMap,
+    set: &HashMap<String, String>,
+    remove: &[String],
+) -> anyhow::Result<()> {
+    for name in remove {
+        headers.remove(name.as_str());
+    }
+    for (name, value) in set {
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    Ok(())
+}
+
+fn failed(plugin: &str, hook: &str, e: anyhow::Error) -> StatusCode {
+    tracing::error!(plugin = %plugin, hook = %hook, "Plugin call failed: {:#}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
@@ -0,0 +1,173 @@
+use axum::{extract::State, Json};
+use dashmap::DashMap;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::api::ApiResponse;
+use crate::upstreams::upstream_of;
+use crate::AdminState;
+
+#[derive(Default)]
+struct HostConnections {
+    opened: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// 规则代理客户端按上游主机统计的新建连接数，由连接器层记录
+///
+/// reqwest 不公开连接池内部状态，空闲连接数无法直接读取；
+/// 新建连接数与请求数之比反映连接复用情况
+#[derive(Clone, Default)]
+pub struct ConnectionStats {
+    hosts: Arc<DashMap<String, HostConnections>>,
+}
+
+impl ConnectionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加到 reqwest 客户端的连接器层
+    pub fn layer(&self) -> CountConnectionsLayer {
+        CountConnectionsLayer {
+            stats: self.clone(),
+        }
+    }
+
+    fn record(&self, upstream: String, ok: bool) {
+        let host = self.hosts.entry(upstream).or_default();
+        let counter = if ok { &host.opened } else { &host.failed };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self, upstream: &str) -> (u64, u64) {
+        self.hosts.get(upstream).map_or((0, 0), |host| {
+            (
+                host.opened.load(Ordering::Relaxed),
+                host.failed.load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct CountConnectionsLayer {
+    stats: ConnectionStats,
+}
+
+impl<S> tower::Layer<S> for CountConnectionsLayer {
+    type Service = CountConnections<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CountConnections {
+            inner,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CountConnections<S> {
+    inner: S,
+    stats: ConnectionStats,
+}
+
+/// reqwest 的连接请求类型不可命名，只能从其 Debug 输出 `Unnameable(uri)` 中取出目标地址
+impl<S, R> tower::Service<R> for CountConnections<S>
+where
+    S: tower::Service<R>,
+    S::Future: Send + 'static,
+    R: Debug,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let debug = format!("{:?}", req);
+        let uri = debug
+            .split_once('(')
+            .and_then(|(_, rest)| rest.strip_suffix(')'))
+            .unwrap_or(&debug);
+        let upstream = upstream_of(uri).unwrap_or_else(|| "unknown".to_string());
+        let stats = self.stats.clone();
+        let connecting = self.inner.call(req);
+        Box::pin(async move {
+            let result = connecting.await;
+            stats.record(upstream, result.is_ok());
+            result
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UpstreamConnections {
+    pub upstream: String,
+    /// 经代理转发的请求数（目标主机不含路径参数的规则）
+    pub requests: u64,
+    /// 正在进行的请求，即正在使用的连接数
+    pub in_flight: usize,
+    /// 新建的连接数
+    pub connections_opened: u64,
+    pub connect_failures: u64,
+    /// 复用已有连接的请求比例，没有请求时为空
+    pub reuse_ratio: Option<f64>,
+    /// 规则 `pool.max_connections` 的上限与已占用的许可
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permits_in_use: Option<usize>,
+}
+
+/// 各上游主机的连接统计
+pub async fn status_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<UpstreamConnections>>> {
+    let mut in_flight: BTreeMap<String, usize> = BTreeMap::new();
+    for request in state.active.snapshot() {
+        if let Some(upstream) = request.target.as_deref().and_then(upstream_of) {
+            *in_flight.entry(upstream).or_default() += 1;
+        }
+    }
+    let limits = state.upstream_limits.snapshot();
+
+    let mut upstreams: BTreeSet<String> = state
+        .pool_stats
+        .hosts
+        .iter()
+        .map(|entry| entry.key().clone())
+        .collect();
+    upstreams.extend(state.upstreams.hosts());
+    upstreams.extend(in_flight.keys().cloned());
+
+    Json(ApiResponse::ok(
+        upstreams
+            .into_iter()
+            .map(|upstream| {
+                let requests = state.upstreams.requests(&upstream);
+                let (opened, failed) = state.pool_stats.get(&upstream);
+                let limit = limits.get(&upstream);
+                UpstreamConnections {
+                    requests,
+                    in_flight: in_flight.get(&upstream).copied().unwrap_or(0),
+                    connections_opened: opened,
+                    connect_failures: failed,
+                    reuse_ratio: (requests > 0)
+                        .then(|| 1.0 - (opened.min(requests) as f64 / requests as f64)),
+                    max_connections: limit.map(|(max, _)| *max),
+                    permits_in_use: limit.map(|(_, in_use)| *in_use),
+                    upstream,
+                }
+            })
+            .collect(),
+    ))
+}
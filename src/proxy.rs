@@ -1,42 +1,163 @@
 use arc_swap::ArcSwap;
 use axum::{
-    body::Body,
+    body::{Body, HttpBody},
     extract::{ConnectInfo, Request, State},
-    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     response::Response,
 };
-use futures::StreamExt;
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::{future::BoxFuture, stream::BoxStream, Stream, StreamExt};
+use http_body_util::{BodyExt, Full, LengthLimitError};
+use hyper::body::Incoming;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::rt::TokioExecutor;
 use regex::Regex;
-use reqwest::Client;
+use reqwest::{Client, ClientBuilder};
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
 
-use crate::db::ProxyRule;
+use crate::access_log::{AccessLogEntry, AccessLogger, LogDecision};
+use crate::auth::AuthState;
+use crate::cache_store::CacheStore;
+use crate::config::ProxyConfig;
+use crate::connections::{ActiveRequest, ActiveRequests};
+use crate::db::{Database, ProxyRule, RuleOptions};
+use crate::direct_cache::{self, DirectCache};
+use crate::discovery::ServiceDiscovery;
+use crate::dns::{self, PoolSettings, UpstreamClients, UpstreamDns};
+use crate::faults::FaultSwitch;
+use crate::forward_proxy::ForwardProxied;
+use crate::goproxy;
+use crate::ha::HaState;
+use crate::hooks::{HookContext, ProxyHooks};
+use crate::idempotency::IdempotencyCache;
+use crate::identity::IdentityAssertions;
+use crate::maven;
+use crate::mirror::CacheQuotas;
+use crate::npm;
+use crate::packages;
+use crate::pipeline::{Flow, Pipeline, StepContext};
+use crate::plugins::Plugins;
+use crate::pool_stats::ConnectionStats;
+use crate::proxy_protocol::UpstreamConnector;
+use crate::pypi;
+use crate::redact;
+use crate::registry::{self, RegistryMirror};
+use crate::reloads::ReloadHistory;
+use crate::rolling::RollingStats;
+use crate::router::RuleSet;
+use crate::rule_auth::ProxyKeys;
+use crate::secrets::Secrets;
+use crate::signed_urls::SignedUrls;
+use crate::stats::{RuleCounters, RuleStats};
+use crate::target_guard::{GuardedResolver, TargetGuard};
+use crate::tasks::TaskRegistry;
+use crate::telemetry::{self, TraceParent};
+use crate::traffic::{TrafficEvent, TrafficTail};
+use crate::upstream_proxy::UpstreamProxies;
+use crate::upstreams::{self, UpstreamHealth, UpstreamLimits, UpstreamProbes};
+
+/// 代理监听器的处理范围，由监听器写入请求扩展；请求没有该扩展时处理全部规则与直接代理，
+/// 管理接口修改监听器时原地替换，已建立的连接随之生效
+#[derive(Debug, Clone)]
+pub struct ListenerScope {
+    settings: Arc<ArcSwap<ScopeSettings>>,
+}
+
+#[derive(Debug)]
+struct ScopeSettings {
+    rule_tags: Vec<String>,
+    direct_proxy: bool,
+}
+
+impl ListenerScope {
+    pub fn new(config: &ProxyConfig) -> Self {
+        Self {
+            settings: Arc::new(ArcSwap::from_pointee(ScopeSettings::new(config))),
+        }
+    }
+
+    pub fn update(&self, config: &ProxyConfig) {
+        self.settings.store(Arc::new(ScopeSettings::new(config)));
+    }
+
+    /// 监听器是否提供直接代理（含正向代理）
+    pub fn direct_proxy(&self) -> bool {
+        self.settings.load().direct_proxy
+    }
+
+    fn load(&self) -> Arc<ScopeSettings> {
+        self.settings.load_full()
+    }
+}
+
+impl ScopeSettings {
+    fn new(config: &ProxyConfig) -> Self {
+        Self {
+            rule_tags: config.rule_tags.clone(),
+            direct_proxy: config.direct_proxy,
+        }
+    }
+
+    fn allows(&self, rule: &CompiledProxyRule) -> bool {
+        self.rule_tags.is_empty()
+            || rule
+                .options
+                .tags
+                .iter()
+                .any(|tag| self.rule_tags.contains(tag))
+    }
+}
 
 /// 编译后的代理规则
 #[derive(Debug, Clone)]
 pub struct CompiledProxyRule {
+    pub id: i64,
+    pub name: String,
     pub source_pattern: Regex,
+    /// 来源路径中第一个参数之前的部分，不含参数时为整个来源路径
+    pub static_prefix: String,
     pub target_template: String,
     pub param_names: Vec<String>,
     pub timeout: Duration,
+    pub options: RuleOptions,
+    /// 上游地址（scheme://host:port），主机部分含参数时为 None
+    pub upstream: Option<String>,
+    /// 按选项生成的请求处理步骤
+    pub pipeline: Pipeline,
+    /// 已匹配的请求数，用于日志采样
+    pub log_counter: Arc<AtomicU64>,
 }
 
 impl CompiledProxyRule {
-    pub fn from_db_rule(rule: &ProxyRule) -> Result<Self, regex::Error> {
-        let (pattern, param_names) = Self::compile_pattern(&rule.source);
+    pub fn from_db_rule(rule: &ProxyRule) -> anyhow::Result<Self> {
+        // 镜像规则的通配参数可以为空，使 /v2/{*path} 同时匹配客户端探测用的 /v2/
+        let (pattern, param_names) =
+            Self::compile_pattern(&rule.source, rule.options.registry.is_some());
         let regex = Regex::new(&pattern)?;
 
         Ok(Self {
+            id: rule.id,
+            name: rule.name.clone(),
             source_pattern: regex,
+            static_prefix: rule.source[..rule.source.find('{').unwrap_or(rule.source.len())]
+                .to_string(),
             target_template: rule.target.clone(),
             param_names,
             timeout: Duration::from_secs(rule.timeout_secs),
+            options: rule.options.clone(),
+            upstream: upstreams::upstream_of(&rule.target),
+            pipeline: Pipeline::build(&rule.options)?,
+            log_counter: Arc::default(),
         })
     }
 
-    fn compile_pattern(source: &str) -> (String, Vec<String>) {
+    fn compile_pattern(source: &str, empty_wildcard: bool) -> (String, Vec<String>) {
         let mut pattern = String::from("^");
         let mut param_names = Vec::new();
         let mut last_end = 0;
@@ -50,7 +171,9 @@ impl CompiledProxyRule {
 
             pattern.push_str(&regex::escape(&source[last_end..full_match.start()]));
 
-            if is_wildcard {
+            if is_wildcard && empty_wildcard {
+                pattern.push_str("(.*)");
+            } else if is_wildcard {
                 pattern.push_str("(.+)");
             } else {
                 pattern.push_str("([^/]+)");
@@ -70,10 +193,61 @@ impl CompiledProxyRule {
         (pattern, param_names)
     }
 
+    #[inline]
+    pub fn matches(&self, path: &str) -> bool {
+        match self.param_names.is_empty() {
+            true => self.matches_static(path),
+            false => self.source_pattern.is_match(path),
+        }
+    }
+
+    /// 来源路径不含参数时直接比较字符串，与正则 `^source(?:\?.*)?$` 等价
+    fn matches_static(&self, path: &str) -> bool {
+        path.strip_prefix(self.static_prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('?'))
+    }
+
+    /// 按请求体长度判断是否匹配本规则，`len` 为 None 表示长度未知
+    pub fn matches_request_size(&self, len: Option<u64>) -> bool {
+        let (min, max) = (
+            self.options.min_request_bytes,
+            self.options.max_request_bytes,
+        );
+        if min.is_none() && max.is_none() {
+            return true;
+        }
+        match len {
+            Some(len) => min.is_none_or(|min| len >= min) && max.is_none_or(|max| len <= max),
+            None => self.options.match_unknown_length,
+        }
+    }
+
+    /// 规则的连接池设置
+    #[inline]
+    pub fn pool_settings(&self) -> PoolSettings {
+        let pool = self.options.pool.as_ref();
+        PoolSettings {
+            family: self.options.ip_family,
+            connect_timeout: self.options.connect_timeout_secs.map(Duration::from_secs),
+            max_idle_per_host: pool.and_then(|pool| pool.max_idle_per_host),
+            idle_timeout: pool
+                .and_then(|pool| pool.idle_timeout_secs)
+                .map(Duration::from_secs),
+        }
+    }
+
     #[inline]
     pub fn match_and_build_target(&self, path: &str) -> Option<String> {
+        self.build_target(path, &self.target_template)
+    }
+
+    /// 用路径参数填充指定的目标模板，路径不匹配时返回 None
+    pub fn build_target(&self, path: &str, template: &str) -> Option<String> {
+        if self.param_names.is_empty() {
+            return self.matches_static(path).then(|| template.to_string());
+        }
         self.source_pattern.captures(path).map(|caps| {
-            let mut target = self.target_template.clone();
+            let mut target = template.to_string();
             for (i, param_name) in self.param_names.iter().enumerate() {
                 if let Some(value) = caps.get(i + 1) {
                     target = target.replace(param_name, value.as_str());
@@ -84,25 +258,415 @@ impl CompiledProxyRule {
     }
 }
 
+/// 仅 HTTP/1 的底层客户端，按请求扩展中记录的原始大小写写出请求头
+pub type RawClient =
+    hyper_util::client::legacy::Client<HttpsConnector<HttpConnector<UpstreamDns>>, Full<Bytes>>;
+
+/// 高性能 HTTP 客户端的公共配置
+pub fn http_client_builder() -> ClientBuilder {
+    Client::builder()
+        .pool_max_idle_per_host(200)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .tcp_nodelay(true)
+        .http2_keep_alive_interval(Duration::from_secs(30))
+        .http2_keep_alive_timeout(Duration::from_secs(10))
+        .gzip(true)
+        .brotli(true)
+        .deflate(true)
+        .connect_timeout(Duration::from_secs(10))
+}
+
+/// 规则代理客户端：上游域名 TTL 过期且解析结果变化时重建连接池，新建连接计入 `connections`
+pub fn build_upstream_client(
+    tasks: &TaskRegistry,
+    upstream_dns: &UpstreamDns,
+    connections: &ConnectionStats,
+) -> anyhow::Result<UpstreamClients> {
+    let connections = connections.clone();
+    let clients = UpstreamClients::new(upstream_dns.clone(), move |dns, settings| {
+        let mut builder = http_client_builder()
+            .dns_resolver(Arc::new(dns))
+            .connector_layer(connections.layer());
+        if let Some(timeout) = settings.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(max_idle) = settings.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = settings.idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        builder.build()
+    })?;
+    dns::start_refresh_task(tasks, clients.clone());
+    Ok(clients)
+}
+
+/// 直接代理客户端：校验解析出的地址与每次重定向，防止 SSRF
+pub fn build_direct_client(guard: &Arc<TargetGuard>) -> reqwest::Result<Client> {
+    http_client_builder()
+        .dns_resolver(Arc::new(GuardedResolver::new(guard.clone())))
+        .redirect(guard.redirect_policy())
+        .build()
+}
+
+/// 保留请求头大小写的底层客户端，连接超时在连接器上设置，因此按规则的连接超时分别建立
+#[derive(Clone)]
+pub struct RawClients {
+    upstream_dns: UpstreamDns,
+    clients: Arc<DashMap<Option<Duration>, RawClient>>,
+}
+
+impl RawClients {
+    pub fn new(upstream_dns: &UpstreamDns) -> Self {
+        Self {
+            upstream_dns: upstream_dns.clone(),
+            clients: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn get(&self, connect_timeout: Option<Duration>) -> anyhow::Result<RawClient> {
+        if let Some(client) = self.clients.get(&connect_timeout) {
+            return Ok(client.clone());
+        }
+        let client = build_raw_client(
+            &self.upstream_dns,
+            connect_timeout.unwrap_or(Duration::from_secs(10)),
+        )?;
+        self.clients.insert(connect_timeout, client.clone());
+        Ok(client)
+    }
+}
+
+fn build_raw_client(
+    upstream_dns: &UpstreamDns,
+    connect_timeout: Duration,
+) -> anyhow::Result<RawClient> {
+    let mut http = HttpConnector::new_with_resolver(upstream_dns.clone());
+    http.enforce_http(false);
+    http.set_nodelay(true);
+    http.set_keepalive(Some(Duration::from_secs(60)));
+    http.set_connect_timeout(Some(connect_timeout));
+
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_provider_and_webpki_roots(tokio_rustls::rustls::crypto::ring::default_provider())?
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http);
+
+    Ok(
+        hyper_util::client::legacy::Client::builder(TokioExecutor::new())
+            .pool_max_idle_per_host(200)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .http1_preserve_header_case(true)
+            .build(https),
+    )
+}
+
 /// 代理服务状态 - 使用 ArcSwap 实现无锁读取
 #[derive(Clone)]
 pub struct ProxyState {
-    pub client: Client,
-    pub rules: Arc<ArcSwap<Vec<CompiledProxyRule>>>,
+    /// 规则代理客户端，按 IP 地址族区分连接池，上游域名解析结果变化时整体替换
+    pub client: UpstreamClients,
+    /// 保留请求头大小写的规则使用的底层客户端
+    pub raw_clients: RawClients,
+    /// 规则代理客户端共用的上游解析缓存
+    pub upstream_dns: UpstreamDns,
+    /// 开启 upstream_proxy_protocol 的规则使用的上游连接器
+    pub proxy_protocol: UpstreamConnector,
+    /// 配置了 upstream_proxy 的规则按代理地址共用的客户端
+    pub upstream_proxies: UpstreamProxies,
+    /// 直接代理专用客户端，DNS 解析与重定向均经过 `direct_guard` 校验
+    pub direct_client: Client,
+    pub direct_guard: Arc<TargetGuard>,
+    /// 嵌入方注册的请求钩子
+    pub hooks: ProxyHooks,
+    pub rules: Arc<ArcSwap<RuleSet>>,
     pub direct_proxy_path: Arc<ArcSwap<String>>,
+    /// 就绪检查使用
+    pub db: Database,
     pub default_timeout: Duration,
+    /// 缓冲转发的请求体上限(字节)，规则未设置 max_body_bytes 时使用
+    pub max_body_bytes: u64,
+    pub stats: RuleStats,
+    pub idempotency: IdempotencyCache,
+    pub tasks: TaskRegistry,
+    pub access_log: Option<AccessLogger>,
+    pub traffic: TrafficTail,
+    pub rolling: RollingStats,
+    pub reloads: ReloadHistory,
+    pub upstreams: UpstreamHealth,
+    /// `required` 规则上游的可达性，就绪检查使用
+    pub upstream_probes: UpstreamProbes,
+    /// 规则 `pool.max_connections` 的上游并发许可
+    pub upstream_limits: UpstreamLimits,
+    /// 规则目标 `consul://`、`etcd://` 的服务实例
+    pub discovery: ServiceDiscovery,
+    pub signed_urls: SignedUrls,
+    pub ha: HaState,
+    pub auth: AuthState,
+    pub identity: IdentityAssertions,
+    /// 规则 API Key 认证使用的密钥缓存
+    pub proxy_keys: ProxyKeys,
+    pub active: ActiveRequests,
+    pub secrets: Secrets,
+    pub registry: RegistryMirror,
+    /// 镜像与下载缓存的存储后端
+    pub cache_store: Arc<dyn CacheStore>,
+    /// 镜像缓存目录的容量检查状态
+    pub cache_quotas: CacheQuotas,
+    /// 直接代理的下载缓存
+    pub direct_cache: DirectCache,
+    /// 规则可引用的 WASM 插件
+    pub plugins: Plugins,
+    /// 故障注入全局开关
+    pub faults: FaultSwitch,
+    /// 首次成功加载规则后置为 true
+    pub rules_ready: Arc<AtomicBool>,
+}
+
+impl ProxyState {
+    /// 规则使用的连接池客户端，按规则的 ip_family 与连接超时选择；配置了出站代理时为该代理的客户端
+    pub fn rule_client(&self, rule: &CompiledProxyRule) -> Result<Arc<Client>, StatusCode> {
+        let Some(ref options) = rule.options.upstream_proxy else {
+            return self.client.get(rule.pool_settings()).map_err(|e| {
+                tracing::error!(rule = %rule.name, "Failed to build upstream client: {}", e);
+                StatusCode::BAD_GATEWAY
+            });
+        };
+        self.upstream_proxies
+            .client(options, &self.secrets, rule.pool_settings().connect_timeout)
+            .map_err(|e| {
+                tracing::error!(rule = %rule.name, "Failed to build upstream proxy client: {}", e);
+                StatusCode::BAD_GATEWAY
+            })
+    }
+
+    /// 规则使用的底层 HTTP/1 客户端，按规则的连接超时选择
+    pub fn raw_client(&self, rule: &CompiledProxyRule) -> Result<RawClient, StatusCode> {
+        self.raw_clients
+            .get(rule.pool_settings().connect_timeout)
+            .map_err(|e| {
+                tracing::error!(rule = %rule.name, "Failed to build upstream client: {}", e);
+                StatusCode::BAD_GATEWAY
+            })
+    }
+}
+
+/// 规则未就绪时建议客户端重试的间隔(秒)
+const NOT_READY_RETRY_AFTER: &str = "5";
+
+/// 请求路由结果，供链路追踪与访问日志使用
+#[derive(Debug, Default)]
+struct RequestMeta {
+    rule: Option<String>,
+    target: Option<String>,
+    /// 进行中请求列表中的登记
+    active: Option<ActiveRequest>,
+    /// 规则的日志设置对本次请求的决定
+    log: LogDecision,
+}
+
+impl RequestMeta {
+    fn set_route(&mut self, rule: Option<&str>, target: &str) {
+        self.rule = rule.map(str::to_string);
+        self.target = Some(target.to_string());
+        if let Some(ref active) = self.active {
+            active.set_route(rule, target);
+        }
+    }
 }
 
 /// 规则代理处理器 - 统一处理直接代理和规则代理，支持动态路径
 pub async fn rule_proxy_handler(
     State(state): State<ProxyState>,
     ConnectInfo(client_addr): ConnectInfo<SocketAddr>,
-    req: Request,
+    mut req: Request,
+) -> Result<Response, StatusCode> {
+    // 每个请求一个 span，启用 OTLP 时导出，并关联请求头中的 traceparent
+    let span = tracing::info_span!(
+        "proxy_request",
+        otel.kind = "server",
+        http.request.method = %req.method(),
+        url.path = %req.uri().path(),
+        rule = tracing::field::Empty,
+        target = tracing::field::Empty,
+        http.response.status_code = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+        trace_id = tracing::field::Empty,
+    );
+    telemetry::set_remote_parent(&span, req.headers());
+
+    // 请求头没有 traceparent 时新建，转发时带给上游
+    let trace = TraceParent::for_request(&span, req.headers());
+    span.record("trace_id", trace.trace_id());
+    req.extensions_mut().insert(trace);
+
+    let access_entry = state.access_log.as_ref().map(|logger| AccessLogEntry {
+        time: logger.timestamp(),
+        client_ip: client_addr.ip().to_string(),
+        method: req.method().to_string(),
+        path: req
+            .uri()
+            .path_and_query()
+            .map(|pq| redact::url(pq.as_str()).into_owned())
+            .unwrap_or_default(),
+        protocol: format!("{:?}", req.version()),
+        rule: None,
+        target: None,
+        status: 0,
+        bytes_out: 0,
+        duration_ms: 0,
+        referer: header_string(req.headers(), "referer"),
+        user_agent: header_string(req.headers(), "user-agent"),
+        trace_id: trace.trace_id(),
+    });
+
+    let tail_event = state.traffic.has_subscribers().then(|| TrafficEvent {
+        time: chrono::Local::now()
+            .format("%Y-%m-%d %H:%M:%S%.3f")
+            .to_string(),
+        client_ip: client_addr.ip().to_string(),
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        rule: None,
+        target: None,
+        status: 0,
+        duration_ms: 0,
+    });
+
+    let hook_request =
+        (!state.hooks.is_empty()).then(|| (req.method().clone(), req.uri().path().to_string()));
+
+    let active = state.active.register(
+        client_addr.ip().to_string(),
+        req.method().to_string(),
+        req.uri().path().to_string(),
+    );
+    let killed = active.killed();
+
+    let start = Instant::now();
+    let mut meta = RequestMeta {
+        active: Some(active),
+        ..Default::default()
+    };
+    // 等待上游响应期间被终止时直接返回
+    let result = tokio::select! {
+        result = route_request(state.clone(), client_addr, req, &mut meta).instrument(span.clone()) => result,
+        _ = killed => Err(StatusCode::SERVICE_UNAVAILABLE),
+    };
+    let active = meta.active.take();
+    let result = match hook_request {
+        Some((method, path)) => {
+            let ctx = HookContext {
+                client_addr,
+                method,
+                path,
+                rule: meta.rule.clone(),
+                target: meta.target.as_deref().map(|t| redact::url(t).into_owned()),
+            };
+            state.hooks.finish(&ctx, result)
+        }
+        None => result,
+    };
+    // 响应体传输期间仍保留在进行中列表，可被终止
+    let result = match active {
+        Some(active) => result.map(|resp| active.wrap_response(resp)),
+        None => result,
+    };
+
+    let status = match &result {
+        Ok(resp) => resp.status(),
+        Err(status) => *status,
+    };
+    if let Some(ref rule) = meta.rule {
+        span.record("rule", rule.as_str());
+    }
+    if let Some(ref target) = meta.target {
+        span.record("target", redact::url(target).as_ref());
+    }
+    span.record("http.response.status_code", status.as_u16());
+    span.record("duration_ms", start.elapsed().as_millis() as u64);
+    state.rolling.record(status.as_u16(), start.elapsed());
+
+    if let Some(mut event) = tail_event {
+        event.rule = meta.rule.clone();
+        event.target = meta.target.as_deref().map(|t| redact::url(t).into_owned());
+        event.status = status.as_u16();
+        event.duration_ms = start.elapsed().as_millis() as u64;
+        state.traffic.publish(event);
+    }
+
+    // 只记录错误的规则，在状态确定后补记转发日志
+    if meta.log == LogDecision::OnError && meta.log.allows(status.as_u16()) {
+        tracing::info!(rule = meta.rule.as_deref().unwrap_or("-"), target = %redact::url(meta.target.as_deref().unwrap_or("-")), client_ip = %client_addr.ip(), status = status.as_u16(), "Rule proxy failed");
+    }
+
+    match (state.access_log.as_ref(), access_entry) {
+        (Some(logger), Some(mut entry)) if meta.log.allows(status.as_u16()) => {
+            entry.rule = meta.rule;
+            entry.target = meta.target.map(|t| redact::url(&t).into_owned());
+            match result {
+                Ok(resp) => Ok(logger.wrap_response(resp, entry, start)),
+                Err(status) => {
+                    entry.status = status.as_u16();
+                    entry.duration_ms = start.elapsed().as_millis() as u64;
+                    logger.write(&entry);
+                    Err(status)
+                }
+            }
+        }
+        _ => result,
+    }
+}
+
+/// 转发前执行请求钩子，钩子返回响应时不再转发
+fn request_hooks(
+    state: &ProxyState,
+    client_addr: SocketAddr,
+    req: &mut Request,
+    meta: &RequestMeta,
+) -> Option<Response> {
+    if state.hooks.is_empty() {
+        return None;
+    }
+    let ctx = HookContext {
+        client_addr,
+        method: req.method().clone(),
+        path: req.uri().path().to_string(),
+        rule: meta.rule.clone(),
+        target: meta.target.as_deref().map(|t| redact::url(t).into_owned()),
+    };
+    state.hooks.on_request(&ctx, req)
+}
+
+async fn route_request(
+    state: ProxyState,
+    client_addr: SocketAddr,
+    mut req: Request,
+    meta: &mut RequestMeta,
 ) -> Result<Response, StatusCode> {
     let path = req.uri().path();
     let query = req.uri().query();
     let client_ip = client_addr.ip().to_string();
 
+    // 待机的备机不处理流量
+    if !state.ha.is_active() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    // 规则尚未加载时返回 503，避免把所有请求误报为 404
+    if !state.rules_ready.load(Ordering::Acquire) {
+        let mut resp = Response::new(Body::from("Rules not loaded"));
+        *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        resp.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from_static(NOT_READY_RETRY_AFTER),
+        );
+        return Ok(resp);
+    }
+
     // 无锁读取直接代理路径
     let direct_path = state.direct_proxy_path.load();
     let direct_path_str = direct_path.as_str();
@@ -110,47 +674,250 @@ pub async fn rule_proxy_handler(
 
     tracing::debug!("Request path: {}, direct_prefix: {}", path, direct_prefix);
 
-    // 检查是否是直接代理请求: /{path}/http://... 或 /{path}/https://...
-    if path.starts_with(&direct_prefix) {
+    let scope = req
+        .extensions()
+        .get::<ListenerScope>()
+        .map(ListenerScope::load);
+
+    // 直接代理请求: /{path}/http://... 或 /{path}/https://...，监听器可关闭直接代理；
+    // 正向代理的绝对地址请求已在 forward_proxy 中认证，不校验签名
+    let direct_url = if req.extensions().get::<ForwardProxied>().is_some() {
+        Some(req.uri().to_string())
+    } else if path.starts_with(&direct_prefix) && scope.as_ref().is_none_or(|s| s.direct_proxy) {
         let target_url = &path[direct_prefix.len()..];
         tracing::debug!("Checking direct proxy, target_url: {}", target_url);
 
         if target_url.starts_with("http://") || target_url.starts_with("https://") {
-            let final_url = match query {
+            // 校验签名链接，转发前去掉 sig/exp 参数
+            let query = match state.signed_urls.check(target_url, query) {
+                Ok(query) => query,
+                Err(reason) => {
+                    tracing::warn!(target = %target_url, client_ip = %client_ip, reason = ?reason, "Direct proxy signature rejected");
+                    return Err(StatusCode::FORBIDDEN);
+                }
+            };
+            Some(match query {
                 Some(q) => format!("{}?{}", target_url, q),
                 None => target_url.to_string(),
-            };
+            })
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    if let Some(final_url) = direct_url {
+        // 目标访问控制，域名解析结果与重定向由 direct_client 继续校验
+        if let Err(reason) = reqwest::Url::parse(&final_url)
+            .map_err(|e| e.to_string())
+            .and_then(|url| state.direct_guard.check_url(&url))
+        {
+            tracing::warn!(target = %redact::url(&final_url), client_ip = %client_ip, reason = %reason, "Direct proxy target blocked");
+            return Err(StatusCode::FORBIDDEN);
+        }
 
-            tracing::info!(method = %req.method(), target = %final_url, client_ip = %client_ip, "Direct proxy");
-            return forward_request_streaming(
-                req,
-                &final_url,
-                &state.client,
-                state.default_timeout,
-                &client_ip,
-            )
-            .await;
+        tracing::info!(method = %req.method(), target = %redact::url(&final_url), client_ip = %client_ip, "Direct proxy");
+        meta.set_route(None, &final_url);
+        if let Some(resp) = request_hooks(&state, client_addr, &mut req, meta) {
+            return Ok(resp);
+        }
+        if let Some(entry) = state.direct_cache.entry(req.method(), &final_url, &state) {
+            return direct_cache::forward(&state, entry, req, &final_url, &client_ip).await;
         }
+        return forward_request_streaming(
+            req,
+            &final_url,
+            UpstreamClient::Pooled(&state.direct_client),
+            ForwardTimeouts::uniform(state.default_timeout),
+            state.max_body_bytes,
+            &client_ip,
+            None,
+        )
+        .await;
     }
 
+    // 请求体长度：来自 Content-Length，分块传输等情况下未知
+    let body_len = req.body().size_hint().exact();
+
     // 无锁读取规则，查找匹配的规则
     let rules = state.rules.load();
-    for rule in rules.iter() {
-        if let Some(mut target_url) = rule.match_and_build_target(path) {
+    let in_scope = |rule: &&CompiledProxyRule| scope.as_ref().is_none_or(|s| s.allows(rule));
+    for rule in rules.candidates(path).filter(in_scope) {
+        if let Some(mut target_url) = rule
+            .match_and_build_target(path)
+            .filter(|_| rule.matches_request_size(body_len))
+        {
             if let Some(q) = query {
                 target_url.push('?');
                 target_url.push_str(q);
             }
 
-            tracing::info!(method = %req.method(), source = %path, target = %target_url, client_ip = %client_ip, "Rule proxy");
-            return forward_request_streaming(
-                req,
-                &target_url,
-                &state.client,
-                rule.timeout,
-                &client_ip,
-            )
-            .await;
+            meta.log = rule.options.log.decide(&rule.log_counter);
+            if meta.log == LogDecision::Always {
+                tracing::info!(method = %req.method(), source = %path, target = %redact::url(&target_url), client_ip = %client_ip, "Rule proxy");
+            }
+            meta.set_route(Some(&rule.name), &target_url);
+
+            let ctx = StepContext {
+                state: &state,
+                rule,
+                client_addr,
+                client_ip: &client_ip,
+            };
+            let response_phase = match rule
+                .pipeline
+                .on_request(&ctx, &mut req, &mut target_url)
+                .await
+            {
+                Flow::Forward(phase) => phase,
+                Flow::Respond(result) => return result,
+            };
+            // 实验分组与脚本可能改写了上游地址，服务发现目标在此选定实例
+            state.discovery.resolve(&mut target_url).await?;
+            meta.set_route(Some(&rule.name), &target_url);
+
+            // 上游并发上限：许可随响应体一起释放
+            let permit = match rule
+                .options
+                .pool
+                .as_ref()
+                .and_then(|pool| pool.max_connections)
+            {
+                Some(max) => Some(
+                    state
+                        .upstream_limits
+                        .acquire(&rule.name, &target_url, max, rule.timeout)
+                        .await?,
+                ),
+                None => None,
+            };
+
+            let counters = state.stats.counters(rule.id);
+            let start = Instant::now();
+            let client = state.rule_client(rule)?;
+            let raw_client =
+                match rule.options.preserve_header_case && !rule.options.upstream_proxy_protocol {
+                    true => Some(state.raw_client(rule)?),
+                    false => None,
+                };
+            let upstream = if rule.options.upstream_proxy_protocol {
+                UpstreamClient::ProxyProtocol {
+                    connector: &state.proxy_protocol,
+                    source: client_addr,
+                    preserve_header_case: rule.options.preserve_header_case,
+                    connect_timeout: rule.pool_settings().connect_timeout,
+                }
+            } else if let Some(ref raw_client) = raw_client {
+                UpstreamClient::Raw(raw_client)
+            } else {
+                UpstreamClient::Pooled(&client)
+            };
+            let is_get = req.method() == Method::GET;
+            let npm = rule.options.npm.as_ref().filter(|_| is_get);
+            let pypi = rule.options.pypi.as_ref().filter(|_| is_get);
+            let is_read = is_get || req.method() == Method::HEAD;
+            let maven = rule.options.maven.as_ref().filter(|_| is_read);
+            let goproxy = rule.options.goproxy.as_ref().filter(|_| is_read);
+            let package_cache = rule.options.package_cache.as_ref().filter(|_| is_read);
+            let result = if let Some(ref options) = rule.options.registry {
+                registry::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else if let Some(options) = npm {
+                npm::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else if let Some(options) = pypi {
+                pypi::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else if let Some(options) = maven {
+                maven::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else if let Some(options) = goproxy {
+                goproxy::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else if let Some(options) = package_cache {
+                packages::forward(
+                    &state,
+                    rule,
+                    options,
+                    req,
+                    &target_url,
+                    &client_ip,
+                    counters.clone(),
+                )
+                .await
+            } else {
+                forward_request_streaming(
+                    req,
+                    &target_url,
+                    upstream,
+                    ForwardTimeouts::for_rule(rule),
+                    rule.options.max_body_bytes.unwrap_or(state.max_body_bytes),
+                    &client_ip,
+                    Some(counters.clone()),
+                )
+                .await
+            };
+
+            let result = match permit {
+                Some(permit) => result.map(|resp| upstreams::hold_permit(resp, permit)),
+                None => result,
+            };
+
+            let is_error = match &result {
+                Ok(resp) => resp.status().is_server_error(),
+                Err(_) => true,
+            };
+            counters.record(start.elapsed(), is_error);
+            if let Some(ref upstream) = rule.upstream {
+                state.upstreams.record(
+                    upstream,
+                    start.elapsed(),
+                    UpstreamHealth::failure_reason(&result),
+                );
+            }
+
+            return response_phase.apply(&ctx, &target_url, result).await;
         }
     }
 
@@ -158,52 +925,380 @@ pub async fn rule_proxy_handler(
     Err(StatusCode::NOT_FOUND)
 }
 
+/// 转发超时设置
+#[derive(Debug, Clone, Copy)]
+pub struct ForwardTimeouts {
+    /// 等待上游返回响应头的时间
+    pub response: Duration,
+    /// 等待上游产生下一个响应体数据块的时间
+    pub upstream_read: Duration,
+    /// 慢客户端接收一个数据块的最长时间，None 表示不限制
+    pub client_write: Option<Duration>,
+}
+
+impl ForwardTimeouts {
+    pub fn uniform(timeout: Duration) -> Self {
+        Self {
+            response: timeout,
+            upstream_read: timeout,
+            client_write: None,
+        }
+    }
+
+    pub fn for_rule(rule: &CompiledProxyRule) -> Self {
+        Self {
+            response: rule.timeout,
+            upstream_read: rule
+                .options
+                .upstream_read_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(rule.timeout),
+            client_write: rule
+                .options
+                .client_write_timeout_secs
+                .map(Duration::from_secs),
+        }
+    }
+}
+
+/// 转发使用的上游客户端
+#[derive(Clone, Copy)]
+pub enum UpstreamClient<'a> {
+    /// reqwest 连接池，支持 HTTP/2，请求头名统一为小写
+    Pooled(&'a Client),
+    /// 底层 HTTP/1 客户端，保留请求头原始大小写与顺序
+    Raw(&'a RawClient),
+    /// 每个请求新建连接并先发送 PROXY 协议头，source 为客户端地址
+    ProxyProtocol {
+        connector: &'a UpstreamConnector,
+        source: SocketAddr,
+        preserve_header_case: bool,
+        /// 规则的连接超时，None 时使用默认值
+        connect_timeout: Option<Duration>,
+    },
+}
+
+/// 上游响应
+struct UpstreamResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    /// 底层客户端记录的响应头原始大小写，随响应返回给客户端
+    extensions: axum::http::Extensions,
+    body: BoxStream<'static, std::io::Result<Bytes>>,
+}
+
 /// 流式转发请求 - 避免大响应体占用内存
 async fn forward_request_streaming(
     req: Request,
     target_url: &str,
-    client: &Client,
-    timeout: Duration,
+    client: UpstreamClient<'_>,
+    timeouts: ForwardTimeouts,
+    max_body_bytes: u64,
     client_ip: &str,
+    counters: Option<Arc<RuleCounters>>,
 ) -> Result<Response, StatusCode> {
     let method = req.method().clone();
-    let headers = req.headers().clone();
+    let git = is_git_request(&req);
+    // 缓冲的请求体按 Content-Length 提前拒绝，不必读到上限
+    let streaming = git && matches!(client, UpstreamClient::Pooled(_));
+    if !streaming
+        && req
+            .body()
+            .size_hint()
+            .exact()
+            .is_some_and(|len| len > max_body_bytes)
+    {
+        return Ok(payload_too_large(max_body_bytes));
+    }
+    let mut headers = forward_headers(
+        req.headers(),
+        target_url,
+        client_ip,
+        req.extensions().get::<TraceParent>().copied(),
+    );
+    if git {
+        // Git 数据包本身已压缩，要求上游不再压缩，代理不解压、原样转发
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("identity"),
+        );
+    }
+    // 监听器开启了大小写记录，原始请求头大小写保存在请求扩展中
+    let extensions = match client {
+        UpstreamClient::Raw(_)
+        | UpstreamClient::ProxyProtocol {
+            preserve_header_case: true,
+            ..
+        } => req.extensions().clone(),
+        _ => Default::default(),
+    };
 
-    // 流式读取请求体
-    let body_stream = req.into_body();
-    let body_bytes = axum::body::to_bytes(body_stream, 100 * 1024 * 1024) // 100MB 限制
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    // 发送请求，只限制等待响应头的时间，响应体按数据块单独计时
+    let body = req.into_body();
+    let send: BoxFuture<'_, Result<UpstreamResponse, StatusCode>> = match client {
+        // Git 推送的数据包可能很大且使用分块传输，请求体边接收边转发
+        UpstreamClient::Pooled(client) if streaming => {
+            let counters = counters.clone();
+            let stream = body.into_data_stream().map(move |result| {
+                if let (Ok(chunk), Some(counters)) = (&result, &counters) {
+                    counters.add_bytes_in(chunk.len() as u64);
+                }
+                result
+            });
+            let body = reqwest::Body::wrap_stream(stream);
+            Box::pin(send_pooled(
+                client,
+                method,
+                target_url,
+                headers,
+                Some(body),
+                timeouts,
+            ))
+        }
+        UpstreamClient::Pooled(client) => {
+            let body_bytes = match read_request_body(body, max_body_bytes, &counters).await {
+                Err(StatusCode::PAYLOAD_TOO_LARGE) => return Ok(payload_too_large(max_body_bytes)),
+                result => result?,
+            };
+            let body = (!body_bytes.is_empty()).then(|| reqwest::Body::from(body_bytes));
+            Box::pin(send_pooled(
+                client, method, target_url, headers, body, timeouts,
+            ))
+        }
+        UpstreamClient::Raw(client) => {
+            let body_bytes = match read_request_body(body, max_body_bytes, &counters).await {
+                Err(StatusCode::PAYLOAD_TOO_LARGE) => return Ok(payload_too_large(max_body_bytes)),
+                result => result?,
+            };
+            Box::pin(send_raw(
+                client, method, target_url, headers, extensions, body_bytes, timeouts,
+            ))
+        }
+        UpstreamClient::ProxyProtocol {
+            connector,
+            source,
+            preserve_header_case,
+            connect_timeout,
+        } => {
+            let body_bytes = match read_request_body(body, max_body_bytes, &counters).await {
+                Err(StatusCode::PAYLOAD_TOO_LARGE) => return Ok(payload_too_large(max_body_bytes)),
+                result => result?,
+            };
+            let forward_req = raw_request(method, target_url, headers, extensions, body_bytes)?;
+            Box::pin(async move {
+                let response = connector
+                    .send(source, forward_req, preserve_header_case, connect_timeout)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Proxy error: {}", redact::url(&e.to_string()));
+                        StatusCode::BAD_GATEWAY
+                    })?;
+                Ok(raw_response(response, timeouts))
+            })
+        }
+    };
+    let upstream = match tokio::time::timeout(timeouts.response, send).await {
+        Ok(result) => result?,
+        Err(_) => {
+            tracing::error!("Upstream response timeout after {:?}", timeouts.response);
+            return Err(StatusCode::GATEWAY_TIMEOUT);
+        }
+    };
+
+    let response_headers = upstream_response_headers(&upstream.headers);
+
+    // 流式响应体
+    let body_stream = upstream.body.map(move |result| {
+        if let (Ok(chunk), Some(counters)) = (&result, &counters) {
+            counters.add_bytes_out(chunk.len() as u64);
+        }
+        result
+    });
+
+    let body = match timeouts.client_write {
+        Some(write_timeout) => Body::from_stream(client_write_limited(body_stream, write_timeout)),
+        None => Body::from_stream(body_stream),
+    };
 
-    // 构建请求
+    let mut resp = Response::new(body);
+    *resp.status_mut() = upstream.status;
+    *resp.headers_mut() = response_headers;
+    *resp.extensions_mut() = upstream.extensions;
+
+    Ok(resp)
+}
+
+/// 读取完整的请求体，超过上限时返回 413
+async fn read_request_body(
+    body: Body,
+    max_body_bytes: u64,
+    counters: &Option<Arc<RuleCounters>>,
+) -> Result<Bytes, StatusCode> {
+    let limit = usize::try_from(max_body_bytes).unwrap_or(usize::MAX);
+    let body_bytes = axum::body::to_bytes(body, limit).await.map_err(|e| {
+        if e.into_inner().is::<LengthLimitError>() {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else {
+            StatusCode::BAD_REQUEST
+        }
+    })?;
+
+    if let Some(ref counters) = counters {
+        counters.add_bytes_in(body_bytes.len() as u64);
+    }
+    Ok(body_bytes)
+}
+
+/// 请求体超过上限时返回的 413 响应
+fn payload_too_large(max_body_bytes: u64) -> Response {
+    tracing::warn!(limit = max_body_bytes, "Request body too large");
+    let body = serde_json::json!({
+        "error": "payload_too_large",
+        "message": format!("Request body exceeds the limit of {} bytes", max_body_bytes),
+        "limit_bytes": max_body_bytes,
+    });
+    let mut resp = Response::new(Body::from(body.to_string()));
+    *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    resp
+}
+
+/// Git smart HTTP 请求：info/refs?service=git-* 或 application/x-git-* 请求体
+fn is_git_request(req: &Request) -> bool {
+    let refs = req.uri().path().ends_with("/info/refs")
+        && req
+            .uri()
+            .query()
+            .is_some_and(|q| q.split('&').any(|p| p.starts_with("service=git-")));
+    refs || is_git_content_type(req.headers())
+}
+
+/// Content-Type 为 application/x-git-*（upload-pack / receive-pack 的请求与结果、refs 广告）
+pub fn is_git_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-git-"))
+}
+
+async fn send_pooled(
+    client: &Client,
+    method: Method,
+    target_url: &str,
+    headers: HeaderMap,
+    body: Option<reqwest::Body>,
+    timeouts: ForwardTimeouts,
+) -> Result<UpstreamResponse, StatusCode> {
     let mut forward_req = client
         .request(convert_method(&method), target_url)
-        .timeout(timeout);
+        .headers(headers);
+
+    if let Some(body) = body {
+        forward_req = forward_req.body(body);
+    }
+
+    let response = forward_req.send().await.map_err(|e| {
+        // reqwest 错误信息包含请求地址
+        tracing::error!("Proxy error: {}", redact::url(&e.to_string()));
+        if e.is_timeout() {
+            StatusCode::GATEWAY_TIMEOUT
+        } else {
+            StatusCode::BAD_GATEWAY
+        }
+    })?;
+
+    Ok(UpstreamResponse {
+        status: StatusCode::from_u16(response.status().as_u16())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        headers: response.headers().clone(),
+        extensions: Default::default(),
+        body: upstream_body_stream(response.bytes_stream(), timeouts.upstream_read).boxed(),
+    })
+}
+
+async fn send_raw(
+    client: &RawClient,
+    method: Method,
+    target_url: &str,
+    headers: HeaderMap,
+    extensions: axum::http::Extensions,
+    body_bytes: Bytes,
+    timeouts: ForwardTimeouts,
+) -> Result<UpstreamResponse, StatusCode> {
+    let forward_req = raw_request(method, target_url, headers, extensions, body_bytes)?;
+    let response = client.request(forward_req).await.map_err(|e| {
+        tracing::error!("Proxy error: {}", redact::url(&e.to_string()));
+        StatusCode::BAD_GATEWAY
+    })?;
+    Ok(raw_response(response, timeouts))
+}
+
+/// 底层 HTTP/1 请求，扩展中带有原始请求头大小写时按原样写出
+fn raw_request(
+    method: Method,
+    target_url: &str,
+    headers: HeaderMap,
+    extensions: axum::http::Extensions,
+    body_bytes: Bytes,
+) -> Result<axum::http::Request<Full<Bytes>>, StatusCode> {
+    let mut forward_req = axum::http::Request::builder()
+        .method(method)
+        .uri(target_url)
+        .body(Full::new(body_bytes))
+        .map_err(|e| {
+            tracing::error!("Invalid upstream url {}: {}", redact::url(target_url), e);
+            StatusCode::BAD_GATEWAY
+        })?;
+    *forward_req.headers_mut() = headers;
+    *forward_req.extensions_mut() = extensions;
+    Ok(forward_req)
+}
+
+fn raw_response(
+    response: axum::http::Response<Incoming>,
+    timeouts: ForwardTimeouts,
+) -> UpstreamResponse {
+    let (parts, body) = response.into_parts();
+    UpstreamResponse {
+        status: parts.status,
+        headers: parts.headers,
+        extensions: parts.extensions,
+        body: upstream_body_stream(body.into_data_stream(), timeouts.upstream_read).boxed(),
+    }
+}
 
-    // 复制请求头
+/// 转发给上游的请求头：去掉逐跳头，补充 X-Forwarded-*、X-Real-IP，traceparent 使用代理生成的值
+pub fn forward_headers(
+    headers: &HeaderMap,
+    target_url: &str,
+    client_ip: &str,
+    trace: Option<TraceParent>,
+) -> HeaderMap {
+    let mut out = HeaderMap::with_capacity(headers.len() + 4);
     for (name, value) in headers.iter() {
-        if !is_hop_by_hop_header(name.as_str()) {
-            if let (Ok(n), Ok(v)) = (
-                reqwest::header::HeaderName::from_bytes(name.as_ref()),
-                reqwest::header::HeaderValue::from_bytes(value.as_bytes()),
-            ) {
-                forward_req = forward_req.header(n, v);
-            }
+        if !is_hop_by_hop_header(name.as_str()) && name != "traceparent" {
+            out.append(name.clone(), value.clone());
         }
     }
 
-    // 添加代理相关头，传递真实客户端 IP
     // X-Forwarded-For: 追加客户端 IP 到现有链
     let xff = headers
         .get("x-forwarded-for")
         .and_then(|v| v.to_str().ok())
         .map(|existing| format!("{}, {}", existing, client_ip))
         .unwrap_or_else(|| client_ip.to_string());
-    forward_req = forward_req.header("X-Forwarded-For", &xff);
+    if let Ok(v) = HeaderValue::from_str(&xff) {
+        out.insert("x-forwarded-for", v);
+    }
 
     // X-Real-IP: 原始客户端 IP（如果还没设置）
     if !headers.contains_key("x-real-ip") {
-        forward_req = forward_req.header("X-Real-IP", client_ip);
+        if let Ok(v) = HeaderValue::from_str(client_ip) {
+            out.insert("x-real-ip", v);
+        }
     }
 
     // X-Forwarded-Proto: 协议
@@ -213,51 +1308,133 @@ async fn forward_request_streaming(
         } else {
             "http"
         };
-        forward_req = forward_req.header("X-Forwarded-Proto", proto);
+        out.insert("x-forwarded-proto", HeaderValue::from_static(proto));
     }
 
-    if !body_bytes.is_empty() {
-        forward_req = forward_req.body(body_bytes.to_vec());
+    if let Some(trace) = trace {
+        if let Ok(v) = HeaderValue::from_str(&trace.to_string()) {
+            out.insert("traceparent", v);
+        }
     }
 
-    // 发送请求
-    let response = forward_req.send().await.map_err(|e| {
-        tracing::error!("Proxy error: {}", e);
-        if e.is_timeout() {
-            StatusCode::GATEWAY_TIMEOUT
-        } else {
-            StatusCode::BAD_GATEWAY
-        }
-    })?;
+    // 字节范围按原始内容计算：要求上游不压缩，客户端库也就不会解压部分内容，206 响应原样返回
+    if headers.contains_key(header::RANGE) {
+        out.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("identity"),
+        );
+    }
 
-    let status = StatusCode::from_u16(response.status().as_u16())
-        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    out
+}
 
-    // 复制响应头
-    let mut response_headers = HeaderMap::new();
-    for (name, value) in response.headers().iter() {
-        if !is_hop_by_hop_header(name.as_str()) {
-            if let (Ok(n), Ok(v)) = (
-                HeaderName::from_bytes(name.as_ref()),
-                HeaderValue::from_bytes(value.as_bytes()),
-            ) {
-                response_headers.insert(n, v);
+/// 写入标注头：X-Proxy-Rule 为规则名，X-Proxy-Target 为目标地址的主机与端口
+pub fn annotate_headers(headers: &mut HeaderMap, rule: &CompiledProxyRule, target_url: &str) {
+    let target = reqwest::Url::parse(target_url)
+        .ok()
+        .and_then(|url| {
+            let host = url.host_str()?.to_string();
+            Some(match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host,
+            })
+        })
+        .unwrap_or_default();
+    headers.insert("x-proxy-rule", annotation_value(&rule.name));
+    headers.insert("x-proxy-target", annotation_value(&target));
+}
+
+/// 规则名可能包含中文等非 ASCII 字符，按百分号编码写入请求头
+fn annotation_value(value: &str) -> HeaderValue {
+    let encoded: String = value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
             }
+        })
+        .collect();
+    HeaderValue::from_str(&encoded).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+/// 返回给客户端的上游响应头，去掉逐跳头
+pub fn upstream_response_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut out = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers.iter() {
+        if !is_hop_by_hop_header(name.as_str()) {
+            out.append(name.clone(), value.clone());
         }
     }
+    out
+}
 
-    // 流式响应体
-    let body_stream = response
-        .bytes_stream()
-        .map(|result| result.map_err(std::io::Error::other));
+/// 上游响应体流，每个数据块单独计时；只在被拉取时计时，慢客户端不会导致上游超时
+pub fn upstream_body_stream<S, E>(
+    stream: S,
+    read_timeout: Duration,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + Unpin + 'static,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    futures::stream::unfold(Some(stream), move |inner| async move {
+        let mut inner = inner?;
+        match tokio::time::timeout(read_timeout, inner.next()).await {
+            Ok(Some(Ok(chunk))) => Some((Ok(chunk), Some(inner))),
+            Ok(Some(Err(e))) => Some((Err(std::io::Error::other(e)), None)),
+            Ok(None) => None,
+            Err(_) => {
+                tracing::warn!("Upstream read timeout after {:?}", read_timeout);
+                Some((
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "upstream read timeout",
+                    )),
+                    None,
+                ))
+            }
+        }
+    })
+}
 
-    let body = Body::from_stream(body_stream);
+/// 通过容量为 1 的通道解耦上游读取与客户端写入，客户端在限定时间内未取走数据块则中止
+fn client_write_limited<S>(
+    upstream: S,
+    write_timeout: Duration,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + 'static
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
 
-    let mut resp = Response::new(body);
-    *resp.status_mut() = status;
-    *resp.headers_mut() = response_headers;
+    tokio::spawn(async move {
+        futures::pin_mut!(upstream);
+        while let Some(item) = upstream.next().await {
+            let is_err = item.is_err();
+            match tokio::time::timeout(write_timeout, tx.send(item)).await {
+                Ok(Ok(())) if !is_err => {}
+                Ok(Ok(())) | Ok(Err(_)) => return,
+                Err(_) => {
+                    tracing::warn!("Client write timeout after {:?}", write_timeout);
+                    return;
+                }
+            }
+        }
+    });
 
-    Ok(resp)
+    futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    })
+}
+
+#[inline]
+fn header_string(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| redact::header(name, v).into_owned())
 }
 
 #[inline]
@@ -0,0 +1,354 @@
+use axum::http::{self, header, HeaderValue};
+use bytes::Bytes;
+use http_body_util::Full;
+use hyper::body::Incoming;
+use hyper_rustls::ConfigBuilderExt;
+use hyper_util::rt::TokioIo;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+
+/// PROXY 协议 v2 的固定签名
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// v1 头部（含 \r\n）的最大长度
+const V1_MAX_LEN: usize = 107;
+
+/// 等待 PROXY 协议头的超时，避免空闲连接占用任务
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 连接上游的超时，与其他上游客户端一致
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 读取连接开头的 PROXY 协议头（v1 文本或 v2 二进制），返回其中的客户端地址；
+/// LOCAL 命令（负载均衡器的健康检查）与 UNKNOWN 协议返回连接本身的地址。
+/// 只读取头部字节，之后的数据留给 HTTP / TLS 处理
+pub async fn read_header<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    peer: SocketAddr,
+) -> anyhow::Result<SocketAddr> {
+    tokio::time::timeout(HEADER_TIMEOUT, read(stream, peer))
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out waiting for PROXY protocol header"))?
+}
+
+async fn read<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    peer: SocketAddr,
+) -> anyhow::Result<SocketAddr> {
+    let mut prefix = [0u8; 12];
+    stream.read_exact(&mut prefix).await?;
+    if prefix == V2_SIGNATURE {
+        return read_v2(stream, peer).await;
+    }
+    if !prefix.starts_with(b"PROXY ") {
+        anyhow::bail!("missing PROXY protocol header");
+    }
+
+    // v1 头部以 \r\n 结束，逐字节读取以免读入后面的请求数据
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            anyhow::bail!("PROXY protocol v1 header too long");
+        }
+        line.push(stream.read_u8().await?);
+    }
+    parse_v1(&line[..line.len() - 2], peer)
+}
+
+/// `PROXY TCP4 <源地址> <目标地址> <源端口> <目标端口>`
+fn parse_v1(line: &[u8], peer: SocketAddr) -> anyhow::Result<SocketAddr> {
+    let line = std::str::from_utf8(line)?;
+    let mut fields = line.split(' ').skip(1);
+    match fields.next() {
+        Some("TCP4") | Some("TCP6") => {}
+        Some("UNKNOWN") => return Ok(peer),
+        _ => anyhow::bail!("invalid PROXY protocol v1 header: {}", line),
+    }
+    let (Some(src), Some(_dst), Some(src_port), Some(_dst_port), None) = (
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+        fields.next(),
+    ) else {
+        anyhow::bail!("invalid PROXY protocol v1 header: {}", line);
+    };
+    let ip: IpAddr = src.parse()?;
+    let port: u16 = src_port.parse()?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+async fn read_v2<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    peer: SocketAddr,
+) -> anyhow::Result<SocketAddr> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let [version_command, family, len_hi, len_lo] = header;
+    if version_command >> 4 != 2 {
+        anyhow::bail!(
+            "unsupported PROXY protocol version: {}",
+            version_command >> 4
+        );
+    }
+    let mut body = vec![0u8; u16::from_be_bytes([len_hi, len_lo]) as usize];
+    stream.read_exact(&mut body).await?;
+
+    match version_command & 0x0f {
+        // LOCAL：负载均衡器自身发起的连接
+        0 => return Ok(peer),
+        1 => {}
+        command => anyhow::bail!("unsupported PROXY protocol command: {}", command),
+    }
+    // 高 4 位为地址族：1 = IPv4，2 = IPv6，其他（UNSPEC / Unix）使用连接地址
+    match family >> 4 {
+        1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(SocketAddr::new(ip.into(), port))
+        }
+        2 if body.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(SocketAddr::new(Ipv6Addr::from(octets).into(), port))
+        }
+        1 | 2 => anyhow::bail!("truncated PROXY protocol v2 address block"),
+        _ => Ok(peer),
+    }
+}
+
+/// 写给上游的 PROXY 协议 v2 头部（PROXY 命令，TCP）；源与目标地址族不同时都用 IPv6 表示
+pub fn encode_v2(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let mut out = V2_SIGNATURE.to_vec();
+    out.push(0x21);
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            out.push(0x11);
+            out.extend_from_slice(&12u16.to_be_bytes());
+            out.extend_from_slice(&src.octets());
+            out.extend_from_slice(&dst.octets());
+        }
+        (src, dst) => {
+            out.push(0x21);
+            out.extend_from_slice(&36u16.to_be_bytes());
+            out.extend_from_slice(&to_ipv6(src).octets());
+            out.extend_from_slice(&to_ipv6(dst).octets());
+        }
+    }
+    out.extend_from_slice(&source.port().to_be_bytes());
+    out.extend_from_slice(&destination.port().to_be_bytes());
+    out
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped(),
+        IpAddr::V6(v6) => v6,
+    }
+}
+
+/// 连接要求 PROXY 协议的上游：头部携带各自的客户端地址，连接不能在客户端之间复用，
+/// 因此每个请求新建连接，写入 v2 头部后按需 TLS 握手，再以 HTTP/1 发送请求
+#[derive(Clone)]
+pub struct UpstreamConnector {
+    tls: TlsConnector,
+}
+
+impl UpstreamConnector {
+    pub fn new() -> anyhow::Result<Self> {
+        let mut config = ClientConfig::builder_with_provider(Arc::new(
+            tokio_rustls::rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()?
+        .with_webpki_roots()
+        .with_no_client_auth();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Self {
+            tls: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    /// 请求地址为完整 URL，发送前改为 origin-form 并按 URL 补充 Host
+    pub async fn send(
+        &self,
+        source: SocketAddr,
+        mut req: http::Request<Full<Bytes>>,
+        preserve_header_case: bool,
+        connect_timeout: Option<Duration>,
+    ) -> anyhow::Result<http::Response<Incoming>> {
+        let uri = req.uri().clone();
+        let https = match uri.scheme_str() {
+            Some("https") => true,
+            Some("http") => false,
+            _ => anyhow::bail!("unsupported upstream url: {}", uri),
+        };
+        let host = uri
+            .host()
+            .ok_or_else(|| anyhow::anyhow!("upstream url has no host: {}", uri))?;
+        let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+        let stream = tokio::time::timeout(
+            connect_timeout.unwrap_or(CONNECT_TIMEOUT),
+            TcpStream::connect((host.trim_start_matches('[').trim_end_matches(']'), port)),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("connect to {} timed out", uri))??;
+        stream.set_nodelay(true)?;
+        let mut stream = stream;
+        stream
+            .write_all(&encode_v2(source, stream.peer_addr()?))
+            .await?;
+
+        if !req.headers().contains_key(header::HOST) {
+            let authority = uri.authority().map(|a| a.as_str()).unwrap_or(host);
+            req.headers_mut()
+                .insert(header::HOST, HeaderValue::from_str(authority)?);
+        }
+        *req.uri_mut() = uri
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/")
+            .parse()?;
+
+        if https {
+            let name = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']'))?
+                .to_owned();
+            let stream = self.tls.connect(name, stream).await?;
+            send_http1(stream, req, preserve_header_case).await
+        } else {
+            send_http1(stream, req, preserve_header_case).await
+        }
+    }
+}
+
+async fn send_http1<S>(
+    io: S,
+    req: http::Request<Full<Bytes>>,
+    preserve_header_case: bool,
+) -> anyhow::Result<http::Response<Incoming>>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::Builder::new()
+        .preserve_header_case(preserve_header_case)
+        .handshake(TokioIo::new(io))
+        .await?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            tracing::debug!("PROXY protocol upstream connection error: {}", e);
+        }
+    });
+    Ok(sender.send_request(req).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        "10.0.0.9:50000".parse().unwrap()
+    }
+
+    /// 解析头部并返回客户端地址与头部之后剩余的数据
+    async fn parse(input: &[u8]) -> anyhow::Result<(SocketAddr, Vec<u8>)> {
+        let mut stream = input;
+        let addr = read_header(&mut stream, peer()).await?;
+        Ok((addr, stream.to_vec()))
+    }
+
+    fn v2(version_command: u8, family: u8, body: &[u8]) -> Vec<u8> {
+        let mut out = V2_SIGNATURE.to_vec();
+        out.extend_from_slice(&[version_command, family]);
+        out.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        out.extend_from_slice(body);
+        out
+    }
+
+    #[tokio::test]
+    async fn parses_v1_header() {
+        let (addr, rest) = parse(b"PROXY TCP4 192.0.2.1 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        assert_eq!(addr, "192.0.2.1:56324".parse().unwrap());
+        assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+
+        let (addr, _) = parse(b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 443\r\n")
+            .await
+            .unwrap();
+        assert_eq!(addr, "[2001:db8::1]:4000".parse().unwrap());
+
+        let (addr, _) = parse(b"PROXY UNKNOWN\r\n").await.unwrap();
+        assert_eq!(addr, peer());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_v1_header() {
+        for input in [
+            &b"GET / HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            b"PROXY UDP4 192.0.2.1 10.0.0.1 1 2\r\n",
+            b"PROXY TCP4 192.0.2.1 10.0.0.1 56324\r\n",
+            b"PROXY TCP4 192.0.2.1 10.0.0.1 56324 443 extra\r\n",
+            b"PROXY TCP4 not-an-ip 10.0.0.1 56324 443\r\n",
+            b"PROXY TCP4 192.0.2.1 10.0.0.1 99999 443\r\n",
+            b"PROXY TCP4 192.0.2.1 10.0.0.1 56324 443",
+        ] {
+            assert!(parse(input).await.is_err(), "{:?}", input);
+        }
+        // 超长头部在读到上限时即拒绝
+        let mut long = b"PROXY TCP4 ".to_vec();
+        long.resize(500, b'1');
+        assert!(parse(&long).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn parses_v2_header() {
+        let mut body = vec![192, 0, 2, 1, 10, 0, 0, 1];
+        body.extend_from_slice(&56324u16.to_be_bytes());
+        body.extend_from_slice(&443u16.to_be_bytes());
+        let mut input = v2(0x21, 0x11, &body);
+        input.extend_from_slice(b"\x16\x03\x01");
+        let (addr, rest) = parse(&input).await.unwrap();
+        assert_eq!(addr, "192.0.2.1:56324".parse().unwrap());
+        assert_eq!(rest, b"\x16\x03\x01");
+
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut body = src.octets().to_vec();
+        body.extend_from_slice(&[0; 16]);
+        body.extend_from_slice(&4000u16.to_be_bytes());
+        body.extend_from_slice(&443u16.to_be_bytes());
+        // 地址块后的 TLV 扩展一并跳过
+        body.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+        let (addr, rest) = parse(&v2(0x21, 0x21, &body)).await.unwrap();
+        assert_eq!(addr, SocketAddr::new(src.into(), 4000));
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn v2_local_and_unspec_use_peer_address() {
+        let (addr, _) = parse(&v2(0x20, 0x00, &[])).await.unwrap();
+        assert_eq!(addr, peer());
+        let (addr, _) = parse(&v2(0x20, 0x11, &[0; 12])).await.unwrap();
+        assert_eq!(addr, peer());
+        let (addr, _) = parse(&v2(0x21, 0x00, &[])).await.unwrap();
+        assert_eq!(addr, peer());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_v2_header() {
+        assert!(parse(&v2(0x11, 0x11, &[0; 12])).await.is_err());
+        assert!(parse(&v2(0x22, 0x11, &[0; 12])).await.is_err());
+        assert!(parse(&v2(0x21, 0x11, &[0; 8])).await.is_err());
+        assert!(parse(&v2(0x21, 0x21, &[0; 12])).await.is_err());
+        // 声明的长度超过实际数据
+        let mut truncated = v2(0x21, 0x11, &[0; 12]);
+        truncated.truncate(20);
+        assert!(parse(&truncated).await.is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    response::Response,
+};
+use bytes::Bytes;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{
+    forward_headers, upstream_response_headers, CompiledProxyRule, ForwardTimeouts, ProxyState,
+};
+use crate::stats::RuleCounters;
+use crate::telemetry::TraceParent;
+use crate::upstreams;
+
+/// 镜像上的文件下载路径（相对规则前缀），项目名不能以下划线开头，不会与索引冲突
+const FILES_PATH: &str = "_files/";
+
+const DEFAULT_FILES_URL: &str = "https://files.pythonhosted.org/";
+
+/// PyPI 镜像（simple 索引）规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PypiOptions {
+    /// 索引中文件链接的上游地址前缀，默认 https://files.pythonhosted.org/
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files_url: Option<String>,
+    /// 文件缓存目录，按 sha256 保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// 镜像对外地址（如 https://mirror.example.com），用于改写文件链接，
+    /// 未设置时按请求的 Host 与 X-Forwarded-Proto 生成
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+impl PypiOptions {
+    fn files_url(&self) -> &str {
+        self.files_url.as_deref().unwrap_or(DEFAULT_FILES_URL)
+    }
+}
+
+/// 镜像规则的 GET 请求：索引中的文件链接改写为 <规则前缀>/_files/<sha256>/...，
+/// 下载时转发到文件服务器，配置缓存目录时校验摘要后保存到本地；其他方法按普通规则转发
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &PypiOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    let path = req.uri().path();
+    let prefix = mirror::source_prefix(rule, path, target_url);
+    let client = state.rule_client(rule)?;
+    let timeouts = ForwardTimeouts::for_rule(rule);
+    let trace = req.extensions().get::<TraceParent>().copied();
+
+    if let Some(file) = prefix.and_then(|prefix| path[prefix.len()..].strip_prefix(FILES_PATH)) {
+        let (sha256, file) = match file.split_once('/') {
+            Some((hash, rest)) if is_sha256(hash) => (Some(hash.to_ascii_lowercase()), rest),
+            _ => (None, file),
+        };
+        // 元数据文件（PEP 658）的摘要与链接中的不同，不缓存
+        let cache = options
+            .cache_dir
+            .as_deref()
+            .zip(sha256.filter(|_| !file.ends_with(".metadata")))
+            .map(|(dir, hex)| {
+                CacheFile::new(
+                    &state.cache_store,
+                    Path::new(dir),
+                    &Path::new("sha256").join(&hex),
+                    Some(hex),
+                )
+            });
+        if let Some(ref cache) = cache {
+            if let Some(resp) =
+                mirror::serve_cached(cache, req.method(), req.headers(), &counters).await
+            {
+                return Ok(resp);
+            }
+        }
+
+        let mut upstream_url = format!("{}/{}", options.files_url().trim_end_matches('/'), file);
+        if let Some(query) = req.uri().query() {
+            upstream_url.push('?');
+            upstream_url.push_str(query);
+        }
+        let mut headers = forward_headers(req.headers(), &upstream_url, client_ip, trace);
+        // 文件服务器与索引不同主机时不发送凭据
+        if upstreams::upstream_of(&upstream_url) != rule.upstream {
+            headers.remove(header::AUTHORIZATION);
+            headers.remove(header::COOKIE);
+        }
+        let resp = mirror::send(client.get(&upstream_url).headers(headers), timeouts).await?;
+        let response_headers = upstream_response_headers(resp.headers());
+        return Ok(mirror::relay(resp, response_headers, timeouts, counters, cache).await);
+    }
+
+    let mut headers = forward_headers(req.headers(), target_url, client_ip, trace);
+    // 索引需要解析改写，由客户端库协商压缩并解压；部分内容无法改写，始终请求完整文档
+    headers.remove(header::ACCEPT_ENCODING);
+    headers.remove(header::RANGE);
+    headers.remove(header::IF_RANGE);
+    let resp = mirror::send(client.get(target_url).headers(headers), timeouts).await?;
+
+    let status = resp.status();
+    let response_headers = upstream_response_headers(resp.headers());
+    let content_type = response_headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let is_json = content_type.contains("json");
+    let is_html = content_type.starts_with("text/html");
+
+    let files_prefix = mirror::public_base(options.public_url.as_deref(), req.headers())
+        .zip(prefix)
+        .map(|(base, prefix)| format!("{}{}{}", base, prefix, FILES_PATH));
+    let Some(files_prefix) =
+        files_prefix.filter(|_| status == StatusCode::OK && (is_json || is_html))
+    else {
+        return Ok(mirror::relay(resp, response_headers, timeouts, counters, None).await);
+    };
+
+    let body = mirror::read_body(resp, timeouts).await?;
+    let links = Links {
+        upstream: options.files_url(),
+        mirror: &files_prefix,
+    };
+    let body = if is_json {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(mut doc) => {
+                if links.rewrite_json(&mut doc) > 0 {
+                    serde_json::to_vec(&doc).map(Into::into).unwrap_or(body)
+                } else {
+                    body
+                }
+            }
+            Err(e) => {
+                tracing::warn!(path = %path, "Failed to parse PyPI index: {}", e);
+                body
+            }
+        }
+    } else {
+        match std::str::from_utf8(&body) {
+            Ok(html) => Bytes::from(links.rewrite_html(html)),
+            Err(_) => body,
+        }
+    };
+    Ok(mirror::buffered(status, response_headers, body, &counters))
+}
+
+fn is_sha256(value: &str) -> bool {
+    value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// 文件链接改写
+struct Links<'a> {
+    /// 上游文件服务器地址前缀
+    upstream: &'a str,
+    /// 镜像的文件下载地址前缀
+    mirror: &'a str,
+}
+
+impl Links<'_> {
+    /// 指向上游文件服务器的链接改写为镜像地址，已知 sha256 时放入路径供下载时校验
+    fn rewrite(&self, url: &str, sha256: Option<&str>) -> Option<String> {
+        let rest = mirror::strip_upstream(url, self.upstream)?;
+        Some(match sha256.filter(|hash| is_sha256(hash)) {
+            Some(hash) => format!("{}{}/{}", self.mirror, hash.to_ascii_lowercase(), rest),
+            None => format!("{}{}", self.mirror, rest),
+        })
+    }
+
+    /// HTML 索引（PEP 503）：改写 href，摘要取自 #sha256= 片段
+    fn rewrite_html(&self, html: &str) -> String {
+        let href = Regex::new(r#"href="([^"]*)""#).unwrap();
+        href.replace_all(html, |caps: &Captures| {
+            let url = &caps[1];
+            let sha256 = url
+                .split_once('#')
+                .and_then(|(_, fragment)| fragment.strip_prefix("sha256="));
+            match self.rewrite(url, sha256) {
+                Some(url) => format!("href=\"{}\"", url),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+    }
+
+    /// JSON 索引（PEP 691）：改写 files[].url，摘要取自 hashes.sha256，返回改写数量
+    fn rewrite_json(&self, doc: &mut Value) -> usize {
+        let Some(files) = doc.get_mut("files").and_then(Value::as_array_mut) else {
+            return 0;
+        };
+        let mut rewritten = 0;
+        for file in files {
+            let sha256 = file
+                .pointer("/hashes/sha256")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            if let Some(Value::String(url)) = file.get_mut("url") {
+                if let Some(new_url) = self.rewrite(url, sha256.as_deref()) {
+                    *url = new_url;
+                    rewritten += 1;
+                }
+            }
+        }
+        rewritten
+    }
+}
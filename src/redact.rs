@@ -0,0 +1,152 @@
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::config::RedactConfig;
+
+/// 脱敏后的替换值
+pub const REDACTED: &str = "[REDACTED]";
+
+struct Redactor {
+    headers: HashSet<String>,
+    query_params: HashSet<String>,
+}
+
+impl Redactor {
+    fn new(config: &RedactConfig) -> Self {
+        let lower = |names: &[String]| names.iter().map(|n| n.to_ascii_lowercase()).collect();
+        Self {
+            headers: lower(&config.headers),
+            query_params: lower(&config.query_params),
+        }
+    }
+
+    fn sensitive_param(&self, pair: &str) -> bool {
+        let name = pair.split('=').next().unwrap_or_default();
+        self.query_params.contains(
+            &percent_decode_str(name)
+                .decode_utf8_lossy()
+                .to_ascii_lowercase(),
+        )
+    }
+
+    fn url<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        let Some((base, rest)) = url.split_once('?') else {
+            return Cow::Borrowed(url);
+        };
+        let (query, fragment) = match rest.split_once('#') {
+            Some((query, fragment)) => (query, Some(fragment)),
+            None => (rest, None),
+        };
+        if !query.split('&').any(|pair| self.sensitive_param(pair)) {
+            return Cow::Borrowed(url);
+        }
+
+        let mut out = String::with_capacity(url.len());
+        out.push_str(base);
+        out.push('?');
+        for (i, pair) in query.split('&').enumerate() {
+            if i > 0 {
+                out.push('&');
+            }
+            if self.sensitive_param(pair) {
+                out.push_str(pair.split('=').next().unwrap_or_default());
+                out.push('=');
+                out.push_str(REDACTED);
+            } else {
+                out.push_str(pair);
+            }
+        }
+        if let Some(fragment) = fragment {
+            out.push('#');
+            out.push_str(fragment);
+        }
+        Cow::Owned(out)
+    }
+
+    fn header<'a>(&self, name: &str, value: &'a str) -> Cow<'a, str> {
+        if self.headers.contains(&name.to_ascii_lowercase()) {
+            Cow::Borrowed(REDACTED)
+        } else {
+            self.url(value)
+        }
+    }
+}
+
+/// 与日志订阅器一样全局生效，各处日志输出无需传递配置
+static REDACTOR: OnceLock<Redactor> = OnceLock::new();
+
+/// 按配置初始化，启动时在输出请求日志前调用；未调用时使用默认列表
+pub fn init(config: &RedactConfig) {
+    let _ = REDACTOR.set(Redactor::new(config));
+}
+
+fn redactor() -> &'static Redactor {
+    REDACTOR.get_or_init(|| Redactor::new(&RedactConfig::default()))
+}
+
+/// URL 或请求路径，脱敏列表中的查询参数值替换为 [REDACTED]
+pub fn url(url: &str) -> Cow<'_, str> {
+    redactor().url(url)
+}
+
+/// 请求头值：名称在脱敏列表中时整体替换，否则脱敏其中 URL 的查询参数（如 Referer）
+pub fn header<'a>(name: &str, value: &'a str) -> Cow<'a, str> {
+    redactor().header(name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::new(&RedactConfig {
+            headers: vec!["X-Api-Key".to_string()],
+            query_params: vec!["Token".to_string(), "sig".to_string()],
+        })
+    }
+
+    #[test]
+    fn query_param_names_ignore_case() {
+        let r = redactor();
+        assert_eq!(
+            r.url("/a?TOKEN=1&token=2&ToKeN=3&page=4"),
+            "/a?TOKEN=[REDACTED]&token=[REDACTED]&ToKeN=[REDACTED]&page=4"
+        );
+    }
+
+    #[test]
+    fn repeated_params_are_all_redacted() {
+        let r = redactor();
+        assert_eq!(
+            r.url("https://example.com/f?sig=a&x=1&sig=b&sig#top"),
+            "https://example.com/f?sig=[REDACTED]&x=1&sig=[REDACTED]&sig=[REDACTED]#top"
+        );
+    }
+
+    #[test]
+    fn percent_encoded_names_are_decoded() {
+        let r = redactor();
+        assert_eq!(r.url("/a?%74oken=1"), "/a?%74oken=[REDACTED]");
+    }
+
+    #[test]
+    fn urls_without_sensitive_params_are_borrowed() {
+        let r = redactor();
+        assert!(matches!(r.url("/a?page=1&tokens=2"), Cow::Borrowed(_)));
+        assert!(matches!(r.url("/a"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn header_names_ignore_case() {
+        let r = redactor();
+        assert_eq!(r.header("x-api-key", "secret"), REDACTED);
+        assert_eq!(r.header("X-API-KEY", "secret"), REDACTED);
+        assert_eq!(
+            r.header("Referer", "https://example.com/?token=abc"),
+            "https://example.com/?token=[REDACTED]"
+        );
+        assert_eq!(r.header("Accept", "text/html"), "text/html");
+    }
+}
@@ -0,0 +1,465 @@
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use dashmap::DashMap;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cache_store::CacheStore;
+use crate::mirror::{self, CacheFile};
+use crate::proxy::{
+    forward_headers, upstream_response_headers, CompiledProxyRule, ForwardTimeouts, ProxyState,
+};
+use crate::secrets::Secrets;
+use crate::stats::RuleCounters;
+use crate::telemetry::TraceParent;
+
+/// 镜像上的 token 路径（相对 /v2/），仓库名不能以下划线开头，不会与仓库 API 冲突
+const TOKEN_PATH: &str = "_token";
+
+/// 认证服务未返回 expires_in 时的有效期，规范规定为 60 秒
+const DEFAULT_TOKEN_TTL_SECS: u64 = 60;
+
+/// token 提前失效的余量
+const TOKEN_EXPIRY_MARGIN_SECS: u64 = 10;
+
+/// token 缓存超过该数量时清理已过期的条目
+const MAX_CACHED_TOKENS: usize = 1024;
+
+const DIGEST_HEADER: &str = "docker-content-digest";
+
+/// 镜像仓库（Docker Registry v2）规则选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RegistryOptions {
+    /// 上游仓库用户名，与 password_secret 一起使用，用于私有镜像或提高拉取限额
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// 上游仓库密码或访问令牌在密钥存储中的名称
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_secret: Option<String>,
+    /// blob 缓存目录，按摘要保存，未设置时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<String>,
+    /// 镜像对外地址（如 https://mirror.example.com），用于改写 WWW-Authenticate，
+    /// 未设置时按请求的 Host 与 X-Forwarded-Proto 生成
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+/// WWW-Authenticate: Bearer 质询
+#[derive(Debug, Clone)]
+struct Challenge {
+    params: Vec<(String, String)>,
+}
+
+impl Challenge {
+    fn parse(value: &str) -> Option<Self> {
+        let (scheme, rest) = value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+        let mut params = Vec::new();
+        let mut chars = rest.chars().peekable();
+        loop {
+            while chars.peek().is_some_and(|c| *c == ',' || c.is_whitespace()) {
+                chars.next();
+            }
+            let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+            if key.is_empty() {
+                break;
+            }
+            let mut value = String::new();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next()),
+                        '"' => break,
+                        c => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| *c != ',') {
+                    value.push(c);
+                }
+            }
+            params.push((key.trim().to_ascii_lowercase(), value));
+        }
+        let challenge = Self { params };
+        challenge.get("realm").is_some().then_some(challenge)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(Self::parse)
+    }
+
+    /// 替换 realm 后重新生成质询头
+    fn with_realm(&self, realm: &str) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(k, v)| {
+                let v = if k == "realm" { realm } else { v };
+                format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\""))
+            })
+            .collect();
+        format!("Bearer {}", params.join(","))
+    }
+}
+
+/// 上游认证服务
+#[derive(Debug, Clone)]
+struct AuthService {
+    realm: String,
+    service: Option<String>,
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+/// 镜像仓库状态：按规则缓存上游认证服务与 Bearer token
+#[derive(Clone, Default)]
+pub struct RegistryMirror {
+    /// 规则 id -> 上游认证服务，从质询中获得
+    auth_services: Arc<DashMap<i64, AuthService>>,
+    /// "规则 id|service|scope" -> token
+    tokens: Arc<DashMap<String, CachedToken>>,
+}
+
+impl RegistryMirror {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn token_key(rule_id: i64, service: Option<&str>, scope: Option<&str>) -> String {
+        format!(
+            "{}|{}|{}",
+            rule_id,
+            service.unwrap_or_default(),
+            scope.unwrap_or_default()
+        )
+    }
+
+    fn cached_token(&self, key: &str) -> Option<String> {
+        self.tokens
+            .get(key)
+            .filter(|t| t.expires_at > Instant::now())
+            .map(|t| t.token.clone())
+    }
+
+    /// 按请求的仓库名查找已缓存的 token，避免每次请求都先收到 401
+    fn token_for_request(&self, rule_id: i64, path: &str) -> Option<String> {
+        let service = self.auth_services.get(&rule_id)?.service.clone();
+        let scope = repository_name(path).map(|name| format!("repository:{}:pull", name));
+        self.cached_token(&Self::token_key(
+            rule_id,
+            service.as_deref(),
+            scope.as_deref(),
+        ))
+    }
+
+    /// 按质询向认证服务获取 token，配置了上游账号时使用 Basic 认证
+    async fn fetch_token(
+        &self,
+        client: &Client,
+        rule_id: i64,
+        challenge: &Challenge,
+        options: &RegistryOptions,
+        secrets: &Secrets,
+    ) -> anyhow::Result<String> {
+        let service = challenge.get("service");
+        let scope = challenge.get("scope");
+        let key = Self::token_key(rule_id, service, scope);
+        if let Some(token) = self.cached_token(&key) {
+            return Ok(token);
+        }
+
+        let mut url = reqwest::Url::parse(challenge.get("realm").unwrap_or_default())?;
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(service) = service {
+                query.append_pair("service", service);
+            }
+            if let Some(scope) = scope {
+                query.append_pair("scope", scope);
+            }
+        }
+        let mut request = client.get(url);
+        if let (Some(username), Some(secret)) = (&options.username, &options.password_secret) {
+            let password = secrets
+                .get(secret)
+                .with_context(|| format!("Secret '{}' not found", secret))?;
+            request = request.basic_auth(username, Some(password));
+        }
+        let resp: TokenResponse = request.send().await?.error_for_status()?.json().await?;
+        let token = resp
+            .token
+            .or(resp.access_token)
+            .context("Token response has no token")?;
+        let ttl = resp
+            .expires_in
+            .unwrap_or(DEFAULT_TOKEN_TTL_SECS)
+            .saturating_sub(TOKEN_EXPIRY_MARGIN_SECS)
+            .max(1);
+
+        if self.tokens.len() >= MAX_CACHED_TOKENS {
+            let now = Instant::now();
+            self.tokens.retain(|_, t| t.expires_at > now);
+        }
+        self.tokens.insert(
+            key,
+            CachedToken {
+                token: token.clone(),
+                expires_at: Instant::now() + Duration::from_secs(ttl),
+            },
+        );
+        Ok(token)
+    }
+
+    /// 镜像的 token 路径：转发到上游认证服务，供收到改写后质询的客户端自行认证
+    async fn token_endpoint(
+        &self,
+        client: &Client,
+        rule_id: i64,
+        target_url: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+        timeouts: ForwardTimeouts,
+    ) -> Result<Response, StatusCode> {
+        if !self.auth_services.contains_key(&rule_id) {
+            // 尚未收到过质询时请求上游 /v2/ 获取认证服务地址
+            let root = v2_root(target_url).ok_or(StatusCode::NOT_FOUND)?;
+            let resp = mirror::send(client.get(root), timeouts).await?;
+            let challenge = Challenge::from_headers(resp.headers()).ok_or(StatusCode::NOT_FOUND)?;
+            self.remember(rule_id, &challenge);
+        }
+        let realm = self
+            .auth_services
+            .get(&rule_id)
+            .map(|s| s.realm.clone())
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let url = match query {
+            Some(q) => format!("{}?{}", realm, q),
+            None => realm,
+        };
+        let mut request = client.get(url);
+        if let Some(auth) = headers.get(header::AUTHORIZATION) {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        let resp = mirror::send(request, timeouts).await?;
+        let status = resp.status();
+        let content_type = resp.headers().get(header::CONTENT_TYPE).cloned();
+        let body = resp.bytes().await.map_err(|e| {
+            tracing::error!("Failed to read registry token response: {}", e);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+        let mut out = Response::new(Body::from(body));
+        *out.status_mut() = status;
+        if let Some(content_type) = content_type {
+            out.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        Ok(out)
+    }
+
+    fn remember(&self, rule_id: i64, challenge: &Challenge) {
+        if let Some(realm) = challenge.get("realm") {
+            self.auth_services.insert(
+                rule_id,
+                AuthService {
+                    realm: realm.to_string(),
+                    service: challenge.get("service").map(str::to_string),
+                },
+            );
+        }
+    }
+}
+
+/// 镜像规则转发：只允许拉取（GET / HEAD），上游要求 Bearer token 时由代理获取并缓存，
+/// 客户端收到的质询指向镜像自身；配置缓存目录时 blob 按摘要保存到本地
+pub async fn forward(
+    state: &ProxyState,
+    rule: &CompiledProxyRule,
+    options: &RegistryOptions,
+    req: Request,
+    target_url: &str,
+    client_ip: &str,
+    counters: Arc<RuleCounters>,
+) -> Result<Response, StatusCode> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return Err(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let registry = &state.registry;
+    let client = state.rule_client(rule)?;
+    let timeouts = ForwardTimeouts::for_rule(rule);
+    let path = req.uri().path();
+
+    if path
+        .rsplit_once('/')
+        .is_some_and(|(_, last)| last == TOKEN_PATH)
+    {
+        return registry
+            .token_endpoint(
+                &client,
+                rule.id,
+                target_url,
+                req.uri().query(),
+                req.headers(),
+                timeouts,
+            )
+            .await;
+    }
+
+    let is_get = req.method() == Method::GET;
+    let blob = options
+        .cache_dir
+        .as_deref()
+        .and_then(|dir| cached_blob(&state.cache_store, dir, path));
+    if let Some((ref file, ref digest)) = blob {
+        if let Some(mut resp) = file.serve(req.headers(), is_get, &counters).await {
+            if let Ok(value) = HeaderValue::from_str(digest) {
+                resp.headers_mut().insert(DIGEST_HEADER, value);
+            }
+            tracing::debug!(digest = %digest, "Registry blob served from cache");
+            return Ok(resp);
+        }
+    }
+
+    let mut headers = forward_headers(
+        req.headers(),
+        target_url,
+        client_ip,
+        req.extensions().get::<TraceParent>().copied(),
+    );
+    let client_auth = headers.contains_key(header::AUTHORIZATION);
+    if !client_auth {
+        if let Some(token) = registry.token_for_request(rule.id, path) {
+            set_bearer(&mut headers, &token);
+        }
+    }
+    let method = if is_get {
+        reqwest::Method::GET
+    } else {
+        reqwest::Method::HEAD
+    };
+    let request = |headers: HeaderMap| client.request(method.clone(), target_url).headers(headers);
+
+    let mut resp = mirror::send(request(headers.clone()), timeouts).await?;
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        if let Some(challenge) = Challenge::from_headers(resp.headers()) {
+            registry.remember(rule.id, &challenge);
+            if !client_auth {
+                match registry
+                    .fetch_token(&client, rule.id, &challenge, options, &state.secrets)
+                    .await
+                {
+                    Ok(token) => {
+                        set_bearer(&mut headers, &token);
+                        resp = mirror::send(request(headers), timeouts).await?;
+                    }
+                    Err(e) => {
+                        tracing::warn!(rule = %rule.name, "Failed to fetch registry token: {}", e)
+                    }
+                }
+            }
+        }
+    }
+
+    let mut response_headers = upstream_response_headers(resp.headers());
+    if resp.status() == StatusCode::UNAUTHORIZED {
+        rewrite_challenge(&mut response_headers, req.headers(), path, options);
+    }
+
+    let cache = blob.filter(|_| is_get).map(|(file, _)| file);
+    Ok(mirror::relay(resp, response_headers, timeouts, counters, cache).await)
+}
+
+fn set_bearer(headers: &mut HeaderMap, token: &str) {
+    if let Ok(mut value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+        value.set_sensitive(true);
+        headers.insert(header::AUTHORIZATION, value);
+    }
+}
+
+/// 将质询的 realm 改为镜像的 token 路径，客户端无需直接访问上游认证服务
+fn rewrite_challenge(
+    response_headers: &mut HeaderMap,
+    request_headers: &HeaderMap,
+    path: &str,
+    options: &RegistryOptions,
+) {
+    let Some(challenge) = Challenge::from_headers(response_headers) else {
+        return;
+    };
+    let Some(prefix) = path.find("/v2/").map(|idx| &path[..idx + 4]) else {
+        return;
+    };
+    let Some(base) = mirror::public_base(options.public_url.as_deref(), request_headers) else {
+        return;
+    };
+    let realm = format!("{}{}{}", base, prefix, TOKEN_PATH);
+    if let Ok(value) = HeaderValue::from_str(&challenge.with_realm(&realm)) {
+        response_headers.insert(header::WWW_AUTHENTICATE, value);
+    }
+}
+
+/// 上游 /v2/ 根地址
+fn v2_root(target_url: &str) -> Option<&str> {
+    target_url.find("/v2/").map(|idx| &target_url[..idx + 4])
+}
+
+/// 从 /v2/<name>/manifests|blobs|tags/... 中取出仓库名
+fn repository_name(path: &str) -> Option<&str> {
+    let rest = &path[path.find("/v2/")? + 4..];
+    ["/manifests/", "/blobs/", "/tags/"]
+        .iter()
+        .filter_map(|marker| rest.rfind(marker))
+        .max()
+        .map(|idx| &rest[..idx])
+        .filter(|name| !name.is_empty())
+}
+
+/// 只缓存 /v2/<name>/blobs/sha256:<hex>，上传等路径不处理；返回缓存文件与摘要
+fn cached_blob(store: &Arc<dyn CacheStore>, dir: &str, path: &str) -> Option<(CacheFile, String)> {
+    let (_, digest) = path.rsplit_once("/blobs/")?;
+    let hex = digest.strip_prefix("sha256:")?;
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = hex.to_ascii_lowercase();
+    let file = CacheFile::new(
+        store,
+        Path::new(dir),
+        &Path::new("sha256").join(&hex),
+        Some(hex.clone()),
+    );
+    Some((file, format!("sha256:{}", hex)))
+}
@@ -0,0 +1,263 @@
+use chrono::Local;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::db::ProxyRule;
+use crate::proxy::CompiledProxyRule;
+use crate::router;
+
+/// 保留最近的重载记录条数
+const HISTORY_SIZE: usize = 50;
+
+/// 一次规则重载的摘要
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadSummary {
+    pub time: String,
+    /// 触发来源，如 startup、create_rule
+    pub trigger: &'static str,
+    pub duration_ms: u64,
+    pub rules_compiled: usize,
+    pub rules_failed: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// 编译失败的规则及原因
+    pub failures: Vec<ReloadFailure>,
+    /// 读取规则失败时的错误，此时规则保持不变
+    pub error: Option<String>,
+}
+
+impl ReloadSummary {
+    pub fn new(trigger: &'static str) -> Self {
+        Self {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            trigger,
+            duration_ms: 0,
+            rules_compiled: 0,
+            rules_failed: 0,
+            added: 0,
+            removed: 0,
+            changed: 0,
+            failures: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadFailure {
+    pub rule_id: i64,
+    pub name: String,
+    pub error: String,
+}
+
+/// 有规则编译失败或相互冲突，新规则未生效，仍使用原规则
+#[derive(Debug)]
+pub struct RulesRejected {
+    pub failures: Vec<ReloadFailure>,
+}
+
+impl std::fmt::Display for RulesRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rule(s) failed to compile or conflict, keeping previous rules",
+            self.failures.len()
+        )
+    }
+}
+
+impl std::error::Error for RulesRejected {}
+
+/// 完整编译规则集，返回能编译的规则以及编译失败、被前面的规则遮蔽的规则
+pub fn compile(rules: &[ProxyRule]) -> (Vec<CompiledProxyRule>, Vec<ReloadFailure>) {
+    let mut failures = Vec::new();
+    let compiled: Vec<CompiledProxyRule> = rules
+        .iter()
+        .filter_map(|rule| match CompiledProxyRule::from_db_rule(rule) {
+            Ok(compiled) => {
+                tracing::debug!(name = %rule.name, source = %rule.source, "Loaded rule");
+                Some(compiled)
+            }
+            Err(e) => {
+                tracing::error!(source = %rule.source, error = %e, "Failed to compile rule");
+                failures.push(ReloadFailure {
+                    rule_id: rule.id,
+                    name: rule.name.clone(),
+                    error: e.to_string(),
+                });
+                None
+            }
+        })
+        .collect();
+    for (later, earlier) in router::shadowed(&compiled) {
+        let (rule, earlier) = (&compiled[later], &compiled[earlier]);
+        tracing::error!(name = %rule.name, shadowed_by = %earlier.name, "Rule conflicts with an earlier rule");
+        failures.push(ReloadFailure {
+            rule_id: rule.id,
+            name: rule.name.clone(),
+            error: format!(
+                "never matched: earlier rule '{}' with the same source always matches first",
+                earlier.name
+            ),
+        });
+    }
+    (compiled, failures)
+}
+
+/// 写入规则前校验修改后的启用规则集，有规则编译失败或冲突时返回 RulesRejected
+pub fn check(rules: &[ProxyRule]) -> anyhow::Result<()> {
+    let (_, failures) = compile(rules);
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(RulesRejected { failures }.into())
+    }
+}
+
+/// 与上次生效规则的差异
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RuleDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    history: VecDeque<ReloadSummary>,
+    /// 上次生效规则的内容指纹，用于计算差异
+    fingerprints: HashMap<i64, String>,
+}
+
+/// 规则重载记录
+#[derive(Clone, Default)]
+pub struct ReloadHistory {
+    inner: Arc<Mutex<Inner>>,
+    total: Arc<AtomicU64>,
+    failures: Arc<AtomicU64>,
+    last_duration_ms: Arc<AtomicU64>,
+}
+
+fn fingerprint(rule: &ProxyRule) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        rule.name,
+        rule.source,
+        rule.target,
+        rule.timeout_secs,
+        serde_json::to_string(&rule.options).unwrap_or_default()
+    )
+}
+
+impl ReloadHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 计算与上次生效规则的差异，并记住本次规则
+    pub fn diff(&self, rules: &[ProxyRule]) -> RuleDiff {
+        let current: HashMap<i64, String> = rules.iter().map(|r| (r.id, fingerprint(r))).collect();
+        let mut inner = self.inner.lock();
+        let previous = &inner.fingerprints;
+
+        let mut diff = RuleDiff::default();
+        for (id, fp) in &current {
+            match previous.get(id) {
+                None => diff.added += 1,
+                Some(old) if old != fp => diff.changed += 1,
+                _ => {}
+            }
+        }
+        diff.removed = previous
+            .keys()
+            .filter(|id| !current.contains_key(id))
+            .count();
+
+        inner.fingerprints = current;
+        diff
+    }
+
+    pub fn record(&self, summary: ReloadSummary) {
+        if let Some(ref error) = summary.error {
+            tracing::error!(trigger = summary.trigger, error = %error, "Rule reload failed");
+        } else {
+            tracing::info!(
+                trigger = summary.trigger,
+                duration_ms = summary.duration_ms,
+                compiled = summary.rules_compiled,
+                failed = summary.rules_failed,
+                added = summary.added,
+                removed = summary.removed,
+                changed = summary.changed,
+                "Rules reloaded"
+            );
+        }
+
+        self.total.fetch_add(1, Ordering::Relaxed);
+        if summary.error.is_some() || summary.rules_failed > 0 {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.last_duration_ms
+            .store(summary.duration_ms, Ordering::Relaxed);
+
+        let mut inner = self.inner.lock();
+        if inner.history.len() >= HISTORY_SIZE {
+            inner.history.pop_front();
+        }
+        inner.history.push_back(summary);
+    }
+
+    /// 最近的重载记录，最新的在前
+    pub fn recent(&self, limit: usize) -> Vec<ReloadSummary> {
+        self.inner
+            .lock()
+            .history
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Prometheus 文本格式
+    pub fn render_prometheus(&self, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "# HELP proxy_rule_reloads_total Number of rule reloads"
+        );
+        let _ = writeln!(out, "# TYPE proxy_rule_reloads_total counter");
+        let _ = writeln!(
+            out,
+            "proxy_rule_reloads_total {}",
+            self.total.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP proxy_rule_reload_failures_total Number of reloads with errors or rules that failed to compile"
+        );
+        let _ = writeln!(out, "# TYPE proxy_rule_reload_failures_total counter");
+        let _ = writeln!(
+            out,
+            "proxy_rule_reload_failures_total {}",
+            self.failures.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "# HELP proxy_rule_reload_last_duration_milliseconds Duration of the last rule reload"
+        );
+        let _ = writeln!(
+            out,
+            "# TYPE proxy_rule_reload_last_duration_milliseconds gauge"
+        );
+        let _ = writeln!(
+            out,
+            "proxy_rule_reload_last_duration_milliseconds {}",
+            self.last_duration_ms.load(Ordering::Relaxed)
+        );
+    }
+}
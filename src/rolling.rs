@@ -0,0 +1,171 @@
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 保留最近 15 分钟，每秒一个桶
+const WINDOW_SECS: usize = 900;
+
+/// 延迟直方图桶上界(毫秒)，超出最后一个上界的计入溢出桶
+const LATENCY_BOUNDS_MS: [u64; 14] = [
+    5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000, 60000, 120000,
+];
+
+#[derive(Clone)]
+struct Bucket {
+    second: i64,
+    requests: u64,
+    errors: u64,
+    latency: [u64; LATENCY_BOUNDS_MS.len() + 1],
+    statuses: Vec<(u16, u64)>,
+}
+
+impl Bucket {
+    fn empty() -> Self {
+        Self {
+            second: -1,
+            requests: 0,
+            errors: 0,
+            latency: [0; LATENCY_BOUNDS_MS.len() + 1],
+            statuses: Vec::new(),
+        }
+    }
+}
+
+/// 直方图中的一个桶，le_ms 为 None 表示溢出桶
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramBucket {
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+/// 单个时间窗口的聚合结果
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowStats {
+    pub window_secs: u64,
+    pub requests: u64,
+    pub requests_per_sec: f64,
+    pub error_rate: f64,
+    /// 分位数取所在直方图桶的上界
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub status_counts: BTreeMap<u16, u64>,
+    pub latency_histogram: Vec<HistogramBucket>,
+}
+
+/// 全局请求滚动统计，按秒分桶，只保留最近 15 分钟
+#[derive(Clone)]
+pub struct RollingStats {
+    buckets: Arc<Mutex<Vec<Bucket>>>,
+    started: i64,
+}
+
+impl Default for RollingStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RollingStats {
+    pub fn new() -> Self {
+        Self {
+            buckets: Arc::new(Mutex::new(vec![Bucket::empty(); WINDOW_SECS])),
+            started: Utc::now().timestamp(),
+        }
+    }
+
+    /// 记录一次请求完成，5xx 计为错误
+    pub fn record(&self, status: u16, latency: Duration) {
+        let now = Utc::now().timestamp();
+        let ms = latency.as_millis() as u64;
+        let slot = LATENCY_BOUNDS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(LATENCY_BOUNDS_MS.len());
+
+        let mut buckets = self.buckets.lock();
+        let bucket = &mut buckets[now as usize % WINDOW_SECS];
+        if bucket.second != now {
+            *bucket = Bucket::empty();
+            bucket.second = now;
+        }
+        bucket.requests += 1;
+        if status >= 500 {
+            bucket.errors += 1;
+        }
+        bucket.latency[slot] += 1;
+        match bucket.statuses.iter_mut().find(|(s, _)| *s == status) {
+            Some((_, count)) => *count += 1,
+            None => bucket.statuses.push((status, 1)),
+        }
+    }
+
+    /// 聚合最近 window_secs 秒（最多 15 分钟）
+    pub fn window(&self, window_secs: u64) -> WindowStats {
+        let window_secs = window_secs.min(WINDOW_SECS as u64);
+        let now = Utc::now().timestamp();
+        let mut requests = 0;
+        let mut errors = 0;
+        let mut latency = [0u64; LATENCY_BOUNDS_MS.len() + 1];
+        let mut status_counts = BTreeMap::new();
+
+        for bucket in self.buckets.lock().iter() {
+            if bucket.second < 0 || now - bucket.second >= window_secs as i64 {
+                continue;
+            }
+            requests += bucket.requests;
+            errors += bucket.errors;
+            for (total, n) in latency.iter_mut().zip(bucket.latency.iter()) {
+                *total += n;
+            }
+            for (status, count) in &bucket.statuses {
+                *status_counts.entry(*status).or_insert(0) += count;
+            }
+        }
+
+        // 启动不足一个窗口时按实际运行时间计算速率
+        let elapsed = (now - self.started + 1).clamp(1, window_secs as i64) as f64;
+
+        WindowStats {
+            window_secs,
+            requests,
+            requests_per_sec: requests as f64 / elapsed,
+            error_rate: if requests == 0 {
+                0.0
+            } else {
+                errors as f64 / requests as f64
+            },
+            p50_ms: histogram_percentile(&latency, requests, 50),
+            p95_ms: histogram_percentile(&latency, requests, 95),
+            p99_ms: histogram_percentile(&latency, requests, 99),
+            status_counts,
+            latency_histogram: latency
+                .iter()
+                .enumerate()
+                .map(|(i, &count)| HistogramBucket {
+                    le_ms: LATENCY_BOUNDS_MS.get(i).copied(),
+                    count,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn histogram_percentile(latency: &[u64], total: u64, p: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    let rank = (total * p).div_ceil(100).max(1);
+    let mut seen = 0;
+    for (i, count) in latency.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            // 溢出桶没有上界，取最后一个上界
+            return LATENCY_BOUNDS_MS[i.min(LATENCY_BOUNDS_MS.len() - 1)];
+        }
+    }
+    LATENCY_BOUNDS_MS[LATENCY_BOUNDS_MS.len() - 1]
+}
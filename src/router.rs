@@ -0,0 +1,186 @@
+use std::ops::Deref;
+
+use crate::proxy::CompiledProxyRule;
+
+/// 按来源路径静态前缀建立的字节前缀树，节点记录前缀在此结束的规则下标
+#[derive(Debug, Default)]
+struct Node {
+    /// 按字节排序，便于二分查找
+    children: Vec<(u8, usize)>,
+    rules: Vec<usize>,
+}
+
+/// 编译后的规则集合：保留规则顺序，并用静态前缀缩小每次请求需要正则匹配的候选规则
+///
+/// 规则在 `reload_rules` 时整体重建，通过 ArcSwap 原子替换
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledProxyRule>,
+    nodes: Vec<Node>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<CompiledProxyRule>) -> Self {
+        let mut nodes = vec![Node::default()];
+        for (index, rule) in rules.iter().enumerate() {
+            let mut node = 0;
+            for &byte in rule.static_prefix.as_bytes() {
+                node = match nodes[node]
+                    .children
+                    .binary_search_by_key(&byte, |(b, _)| *b)
+                {
+                    Ok(i) => nodes[node].children[i].1,
+                    Err(i) => {
+                        nodes.push(Node::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].children.insert(i, (byte, child));
+                        child
+                    }
+                };
+            }
+            nodes[node].rules.push(index);
+        }
+        Self { rules, nodes }
+    }
+
+    /// 静态前缀与路径相符的规则，按规则顺序返回；是否真正匹配仍由规则自身判断
+    pub fn candidates(&self, path: &str) -> impl Iterator<Item = &CompiledProxyRule> {
+        let mut indices = self.nodes[0].rules.clone();
+        let mut node = 0;
+        for &byte in path.as_bytes() {
+            let children = &self.nodes[node].children;
+            match children.binary_search_by_key(&byte, |(b, _)| *b) {
+                Ok(i) => node = children[i].1,
+                Err(_) => break,
+            }
+            indices.extend_from_slice(&self.nodes[node].rules);
+        }
+        indices.sort_unstable();
+        indices.into_iter().map(|index| &self.rules[index])
+    }
+}
+
+impl Deref for RuleSet {
+    type Target = [CompiledProxyRule];
+
+    fn deref(&self) -> &Self::Target {
+        &self.rules
+    }
+}
+
+/// 永远不会被匹配的规则：与前面某条规则的源路径相同，且前面的规则不附加更严格的匹配条件
+///
+/// 返回 (被遮蔽的规则下标, 遮蔽它的规则下标)
+pub fn shadowed(rules: &[CompiledProxyRule]) -> Vec<(usize, usize)> {
+    let mut shadowed = Vec::new();
+    for (later, rule) in rules.iter().enumerate() {
+        let earlier = rules[..later].iter().position(|earlier| {
+            earlier.source_pattern.as_str() == rule.source_pattern.as_str()
+                && covers_size(earlier, rule)
+                && rule
+                    .options
+                    .tags
+                    .iter()
+                    .all(|tag| earlier.options.tags.contains(tag))
+        });
+        if let Some(earlier) = earlier {
+            shadowed.push((later, earlier));
+        }
+    }
+    shadowed
+}
+
+/// 前面规则的请求体大小条件是否覆盖后面规则可能匹配的全部请求
+fn covers_size(earlier: &CompiledProxyRule, later: &CompiledProxyRule) -> bool {
+    let (e, l) = (&earlier.options, &later.options);
+    let unrestricted = e.min_request_bytes.is_none() && e.max_request_bytes.is_none();
+    unrestricted
+        || (e.min_request_bytes == l.min_request_bytes
+            && e.max_request_bytes == l.max_request_bytes
+            && e.match_unknown_length == l.match_unknown_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{ProxyRule, RuleOptions};
+
+    fn rule(id: i64, source: &str, options: RuleOptions) -> CompiledProxyRule {
+        CompiledProxyRule::from_db_rule(&ProxyRule {
+            id,
+            name: format!("rule-{}", id),
+            source: source.to_string(),
+            target: "http://upstream/{*path}".to_string(),
+            timeout_secs: 30,
+            enabled: true,
+            options,
+            created_at: String::new(),
+            updated_at: String::new(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn duplicate_source_is_shadowed_by_first_rule() {
+        let rules = [
+            rule(1, "/api/{*path}", RuleOptions::default()),
+            rule(2, "/other/{*path}", RuleOptions::default()),
+            rule(3, "/api/{*path}", RuleOptions::default()),
+            rule(4, "/api/{*path}", RuleOptions::default()),
+        ];
+        assert_eq!(shadowed(&rules), vec![(2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn different_sources_do_not_conflict() {
+        let rules = [
+            rule(1, "/api/{*path}", RuleOptions::default()),
+            rule(2, "/api/v2/{*path}", RuleOptions::default()),
+            rule(3, "/api/{id}", RuleOptions::default()),
+        ];
+        assert!(shadowed(&rules).is_empty());
+    }
+
+    #[test]
+    fn size_restricted_rule_does_not_shadow() {
+        let large = RuleOptions {
+            min_request_bytes: Some(1024),
+            ..Default::default()
+        };
+        let rules = [
+            rule(1, "/upload/{*path}", large.clone()),
+            rule(2, "/upload/{*path}", RuleOptions::default()),
+            rule(3, "/upload/{*path}", large),
+        ];
+        // 同样的大小条件仍然冲突，不限大小的规则覆盖后续所有规则
+        assert_eq!(shadowed(&rules), vec![(2, 0)]);
+
+        let rules = [
+            rule(1, "/upload/{*path}", RuleOptions::default()),
+            rule(
+                2,
+                "/upload/{*path}",
+                RuleOptions {
+                    max_request_bytes: Some(10),
+                    ..Default::default()
+                },
+            ),
+        ];
+        assert_eq!(shadowed(&rules), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn rule_with_uncovered_tags_is_not_shadowed() {
+        let tagged = |tags: &[&str]| RuleOptions {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let rules = [
+            rule(1, "/api/{*path}", tagged(&["canary"])),
+            rule(2, "/api/{*path}", tagged(&["beta"])),
+            rule(3, "/api/{*path}", tagged(&[])),
+            rule(4, "/api/{*path}", tagged(&["beta"])),
+        ];
+        assert_eq!(shadowed(&rules), vec![(2, 0), (3, 1)]);
+    }
+}
@@ -0,0 +1,443 @@
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::api::ApiResponse;
+use crate::auth;
+use crate::db::{Database, ProxyKey};
+use crate::endpoints::constant_time_eq;
+use crate::secrets::Secrets;
+use crate::AdminState;
+
+/// 传递 API Key 的请求头，也可使用 Authorization: Bearer
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// 生成的 API Key 前缀，便于在日志或代码仓库中识别泄露的密钥
+const KEY_PREFIX: &str = "pk_";
+
+/// 列表中展示的密钥开头长度
+const DISPLAY_PREFIX_LEN: usize = 11;
+
+/// 规则访问认证方式，令牌与密码引用加密密钥存储中的名称
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAuth {
+    /// 请求需携带 Authorization: Bearer <token>
+    Bearer { token_secret: String },
+    /// HTTP Basic 认证，失败时返回质询，浏览器会弹出登录框
+    Basic {
+        username: String,
+        password_secret: String,
+    },
+    /// proxy_keys 表中的 API Key
+    ApiKey,
+}
+
+impl RuleAuth {
+    /// 引用的密钥名
+    pub fn secret(&self) -> Option<&str> {
+        match self {
+            Self::Bearer { token_secret } => Some(token_secret),
+            Self::Basic {
+                password_secret, ..
+            } => Some(password_secret),
+            Self::ApiKey => None,
+        }
+    }
+
+    /// 校验请求凭据，成功时移除凭据请求头（不转发给上游）并返回认证主体；
+    /// 引用的密钥不存在时拒绝所有请求
+    pub fn authenticate(
+        &self,
+        headers: &mut HeaderMap,
+        rule_id: i64,
+        keys: &ProxyKeys,
+        secrets: &Secrets,
+    ) -> Option<String> {
+        let secret = match self.secret() {
+            Some(name) => {
+                let secret = secrets.get(name);
+                if secret.is_none() {
+                    tracing::warn!(rule_id, secret = %name, "Rule auth secret not found");
+                }
+                secret
+            }
+            None => None,
+        };
+        let principal = match self {
+            Self::Bearer { .. } => {
+                let token = secret?;
+                auth::bearer_token(headers)
+                    .filter(|t| constant_time_eq(t.as_bytes(), token.as_bytes()))
+                    .map(|_| "bearer".to_string())
+            }
+            Self::Basic { username, .. } => {
+                let password = secret?;
+                let (user, pass) = basic_credentials(headers)?;
+                // 两项都比较，避免通过耗时判断用户名是否正确
+                let user_ok = constant_time_eq(user.as_bytes(), username.as_bytes());
+                let pass_ok = constant_time_eq(pass.as_bytes(), password.as_bytes());
+                (user_ok & pass_ok).then_some(user)
+            }
+            Self::ApiKey => {
+                let key = match headers.get(API_KEY_HEADER) {
+                    Some(value) => value.to_str().ok()?,
+                    None => auth::bearer_token(headers)?,
+                };
+                keys.verify(key, rule_id)
+                    .map(|name| format!("key:{}", name))
+            }
+        }?;
+
+        headers.remove(header::AUTHORIZATION);
+        headers.remove(API_KEY_HEADER);
+        Some(principal)
+    }
+
+    /// 认证失败的 401 响应
+    pub fn challenge(&self, realm: &str) -> Response {
+        let challenge = match self {
+            Self::Basic { .. } => format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm),
+            Self::Bearer { .. } | Self::ApiKey => format!("Bearer realm=\"{}\"", realm),
+        };
+        let mut resp = (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+        if let Ok(value) = HeaderValue::from_str(&challenge) {
+            resp.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+        }
+        resp
+    }
+}
+
+/// 迁移旧版本明文保存在规则选项中的令牌与密码：写入密钥存储（rule-<id>-auth）并改为引用密钥名
+pub fn migrate_plaintext(db: &Database, secrets: &Secrets) -> anyhow::Result<()> {
+    for (id, options) in db.list_rule_options()? {
+        let Ok(mut options) = serde_json::from_str::<serde_json::Value>(&options) else {
+            continue;
+        };
+        let Some(auth) = options.get_mut("auth").and_then(|a| a.as_object_mut()) else {
+            continue;
+        };
+        let (field, secret_field) = match auth.get("type").and_then(|t| t.as_str()) {
+            Some("bearer") => ("token", "token_secret"),
+            Some("basic") => ("password", "password_secret"),
+            _ => continue,
+        };
+        let Some(serde_json::Value::String(value)) = auth.remove(field) else {
+            continue;
+        };
+        let name = (1..)
+            .map(|n| match n {
+                1 => format!("rule-{}-auth", id),
+                n => format!("rule-{}-auth-{}", id, n),
+            })
+            .find(|name| !secrets.contains(name))
+            .unwrap_or_default();
+        secrets.set(&name, &value)?;
+        auth.insert(secret_field.to_string(), name.clone().into());
+        db.set_rule_options(id, &options.to_string())?;
+        tracing::info!(rule_id = id, secret = %name, "Migrated plaintext rule auth credential to secrets");
+    }
+    Ok(())
+}
+
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    basic_header_credentials(headers, header::AUTHORIZATION)
+}
+
+/// Basic 认证请求头中的用户名与密码
+pub fn basic_header_credentials(
+    headers: &HeaderMap,
+    name: header::HeaderName,
+) -> Option<(String, String)> {
+    let encoded = headers.get(name)?.to_str().ok()?.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, pass) = decoded.split_once(':')?;
+    Some((user.to_string(), pass.to_string()))
+}
+
+fn hash_key(key: &str) -> String {
+    digest::digest(&digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+struct KeyScope {
+    name: String,
+    rule_id: Option<i64>,
+}
+
+/// API Key 缓存，按摘要索引，密钥增删后重新加载
+#[derive(Clone, Default)]
+pub struct ProxyKeys {
+    keys: Arc<ArcSwap<HashMap<String, KeyScope>>>,
+}
+
+impl ProxyKeys {
+    pub fn load(db: &Database) -> anyhow::Result<Self> {
+        let keys = Self::default();
+        keys.reload(db)?;
+        Ok(keys)
+    }
+
+    pub fn reload(&self, db: &Database) -> anyhow::Result<()> {
+        let keys = db
+            .list_proxy_keys()?
+            .into_iter()
+            .map(|key| {
+                (
+                    key.key_hash,
+                    KeyScope {
+                        name: key.name,
+                        rule_id: key.rule_id,
+                    },
+                )
+            })
+            .collect();
+        self.keys.store(Arc::new(keys));
+        Ok(())
+    }
+
+    /// 未绑定规则的密钥，用于正向代理认证
+    pub fn verify_unscoped(&self, key: &str) -> Option<String> {
+        self.keys
+            .load()
+            .get(&hash_key(key))
+            .filter(|scope| scope.rule_id.is_none())
+            .map(|scope| scope.name.clone())
+    }
+
+    /// 返回密钥名称，密钥不存在或不适用于该规则时返回 None
+    fn verify(&self, key: &str, rule_id: i64) -> Option<String> {
+        self.keys
+            .load()
+            .get(&hash_key(key))
+            .filter(|scope| scope.rule_id.is_none_or(|id| id == rule_id))
+            .map(|scope| scope.name.clone())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub rule_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedKey {
+    pub id: i64,
+    /// 完整密钥，只在创建时返回一次
+    pub key: String,
+}
+
+pub async fn list_keys(
+    State(state): State<AdminState>,
+) -> Result<Json<ApiResponse<Vec<ProxyKey>>>, StatusCode> {
+    state
+        .db
+        .list_proxy_keys()
+        .map(|keys| Json(ApiResponse::ok(keys)))
+        .map_err(|e| {
+            tracing::error!("Failed to list proxy keys: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn create_key(
+    State(state): State<AdminState>,
+    Json(req): Json<CreateKeyRequest>,
+) -> Result<Json<ApiResponse<CreatedKey>>, StatusCode> {
+    if req.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let key = auth::generate_token()
+        .map(|token| format!("{}{}", KEY_PREFIX, token))
+        .map_err(|e| {
+            tracing::error!("Failed to generate proxy key: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = state
+        .db
+        .create_proxy_key(
+            req.name.trim(),
+            &hash_key(&key),
+            &key[..DISPLAY_PREFIX_LEN],
+            req.rule_id,
+        )
+        .map_err(|e| {
+            tracing::error!("Failed to create proxy key: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(e) = state.proxy_keys.reload(&state.db) {
+        tracing::error!("Failed to reload proxy keys: {}", e);
+    }
+    tracing::info!(id, name = %req.name, rule_id = ?req.rule_id, "Proxy key created");
+    Ok(Json(ApiResponse::ok(CreatedKey { id, key })))
+}
+
+pub async fn delete_key(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    let deleted = state.db.delete_proxy_key(id).map_err(|e| {
+        tracing::error!("Failed to delete proxy key: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Err(e) = state.proxy_keys.reload(&state.db) {
+        tracing::error!("Failed to reload proxy keys: {}", e);
+    }
+    tracing::info!(id, "Proxy key deleted");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{RuleInput, RuleOptions};
+    use crate::testing;
+
+    fn secrets(db: &Database) -> Secrets {
+        Secrets::load(db, &testing::config().secrets, None).unwrap()
+    }
+
+    fn authorization(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn bearer_token_is_resolved_from_secrets() {
+        let db = Database::in_memory().unwrap();
+        let secrets = secrets(&db);
+        let keys = ProxyKeys::default();
+        let auth = RuleAuth::Bearer {
+            token_secret: "api-token".to_string(),
+        };
+
+        // 引用的密钥不存在时拒绝所有请求
+        let mut headers = authorization("Bearer ");
+        assert!(auth
+            .authenticate(&mut headers, 1, &keys, &secrets)
+            .is_none());
+
+        secrets.set("api-token", "s3cret").unwrap();
+        let mut headers = authorization("Bearer wrong");
+        assert!(auth
+            .authenticate(&mut headers, 1, &keys, &secrets)
+            .is_none());
+        assert!(headers.contains_key(header::AUTHORIZATION));
+
+        let mut headers = authorization("Bearer s3cret");
+        let principal = auth.authenticate(&mut headers, 1, &keys, &secrets);
+        assert_eq!(principal.as_deref(), Some("bearer"));
+        // 凭据不转发给上游
+        assert!(!headers.contains_key(header::AUTHORIZATION));
+    }
+
+    #[test]
+    fn basic_password_is_resolved_from_secrets() {
+        let db = Database::in_memory().unwrap();
+        let secrets = secrets(&db);
+        let keys = ProxyKeys::default();
+        let auth = RuleAuth::Basic {
+            username: "ops".to_string(),
+            password_secret: "ops-password".to_string(),
+        };
+        let basic = |user: &str, pass: &str| {
+            authorization(&format!(
+                "Basic {}",
+                STANDARD.encode(format!("{}:{}", user, pass))
+            ))
+        };
+
+        assert!(auth
+            .authenticate(&mut basic("ops", ""), 1, &keys, &secrets)
+            .is_none());
+        secrets.set("ops-password", "pa:ss").unwrap();
+        assert!(auth
+            .authenticate(&mut basic("ops", "wrong"), 1, &keys, &secrets)
+            .is_none());
+        assert!(auth
+            .authenticate(&mut basic("admin", "pa:ss"), 1, &keys, &secrets)
+            .is_none());
+        let principal = auth.authenticate(&mut basic("ops", "pa:ss"), 1, &keys, &secrets);
+        assert_eq!(principal.as_deref(), Some("ops"));
+    }
+
+    #[test]
+    fn plaintext_credentials_are_moved_to_secrets() {
+        let db = Database::in_memory().unwrap();
+        let secrets = secrets(&db);
+        let rule = |name: &str| {
+            db.create_rule(
+                &RuleInput {
+                    name,
+                    source: &format!("/{}/{{*path}}", name),
+                    target: "http://a/{*path}",
+                    timeout_secs: 30,
+                    options: None,
+                },
+                |_| Ok(()),
+            )
+            .unwrap()
+        };
+        let (bearer, basic) = (rule("bearer"), rule("basic"));
+        // 密钥名已被占用时使用带序号的名称
+        secrets
+            .set(&format!("rule-{}-auth", bearer), "taken")
+            .unwrap();
+        db.set_rule_options(bearer, r#"{"auth":{"type":"bearer","token":"t0ken"}}"#)
+            .unwrap();
+        db.set_rule_options(
+            basic,
+            r#"{"auth":{"type":"basic","username":"ops","password":"pa:ss"}}"#,
+        )
+        .unwrap();
+
+        migrate_plaintext(&db, &secrets).unwrap();
+        let options: HashMap<i64, RuleOptions> = db
+            .list_rule_options()
+            .unwrap()
+            .into_iter()
+            .map(|(id, options)| (id, serde_json::from_str(&options).unwrap()))
+            .collect();
+
+        let bearer_name = format!("rule-{}-auth-2", bearer);
+        assert!(matches!(
+            &options[&bearer].auth,
+            Some(RuleAuth::Bearer { token_secret }) if *token_secret == bearer_name
+        ));
+        assert_eq!(secrets.get(&bearer_name).as_deref(), Some("t0ken"));
+
+        let basic_name = format!("rule-{}-auth", basic);
+        assert!(matches!(
+            &options[&basic].auth,
+            Some(RuleAuth::Basic { username, password_secret })
+                if username == "ops" && *password_secret == basic_name
+        ));
+        assert_eq!(secrets.get(&basic_name).as_deref(), Some("pa:ss"));
+
+        // 规则选项中不再保留明文
+        for (_, raw) in db.list_rule_options().unwrap() {
+            assert!(!raw.contains("t0ken") && !raw.contains("pa:ss"));
+        }
+        // 再次迁移不会改动已迁移的规则
+        migrate_plaintext(&db, &secrets).unwrap();
+        assert!(!secrets.contains(&format!("rule-{}-auth-2", basic)));
+    }
+}
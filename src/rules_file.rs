@@ -0,0 +1,302 @@
+use anyhow::Context;
+use axum::http::StatusCode;
+use notify::{RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api;
+use crate::config::{RulesFileConfig, RulesFileMode};
+use crate::db::{ProxyRule, RuleImport};
+use crate::pipeline::Pipeline;
+use crate::proxy::CompiledProxyRule;
+use crate::reloads;
+use crate::router;
+use crate::AdminState;
+
+/// 文件变化后等待的时间，编辑器保存与 ConfigMap 更新常常连续产生多个事件
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 规则文件：监视文件变化，校验通过后整体替换数据库中的规则并重载
+#[derive(Clone)]
+pub struct RulesFile {
+    path: PathBuf,
+    mode: RulesFileMode,
+    /// 最近一次读取或写回的文件内容，内容未变化的事件（包括自己写回）不再处理
+    last: Arc<Mutex<Option<String>>>,
+}
+
+impl RulesFile {
+    pub fn new(config: &RulesFileConfig) -> Option<Self> {
+        Some(Self {
+            path: PathBuf::from(config.path.as_ref()?),
+            mode: config.mode,
+            last: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// 以文件为准时管理接口不能修改规则
+    pub fn read_only(&self) -> bool {
+        self.mode == RulesFileMode::File
+    }
+
+    /// 启动时同步：以文件为准时导入文件，以数据库为准时把规则写回文件
+    pub fn sync_on_startup(&self, admin: &AdminState) -> anyhow::Result<()> {
+        match self.mode {
+            RulesFileMode::File => self.apply(admin).map(drop),
+            RulesFileMode::Db => self.write_back(admin),
+        }
+    }
+
+    /// 读取并校验文件，全部规则有效时替换数据库中的规则，由调用方重载；内容未变化时返回 false
+    fn apply(&self, admin: &AdminState) -> anyhow::Result<bool> {
+        let content = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))?;
+        if self.last.lock().as_deref() == Some(content.as_str()) {
+            return Ok(false);
+        }
+        // YAML 兼容 JSON，导出的规则列表与手写的 YAML 都可使用
+        // 无效的内容同样记住，修改文件之前不再重复报错
+        *self.last.lock() = Some(content.clone());
+        let rules: Vec<RuleImport> = serde_yaml::from_str(&content).context("invalid rule list")?;
+        let before = admin.db.get_all_rules()?;
+        validate(admin, &rules, &before)?;
+
+        let summary = admin.db.import_rules(&rules, reloads::check)?;
+        let remaining = admin.db.get_all_rules()?;
+        for rule in before {
+            if !remaining.iter().any(|r| r.id == rule.id) {
+                admin.stats.remove(rule.id);
+            }
+        }
+        tracing::info!(
+            path = %self.path.display(),
+            created = summary.created,
+            updated = summary.updated,
+            deleted = summary.deleted,
+            "Rules file applied"
+        );
+        Ok(true)
+    }
+
+    /// 以数据库为准时把当前规则写回文件，先写临时文件再改名，监视方不会读到写了一半的文件
+    pub fn write_back(&self, admin: &AdminState) -> anyhow::Result<()> {
+        if self.mode != RulesFileMode::Db {
+            return Ok(());
+        }
+        let rules: Vec<RuleImport> = admin
+            .db
+            .get_all_rules()?
+            .into_iter()
+            .map(to_import)
+            .collect();
+        let content = serde_yaml::to_string(&rules)?;
+        if self.last.lock().as_deref() == Some(content.as_str()) {
+            return Ok(());
+        }
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, &content)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        *self.last.lock() = Some(content);
+        tracing::debug!(path = %self.path.display(), rules = rules.len(), "Rules written back to file");
+        Ok(())
+    }
+
+    /// 监视文件所在目录（编辑器与 ConfigMap 都以替换文件的方式更新），变化时重新导入
+    pub fn start_watcher(&self, admin: AdminState) -> anyhow::Result<()> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = self.path.file_name().map(|name| name.to_os_string());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        // 只关心规则文件本身与 ConfigMap 挂载目录中以 .. 开头的数据链接，忽略同目录的其他文件
+        let relevant = move |path: &Path| {
+            path.file_name().is_some_and(|file| {
+                Some(file) == name.as_deref() || file.to_string_lossy().starts_with("..")
+            })
+        };
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if event.kind.is_access() => {}
+                Ok(event) if event.paths.iter().any(|path| relevant(path)) => {
+                    let _ = tx.send(());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Rules file watch error: {}", e),
+            })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        tracing::info!(path = %self.path.display(), mode = ?self.mode, "Watching rules file");
+
+        let file = self.clone();
+        tokio::spawn(async move {
+            // watcher 随任务存活，停止时一起释放
+            let _watcher = watcher;
+            while rx.recv().await.is_some() {
+                tokio::time::sleep(DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                if !file.path.exists() {
+                    continue;
+                }
+                let (file, admin) = (file.clone(), admin.clone());
+                let result = tokio::task::spawn_blocking(move || match file.apply(&admin)? {
+                    true => admin.reload_rules("rules_file"),
+                    false => Ok(()),
+                })
+                .await;
+                match result {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => {
+                        tracing::error!("Rules file rejected, keeping current rules: {:#}", e)
+                    }
+                    Err(e) => tracing::error!("Rules file task failed: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// 与管理接口相同的规则校验，并按导入后的顺序编译全部启用的规则，确认不存在永远不会被匹配的规则：
+/// 同名的已有规则保留原 id，新规则排在已有规则之后
+fn validate(
+    admin: &AdminState,
+    rules: &[RuleImport],
+    existing: &[ProxyRule],
+) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    let mut compiled = Vec::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let context = || format!("rule '{}'", rule.name);
+        if !names.insert(rule.name.as_str()) {
+            anyhow::bail!("duplicate rule name '{}'", rule.name);
+        }
+        if let Some(name) = rule
+            .options
+            .secret_refs()
+            .find(|name| !admin.secrets.contains(name))
+        {
+            anyhow::bail!("rule '{}' references unknown secret {}", rule.name, name);
+        }
+        if let Some(name) = rule
+            .options
+            .plugins
+            .iter()
+            .find(|name| !admin.plugins.contains(name))
+        {
+            anyhow::bail!("rule '{}' references unknown plugin {}", rule.name, name);
+        }
+        Pipeline::build(&rule.options).with_context(context)?;
+        api::validate_upstream_proxy(&rule.options)
+            .map_err(anyhow::Error::msg)
+            .with_context(context)?;
+        if rule.enabled {
+            let id = existing
+                .iter()
+                .find(|r| r.name == rule.name)
+                .map_or(i64::MAX / 2 + index as i64, |r| r.id);
+            let rule = ProxyRule {
+                id,
+                name: rule.name.clone(),
+                source: rule.source.clone(),
+                target: rule.target.clone(),
+                timeout_secs: rule.timeout_secs,
+                enabled: true,
+                options: rule.options.clone(),
+                created_at: String::new(),
+                updated_at: String::new(),
+            };
+            compiled.push(CompiledProxyRule::from_db_rule(&rule).with_context(context)?);
+        }
+    }
+    compiled.sort_by_key(|rule| rule.id);
+    if let Some((later, earlier)) = router::shadowed(&compiled).first() {
+        anyhow::bail!(
+            "rule '{}' is never matched: earlier rule '{}' has the same source",
+            compiled[*later].name,
+            compiled[*earlier].name
+        );
+    }
+    Ok(())
+}
+
+fn to_import(rule: ProxyRule) -> RuleImport {
+    RuleImport {
+        name: rule.name,
+        source: rule.source,
+        target: rule.target,
+        timeout_secs: rule.timeout_secs,
+        enabled: rule.enabled,
+        options: rule.options,
+    }
+}
+
+/// 以文件为准时拒绝通过管理接口修改规则
+pub fn check_writable(admin: &AdminState) -> Result<(), StatusCode> {
+    match admin.rules_file {
+        Some(ref file) if file.read_only() => {
+            tracing::warn!(path = %file.path().display(), "Rules are managed by the rules file");
+            Err(StatusCode::FORBIDDEN)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::RuleOptions;
+    use crate::testing;
+
+    fn rule(id: i64, source: &str) -> ProxyRule {
+        ProxyRule {
+            id,
+            name: format!("rule-{}", id),
+            source: source.to_string(),
+            target: "http://upstream/{*path}".to_string(),
+            timeout_secs: 30,
+            enabled: true,
+            options: RuleOptions::default(),
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rejected_reload_is_not_written_back() {
+        let path = std::env::temp_dir().join(format!("rules-file-{}.yaml", std::process::id()));
+        std::fs::write(&path, "original").unwrap();
+        let mut state = testing::admin_state(&testing::config());
+        state.rules_file = RulesFile::new(&RulesFileConfig {
+            path: Some(path.to_string_lossy().into_owned()),
+            mode: RulesFileMode::Db,
+        });
+
+        // 绕过写入时的校验，模拟同步来的规则集在重载时被拒绝
+        let conflicting = [rule(1, "/api/{*path}"), rule(2, "/api/{*path}")];
+        state
+            .db
+            .restore(&conflicting, &[], &[], |_| Ok(()))
+            .unwrap();
+        assert!(state.reload_rules("ha_sync").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+
+        state
+            .db
+            .restore(&[rule(1, "/api/{*path}")], &[], &[], reloads::check)
+            .unwrap();
+        state.reload_rules("ha_sync").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rules: Vec<RuleImport> = serde_yaml::from_str(&written).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].source, "/api/{*path}");
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Context;
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{header, Client, Method, StatusCode, Url};
+use ring::{digest, hmac};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cache_store::{self, ByteStream, CacheStore, ObjectMeta, StoreFuture};
+use crate::config::S3StoreConfig;
+use crate::proxy::http_client_builder;
+
+/// 对象键中保留原样的字符（RFC 3986 非保留字符与路径分隔符）
+const KEY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~')
+    .remove(b'/');
+
+/// 流式上传不计算请求体摘要
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// S3 兼容对象存储，请求使用 AWS Signature V4 签名
+pub struct S3Store {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    prefix: String,
+    path_style: bool,
+}
+
+impl S3Store {
+    pub fn new(config: &S3StoreConfig) -> anyhow::Result<Self> {
+        let endpoint = Url::parse(&config.endpoint)
+            .with_context(|| format!("Invalid S3 endpoint: {}", config.endpoint))?;
+        Ok(Self {
+            client: http_client_builder().build()?,
+            endpoint,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            prefix: config.prefix.trim_matches('/').to_string(),
+            path_style: config.path_style,
+        })
+    }
+
+    /// 缓存路径转换为对象键：去掉 ./、/ 等非普通路径段，加上配置的前缀
+    fn object_key(&self, path: &Path) -> String {
+        let mut segments: Vec<String> = Vec::new();
+        if !self.prefix.is_empty() {
+            segments.push(self.prefix.clone());
+        }
+        segments.extend(path.components().filter_map(|component| match component {
+            Component::Normal(segment) => Some(segment.to_string_lossy().into_owned()),
+            _ => None,
+        }));
+        segments.join("/")
+    }
+
+    /// 对象地址与签名使用的 URI 路径
+    fn object_url(&self, key: &str) -> anyhow::Result<(Url, String)> {
+        let key = utf8_percent_encode(key, KEY_ENCODE).to_string();
+        let mut url = self.endpoint.clone();
+        let base = self.endpoint.path().trim_end_matches('/');
+        let path = if self.path_style {
+            format!("{}/{}/{}", base, self.bucket, key)
+        } else {
+            let host = format!(
+                "{}.{}",
+                self.bucket,
+                self.endpoint.host_str().unwrap_or_default()
+            );
+            url.set_host(Some(&host))?;
+            format!("{}/{}", base, key)
+        };
+        url.set_path(&path);
+        Ok((url, path))
+    }
+
+    /// 生成带 Signature V4 签名的请求
+    fn request(&self, method: Method, path: &Path) -> std::io::Result<reqwest::RequestBuilder> {
+        let (url, canonical_uri) = self
+            .object_url(&self.object_key(path))
+            .map_err(std::io::Error::other)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method,
+            canonical_uri,
+            host,
+            UNSIGNED_PAYLOAD,
+            amz_date,
+            signed_headers,
+            UNSIGNED_PAYLOAD
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let mut key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in [date.as_str(), self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        Ok(self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .header("x-amz-date", amz_date)
+            .header(header::AUTHORIZATION, authorization))
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn status_error(operation: &str, status: StatusCode) -> std::io::Error {
+    std::io::Error::other(format!("S3 {} failed with status {}", operation, status))
+}
+
+impl CacheStore for S3Store {
+    fn head<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, Option<ObjectMeta>> {
+        Box::pin(async move {
+            let resp = self
+                .request(Method::HEAD, path)?
+                .send()
+                .await
+                .map_err(std::io::Error::other)?;
+            match resp.status() {
+                StatusCode::NOT_FOUND => Ok(None),
+                status if status.is_success() => {
+                    let headers = resp.headers();
+                    let len = headers
+                        .get(header::CONTENT_LENGTH)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse().ok())
+                        .ok_or_else(|| std::io::Error::other("S3 HEAD without Content-Length"))?;
+                    let modified = headers
+                        .get(header::LAST_MODIFIED)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+                        .map(SystemTime::from)
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    Ok(Some(ObjectMeta { len, modified }))
+                }
+                status => Err(status_error("HEAD", status)),
+            }
+        })
+    }
+
+    fn read<'a>(&'a self, path: &'a Path, start: u64, end: u64) -> StoreFuture<'a, ByteStream> {
+        Box::pin(async move {
+            if end <= start {
+                return Ok(futures::stream::empty().boxed());
+            }
+            let resp = self
+                .request(Method::GET, path)?
+                .header(header::RANGE, format!("bytes={}-{}", start, end - 1))
+                .send()
+                .await
+                .map_err(std::io::Error::other)?;
+            if !resp.status().is_success() {
+                return Err(status_error("GET", resp.status()));
+            }
+            Ok(resp.bytes_stream().map_err(std::io::Error::other).boxed())
+        })
+    }
+
+    fn staging_dir(&self, _path: &Path) -> PathBuf {
+        cache_store::temp_staging_dir()
+    }
+
+    fn commit<'a>(
+        &'a self,
+        path: &'a Path,
+        staged: &'a Path,
+        len: u64,
+        _sha256: &'a str,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let file = tokio::fs::File::open(staged).await?;
+            let resp = self
+                .request(Method::PUT, path)?
+                .header(header::CONTENT_LENGTH, len)
+                .body(reqwest::Body::wrap_stream(cache_store::file_stream(file)))
+                .send()
+                .await
+                .map_err(std::io::Error::other)?;
+            if !resp.status().is_success() {
+                return Err(status_error("PUT", resp.status()));
+            }
+            Ok(())
+        })
+    }
+
+    fn remove<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, bool> {
+        Box::pin(async move {
+            if self.head(path).await?.is_none() {
+                return Ok(false);
+            }
+            let resp = self
+                .request(Method::DELETE, path)?
+                .send()
+                .await
+                .map_err(std::io::Error::other)?;
+            if !resp.status().is_success() {
+                return Err(status_error("DELETE", resp.status()));
+            }
+            Ok(true)
+        })
+    }
+}
@@ -0,0 +1,254 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+/// 脚本可读取的请求体上限，超过或长度未知时 `request.body` 为 ()
+const MAX_SCRIPT_BODY: u64 = 64 * 1024;
+/// 单次执行的操作数上限，防止死循环占住工作线程
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// 规则的 Rhai 请求脚本
+///
+/// 脚本在转发前执行，作用域中的 `request` 为对象：`method`、`path` / `query`（上游地址的路径与查询串）、
+/// `headers`（小写名 → 值，同名头以 ", " 合并）、`body`（64KB 以内的 UTF-8 请求体，否则为 ()）、
+/// `client_ip` 与 `rule`。修改 `request` 的字段即修改转发的请求；
+/// `return #{status: 403, headers: #{...}, body: "..."}` 直接返回响应，不再转发
+#[derive(Debug, Clone)]
+pub struct RuleScript {
+    ast: Arc<AST>,
+}
+
+impl RuleScript {
+    /// 编译脚本，语法错误时返回错误信息
+    pub fn compile(source: &str) -> Result<Self, String> {
+        engine()
+            .compile(source)
+            .map(|ast| Self { ast: Arc::new(ast) })
+            .map_err(|e| e.to_string())
+    }
+
+    /// 执行脚本并把修改应用到请求与上游地址，脚本返回响应时为 Some；执行失败返回 500
+    pub async fn run(
+        &self,
+        rule: &str,
+        client_ip: &str,
+        req: &mut Request,
+        target_url: &mut String,
+    ) -> Result<Option<Response>, StatusCode> {
+        let mut url = reqwest::Url::parse(target_url).map_err(|e| {
+            tracing::error!(rule = %rule, "Invalid target for script: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let body = read_short_body(req).await?;
+        let headers = header_map(req.headers());
+
+        let mut request = Map::new();
+        request.insert("method".into(), req.method().as_str().into());
+        request.insert("path".into(), url.path().into());
+        request.insert(
+            "query".into(),
+            url.query().map_or(Dynamic::UNIT, Into::into),
+        );
+        request.insert("headers".into(), to_rhai_map(&headers).into());
+        request.insert(
+            "body".into(),
+            body.clone().map_or(Dynamic::UNIT, Into::into),
+        );
+        request.insert("client_ip".into(), client_ip.into());
+        request.insert("rule".into(), rule.into());
+
+        let mut scope = Scope::new();
+        scope.push("request", request);
+        let result = engine()
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| failed(rule, e.to_string()))?;
+
+        if let Some(resp) = result.try_cast::<Map>() {
+            tracing::debug!(rule = %rule, "Script short-circuited request");
+            return into_response(resp).map(Some).map_err(|e| failed(rule, e));
+        }
+
+        let request = scope
+            .get_value::<Map>("request")
+            .ok_or_else(|| failed(rule, "request is no longer an object".to_string()))?;
+        apply(request, req, &mut url, &headers, body).map_err(|e| failed(rule, e))?;
+        *target_url = url.to_string();
+        Ok(None)
+    }
+}
+
+/// 共享的脚本引擎：限制操作数、调用深度与数据大小，关闭 eval，print / debug 输出到日志
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(1024 * 1024)
+            .set_max_array_size(10_000)
+            .set_max_map_size(10_000)
+            .disable_symbol("eval")
+            .on_print(|text| tracing::info!(target: "script", "{}", text))
+            .on_debug(
+                |text, _, pos| tracing::debug!(target: "script", position = %pos, "{}", text),
+            );
+        engine
+    })
+}
+
+/// 读取长度已知且不超过上限的请求体，放回请求后返回其 UTF-8 文本
+async fn read_short_body(req: &mut Request) -> Result<Option<String>, StatusCode> {
+    let len = req.body().size_hint().exact();
+    if len.is_none_or(|len| len > MAX_SCRIPT_BODY) {
+        return Ok(None);
+    }
+    let body = std::mem::take(req.body_mut());
+    let bytes = axum::body::to_bytes(body, MAX_SCRIPT_BODY as usize)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Failed to read request body for script: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    let text = std::str::from_utf8(&bytes).ok().map(str::to_string);
+    *req.body_mut() = Body::from(bytes);
+    Ok(text)
+}
+
+fn header_map(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut map: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        map.entry(name.as_str().to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+fn to_rhai_map(headers: &BTreeMap<String, String>) -> Map {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().into(), value.as_str().into()))
+        .collect()
+}
+
+/// 脚本中的值转为字符串，() 为 None
+fn string_field(map: &Map, key: &str) -> Result<Option<String>, String> {
+    match map.get(key) {
+        None => Ok(None),
+        Some(value) if value.is_unit() => Ok(None),
+        Some(value) => value
+            .clone()
+            .into_string()
+            .map(Some)
+            .map_err(|t| format!("{} must be a string, got {}", key, t)),
+    }
+}
+
+fn headers_field(map: &Map) -> Result<BTreeMap<String, String>, String> {
+    let Some(value) = map.get("headers") else {
+        return Ok(BTreeMap::new());
+    };
+    let headers = value
+        .clone()
+        .try_cast::<Map>()
+        .ok_or_else(|| "headers must be an object".to_string())?;
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let value = value
+                .into_string()
+                .map_err(|t| format!("header {} must be a string, got {}", name, t))?;
+            Ok((name.to_lowercase(), value))
+        })
+        .collect()
+}
+
+/// 只改写脚本修改过的请求头，未改动的同名多值头保持原样
+fn apply(
+    request: Map,
+    req: &mut Request,
+    url: &mut reqwest::Url,
+    original_headers: &BTreeMap<String, String>,
+    original_body: Option<String>,
+) -> Result<(), String> {
+    if let Some(method) = string_field(&request, "method")? {
+        if method != req.method().as_str() {
+            *req.method_mut() = Method::from_bytes(method.as_bytes()).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(path) = string_field(&request, "path")? {
+        url.set_path(&path);
+    }
+    url.set_query(string_field(&request, "query")?.as_deref());
+
+    let headers = match request.contains_key("headers") {
+        true => headers_field(&request)?,
+        false => original_headers.clone(),
+    };
+    for name in original_headers
+        .keys()
+        .filter(|name| !headers.contains_key(*name))
+    {
+        req.headers_mut().remove(name.as_str());
+    }
+    for (name, value) in &headers {
+        if original_headers.get(name) != Some(value) {
+            req.headers_mut().insert(
+                HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?,
+                HeaderValue::from_str(value).map_err(|e| e.to_string())?,
+            );
+        }
+    }
+
+    let body = string_field(&request, "body")?;
+    if body.is_some() && body != original_body {
+        let body = body.unwrap_or_default();
+        req.headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        *req.body_mut() = Body::from(body);
+    }
+    Ok(())
+}
+
+fn into_response(map: Map) -> Result<Response, String> {
+    let status = match map.get("status") {
+        None => StatusCode::OK,
+        Some(status) => {
+            let status = status
+                .as_int()
+                .map_err(|t| format!("status must be an integer, got {}", t))?;
+            u16::try_from(status)
+                .ok()
+                .and_then(|status| StatusCode::from_u16(status).ok())
+                .ok_or_else(|| format!("invalid status {}", status))?
+        }
+    };
+    let mut resp = Response::new(Body::from(string_field(&map, "body")?.unwrap_or_default()));
+    *resp.status_mut() = status;
+    for (name, value) in headers_field(&map)? {
+        resp.headers_mut().insert(
+            HeaderName::from_bytes(name.as_bytes()).map_err(|e| e.to_string())?,
+            HeaderValue::from_str(&value).map_err(|e| e.to_string())?,
+        );
+    }
+    Ok(resp)
+}
+
+fn failed(rule: &str, e: String) -> StatusCode {
+    tracing::error!(rule = %rule, "Rule script failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
@@ -0,0 +1,333 @@
+use anyhow::{bail, Context};
+use arc_swap::ArcSwap;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::api::ApiResponse;
+use crate::config::SecretsConfig;
+use crate::db::{Database, StoredSecret};
+use crate::AdminState;
+
+/// 内部使用的密钥名前缀（签名密钥、证书私钥等），不允许通过接口修改
+pub const SYSTEM_PREFIX: &str = "system.";
+
+const KEY_LEN: usize = 32;
+
+const MAX_NAME_LEN: usize = 128;
+
+/// 加密密钥存储：AES-256-GCM 加密后保存在 secrets 表，密钥名作为附加数据，
+/// 解密后的值只保存在内存中
+#[derive(Clone)]
+pub struct Secrets {
+    db: Database,
+    key: Arc<LessSafeKey>,
+    values: Arc<ArcSwap<HashMap<String, String>>>,
+}
+
+impl Secrets {
+    /// 加载主密钥并解密全部密钥，主密钥错误时启动失败
+    pub fn load(
+        db: &Database,
+        config: &SecretsConfig,
+        db_path: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let key = master_key(config, db_path)?;
+        let key = UnboundKey::new(&AES_256_GCM, &key)
+            .map_err(|_| anyhow::anyhow!("Invalid secrets key"))?;
+        let secrets = Self {
+            db: db.clone(),
+            key: Arc::new(LessSafeKey::new(key)),
+            values: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+        };
+
+        secrets.reload()?;
+        Ok(secrets)
+    }
+
+    /// 重新读取并解密全部密钥，集群同步写入数据库后调用
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let mut values = HashMap::new();
+        for stored in self.db.list_secrets()? {
+            let value = self.decrypt(&stored.name, &stored.value).with_context(|| {
+                format!(
+                    "Failed to decrypt secret '{}', check the secrets key",
+                    stored.name
+                )
+            })?;
+            values.insert(stored.name, value);
+        }
+        self.values.store(Arc::new(values));
+        Ok(())
+    }
+
+    /// 校验其他节点的加密值能否用本节点的主密钥解密
+    pub fn check_sealed(&self, name: &str, sealed: &str) -> anyhow::Result<()> {
+        self.decrypt(name, sealed)
+            .with_context(|| format!("Failed to decrypt secret '{}', check the secrets key", name))
+            .map(|_| ())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.load().get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.load().contains_key(name)
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> anyhow::Result<()> {
+        self.db.upsert_secret(name, &self.encrypt(name, value)?)?;
+        self.values.rcu(|values| {
+            let mut values = HashMap::clone(values);
+            values.insert(name.to_string(), value.to_string());
+            values
+        });
+        Ok(())
+    }
+
+    /// 返回是否删除了密钥
+    pub fn delete(&self, name: &str) -> anyhow::Result<bool> {
+        let deleted = self.db.delete_secret(name)?;
+        self.values.rcu(|values| {
+            let mut values = HashMap::clone(values);
+            values.remove(name);
+            values
+        });
+        Ok(deleted)
+    }
+
+    /// 读取内部密钥：不存在时迁移 system_config 中的旧明文值（并删除该行），否则生成新值
+    pub fn get_or_init(
+        &self,
+        name: &str,
+        legacy_config_key: &str,
+        generate: impl FnOnce() -> anyhow::Result<String>,
+    ) -> anyhow::Result<String> {
+        if let Some(value) = self.get(name) {
+            return Ok(value);
+        }
+        let value = match self.db.get_config(legacy_config_key)? {
+            Some(value) => {
+                tracing::info!(name, "Migrated plaintext secret from system config");
+                value
+            }
+            None => generate()?,
+        };
+        self.set(name, &value)?;
+        self.db.delete_config(legacy_config_key)?;
+        Ok(value)
+    }
+
+    fn encrypt(&self, name: &str, value: &str) -> anyhow::Result<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow::anyhow!("Failed to generate nonce"))?;
+        let mut data = value.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(name.as_bytes()),
+                &mut data,
+            )
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secret"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&data);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    fn decrypt(&self, name: &str, sealed: &str) -> anyhow::Result<String> {
+        let mut sealed = STANDARD.decode(sealed)?;
+        if sealed.len() < NONCE_LEN {
+            bail!("Secret value is truncated");
+        }
+        let mut data = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed)
+            .map_err(|_| anyhow::anyhow!("Invalid nonce"))?;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut data)
+            .map_err(|_| anyhow::anyhow!("Authentication failed"))?;
+        Ok(String::from_utf8(plain.to_vec())?)
+    }
+}
+
+/// 主密钥：优先使用配置值，其次读取密钥文件（不存在时生成）；内存数据库且未配置时使用临时随机密钥
+fn master_key(config: &SecretsConfig, db_path: Option<&str>) -> anyhow::Result<[u8; KEY_LEN]> {
+    if let Some(key) = config.key.as_deref().filter(|k| !k.trim().is_empty()) {
+        return decode_key(key);
+    }
+    let path = match (&config.key_file, db_path) {
+        (Some(file), _) => file.clone(),
+        (None, Some(db_path)) => format!("{}.key", db_path),
+        (None, None) => return random_key(),
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(key) => decode_key(&key).with_context(|| format!("Invalid secrets key file {}", path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = random_key()?;
+            write_private(std::path::Path::new(&path), STANDARD.encode(key).as_bytes())
+                .with_context(|| format!("Failed to write secrets key file {}", path))?;
+            tracing::warn!(path = %path, "Generated secrets key file, back it up together with the database");
+            Ok(key)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read secrets key file {}", path)),
+    }
+}
+
+/// 写入私钥文件，Unix 下权限为 600
+fn write_private(path: &std::path::Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(path)?, data)?;
+    Ok(())
+}
+
+fn decode_key(key: &str) -> anyhow::Result<[u8; KEY_LEN]> {
+    STANDARD
+        .decode(key.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .context("Secrets key must be 32 bytes encoded as base64")
+}
+
+fn random_key() -> anyhow::Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    SystemRandom::new()
+        .fill(&mut key)
+        .map_err(|_| anyhow::anyhow!("Failed to generate secrets key"))?;
+    Ok(key)
+}
+
+/// 向上游注入的认证凭据，secret 为密钥名
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpstreamAuth {
+    /// Authorization: Bearer <secret>
+    Bearer { secret: String },
+    /// HTTP Basic 认证，密码取自密钥
+    Basic { username: String, secret: String },
+    /// 自定义请求头，如 X-API-Key
+    Header { name: String, secret: String },
+}
+
+impl UpstreamAuth {
+    pub fn secret(&self) -> &str {
+        match self {
+            Self::Bearer { secret } | Self::Basic { secret, .. } | Self::Header { secret, .. } => {
+                secret
+            }
+        }
+    }
+
+    /// 写入凭据请求头，覆盖客户端传入的同名请求头
+    pub fn apply(&self, headers: &mut HeaderMap, secrets: &Secrets) -> anyhow::Result<()> {
+        let secret = secrets
+            .get(self.secret())
+            .with_context(|| format!("Secret '{}' not found", self.secret()))?;
+        let (name, value) = match self {
+            Self::Bearer { .. } => (header::AUTHORIZATION, format!("Bearer {}", secret)),
+            Self::Basic { username, .. } => (
+                header::AUTHORIZATION,
+                format!(
+                    "Basic {}",
+                    STANDARD.encode(format!("{}:{}", username, secret))
+                ),
+            ),
+            Self::Header { name, .. } => (HeaderName::try_from(name.as_str())?, secret),
+        };
+        let mut value = HeaderValue::from_str(&value)?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+        Ok(())
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with(SYSTEM_PREFIX)
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetSecretRequest {
+    pub value: String,
+}
+
+/// 密钥列表，只返回名称与时间，不返回密钥值
+pub async fn list_handler(
+    State(state): State<AdminState>,
+) -> Result<Json<ApiResponse<Vec<StoredSecret>>>, StatusCode> {
+    state
+        .db
+        .list_secrets()
+        .map(|secrets| Json(ApiResponse::ok(secrets)))
+        .map_err(|e| {
+            tracing::error!("Failed to list secrets: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// 创建或更新密钥
+pub async fn set_handler(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(req): Json<SetSecretRequest>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if !valid_name(&name) || req.value.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state.secrets.set(&name, &req.value).map_err(|e| {
+        tracing::error!("Failed to save secret: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    tracing::info!(name = %name, "Secret saved");
+    Ok(Json(ApiResponse::ok(())))
+}
+
+/// 删除密钥，仍被规则引用时返回 409
+pub async fn delete_handler(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<()>>, StatusCode> {
+    if !valid_name(&name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let rules = state.db.get_all_rules().map_err(|e| {
+        tracing::error!("Failed to load rules: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some(rule) = rules
+        .iter()
+        .find(|rule| rule.options.secret_refs().any(|secret| secret == name))
+    {
+        tracing::warn!(name = %name, rule = %rule.name, "Secret is still referenced by a rule");
+        return Err(StatusCode::CONFLICT);
+    }
+    let deleted = state.secrets.delete(&name).map_err(|e| {
+        tracing::error!("Failed to delete secret: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    tracing::info!(name = %name, "Secret deleted");
+    Ok(Json(ApiResponse::ok(())))
+}
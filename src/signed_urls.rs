@@ -0,0 +1,293 @@
+use axum::{extract::State, http::StatusCode, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::ApiResponse;
+use crate::db::Database;
+use crate::redact;
+use crate::secrets::Secrets;
+use crate::AdminState;
+
+/// 旧版本保存签名密钥的 system_config 键名，启动时迁移到加密密钥存储
+const LEGACY_SECRET_KEY: &str = "direct_proxy_secret";
+
+/// 签名密钥在加密密钥存储中的名称
+const SECRET_NAME: &str = "system.direct_proxy_secret";
+
+/// 直接代理访问模式在 system_config 中的键名：open / signed
+pub const MODE_KEY: &str = "direct_proxy_mode";
+
+/// 签名链接默认有效期（秒）
+const DEFAULT_TTL_SECS: u64 = 3600;
+
+/// 签名链接最长有效期（秒）
+const MAX_TTL_SECS: u64 = 30 * 24 * 3600;
+
+/// 访问模式是否要求签名
+pub fn requires_signature(mode: &str) -> anyhow::Result<bool> {
+    match mode {
+        "" | "open" => Ok(false),
+        "signed" => Ok(true),
+        other => anyhow::bail!("Unknown direct proxy mode: {}", other),
+    }
+}
+
+/// 签名校验失败原因
+#[derive(Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// 签名模式下未携带签名
+    Missing,
+    /// 签名或过期时间无效
+    Invalid,
+    /// 已过期
+    Expired,
+}
+
+/// 直接代理的签名链接：HMAC-SHA256(密钥, "{exp}:{目标地址}")
+#[derive(Clone)]
+pub struct SignedUrls {
+    key: hmac::Key,
+    require_signature: Arc<AtomicBool>,
+}
+
+impl SignedUrls {
+    /// 加载签名密钥与访问模式，首次启动时生成随机密钥
+    pub fn load(db: &Database, secrets: &Secrets) -> anyhow::Result<Self> {
+        let secret = secrets.get_or_init(SECRET_NAME, LEGACY_SECRET_KEY, || {
+            let mut bytes = [0u8; 32];
+            SystemRandom::new()
+                .fill(&mut bytes)
+                .map_err(|_| anyhow::anyhow!("Failed to generate signing secret"))?;
+            tracing::info!("Generated direct proxy signing secret");
+            Ok(URL_SAFE_NO_PAD.encode(bytes))
+        })?;
+        let mode = db.get_config(MODE_KEY)?.unwrap_or_default();
+
+        let urls = Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+            require_signature: Arc::new(AtomicBool::new(false)),
+        };
+        urls.set_mode(&mode)?;
+        Ok(urls)
+    }
+
+    /// 设置访问模式，open 允许未签名访问，signed 仅允许签名链接
+    pub fn set_mode(&self, mode: &str) -> anyhow::Result<()> {
+        self.require_signature
+            .store(requires_signature(mode)?, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn payload(exp: u64, target: &str) -> String {
+        format!("{}:{}", exp, target)
+    }
+
+    /// 为目标地址签名，返回 (exp, sig)
+    pub fn sign(&self, target: &str, ttl_secs: u64) -> (u64, String) {
+        let exp = now_secs() + ttl_secs;
+        let tag = hmac::sign(&self.key, Self::payload(exp, target).as_bytes());
+        (exp, URL_SAFE_NO_PAD.encode(tag.as_ref()))
+    }
+
+    /// 校验直接代理请求，返回去掉 sig/exp 参数后的查询串
+    pub fn check(&self, target: &str, query: Option<&str>) -> Result<Option<String>, Rejection> {
+        let mut exp = None;
+        let mut sig = None;
+        let mut rest = Vec::new();
+        for pair in query.unwrap_or_default().split('&') {
+            if let Some(v) = pair.strip_prefix("exp=") {
+                exp = Some(v);
+            } else if let Some(v) = pair.strip_prefix("sig=") {
+                sig = Some(v);
+            } else if !pair.is_empty() {
+                rest.push(pair);
+            }
+        }
+        let rest = (!rest.is_empty()).then(|| rest.join("&"));
+
+        let (exp, sig) = match (exp, sig) {
+            (Some(exp), Some(sig)) => (exp, sig),
+            _ if self.require_signature.load(Ordering::Relaxed) => return Err(Rejection::Missing),
+            _ => return Ok(query.map(str::to_string)),
+        };
+
+        let exp: u64 = exp.parse().map_err(|_| Rejection::Invalid)?;
+        let sig = URL_SAFE_NO_PAD
+            .decode(sig)
+            .map_err(|_| Rejection::Invalid)?;
+        let signed = match &rest {
+            Some(q) => format!("{}?{}", target, q),
+            None => target.to_string(),
+        };
+        hmac::verify(&self.key, Self::payload(exp, &signed).as_bytes(), &sig)
+            .map_err(|_| Rejection::Invalid)?;
+        if exp < now_secs() {
+            return Err(Rejection::Expired);
+        }
+        Ok(rest)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignRequest {
+    pub url: String,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignedUrl {
+    /// 代理端口上的访问路径
+    pub path: String,
+    pub expires_at: u64,
+}
+
+/// 生成直接代理的限时签名链接
+pub async fn sign_handler(
+    State(state): State<AdminState>,
+    Json(req): Json<SignRequest>,
+) -> Result<Json<ApiResponse<SignedUrl>>, StatusCode> {
+    // 片段不会发送到服务端，签名时一并去掉
+    let url = req.url.split('#').next().unwrap_or_default();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // 与校验时一致：去掉空参数，且不允许与签名参数重名
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let pairs: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+    if pairs
+        .iter()
+        .any(|p| p.starts_with("exp=") || p.starts_with("sig="))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let target = if pairs.is_empty() {
+        base.to_string()
+    } else {
+        format!("{}?{}", base, pairs.join("&"))
+    };
+    let ttl = req.ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    if ttl == 0 || ttl > MAX_TTL_SECS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (exp, sig) = state.signed_urls.sign(&target, ttl);
+    let separator = if target.contains('?') { '&' } else { '?' };
+    let path = format!(
+        "/{}/{}{}exp={}&sig={}",
+        state.direct_proxy_path.load(),
+        target,
+        separator,
+        exp,
+        sig
+    );
+    tracing::info!(target = %redact::url(&target), expires_at = exp, "Issued signed direct proxy URL");
+    Ok(Json(ApiResponse::ok(SignedUrl {
+        path,
+        expires_at: exp,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_urls(require_signature: bool) -> SignedUrls {
+        SignedUrls {
+            key: hmac::Key::new(hmac::HMAC_SHA256, b"test-secret"),
+            require_signature: Arc::new(AtomicBool::new(require_signature)),
+        }
+    }
+
+    /// 直接用过期时间签名，构造已过期的链接
+    fn sign_at(urls: &SignedUrls, target: &str, exp: u64) -> String {
+        let tag = hmac::sign(&urls.key, SignedUrls::payload(exp, target).as_bytes());
+        URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+
+    const TARGET: &str = "https://example.com/file.tar.gz";
+
+    #[test]
+    fn valid_signature_strips_sig_and_exp() {
+        let urls = signed_urls(true);
+        let (exp, sig) = urls.sign(&format!("{}?v=1", TARGET), 60);
+        let query = format!("v=1&exp={}&sig={}", exp, sig);
+        assert_eq!(
+            urls.check(TARGET, Some(&query)),
+            Ok(Some("v=1".to_string()))
+        );
+
+        let (exp, sig) = urls.sign(TARGET, 60);
+        let query = format!("exp={}&sig={}", exp, sig);
+        assert_eq!(urls.check(TARGET, Some(&query)), Ok(None));
+    }
+
+    #[test]
+    fn expired_signature_is_rejected() {
+        let urls = signed_urls(true);
+        let exp = now_secs() - 10;
+        let query = format!("exp={}&sig={}", exp, sign_at(&urls, TARGET, exp));
+        assert_eq!(urls.check(TARGET, Some(&query)), Err(Rejection::Expired));
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let urls = signed_urls(true);
+        let (exp, sig) = urls.sign(TARGET, 60);
+
+        // 修改目标、查询参数、过期时间或签名本身都无法通过校验
+        let query = format!("exp={}&sig={}", exp, sig);
+        assert_eq!(
+            urls.check("https://example.com/other", Some(&query)),
+            Err(Rejection::Invalid)
+        );
+        let query = format!("v=2&exp={}&sig={}", exp, sig);
+        assert_eq!(urls.check(TARGET, Some(&query)), Err(Rejection::Invalid));
+        let query = format!("exp={}&sig={}", exp + 3600, sig);
+        assert_eq!(urls.check(TARGET, Some(&query)), Err(Rejection::Invalid));
+        let mut forged = sig.clone().into_bytes();
+        forged[0] = if forged[0] == b'A' { b'B' } else { b'A' };
+        let query = format!("exp={}&sig={}", exp, String::from_utf8(forged).unwrap());
+        assert_eq!(urls.check(TARGET, Some(&query)), Err(Rejection::Invalid));
+        let query = format!("exp=soon&sig={}", sig);
+        assert_eq!(urls.check(TARGET, Some(&query)), Err(Rejection::Invalid));
+    }
+
+    #[test]
+    fn signature_from_another_key_is_rejected() {
+        let other = SignedUrls {
+            key: hmac::Key::new(hmac::HMAC_SHA256, b"other-secret"),
+            ..signed_urls(true)
+        };
+        let (exp, sig) = other.sign(TARGET, 60);
+        let query = format!("exp={}&sig={}", exp, sig);
+        assert_eq!(
+            signed_urls(true).check(TARGET, Some(&query)),
+            Err(Rejection::Invalid)
+        );
+    }
+
+    #[test]
+    fn unsigned_requests_depend_on_mode() {
+        assert_eq!(
+            signed_urls(true).check(TARGET, Some("v=1")),
+            Err(Rejection::Missing)
+        );
+        assert_eq!(
+            signed_urls(false).check(TARGET, Some("v=1")),
+            Ok(Some("v=1".to_string()))
+        );
+        assert_eq!(signed_urls(false).check(TARGET, None), Ok(None));
+    }
+}
@@ -0,0 +1,407 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::api::ApiResponse;
+use crate::auth;
+use crate::idempotency::IdempotencyCache;
+use crate::pipeline::Step;
+use crate::proxy::{
+    annotate_headers, forward_headers, upstream_response_headers, CompiledProxyRule,
+    ForwardTimeouts,
+};
+use crate::telemetry::TraceParent;
+use crate::tls;
+use crate::AdminState;
+
+/// 请求体大小上限，与实际转发一致
+const MAX_BODY_BYTES: usize = 100 * 1024 * 1024;
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_client_ip() -> String {
+    "127.0.0.1".to_string()
+}
+
+/// 样本请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureRequest {
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default = "default_client_ip")]
+    pub client_ip: String,
+}
+
+/// 模拟的上游响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixtureResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+}
+
+impl Default for FixtureResponse {
+    fn default() -> Self {
+        Self {
+            status: 200,
+            headers: BTreeMap::new(),
+            body: None,
+        }
+    }
+}
+
+/// 模拟参数：引用已保存的样本，或直接提供请求
+#[derive(Debug, Deserialize)]
+pub struct SimulateRequest {
+    #[serde(default)]
+    pub fixture_id: Option<i64>,
+    #[serde(default)]
+    pub request: Option<FixtureRequest>,
+    #[serde(default)]
+    pub response: Option<FixtureResponse>,
+}
+
+/// 处理流程中的一个阶段
+#[derive(Debug, Serialize)]
+pub struct SimulationStage {
+    pub stage: &'static str,
+    pub detail: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationResult {
+    pub rule_id: i64,
+    pub matched: bool,
+    pub stages: Vec<SimulationStage>,
+    /// 返回给客户端的响应，规则未匹配时为空
+    pub response: Option<Value>,
+}
+
+/// 在样本请求上执行规则的完整处理流程，上游响应使用模拟值，不会发出真实请求
+pub async fn simulate_handler(
+    State(state): State<AdminState>,
+    Path(id): Path<i64>,
+    Json(req): Json<SimulateRequest>,
+) -> Result<Json<ApiResponse<SimulationResult>>, StatusCode> {
+    let rule = state
+        .db
+        .get_rule(id)
+        .map_err(|e| {
+            tracing::error!("Failed to get rule: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let rule = CompiledProxyRule::from_db_rule(&rule).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let (request, response) = match (req.fixture_id, req.request) {
+        (_, Some(request)) => (request, req.response.unwrap_or_default()),
+        (Some(fixture_id), None) => {
+            let fixture = state
+                .db
+                .get_fixture(id, fixture_id)
+                .map_err(|e| {
+                    tracing::error!("Failed to get fixture: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (fixture.request, req.response.unwrap_or(fixture.response))
+        }
+        (None, None) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    simulate(&state, &rule, &request, &response)
+        .map(|result| Json(ApiResponse::ok(result)))
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
+/// 样本中的请求头无法解析时返回 None
+fn simulate(
+    state: &AdminState,
+    rule: &CompiledProxyRule,
+    request: &FixtureRequest,
+    response: &FixtureResponse,
+) -> Option<SimulationResult> {
+    let mut stages = Vec::new();
+    let mut headers = to_header_map(&request.headers)?;
+
+    // 1. 路径匹配与目标地址
+    let request_bytes = headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok()?.parse().ok())
+        .or(Some(request.body.as_ref().map_or(0, |b| b.len() as u64)));
+    let size_matched = rule.matches_request_size(request_bytes);
+    let target_url = rule
+        .match_and_build_target(&request.path)
+        .filter(|_| size_matched)
+        .map(|mut target| {
+            if let Some(ref q) = request.query {
+                target.push('?');
+                target.push_str(q);
+            }
+            target
+        });
+    stages.push(SimulationStage {
+        stage: "match",
+        detail: json!({
+            "path": request.path,
+            "pattern": rule.source_pattern.as_str(),
+            "request_bytes": request_bytes,
+            "size_matched": size_matched,
+            "target_url": target_url,
+            "pipeline": rule.pipeline.steps().iter().map(Step::name).collect::<Vec<_>>(),
+        }),
+    });
+    let Some(mut target_url) = target_url else {
+        return Some(SimulationResult {
+            rule_id: rule.id,
+            matched: false,
+            stages,
+            response: None,
+        });
+    };
+
+    // A/B 实验分组，分组可改写目标地址
+    let mut bucket = None;
+    if let Some(ref experiment) = rule.options.experiment {
+        let variant = experiment.select(&headers);
+        bucket = Some((experiment.response_header.as_str(), variant.name.as_str()));
+        if let Some(mut target) = variant
+            .target
+            .as_deref()
+            .and_then(|template| rule.build_target(&request.path, template))
+        {
+            if let Some(ref q) = request.query {
+                target.push('?');
+                target.push_str(q);
+            }
+            target_url = target;
+        }
+        stages.push(SimulationStage {
+            stage: "experiment",
+            detail: json!({
+                "bucket": variant.name,
+                "target_url": target_url,
+            }),
+        });
+    }
+
+    // 2. 规则认证，凭据不正确时代理返回 401，不再执行后续阶段
+    if let Some(ref rule_auth) = rule.options.auth {
+        let before = headers.clone();
+        let principal =
+            rule_auth.authenticate(&mut headers, rule.id, &state.proxy_keys, &state.secrets);
+        let mut detail = header_diff(&before, &headers);
+        detail["authenticated"] = json!(principal.is_some());
+        detail["principal"] = json!(principal);
+        stages.push(SimulationStage {
+            stage: "auth",
+            detail,
+        });
+        if principal.is_none() {
+            let challenge = rule_auth.challenge(&rule.name);
+            return Some(SimulationResult {
+                rule_id: rule.id,
+                matched: true,
+                stages,
+                response: Some(json!({
+                    "status": challenge.status().as_u16(),
+                    "headers": headers_json(challenge.headers()),
+                })),
+            });
+        }
+    }
+
+    // 3. 幂等键
+    let method =
+        Method::from_bytes(request.method.to_ascii_uppercase().as_bytes()).unwrap_or(Method::GET);
+    let idempotency_key = IdempotencyCache::key_for(rule.id, &method, &request.path, &headers);
+    let cacheable = rule.options.idempotency_ttl_secs.is_some()
+        && idempotency_key.is_some()
+        && response.status < 500;
+    stages.push(SimulationStage {
+        stage: "idempotency",
+        detail: json!({
+            "ttl_secs": rule.options.idempotency_ttl_secs,
+            "key": idempotency_key,
+            "response_cached": cacheable,
+        }),
+    });
+
+    // 4. 客户端证书（模拟请求没有 TLS 连接，按未出示证书处理）
+    if let Some(format) = rule.options.client_cert_headers {
+        let before = headers.clone();
+        tls::apply_client_cert_headers(&mut headers, None, format);
+        stages.push(SimulationStage {
+            stage: "client_cert",
+            detail: header_diff(&before, &headers),
+        });
+    }
+
+    // 5. 规则标注头
+    if rule.options.annotate_upstream {
+        let before = headers.clone();
+        annotate_headers(&mut headers, rule, &target_url);
+        stages.push(SimulationStage {
+            stage: "annotate",
+            detail: header_diff(&before, &headers),
+        });
+    }
+
+    // 6. 身份透传，样本 cookie 中没有有效会话时代理返回 401
+    if rule.options.identity_headers {
+        let before = headers.clone();
+        let session = state
+            .auth
+            .cookie_session(&headers, request.client_ip.parse().ok());
+        if let Some(ref session) = session {
+            auth::strip_session_cookies(&mut headers);
+            state.identity.apply(&mut headers, session, &rule.name);
+        }
+        let mut detail = header_diff(&before, &headers);
+        detail["authenticated"] = json!(session.is_some());
+        stages.push(SimulationStage {
+            stage: "identity",
+            detail,
+        });
+    }
+
+    // 7. 上游凭据，只展示引用的密钥名，不展示密钥值
+    if let Some(ref upstream_auth) = rule.options.upstream_auth {
+        stages.push(SimulationStage {
+            stage: "upstream_auth",
+            detail: json!({
+                "auth": upstream_auth,
+                "secret_found": state.secrets.contains(upstream_auth.secret()),
+            }),
+        });
+    }
+
+    // 8. 转发请求头
+    let trace = TraceParent::for_request(&tracing::Span::none(), &headers);
+    let forwarded = forward_headers(&headers, &target_url, &request.client_ip, Some(trace));
+    let body_len = request.body.as_ref().map_or(0, |b| b.len());
+    let mut detail = header_diff(&headers, &forwarded);
+    detail["method"] = json!(request.method);
+    detail["url"] = json!(target_url);
+    detail["body_bytes"] = json!(body_len);
+    detail["body_rejected"] = json!(body_len > MAX_BODY_BYTES);
+    stages.push(SimulationStage {
+        stage: "forward_request",
+        detail,
+    });
+
+    // 9. 超时设置
+    let timeouts = ForwardTimeouts::for_rule(rule);
+    stages.push(SimulationStage {
+        stage: "timeouts",
+        detail: json!({
+            "connect_secs": rule.pool_settings().connect_timeout.map(|d| d.as_secs()),
+            "response_secs": timeouts.response.as_secs(),
+            "upstream_read_secs": timeouts.upstream_read.as_secs(),
+            "client_write_secs": timeouts.client_write.map(|d| d.as_secs()),
+        }),
+    });
+
+    // 10. 上游响应（模拟）与返回给客户端的响应头
+    let upstream = to_header_map(&response.headers)?;
+    let mut client_headers = upstream_response_headers(&upstream);
+    if let Some((name, value)) = bucket {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            client_headers.insert(name, value);
+        }
+    }
+    if rule.options.annotate_response {
+        annotate_headers(&mut client_headers, rule, &target_url);
+    }
+    stages.push(SimulationStage {
+        stage: "response",
+        detail: header_diff(&upstream, &client_headers),
+    });
+
+    Some(SimulationResult {
+        rule_id: rule.id,
+        matched: true,
+        stages,
+        response: Some(json!({
+            "status": response.status,
+            "headers": headers_json(&client_headers),
+            "body": response.body,
+        })),
+    })
+}
+
+fn to_header_map(headers: &BTreeMap<String, String>) -> Option<HeaderMap> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        let value = HeaderValue::from_str(value).ok()?;
+        map.append(name, value);
+    }
+    Some(map)
+}
+
+fn headers_json(headers: &HeaderMap) -> Value {
+    let mut out = serde_json::Map::new();
+    for name in headers.keys() {
+        let values: Vec<&str> = headers
+            .get_all(name)
+            .iter()
+            .map(|v| v.to_str().unwrap_or("<binary>"))
+            .collect();
+        out.insert(name.to_string(), json!(values.join(", ")));
+    }
+    Value::Object(out)
+}
+
+/// 对比处理前后的请求头
+fn header_diff(before: &HeaderMap, after: &HeaderMap) -> Value {
+    let before_json = headers_json(before);
+    let after_json = headers_json(after);
+    let mut added = serde_json::Map::new();
+    let mut changed = serde_json::Map::new();
+    let mut removed = Vec::new();
+
+    for (name, value) in after_json.as_object().into_iter().flatten() {
+        match before_json.get(name) {
+            None => {
+                added.insert(name.clone(), value.clone());
+            }
+            Some(old) if old != value => {
+                changed.insert(name.clone(), json!({ "from": old, "to": value }));
+            }
+            _ => {}
+        }
+    }
+    for name in before_json
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(k, _)| k)
+    {
+        if after_json.get(name).is_none() {
+            removed.push(name.clone());
+        }
+    }
+
+    json!({
+        "added": added,
+        "changed": changed,
+        "removed": removed,
+        "headers": after_json,
+    })
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::config::SniPassthroughConfig;
+use crate::proxy_protocol;
+
+/// 等待客户端发送完整 ClientHello 的超时
+const PEEK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ClientHello 未收全时再次查看的间隔；peek 不消费数据，已有数据时可读事件会立即返回
+const PEEK_RETRY: Duration = Duration::from_millis(5);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS 记录头与最大记录长度
+const RECORD_HEADER_LEN: usize = 5;
+const MAX_RECORD_LEN: usize = RECORD_HEADER_LEN + 16384;
+
+const CONTENT_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXT_SERVER_NAME: u16 = 0x0000;
+const NAME_TYPE_HOST: u8 = 0x00;
+
+/// 连接开头的内容
+pub enum Peeked {
+    /// 不是 TLS 握手，如明文 HTTP 请求
+    NotTls,
+    /// TLS ClientHello 中的服务器名称，客户端未发送 SNI 时为 None
+    Tls(Option<String>),
+}
+
+/// 监听器的 SNI 透传路由：按 ClientHello 中的服务器名称将 TLS 连接原样转发到后端，不在本机终结 TLS
+#[derive(Debug)]
+pub struct SniRouter {
+    exact: HashMap<String, String>,
+    /// `*.example.com` 去掉 `*` 后的后缀，较长的后缀优先
+    wildcard: Vec<(String, String)>,
+    default_backend: Option<String>,
+    proxy_protocol: bool,
+}
+
+impl SniRouter {
+    pub fn from_config(config: &SniPassthroughConfig) -> anyhow::Result<Self> {
+        let mut exact = HashMap::new();
+        let mut wildcard = Vec::new();
+        for (name, backend) in &config.routes {
+            check_backend(backend)?;
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            match name.strip_prefix('*') {
+                Some(suffix) if suffix.starts_with('.') => {
+                    wildcard.push((suffix.to_string(), backend.clone()))
+                }
+                Some(_) => anyhow::bail!("invalid SNI route pattern: {}", name),
+                None => {
+                    exact.insert(name, backend.clone());
+                }
+            }
+        }
+        wildcard.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        if let Some(ref backend) = config.default_backend {
+            check_backend(backend)?;
+        }
+        Ok(Self {
+            exact,
+            wildcard,
+            default_backend: config.default_backend.clone(),
+            proxy_protocol: config.proxy_protocol,
+        })
+    }
+
+    /// 服务器名称对应的后端，先精确匹配再匹配通配符，都不匹配时为默认后端
+    pub fn backend(&self, server_name: Option<&str>) -> Option<&str> {
+        let routed = server_name.and_then(|name| {
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            self.exact.get(&name).or_else(|| {
+                self.wildcard
+                    .iter()
+                    .find(|(suffix, _)| {
+                        name.len() > suffix.len() && name.ends_with(suffix.as_str())
+                    })
+                    .map(|(_, backend)| backend)
+            })
+        });
+        routed.or(self.default_backend.as_ref()).map(String::as_str)
+    }
+
+    /// 连接后端并双向转发，ClientHello 仍在客户端连接的接收缓冲区中，随后续数据一起转发
+    pub async fn forward(
+        &self,
+        mut stream: TcpStream,
+        client: SocketAddr,
+        backend: &str,
+    ) -> anyhow::Result<()> {
+        let mut upstream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(backend))
+            .await
+            .map_err(|_| anyhow::anyhow!("connect to {} timed out", backend))??;
+        upstream.set_nodelay(true)?;
+        if self.proxy_protocol {
+            upstream
+                .write_all(&proxy_protocol::encode_v2(client, upstream.peer_addr()?))
+                .await?;
+        }
+        let _ = stream.set_nodelay(true);
+        let (sent, received) = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+        tracing::debug!(backend = %backend, client_ip = %client.ip(), sent, received, "SNI passthrough closed");
+        Ok(())
+    }
+}
+
+/// 后端地址需为 host:port
+fn check_backend(backend: &str) -> anyhow::Result<()> {
+    match backend.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => anyhow::bail!("SNI backend must be host:port: {}", backend),
+    }
+}
+
+/// 不消费数据地查看连接开头的 TLS 记录，收全第一个记录后解析其中的 ClientHello
+pub async fn peek_client_hello(stream: &TcpStream) -> anyhow::Result<Peeked> {
+    let mut buf = vec![0u8; MAX_RECORD_LEN];
+    let peek = async {
+        loop {
+            let n = stream.peek(&mut buf).await?;
+            if n == 0 {
+                anyhow::bail!("connection closed before ClientHello");
+            }
+            if buf[0] != CONTENT_HANDSHAKE {
+                return Ok(Peeked::NotTls);
+            }
+            if n >= RECORD_HEADER_LEN {
+                let len = RECORD_HEADER_LEN + u16::from_be_bytes([buf[3], buf[4]]) as usize;
+                let len = len.min(MAX_RECORD_LEN);
+                if n >= len {
+                    return Ok(Peeked::Tls(parse_client_hello(
+                        &buf[RECORD_HEADER_LEN..len],
+                    )));
+                }
+            }
+            tokio::time::sleep(PEEK_RETRY).await;
+        }
+    };
+    tokio::time::timeout(PEEK_TIMEOUT, peek)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out waiting for ClientHello"))?
+}
+
+/// 解析握手消息中的 server_name 扩展；ClientHello 跨多个 TLS 记录时只查看第一个记录，
+/// 扩展不完整时视为没有 SNI
+fn parse_client_hello(record: &[u8]) -> Option<String> {
+    let mut r = Reader(record);
+    if r.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    r.skip(3)?; // 握手消息长度
+    r.skip(2 + 32)?; // 版本与随机数
+    let session_id = r.u8()? as usize;
+    r.skip(session_id)?;
+    let cipher_suites = r.u16()? as usize;
+    r.skip(cipher_suites)?;
+    let compression = r.u8()? as usize;
+    r.skip(compression)?;
+
+    let mut extensions = Reader(r.prefixed()?);
+    while let (Some(kind), Some(len)) = (extensions.u16(), extensions.u16()) {
+        let data = extensions.take(len as usize)?;
+        if kind != EXT_SERVER_NAME {
+            continue;
+        }
+        let mut list = Reader(Reader(data).prefixed()?);
+        while let Some(name_type) = list.u8() {
+            let name = list.prefixed()?;
+            if name_type == NAME_TYPE_HOST {
+                return std::str::from_utf8(name).ok().map(str::to_ascii_lowercase);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+/// 按网络字节序读取的游标
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// 两字节长度前缀的数据
+    fn prefixed(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
@@ -0,0 +1,380 @@
+use std::future::Future;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::SocksConfig;
+use crate::forward_proxy::{self, TunnelError};
+use crate::rule_auth::ProxyKeys;
+use crate::target_guard::TargetGuard;
+
+/// 完成握手（认证与请求）的超时，避免空闲连接占用任务
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const VERSION: u8 = 0x05;
+const AUTH_VERSION: u8 = 0x01;
+const METHOD_NONE: u8 = 0x00;
+const METHOD_PASSWORD: u8 = 0x02;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// 应答码（RFC 1928 第 6 节）
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+enum Reply {
+    Succeeded = 0x00,
+    GeneralFailure = 0x01,
+    NotAllowed = 0x02,
+    HostUnreachable = 0x04,
+    ConnectionRefused = 0x05,
+    TtlExpired = 0x06,
+    CommandNotSupported = 0x07,
+    AddressTypeNotSupported = 0x08,
+}
+
+impl From<&TunnelError> for Reply {
+    fn from(e: &TunnelError) -> Self {
+        match e {
+            TunnelError::Blocked(_) => Self::NotAllowed,
+            TunnelError::Resolve(_) => Self::HostUnreachable,
+            TunnelError::Connect(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                Self::ConnectionRefused
+            }
+            TunnelError::Connect(_) => Self::GeneralFailure,
+            TunnelError::Timeout => Self::TtlExpired,
+        }
+    }
+}
+
+/// SOCKS5 代理（RFC 1928），只支持 CONNECT；用户名/密码认证（RFC 1929）的密码为未绑定规则的 API Key，
+/// 目标按直接代理的允许/禁止列表与内网限制校验
+#[derive(Clone)]
+pub struct SocksServer {
+    require_auth: bool,
+    keys: ProxyKeys,
+    guard: Arc<TargetGuard>,
+}
+
+impl SocksServer {
+    pub fn new(config: &SocksConfig, keys: ProxyKeys, guard: Arc<TargetGuard>) -> Self {
+        Self {
+            require_auth: config.require_auth,
+            keys,
+            guard,
+        }
+    }
+
+    /// 收到关闭信号后停止接受新连接，已建立的隧道随进程退出关闭
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()>,
+    ) -> anyhow::Result<()> {
+        tokio::pin!(shutdown);
+        loop {
+            let (stream, client) = tokio::select! {
+                r = listener.accept() => match r {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept SOCKS connection: {}", e);
+                        continue;
+                    }
+                },
+                _ = &mut shutdown => break,
+            };
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle(stream, client).await {
+                    tracing::debug!(client_ip = %client.ip(), "SOCKS connection error: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, mut stream: TcpStream, client: SocketAddr) -> anyhow::Result<()> {
+        let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, self.handshake(&mut stream));
+        let Some((principal, host, port)) = handshake
+            .await
+            .map_err(|_| anyhow::anyhow!("handshake timed out"))??
+        else {
+            return Ok(());
+        };
+        let target = if host.contains(':') {
+            format!("[{}]:{}", host, port)
+        } else {
+            format!("{}:{}", host, port)
+        };
+
+        let mut upstream = match forward_proxy::connect_target(&self.guard, &host, port).await {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                tracing::warn!(target = %target, client_ip = %client.ip(), "SOCKS tunnel rejected: {}", e);
+                write_reply(&mut stream, Reply::from(&e), None).await?;
+                return Ok(());
+            }
+        };
+        write_reply(&mut stream, Reply::Succeeded, upstream.local_addr().ok()).await?;
+        tracing::info!(target = %target, client_ip = %client.ip(), principal = %principal, "SOCKS tunnel");
+
+        let _ = stream.set_nodelay(true);
+        let (sent, received) = tokio::io::copy_bidirectional(&mut stream, &mut upstream).await?;
+        tracing::debug!(target = %target, sent, received, "SOCKS tunnel closed");
+        Ok(())
+    }
+
+    /// 协商认证方式、校验凭据并读取请求，返回认证主体与目标；
+    /// 已向客户端应答拒绝时返回 None
+    async fn handshake(
+        &self,
+        stream: &mut TcpStream,
+    ) -> anyhow::Result<Option<(String, String, u16)>> {
+        let [version, count] = read_array(stream).await?;
+        if version != VERSION {
+            anyhow::bail!("unsupported SOCKS version: {}", version);
+        }
+        let mut methods = vec![0u8; count as usize];
+        stream.read_exact(&mut methods).await?;
+
+        // 不要求认证时也接受只支持用户名/密码的客户端，凭据不校验
+        let method = if !self.require_auth && methods.contains(&METHOD_NONE) {
+            METHOD_NONE
+        } else if methods.contains(&METHOD_PASSWORD) {
+            METHOD_PASSWORD
+        } else {
+            METHOD_UNACCEPTABLE
+        };
+        stream.write_all(&[VERSION, method]).await?;
+        let principal = match method {
+            METHOD_NONE => "anonymous".to_string(),
+            METHOD_PASSWORD => {
+                let (_, password) = read_credentials(stream).await?;
+                let principal = if self.require_auth {
+                    self.keys
+                        .verify_unscoped(&password)
+                        .map(|name| format!("key:{}", name))
+                } else {
+                    Some("anonymous".to_string())
+                };
+                let Some(principal) = principal else {
+                    tracing::warn!(
+                        client_ip = %stream.peer_addr()?.ip(),
+                        "SOCKS authentication failed"
+                    );
+                    stream.write_all(&[AUTH_VERSION, 0x01]).await?;
+                    return Ok(None);
+                };
+                stream.write_all(&[AUTH_VERSION, 0x00]).await?;
+                principal
+            }
+            _ => return Ok(None),
+        };
+
+        let [version, command, _reserved, address_type] = read_array(stream).await?;
+        if version != VERSION {
+            anyhow::bail!("unsupported SOCKS version: {}", version);
+        }
+        let host = match address_type {
+            ATYP_IPV4 => Ipv4Addr::from(read_array::<4>(stream).await?).to_string(),
+            ATYP_IPV6 => Ipv6Addr::from(read_array::<16>(stream).await?).to_string(),
+            ATYP_DOMAIN => {
+                let [len] = read_array(stream).await?;
+                let mut domain = vec![0u8; len as usize];
+                stream.read_exact(&mut domain).await?;
+                String::from_utf8(domain)?
+            }
+            _ => {
+                write_reply(stream, Reply::AddressTypeNotSupported, None).await?;
+                return Ok(None);
+            }
+        };
+        let port = u16::from_be_bytes(read_array(stream).await?);
+        if command != CMD_CONNECT {
+            write_reply(stream, Reply::CommandNotSupported, None).await?;
+            return Ok(None);
+        }
+        Ok(Some((principal, host, port)))
+    }
+}
+
+/// 用户名/密码子协商（RFC 1929）
+async fn read_credentials(stream: &mut TcpStream) -> anyhow::Result<(String, String)> {
+    let [version, len] = read_array(stream).await?;
+    if version != AUTH_VERSION {
+        anyhow::bail!("unsupported SOCKS auth version: {}", version);
+    }
+    let mut username = vec![0u8; len as usize];
+    stream.read_exact(&mut username).await?;
+    let [len] = read_array(stream).await?;
+    let mut password = vec![0u8; len as usize];
+    stream.read_exact(&mut password).await?;
+    Ok((
+        String::from_utf8_lossy(&username).into_owned(),
+        String::from_utf8_lossy(&password).into_owned(),
+    ))
+}
+
+async fn read_array<const N: usize>(stream: &mut TcpStream) -> std::io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// 应答中的绑定地址为连接目标使用的本地地址，失败应答填 0.0.0.0:0
+async fn write_reply(
+    stream: &mut TcpStream,
+    reply: Reply,
+    bound: Option<SocketAddr>,
+) -> std::io::Result<()> {
+    let bound = bound.unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    let mut out = vec![VERSION, reply as u8, 0x00];
+    match bound {
+        SocketAddr::V4(addr) => {
+            out.push(ATYP_IPV4);
+            out.extend_from_slice(&addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            out.push(ATYP_IPV6);
+            out.extend_from_slice(&addr.ip().octets());
+        }
+    }
+    out.extend_from_slice(&bound.port().to_be_bytes());
+    stream.write_all(&out).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::testing;
+
+    const KEY: &str = "pk_socks";
+
+    /// 启动 SOCKS 服务，数据库中有一个未绑定规则的密钥与一个绑定规则的密钥
+    async fn server(require_auth: bool, block_private: bool) -> SocketAddr {
+        let db = Database::in_memory().unwrap();
+        let hash = |key: &str| {
+            ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+                .as_ref()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        db.create_proxy_key("ci", &hash(KEY), "pk_socks", None)
+            .unwrap();
+        db.create_proxy_key("rule", &hash("pk_rule"), "pk_rule", Some(1))
+            .unwrap();
+        let mut config = testing::config();
+        config.socks.require_auth = require_auth;
+        config.direct_proxy.block_private = block_private;
+        let guard = TargetGuard::from_config(&config.direct_proxy).unwrap();
+        let server = SocksServer::new(
+            &config.socks,
+            ProxyKeys::load(&db).unwrap(),
+            Arc::new(guard),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(server.serve(listener, std::future::pending()));
+        addr
+    }
+
+    /// 回显服务作为隧道目标
+    async fn echo() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut read, mut write) = stream.split();
+                    let _ = tokio::io::copy(&mut read, &mut write).await;
+                });
+            }
+        });
+        addr
+    }
+
+    async fn read<const N: usize>(stream: &mut TcpStream) -> [u8; N] {
+        read_array(stream).await.unwrap()
+    }
+
+    /// 以用户名/密码认证，返回认证应答状态
+    async fn login(stream: &mut TcpStream, password: &str) -> u8 {
+        stream
+            .write_all(&[VERSION, 1, METHOD_PASSWORD])
+            .await
+            .unwrap();
+        assert_eq!(read::<2>(stream).await, [VERSION, METHOD_PASSWORD]);
+        let mut auth = vec![AUTH_VERSION, 4];
+        auth.extend_from_slice(b"user");
+        auth.push(password.len() as u8);
+        auth.extend_from_slice(password.as_bytes());
+        stream.write_all(&auth).await.unwrap();
+        let [version, status] = read::<2>(stream).await;
+        assert_eq!(version, AUTH_VERSION);
+        status
+    }
+
+    /// 发送 CONNECT 请求，返回应答码
+    async fn connect(stream: &mut TcpStream, target: SocketAddr) -> u8 {
+        let SocketAddr::V4(target) = target else {
+            unreachable!()
+        };
+        let mut req = vec![VERSION, CMD_CONNECT, 0, ATYP_IPV4];
+        req.extend_from_slice(&target.ip().octets());
+        req.extend_from_slice(&target.port().to_be_bytes());
+        stream.write_all(&req).await.unwrap();
+        let reply = read::<10>(stream).await;
+        reply[1]
+    }
+
+    #[tokio::test]
+    async fn api_key_opens_tunnel() {
+        let (server, echo) = (server(true, false).await, echo().await);
+        let mut stream = TcpStream::connect(server).await.unwrap();
+        assert_eq!(login(&mut stream, KEY).await, 0x00);
+        assert_eq!(connect(&mut stream, echo).await, Reply::Succeeded as u8);
+
+        stream.write_all(b"ping").await.unwrap();
+        assert_eq!(&read::<4>(&mut stream).await, b"ping");
+    }
+
+    #[tokio::test]
+    async fn invalid_or_rule_scoped_key_is_rejected() {
+        let server = server(true, false).await;
+        for password in ["wrong", "pk_rule"] {
+            let mut stream = TcpStream::connect(server).await.unwrap();
+            assert_eq!(login(&mut stream, password).await, 0x01, "{}", password);
+            // 拒绝后服务端关闭连接
+            let mut buf = [0u8; 1];
+            assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn anonymous_method_requires_auth_disabled() {
+        let server_auth = server(true, false).await;
+        let mut stream = TcpStream::connect(server_auth).await.unwrap();
+        stream.write_all(&[VERSION, 1, METHOD_NONE]).await.unwrap();
+        assert_eq!(read::<2>(&mut stream).await, [VERSION, METHOD_UNACCEPTABLE]);
+
+        let (server_open, echo) = (server(false, false).await, echo().await);
+        let mut stream = TcpStream::connect(server_open).await.unwrap();
+        stream.write_all(&[VERSION, 1, METHOD_NONE]).await.unwrap();
+        assert_eq!(read::<2>(&mut stream).await, [VERSION, METHOD_NONE]);
+        assert_eq!(connect(&mut stream, echo).await, Reply::Succeeded as u8);
+    }
+
+    #[tokio::test]
+    async fn blocked_target_is_not_allowed() {
+        let (server, echo) = (server(true, true).await, echo().await);
+        let mut stream = TcpStream::connect(server).await.unwrap();
+        assert_eq!(login(&mut stream, KEY).await, 0x00);
+        assert_eq!(connect(&mut stream, echo).await, Reply::NotAllowed as u8);
+    }
+}
@@ -0,0 +1,163 @@
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::db::Database;
+use crate::tasks::TaskRegistry;
+
+/// 延迟采样窗口大小（保留最近 N 个请求）
+const LATENCY_WINDOW: usize = 1024;
+
+/// 单条规则的流量计数器，计数为上次落库之后的增量
+#[derive(Default)]
+pub struct RuleCounters {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    latencies: Mutex<LatencyWindow>,
+}
+
+#[derive(Default)]
+struct LatencyWindow {
+    samples: Vec<u64>,
+    next: usize,
+}
+
+impl LatencyWindow {
+    fn push(&mut self, ms: u64) {
+        if self.samples.len() < LATENCY_WINDOW {
+            self.samples.push(ms);
+        } else {
+            self.samples[self.next] = ms;
+        }
+        self.next = (self.next + 1) % LATENCY_WINDOW;
+    }
+
+    fn percentiles(&self) -> Option<(u64, u64)> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        Some((percentile(&sorted, 50), percentile(&sorted, 95)))
+    }
+}
+
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    let idx = (sorted.len() * p).div_ceil(100).saturating_sub(1);
+    sorted[idx.min(sorted.len() - 1)]
+}
+
+impl RuleCounters {
+    /// 记录一次请求完成
+    pub fn record(&self, latency: Duration, is_error: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if is_error {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latencies.lock().push(latency.as_millis() as u64);
+    }
+
+    #[inline]
+    pub fn add_bytes_in(&self, n: u64) {
+        self.bytes_in.fetch_add(n, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_bytes_out(&self, n: u64) {
+        self.bytes_out.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// 尚未落库的响应字节数
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+}
+
+/// 规则统计快照
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RuleStatsSnapshot {
+    pub rule_id: i64,
+    pub request_count: u64,
+    pub error_count: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+/// 按规则 ID 聚合的流量统计
+#[derive(Clone, Default)]
+pub struct RuleStats {
+    rules: Arc<DashMap<i64, Arc<RuleCounters>>>,
+}
+
+impl RuleStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn counters(&self, rule_id: i64) -> Arc<RuleCounters> {
+        self.rules.entry(rule_id).or_default().clone()
+    }
+
+    /// 数据库累计值 + 内存中尚未落库的增量
+    pub fn snapshot(&self, db: &Database, rule_id: i64) -> anyhow::Result<RuleStatsSnapshot> {
+        let mut snapshot = db.get_rule_stats(rule_id)?.unwrap_or(RuleStatsSnapshot {
+            rule_id,
+            ..Default::default()
+        });
+
+        if let Some(counters) = self.rules.get(&rule_id) {
+            snapshot.request_count += counters.requests.load(Ordering::Relaxed);
+            snapshot.error_count += counters.errors.load(Ordering::Relaxed);
+            snapshot.bytes_in += counters.bytes_in.load(Ordering::Relaxed);
+            snapshot.bytes_out += counters.bytes_out.load(Ordering::Relaxed);
+            if let Some((p50, p95)) = counters.latencies.lock().percentiles() {
+                snapshot.p50_ms = p50;
+                snapshot.p95_ms = p95;
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// 将增量累加写入 rule_stats 表
+    pub fn flush(&self, db: &Database) -> anyhow::Result<()> {
+        for entry in self.rules.iter() {
+            let counters = entry.value();
+            let requests = counters.requests.swap(0, Ordering::Relaxed);
+            if requests == 0 {
+                continue;
+            }
+            let (p50, p95) = counters.latencies.lock().percentiles().unwrap_or_default();
+            let delta = RuleStatsSnapshot {
+                rule_id: *entry.key(),
+                request_count: requests,
+                error_count: counters.errors.swap(0, Ordering::Relaxed),
+                bytes_in: counters.bytes_in.swap(0, Ordering::Relaxed),
+                bytes_out: counters.bytes_out.swap(0, Ordering::Relaxed),
+                p50_ms: p50,
+                p95_ms: p95,
+            };
+            db.add_rule_stats(&delta)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, rule_id: i64) {
+        self.rules.remove(&rule_id);
+    }
+}
+
+/// 启动定时落库任务
+pub fn start_flush_task(tasks: &TaskRegistry, stats: RuleStats, db: Database) {
+    tasks.spawn_periodic("stats_flush", Duration::from_secs(60), move || {
+        let result = stats.flush(&db);
+        async move { result }
+    });
+}
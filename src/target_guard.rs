@@ -0,0 +1,268 @@
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Url};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+use crate::acl::IpAcl;
+use crate::config::DirectProxyConfig;
+
+/// 默认禁止访问的内网、回环、链路本地等地址段
+const PRIVATE_RANGES: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
+
+/// 直接代理最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 10;
+
+/// 直接代理目标访问控制（SSRF 防护）：域名与 IP 均可配置允许/禁止列表
+#[derive(Debug)]
+pub struct TargetGuard {
+    allow_domains: Vec<String>,
+    allow_nets: IpAcl,
+    deny_domains: Vec<String>,
+    deny_nets: IpAcl,
+    private: Option<IpAcl>,
+}
+
+impl TargetGuard {
+    pub fn from_config(config: &DirectProxyConfig) -> anyhow::Result<Self> {
+        let (allow_domains, allow_nets) = split_entries(&config.allow)?;
+        let (deny_domains, deny_nets) = split_entries(&config.deny)?;
+        Ok(Self {
+            allow_domains,
+            allow_nets,
+            deny_domains,
+            deny_nets,
+            private: config
+                .block_private
+                .then(|| IpAcl::parse(PRIVATE_RANGES))
+                .transpose()?,
+        })
+    }
+
+    /// 校验目标地址的主机名；域名解析出的 IP 由 [`GuardedResolver`] 在连接前校验
+    pub fn check_url(&self, url: &Url) -> Result<(), String> {
+        let host = url.host_str().ok_or_else(|| "missing host".to_string())?;
+        // IPv6 主机名带方括号
+        self.check_host(host.trim_start_matches('[').trim_end_matches(']'))
+    }
+
+    /// 校验主机名（域名或不带方括号的 IP）
+    pub fn check_host(&self, host: &str) -> Result<(), String> {
+        match host.parse::<IpAddr>() {
+            Ok(ip) => self.check_ip(ip, false),
+            Err(_) => self.check_domain(host),
+        }
+    }
+
+    /// 解析主机名并丢弃被禁止的地址，全部被禁止时返回错误；
+    /// 也用于 CONNECT 隧道等不经过 direct_client 的连接
+    pub async fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+        let allowed_domain = self.is_allowed_domain(host);
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+        let allowed: Vec<SocketAddr> = addrs
+            .iter()
+            .filter(|addr| self.check_ip(addr.ip(), allowed_domain).is_ok())
+            .copied()
+            .collect();
+        if allowed.is_empty() {
+            tracing::warn!(host = %host, addrs = ?addrs, "Direct proxy target resolves to blocked addresses");
+            return Err(format!("{} resolves to blocked addresses", host).into());
+        }
+        Ok(allowed)
+    }
+
+    fn check_domain(&self, domain: &str) -> Result<(), String> {
+        let domain = normalize(domain);
+        if matches_domain(&self.deny_domains, &domain) {
+            return Err(format!("domain {} is denied", domain));
+        }
+        // 只配置了域名允许列表时可直接拒绝，配置了网段则需解析后判断
+        if !self.allow_domains.is_empty()
+            && self.allow_nets.is_empty()
+            && !matches_domain(&self.allow_domains, &domain)
+        {
+            return Err(format!("domain {} is not allowed", domain));
+        }
+        Ok(())
+    }
+
+    fn is_allowed_domain(&self, domain: &str) -> bool {
+        matches_domain(&self.allow_domains, &normalize(domain))
+    }
+
+    /// `allowed_domain` 表示 IP 来自允许列表中域名的解析结果，此时只受禁止网段限制
+    fn check_ip(&self, ip: IpAddr, allowed_domain: bool) -> Result<(), String> {
+        if self.deny_nets.contains(ip) {
+            return Err(format!("address {} is denied", ip));
+        }
+        // 显式允许的域名与网段优先于默认内网禁止
+        if allowed_domain || self.allow_nets.contains(ip) {
+            return Ok(());
+        }
+        if !self.allow_domains.is_empty() || !self.allow_nets.is_empty() {
+            return Err(format!("address {} is not allowed", ip));
+        }
+        if self.private.as_ref().is_some_and(|acl| acl.contains(ip)) {
+            return Err(format!("address {} is private", ip));
+        }
+        Ok(())
+    }
+
+    /// 每次重定向都重新校验目标地址
+    pub fn redirect_policy(self: &Arc<Self>) -> redirect::Policy {
+        let guard = self.clone();
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match guard.check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(reason) => attempt.error(format!("redirect blocked: {}", reason)),
+            }
+        })
+    }
+}
+
+/// 直接代理客户端的 DNS 解析：丢弃被禁止的地址，避免域名指向内网绕过校验
+pub struct GuardedResolver {
+    guard: Arc<TargetGuard>,
+}
+
+impl GuardedResolver {
+    pub fn new(guard: Arc<TargetGuard>) -> Self {
+        Self { guard }
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let guard = self.guard.clone();
+        Box::pin(async move {
+            let addrs = guard.resolve(name.as_str(), 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 列表项可以是 IP / CIDR 或域名，域名同时匹配其子域名
+fn split_entries(entries: &[String]) -> anyhow::Result<(Vec<String>, IpAcl)> {
+    let (nets, domains): (Vec<&str>, Vec<&str>) = entries
+        .iter()
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .partition(|e| e.parse::<ipnet::IpNet>().is_ok() || e.parse::<IpAddr>().is_ok());
+    let domains = domains
+        .into_iter()
+        .map(|d| normalize(d.trim_start_matches("*.")))
+        .collect();
+    Ok((domains, IpAcl::parse(&nets)?))
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn matches_domain(patterns: &[String], domain: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        domain == pattern
+            || domain
+                .strip_suffix(pattern.as_str())
+                .is_some_and(|prefix| prefix.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn guard(allow: &[&str], deny: &[&str], block_private: bool) -> TargetGuard {
+        let mut config = testing::config().direct_proxy;
+        config.allow = allow.iter().map(|s| s.to_string()).collect();
+        config.deny = deny.iter().map(|s| s.to_string()).collect();
+        config.block_private = block_private;
+        TargetGuard::from_config(&config).unwrap()
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn blocks_private_addresses_by_default() {
+        let strict = guard(&[], &[], true);
+        for target in [
+            "http://127.0.0.1/",
+            "http://10.1.2.3:8080/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://192.168.1.1/",
+            "http://[::1]/",
+            "http://[fe80::1]/",
+            "http://0.0.0.0/",
+        ] {
+            assert!(strict.check_url(&url(target)).is_err(), "{}", target);
+        }
+        assert!(strict.check_url(&url("http://8.8.8.8/")).is_ok());
+        assert!(strict.check_url(&url("https://example.com/")).is_ok());
+
+        let open = guard(&[], &[], false);
+        assert!(open.check_url(&url("http://127.0.0.1/")).is_ok());
+    }
+
+    #[test]
+    fn deny_list_matches_domains_and_networks() {
+        let guard = guard(&[], &["example.com", "8.8.8.0/24"], true);
+        assert!(guard.check_host("example.com").is_err());
+        assert!(guard.check_host("API.Example.com.").is_err());
+        assert!(guard.check_host("notexample.com").is_ok());
+        assert!(guard.check_host("8.8.8.8").is_err());
+        assert!(guard.check_host("8.8.4.4").is_ok());
+    }
+
+    #[test]
+    fn allow_list_overrides_private_block() {
+        let domains = guard(&["*.internal.test"], &[], true);
+        assert!(domains.check_host("svc.internal.test").is_ok());
+        assert!(domains.check_host("internal.test").is_ok());
+        assert!(domains.check_host("example.com").is_err());
+        assert!(domains.check_host("8.8.8.8").is_err());
+
+        let nets = guard(&["10.0.0.0/8"], &["10.0.0.1"], true);
+        assert!(nets.check_host("10.2.3.4").is_ok());
+        // 禁止列表优先于允许列表
+        assert!(nets.check_host("10.0.0.1").is_err());
+        assert!(nets.check_host("192.168.1.1").is_err());
+        assert!(nets.check_host("8.8.8.8").is_err());
+    }
+
+    #[tokio::test]
+    async fn resolved_addresses_are_checked() {
+        // 域名解析到内网地址时同样被拦截，显式允许的域名除外
+        let blocked = guard(&[], &[], true);
+        assert!(blocked.resolve("localhost", 80).await.is_err());
+
+        let allowed = guard(&["localhost"], &[], true);
+        let addrs = allowed.resolve("localhost", 80).await.unwrap();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+    }
+}
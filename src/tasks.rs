@@ -0,0 +1,167 @@
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 后台任务运行状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub name: &'static str,
+    pub interval_secs: u64,
+    pub last_run: Option<i64>,
+    pub last_success: Option<i64>,
+    pub runs: u64,
+    pub errors: u64,
+    pub last_error: Option<String>,
+    /// 超过两个周期未运行视为任务已停止
+    pub alive: bool,
+    #[serde(skip)]
+    registered_at: i64,
+}
+
+impl TaskStatus {
+    fn refresh_alive(&mut self, now: i64) {
+        let grace = (self.interval_secs * 2 + 60) as i64;
+        let last = self.last_run.unwrap_or(self.registered_at);
+        self.alive = now - last <= grace;
+    }
+}
+
+/// 后台任务注册表，记录每次执行结果，便于发现静默退出的任务
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    tasks: Arc<DashMap<&'static str, TaskStatus>>,
+}
+
+impl TaskRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按固定周期执行任务（首次立即执行）并记录结果
+    pub fn spawn_periodic<F, Fut>(&self, name: &'static str, interval: Duration, mut task: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send,
+    {
+        self.tasks.insert(
+            name,
+            TaskStatus {
+                name,
+                interval_secs: interval.as_secs(),
+                last_run: None,
+                last_success: None,
+                runs: 0,
+                errors: 0,
+                last_error: None,
+                alive: true,
+                registered_at: Utc::now().timestamp(),
+            },
+        );
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let result = task().await;
+                registry.record(name, result);
+            }
+        });
+    }
+
+    fn record(&self, name: &'static str, result: anyhow::Result<()>) {
+        let now = Utc::now().timestamp();
+        if let Some(mut status) = self.tasks.get_mut(name) {
+            status.runs += 1;
+            status.last_run = Some(now);
+            match result {
+                Ok(()) => status.last_success = Some(now),
+                Err(e) => {
+                    tracing::error!(task = name, error = %e, "Background task failed");
+                    status.errors += 1;
+                    status.last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<TaskStatus> {
+        let now = Utc::now().timestamp();
+        let mut tasks: Vec<TaskStatus> = self
+            .tasks
+            .iter()
+            .map(|entry| {
+                let mut status = entry.value().clone();
+                status.refresh_alive(now);
+                status
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.name);
+        tasks
+    }
+
+    /// Prometheus 文本格式
+    pub fn render_prometheus(&self, out: &mut String) {
+        let tasks = self.snapshot();
+
+        write_task_metric(
+            out,
+            &tasks,
+            "proxy_background_task_up",
+            "gauge",
+            "Whether the background task ran within two intervals",
+            |t| t.alive as i64,
+        );
+        write_task_metric(
+            out,
+            &tasks,
+            "proxy_background_task_last_run_timestamp_seconds",
+            "gauge",
+            "Unix time of the last run",
+            |t| t.last_run.unwrap_or(0),
+        );
+        write_task_metric(
+            out,
+            &tasks,
+            "proxy_background_task_last_success_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful run",
+            |t| t.last_success.unwrap_or(0),
+        );
+        write_task_metric(
+            out,
+            &tasks,
+            "proxy_background_task_runs_total",
+            "counter",
+            "Number of completed runs",
+            |t| t.runs as i64,
+        );
+        write_task_metric(
+            out,
+            &tasks,
+            "proxy_background_task_errors_total",
+            "counter",
+            "Number of failed runs",
+            |t| t.errors as i64,
+        );
+    }
+}
+
+fn write_task_metric(
+    out: &mut String,
+    tasks: &[TaskStatus],
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl Fn(&TaskStatus) -> i64,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for t in tasks {
+        let _ = writeln!(out, "{}{{task=\"{}\"}} {}", name, t.name, value(t));
+    }
+}
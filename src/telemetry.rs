@@ -0,0 +1,129 @@
+use axum::http::HeaderMap;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{SpanId, TraceContextExt, TraceFlags, TraceId, TracerProvider as _};
+use opentelemetry::Context;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{IdGenerator, RandomIdGenerator, Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::fmt;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::TelemetryConfig;
+
+/// 初始化 OTLP 导出，未配置 endpoint 时返回 None
+pub fn init_tracer_provider(config: &TelemetryConfig) -> anyhow::Result<Option<SdkTracerProvider>> {
+    opentelemetry::global::set_text_map_propagator(
+        opentelemetry_sdk::propagation::TraceContextPropagator::new(),
+    );
+
+    let Some(endpoint) = config.otlp_endpoint.as_deref().filter(|e| !e.is_empty()) else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+
+    Ok(Some(provider))
+}
+
+/// 构造 tracing 到 OpenTelemetry 的桥接层
+pub fn layer<S>(
+    provider: &SdkTracerProvider,
+) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("proxy-server"))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+fn extract(headers: &HeaderMap) -> Context {
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    })
+}
+
+/// 将请求头中的 traceparent 设为 span 的父上下文
+pub fn set_remote_parent(span: &tracing::Span, headers: &HeaderMap) {
+    let _ = span.set_parent(extract(headers));
+}
+
+/// 转发给上游的 W3C traceparent
+#[derive(Debug, Clone, Copy)]
+pub struct TraceParent {
+    trace_id: TraceId,
+    span_id: SpanId,
+    flags: TraceFlags,
+}
+
+impl TraceParent {
+    pub fn trace_id(&self) -> String {
+        self.trace_id.to_string()
+    }
+
+    /// 启用 OTLP 时使用代理 span 的上下文；否则沿用请求头中的 trace id
+    /// （没有则新建），并为代理这一跳生成新的 span id
+    pub fn for_request(span: &tracing::Span, headers: &HeaderMap) -> Self {
+        let current = span.context().span().span_context().clone();
+        if current.is_valid() {
+            return Self {
+                trace_id: current.trace_id(),
+                span_id: current.span_id(),
+                flags: current.trace_flags(),
+            };
+        }
+
+        let ids = RandomIdGenerator::default();
+        let remote = extract(headers).span().span_context().clone();
+        if remote.is_valid() {
+            Self {
+                trace_id: remote.trace_id(),
+                span_id: ids.new_span_id(),
+                flags: remote.trace_flags(),
+            }
+        } else {
+            Self {
+                trace_id: ids.new_trace_id(),
+                span_id: ids.new_span_id(),
+                flags: TraceFlags::SAMPLED,
+            }
+        }
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            self.flags.to_u8()
+        )
+    }
+}
@@ -0,0 +1,97 @@
+//! 单元测试共用的配置与管理状态
+
+use arc_swap::ArcSwap;
+use std::sync::atomic::{AtomicBool, AtomicU16};
+use std::sync::Arc;
+
+use crate::alerts::Alerts;
+use crate::auth::AuthState;
+use crate::changes::ChangeControl;
+use crate::config::Config;
+use crate::connections::ActiveRequests;
+use crate::db::Database;
+use crate::direct_cache::DirectCache;
+use crate::discovery::ServiceDiscovery;
+use crate::events::AdminEvents;
+use crate::faults::FaultSwitch;
+use crate::ha::HaState;
+use crate::identity::IdentityAssertions;
+use crate::lifecycle::{DrainTrigger, LifecycleHooks};
+use crate::listeners::ListenerManager;
+use crate::plugins::Plugins;
+use crate::pool_stats::ConnectionStats;
+use crate::reloads::ReloadHistory;
+use crate::rolling::RollingStats;
+use crate::router::RuleSet;
+use crate::rule_auth::ProxyKeys;
+use crate::secrets::Secrets;
+use crate::signed_urls::SignedUrls;
+use crate::stats::RuleStats;
+use crate::tasks::TaskRegistry;
+use crate::traffic::TrafficTail;
+use crate::upstreams::{UpstreamHealth, UpstreamLimits};
+use crate::webhooks::ChangeWebhooks;
+use crate::AdminState;
+
+/// 仓库中的默认配置文件
+pub fn config() -> Config {
+    serde_yaml::from_str(include_str!("../config.yaml")).unwrap()
+}
+
+/// 使用内存数据库与随机主密钥的管理状态，规则已加载
+pub fn admin_state(config: &Config) -> AdminState {
+    let db = Database::in_memory().unwrap();
+    let client = reqwest::Client::new();
+    let secrets = Secrets::load(&db, &config.secrets, None).unwrap();
+    let events = AdminEvents::new();
+    let (_, shutdown) = tokio::sync::watch::channel(false);
+    let state = AdminState {
+        db: db.clone(),
+        rules: Arc::new(ArcSwap::from_pointee(RuleSet::default())),
+        direct_proxy_path: Arc::new(ArcSwap::from_pointee("proxy".to_string())),
+        proxy_port: Arc::new(AtomicU16::new(config.proxy[0].port)),
+        auth: AuthState::new(db.clone(), &config.auth).unwrap(),
+        stats: RuleStats::new(),
+        lifecycle: LifecycleHooks::new(client.clone(), &config.lifecycle),
+        tasks: TaskRegistry::new(),
+        traffic: TrafficTail::new(),
+        rolling: RollingStats::new(),
+        reloads: ReloadHistory::new(),
+        upstreams: UpstreamHealth::new(events.clone()),
+        signed_urls: SignedUrls::load(&db, &secrets).unwrap(),
+        changes: ChangeControl {
+            enabled: config.change_approval.enabled,
+            approval_delay_secs: config.change_approval.approval_delay_secs,
+        },
+        ha: HaState::new(&config.ha).unwrap(),
+        oidc: None,
+        identity: IdentityAssertions::load(&secrets).unwrap(),
+        proxy_keys: ProxyKeys::load(&db).unwrap(),
+        active: ActiveRequests::new(),
+        secrets,
+        direct_cache: DirectCache::new(&config.direct_proxy.cache),
+        rules_ready: Arc::new(AtomicBool::new(false)),
+        listeners: ListenerManager::new(db, shutdown, false),
+        drain: DrainTrigger::new(),
+        plugins: Plugins::load(&config.plugins).unwrap(),
+        faults: FaultSwitch::default(),
+        rules_file: None,
+        cluster: None,
+        pool_stats: ConnectionStats::new(),
+        upstream_limits: UpstreamLimits::default(),
+        discovery: ServiceDiscovery::new(&config.discovery, client.clone()).unwrap(),
+        events,
+        webhooks: ChangeWebhooks::new(client.clone(), &config.change_webhooks),
+        alerts: Alerts::new(client, &config.alerts).unwrap(),
+    };
+    state.load_rules("startup").unwrap();
+    state
+}
+
+/// 将请求交给路由处理并返回响应
+pub async fn send(mut app: axum::Router, req: axum::extract::Request) -> axum::response::Response {
+    std::future::poll_fn(|cx| tower::Service::<axum::extract::Request>::poll_ready(&mut app, cx))
+        .await
+        .unwrap();
+    tower::Service::call(&mut app, req).await.unwrap()
+}
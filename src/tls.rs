@@ -0,0 +1,364 @@
+use arc_swap::ArcSwap;
+use axum::http::{HeaderMap, HeaderValue};
+use base64::Engine;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_rustls::rustls::{
+    self,
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate, X509Name};
+
+use crate::config::TlsConfig;
+
+/// 转发给上游的客户端证书请求头，客户端自带的同名头会被移除
+const CLIENT_CERT_HEADERS: &[&str] = &[
+    "x-ssl-client-cert",
+    "x-ssl-client-s-dn",
+    "x-ssl-client-i-dn",
+    "x-ssl-client-verify",
+];
+
+/// 与 nginx $ssl_client_escaped_cert 一致，仅保留 RFC 3986 非保留字符
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// X-SSL-Client-Cert 的证书编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientCertFormat {
+    /// 同 nginx $ssl_client_cert，PEM 各行以空格连接
+    Nginx,
+    /// 同 nginx $ssl_client_escaped_cert，URL 编码的 PEM
+    UrlEncoded,
+}
+
+/// 已通过校验的客户端证书，按连接解析一次后附加到每个请求
+#[derive(Debug, Clone)]
+pub struct ClientCert {
+    der: Arc<Vec<u8>>,
+    /// RFC 2253 格式的主题 DN
+    pub subject: String,
+    /// RFC 2253 格式的颁发者 DN
+    pub issuer: String,
+}
+
+impl ClientCert {
+    pub fn parse(der: &[u8]) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+        Some(Self {
+            der: Arc::new(der.to_vec()),
+            subject: rfc2253(cert.subject()),
+            issuer: rfc2253(cert.issuer()),
+        })
+    }
+
+    fn pem_lines(&self) -> Vec<String> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(self.der.as_slice());
+        let mut lines = vec!["-----BEGIN CERTIFICATE-----".to_string()];
+        lines.extend(
+            encoded
+                .as_bytes()
+                .chunks(64)
+                .map(|c| String::from_utf8_lossy(c).into_owned()),
+        );
+        lines.push("-----END CERTIFICATE-----".to_string());
+        lines
+    }
+
+    pub fn encode(&self, format: ClientCertFormat) -> String {
+        let lines = self.pem_lines();
+        match format {
+            ClientCertFormat::Nginx => lines.join(" "),
+            ClientCertFormat::UrlEncoded => {
+                let mut pem = lines.join("\n");
+                pem.push('\n');
+                utf8_percent_encode(&pem, URL_ENCODE_SET).to_string()
+            }
+        }
+    }
+}
+
+/// 按 nginx 约定写入客户端证书请求头，未提供证书时 Verify 为 NONE
+pub fn apply_client_cert_headers(
+    headers: &mut HeaderMap,
+    cert: Option<&ClientCert>,
+    format: ClientCertFormat,
+) {
+    for name in CLIENT_CERT_HEADERS {
+        headers.remove(*name);
+    }
+
+    let Some(cert) = cert else {
+        headers.insert("x-ssl-client-verify", HeaderValue::from_static("NONE"));
+        return;
+    };
+
+    headers.insert("x-ssl-client-verify", HeaderValue::from_static("SUCCESS"));
+    let values = [
+        ("x-ssl-client-cert", cert.encode(format)),
+        ("x-ssl-client-s-dn", cert.subject.clone()),
+        ("x-ssl-client-i-dn", cert.issuer.clone()),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// RFC 2253 DN：RDN 逆序，逗号分隔
+fn rfc2253(name: &X509Name) -> String {
+    let registry = x509_parser::objects::oid_registry();
+    let mut rdns: Vec<String> = name
+        .iter_rdn()
+        .map(|rdn| {
+            rdn.iter()
+                .map(|attr| {
+                    let key = x509_parser::objects::oid2abbrev(attr.attr_type(), registry)
+                        .map(|s| s.to_string())
+                        .unwrap_or_else(|_| attr.attr_type().to_id_string());
+                    let value = attr
+                        .as_str()
+                        .map(escape_dn_value)
+                        .unwrap_or_else(|_| format!("#{}", hex(attr.as_slice())));
+                    format!("{}={}", key, value)
+                })
+                .collect::<Vec<_>>()
+                .join("+")
+        })
+        .collect();
+    rdns.reverse();
+    rdns.join(",")
+}
+
+fn escape_dn_value(value: &str) -> String {
+    let len = value.chars().count();
+    let mut out = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        let special = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+            || (i == 0 && (c == '#' || c == ' '))
+            || (i + 1 == len && c == ' ');
+        if special {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 按 SNI 选择证书：优先匹配 ACME 签发的证书，其次使用配置文件中的证书
+#[derive(Debug)]
+pub struct CertStore {
+    provider: Arc<CryptoProvider>,
+    default: Arc<CertifiedKey>,
+    /// 默认证书是仅使用 ACME 时的自签名占位证书
+    placeholder: bool,
+    managed: ArcSwap<Vec<ManagedCert>>,
+}
+
+/// 证书的域名与到期时间（Unix 秒）
+#[derive(Debug, Clone)]
+pub struct CertExpiry {
+    pub domains: Vec<String>,
+    pub not_after: i64,
+}
+
+#[derive(Debug)]
+struct ManagedCert {
+    domains: Vec<String>,
+    key: Arc<CertifiedKey>,
+}
+
+impl CertStore {
+    pub fn from_config(config: &TlsConfig) -> anyhow::Result<Self> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let default = if config.cert_path.is_empty() {
+            if config.acme.is_none() {
+                anyhow::bail!("TLS requires cert_path or acme to be configured");
+            }
+            // 仅使用 ACME 时，在首张证书签发前用自签名证书完成握手，
+            // 使重定向到 HTTPS 的 HTTP-01 验证请求可以到达
+            let placeholder = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+            let key = PrivateKeyDer::try_from(placeholder.key_pair.serialize_der())
+                .map_err(|e| anyhow::anyhow!(e))?;
+            Arc::new(CertifiedKey::from_der(
+                vec![placeholder.cert.der().clone()],
+                key,
+                &provider,
+            )?)
+        } else {
+            let certs = CertificateDer::pem_file_iter(&config.cert_path)
+                .map_err(|e| {
+                    anyhow::anyhow!("Failed to read TLS cert {}: {}", config.cert_path, e)
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            let key = PrivateKeyDer::from_pem_file(&config.key_path).map_err(|e| {
+                anyhow::anyhow!("Failed to read TLS key {}: {}", config.key_path, e)
+            })?;
+            Arc::new(CertifiedKey::from_der(certs, key, &provider)?)
+        };
+
+        Ok(Self {
+            provider,
+            default,
+            placeholder: config.cert_path.is_empty(),
+            managed: ArcSwap::from_pointee(Vec::new()),
+        })
+    }
+
+    /// 安装或替换一张证书，domains 相同的旧证书被替换
+    pub fn install(
+        &self,
+        domains: &[String],
+        cert_pem: &[u8],
+        key_pem: &[u8],
+    ) -> anyhow::Result<()> {
+        let certs = CertificateDer::pem_slice_iter(cert_pem).collect::<Result<Vec<_>, _>>()?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem)?;
+        let key = Arc::new(CertifiedKey::from_der(certs, key, &self.provider)?);
+        let domains: Vec<String> = domains.iter().map(|d| d.to_ascii_lowercase()).collect();
+
+        self.managed.rcu(|current| {
+            let mut next: Vec<ManagedCert> = current
+                .iter()
+                .filter(|c| c.domains != domains)
+                .map(|c| ManagedCert {
+                    domains: c.domains.clone(),
+                    key: c.key.clone(),
+                })
+                .collect();
+            next.push(ManagedCert {
+                domains: domains.clone(),
+                key: key.clone(),
+            });
+            next
+        });
+        Ok(())
+    }
+
+    /// 配置文件证书与 ACME 证书的到期时间，占位证书不包含在内
+    pub fn expirations(&self) -> Vec<CertExpiry> {
+        let mut expirations = Vec::new();
+        if !self.placeholder {
+            expirations.extend(cert_expiry(&self.default, None));
+        }
+        for cert in self.managed.load().iter() {
+            expirations.extend(cert_expiry(&cert.key, Some(&cert.domains)));
+        }
+        expirations
+    }
+
+    fn lookup(&self, server_name: &str) -> Option<Arc<CertifiedKey>> {
+        let name = server_name.to_ascii_lowercase();
+        let managed = self.managed.load();
+        managed
+            .iter()
+            .find(|c| c.domains.iter().any(|d| domain_matches(d, &name)))
+            .map(|c| c.key.clone())
+    }
+}
+
+/// 解析叶子证书的到期时间，未给出域名时取证书的 DNS 名称或 CN
+fn cert_expiry(key: &CertifiedKey, domains: Option<&[String]>) -> Option<CertExpiry> {
+    let (_, x509) = X509Certificate::from_der(key.cert.first()?).ok()?;
+    let domains = match domains {
+        Some(domains) => domains.to_vec(),
+        None => {
+            let mut names: Vec<String> = x509
+                .subject_alternative_name()
+                .ok()
+                .flatten()
+                .map(|san| {
+                    san.value
+                        .general_names
+                        .iter()
+                        .filter_map(|name| match name {
+                            GeneralName::DNSName(name) => Some(name.to_string()),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            if names.is_empty() {
+                names.extend(
+                    x509.subject()
+                        .iter_common_name()
+                        .filter_map(|cn| cn.as_str().ok())
+                        .map(str::to_string),
+                );
+            }
+            names
+        }
+    };
+    Some(CertExpiry {
+        domains,
+        not_after: x509.validity().not_after.timestamp(),
+    })
+}
+
+/// 通配符只匹配一级子域名
+fn domain_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => name
+            .strip_suffix(suffix)
+            .and_then(|label| label.strip_suffix('.'))
+            .is_some_and(|label| !label.is_empty() && !label.contains('.')),
+        None => pattern == name,
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        if let Some(key) = client_hello.server_name().and_then(|n| self.lookup(n)) {
+            return Some(key);
+        }
+        // 未携带 SNI 或无匹配证书时回退到默认证书
+        Some(self.default.clone())
+    }
+}
+
+/// 根据配置构造 TLS 接收器，配置 client_ca_path 时启用客户端证书校验
+pub fn build_acceptor(config: &TlsConfig, certs: Arc<CertStore>) -> anyhow::Result<TlsAcceptor> {
+    let provider = certs.provider.clone();
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match config.client_ca_path.as_deref().filter(|p| !p.is_empty()) {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(ca_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read client CA {}: {}", ca_path, e))?
+            {
+                roots.add(cert?)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.client_cert_required {
+                verifier.build()?
+            } else {
+                verifier.allow_unauthenticated().build()?
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(certs);
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
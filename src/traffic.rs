@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+
+use crate::AdminState;
+
+/// 广播缓冲区大小，订阅者落后超过该数量时丢弃旧事件
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// 实时流量事件
+#[derive(Debug, Clone, Serialize)]
+pub struct TrafficEvent {
+    pub time: String,
+    pub client_ip: String,
+    pub method: String,
+    pub path: String,
+    pub rule: Option<String>,
+    pub target: Option<String>,
+    pub status: u16,
+    pub duration_ms: u64,
+}
+
+/// 代理请求事件广播，无订阅者时不构造事件
+#[derive(Clone)]
+pub struct TrafficTail {
+    tx: broadcast::Sender<TrafficEvent>,
+}
+
+impl Default for TrafficTail {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrafficTail {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    #[inline]
+    pub fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+
+    pub fn publish(&self, event: TrafficEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TrafficEvent> {
+        self.tx.subscribe()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TailFilter {
+    /// 规则名称
+    pub rule: Option<String>,
+    /// 状态码，支持精确值（404）或分类（5xx）
+    pub status: Option<String>,
+}
+
+impl TailFilter {
+    fn matches(&self, event: &TrafficEvent) -> bool {
+        if let Some(ref rule) = self.rule {
+            if event.rule.as_deref() != Some(rule.as_str()) {
+                return false;
+            }
+        }
+        if let Some(ref status) = self.status {
+            let status = status.to_ascii_lowercase();
+            let matched = match status.strip_suffix("xx") {
+                Some(class) => class.parse::<u16>().is_ok_and(|c| event.status / 100 == c),
+                None => status.parse::<u16>().is_ok_and(|s| event.status == s),
+            };
+            if !matched {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 实时流量 SSE 推送
+pub async fn stream_handler(
+    State(state): State<AdminState>,
+    Query(filter): Query<TailFilter>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let rx = state.traffic.subscribe();
+
+    let stream = futures::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) if filter.matches(&event) => {
+                    let sse = Event::default()
+                        .event("request")
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok(sse), (rx, filter)));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let sse = Event::default().event("lagged").data(skipped.to_string());
+                    return Some((Ok(sse), (rx, filter)));
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
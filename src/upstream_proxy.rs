@@ -0,0 +1,88 @@
+use dashmap::DashMap;
+use reqwest::{Client, Proxy};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::proxy::http_client_builder;
+use crate::secrets::Secrets;
+
+/// 规则的出站代理：经企业出口或指定地区的 HTTP / SOCKS5 代理访问上游
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProxyOptions {
+    /// 代理地址：http://、https://、socks5://（本地解析目标域名）或 socks5h://（由代理解析）
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// 代理密码引用的密钥名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_secret: Option<String>,
+}
+
+impl UpstreamProxyOptions {
+    /// 校验代理地址，保存规则时调用
+    pub fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|e| e.to_string())?;
+        if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+            return Err(format!("unsupported proxy scheme: {}", url.scheme()));
+        }
+        if url.host_str().is_none() {
+            return Err("proxy url has no host".to_string());
+        }
+        if !url.username().is_empty() || url.password().is_some() {
+            return Err("proxy credentials must use username / password_secret".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 代理地址、用户名、密码与连接超时
+type ProxyKey = (String, Option<String>, Option<String>, Option<Duration>);
+
+/// 凭据相同的出站代理共用一个客户端及连接池；密码按请求从密钥存储读取，密钥更新后使用新客户端
+#[derive(Clone, Default)]
+pub struct UpstreamProxies {
+    clients: Arc<DashMap<ProxyKey, Arc<Client>>>,
+}
+
+impl UpstreamProxies {
+    pub fn client(
+        &self,
+        options: &UpstreamProxyOptions,
+        secrets: &Secrets,
+        connect_timeout: Option<Duration>,
+    ) -> anyhow::Result<Arc<Client>> {
+        let password = match options.password_secret.as_deref() {
+            Some(name) => Some(
+                secrets
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("secret {} not found", name))?,
+            ),
+            None => None,
+        };
+        let key: ProxyKey = (
+            options.url.clone(),
+            options.username.clone(),
+            password,
+            connect_timeout,
+        );
+        if let Some(client) = self.clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let mut proxy = Proxy::all(&options.url)?;
+        if key.1.is_some() || key.2.is_some() {
+            proxy = proxy.basic_auth(
+                key.1.as_deref().unwrap_or_default(),
+                key.2.as_deref().unwrap_or_default(),
+            );
+        }
+        let mut builder = http_client_builder().proxy(proxy);
+        if let Some(timeout) = connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let client = Arc::new(builder.build()?);
+        self.clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
@@ -0,0 +1,394 @@
+use arc_swap::ArcSwap;
+use axum::{body::Body, extract::State, http::StatusCode, response::Response, Json};
+use chrono::Local;
+use dashmap::DashMap;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::api::ApiResponse;
+use crate::events::AdminEvents;
+use crate::router::RuleSet;
+use crate::tasks::TaskRegistry;
+use crate::AdminState;
+
+/// 保留最近的延迟样本数
+const LATENCY_SAMPLES: usize = 64;
+
+/// 连续失败达到该次数视为不健康
+const UNHEALTHY_THRESHOLD: u32 = 3;
+
+/// 从目标地址模板提取上游（scheme://host:port），主机部分含路径参数时返回 None
+pub fn upstream_of(target: &str) -> Option<String> {
+    let scheme_end = target.find("://")? + 3;
+    let authority_end = target[scheme_end..]
+        .find(['/', '?', '#'])
+        .map(|i| scheme_end + i)
+        .unwrap_or(target.len());
+    let upstream = &target[..authority_end];
+    if upstream.contains('{') || authority_end == scheme_end {
+        return None;
+    }
+    Some(upstream.to_ascii_lowercase())
+}
+
+#[derive(Default)]
+struct HostState {
+    requests: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+    last_error_at: Option<String>,
+    last_success_at: Option<String>,
+    latencies: VecDeque<u64>,
+}
+
+/// 上游健康状态
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    Healthy,
+    Degraded,
+    Unhealthy,
+    /// 还没有经过代理的请求
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStatus {
+    pub upstream: String,
+    pub health: Health,
+    pub rules: Vec<String>,
+    pub requests: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<String>,
+    pub last_success_at: Option<String>,
+    pub last_latency_ms: Option<u64>,
+    pub avg_latency_ms: Option<u64>,
+}
+
+/// 根据代理结果被动统计各上游的健康状态
+#[derive(Clone, Default)]
+pub struct UpstreamHealth {
+    hosts: Arc<DashMap<String, HostState>>,
+    events: AdminEvents,
+}
+
+impl UpstreamHealth {
+    pub fn new(events: AdminEvents) -> Self {
+        Self {
+            hosts: Arc::default(),
+            events,
+        }
+    }
+
+    /// 记录一次转发结果，error 为 None 表示成功
+    pub fn record(&self, upstream: &str, latency: Duration, error: Option<String>) {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut host = self.hosts.entry(upstream.to_string()).or_default();
+        host.requests += 1;
+        if host.latencies.len() >= LATENCY_SAMPLES {
+            host.latencies.pop_front();
+        }
+        host.latencies.push_back(latency.as_millis() as u64);
+
+        let transition = match error {
+            Some(error) => {
+                host.failures += 1;
+                host.consecutive_failures += 1;
+                let transition = (host.consecutive_failures == UNHEALTHY_THRESHOLD)
+                    .then(|| (Health::Unhealthy, Some(error.clone())));
+                host.last_error = Some(error);
+                host.last_error_at = Some(now);
+                transition
+            }
+            None => {
+                let recovered = host.consecutive_failures >= UNHEALTHY_THRESHOLD;
+                host.consecutive_failures = 0;
+                host.last_success_at = Some(now);
+                recovered.then_some((Health::Healthy, None))
+            }
+        };
+        drop(host);
+        // 只推送不健康与恢复两种变化，降级状态变化频繁不推送
+        if let Some((health, error)) = transition {
+            self.events.publish(
+                "upstream_health",
+                serde_json::json!({ "upstream": upstream, "health": health, "error": error }),
+            );
+        }
+    }
+
+    /// 结果转换为失败原因：网关错误与 5xx 响应计为失败
+    pub fn failure_reason<T>(
+        result: &Result<axum::http::Response<T>, StatusCode>,
+    ) -> Option<String> {
+        match result {
+            Ok(resp) if resp.status().is_server_error() => {
+                Some(format!("upstream returned {}", resp.status()))
+            }
+            Ok(_) => None,
+            Err(StatusCode::GATEWAY_TIMEOUT) => Some("upstream timeout".to_string()),
+            Err(StatusCode::BAD_GATEWAY) => Some("upstream connection failed".to_string()),
+            Err(status) => Some(format!("proxy error {}", status)),
+        }
+    }
+
+    /// 已有转发记录的上游
+    pub fn hosts(&self) -> Vec<String> {
+        self.hosts.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    /// 当前被标记为不健康（连续失败达到阈值）的上游数量
+    pub fn unhealthy_count(&self) -> usize {
+        self.hosts
+            .iter()
+            .filter(|host| host.consecutive_failures >= UNHEALTHY_THRESHOLD)
+            .count()
+    }
+
+    /// 上游的累计转发次数
+    pub fn requests(&self, upstream: &str) -> u64 {
+        self.hosts.get(upstream).map_or(0, |host| host.requests)
+    }
+
+    fn status(&self, upstream: &str, rules: Vec<String>) -> UpstreamStatus {
+        let mut status = UpstreamStatus {
+            upstream: upstream.to_string(),
+            health: Health::Unknown,
+            rules,
+            requests: 0,
+            failures: 0,
+            consecutive_failures: 0,
+            last_error: None,
+            last_error_at: None,
+            last_success_at: None,
+            last_latency_ms: None,
+            avg_latency_ms: None,
+        };
+
+        if let Some(host) = self.hosts.get(upstream) {
+            status.health = match host.consecutive_failures {
+                0 => Health::Healthy,
+                n if n < UNHEALTHY_THRESHOLD => Health::Degraded,
+                _ => Health::Unhealthy,
+            };
+            status.requests = host.requests;
+            status.failures = host.failures;
+            status.consecutive_failures = host.consecutive_failures;
+            status.last_error = host.last_error.clone();
+            status.last_error_at = host.last_error_at.clone();
+            status.last_success_at = host.last_success_at.clone();
+            status.last_latency_ms = host.latencies.back().copied();
+            if !host.latencies.is_empty() {
+                status.avg_latency_ms =
+                    Some(host.latencies.iter().sum::<u64>() / host.latencies.len() as u64);
+            }
+        }
+
+        status
+    }
+}
+
+/// 启用规则使用的全部上游及其健康状态
+pub async fn list_handler(
+    State(state): State<AdminState>,
+) -> Json<ApiResponse<Vec<UpstreamStatus>>> {
+    let mut upstreams: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for rule in state.rules.load().iter() {
+        if let Some(ref upstream) = rule.upstream {
+            upstreams
+                .entry(upstream.clone())
+                .or_default()
+                .push(rule.name.clone());
+        }
+    }
+
+    Json(ApiResponse::ok(
+        upstreams
+            .into_iter()
+            .map(|(upstream, rules)| state.upstreams.status(&upstream, rules))
+            .collect(),
+    ))
+}
+
+/// 关键上游的可达性探测间隔
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RequiredUpstream {
+    pub upstream: String,
+    pub rules: Vec<String>,
+    /// 尚未探测时为 null
+    pub reachable: Option<bool>,
+    pub error: Option<String>,
+    pub checked_at: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Probe {
+    reachable: bool,
+    error: Option<String>,
+    checked_at: String,
+}
+
+/// `required` 规则的上游可达性，由后台任务主动探测；摘除后没有流量时被动统计无法恢复，因此不依赖转发结果
+#[derive(Clone, Default)]
+pub struct UpstreamProbes {
+    probes: Arc<DashMap<String, Probe>>,
+    events: AdminEvents,
+}
+
+impl UpstreamProbes {
+    pub fn new(events: AdminEvents) -> Self {
+        Self {
+            probes: Arc::default(),
+            events,
+        }
+    }
+
+    /// 启用的 `required` 规则的上游及最近一次探测结果
+    pub fn required(&self, rules: &RuleSet) -> Vec<RequiredUpstream> {
+        let mut upstreams: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for rule in rules.iter().filter(|rule| rule.options.required) {
+            if let Some(upstream) = rule.upstream.as_ref().filter(|u| u.starts_with("http")) {
+                upstreams
+                    .entry(upstream.clone())
+                    .or_default()
+                    .push(rule.name.clone());
+            }
+        }
+        upstreams
+            .into_iter()
+            .map(|(upstream, rules)| {
+                let probe = self.probes.get(&upstream).map(|probe| probe.clone());
+                RequiredUpstream {
+                    reachable: probe.as_ref().map(|probe| probe.reachable),
+                    error: probe.as_ref().and_then(|probe| probe.error.clone()),
+                    checked_at: probe.map(|probe| probe.checked_at),
+                    upstream,
+                    rules,
+                }
+            })
+            .collect()
+    }
+
+    /// 对每个关键上游发送 HEAD 请求，收到任何响应即视为可达
+    async fn probe_all(&self, client: &Client, rules: &RuleSet) {
+        let upstreams = self.required(rules);
+        self.probes
+            .retain(|upstream, _| upstreams.iter().any(|u| &u.upstream == upstream));
+        let probes = upstreams.into_iter().map(|required| async move {
+            let result = client
+                .head(format!("{}/", required.upstream))
+                .timeout(PROBE_TIMEOUT)
+                .send()
+                .await;
+            (required.upstream, result.err().map(|e| e.to_string()))
+        });
+        for (upstream, error) in futures::future::join_all(probes).await {
+            if let Some(ref error) = error {
+                tracing::warn!(upstream = %upstream, "Required upstream unreachable: {}", error);
+            }
+            let reachable = error.is_none();
+            let previous = self.probes.insert(
+                upstream.clone(),
+                Probe {
+                    reachable,
+                    error: error.clone(),
+                    checked_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                },
+            );
+            // 首次探测不可达或可达性变化时推送
+            if previous.map_or(!reachable, |probe| probe.reachable != reachable) {
+                self.events.publish(
+                    "upstream_probe",
+                    serde_json::json!({ "upstream": upstream, "reachable": reachable, "error": error }),
+                );
+            }
+        }
+    }
+}
+
+/// 启动关键上游探测任务
+pub fn start_probe_task(
+    tasks: &TaskRegistry,
+    probes: UpstreamProbes,
+    rules: Arc<ArcSwap<RuleSet>>,
+    client: Client,
+) {
+    tasks.spawn_periodic("upstream_probe", PROBE_INTERVAL, move || {
+        let (probes, rules, client) = (probes.clone(), rules.load_full(), client.clone());
+        async move {
+            probes.probe_all(&client, &rules).await;
+            Ok(())
+        }
+    });
+}
+
+/// 规则 `pool.max_connections` 的并发许可，按上游主机与上限值区分
+#[derive(Clone, Default)]
+pub struct UpstreamLimits {
+    limits: Arc<DashMap<(String, usize), Arc<Semaphore>>>,
+}
+
+impl UpstreamLimits {
+    /// 等待目标主机的并发许可，超过 `wait` 仍未取得时返回 503
+    pub async fn acquire(
+        &self,
+        rule: &str,
+        target_url: &str,
+        max: usize,
+        wait: Duration,
+    ) -> Result<OwnedSemaphorePermit, StatusCode> {
+        let upstream = upstream_of(target_url).unwrap_or_default();
+        let semaphore = self
+            .limits
+            .entry((upstream.clone(), max))
+            .or_insert_with(|| Arc::new(Semaphore::new(max)))
+            .clone();
+        match tokio::time::timeout(wait, semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => {
+                tracing::warn!(rule = %rule, upstream = %upstream, max, "Upstream connection limit reached");
+                Err(StatusCode::SERVICE_UNAVAILABLE)
+            }
+        }
+    }
+
+    /// 各上游的并发上限与已占用的许可，同一上游有多个上限时取最小者
+    pub fn snapshot(&self) -> BTreeMap<String, (usize, usize)> {
+        let mut limits: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        for entry in self.limits.iter() {
+            let (upstream, max) = entry.key();
+            let in_use = max - entry.value().available_permits();
+            limits
+                .entry(upstream.clone())
+                .and_modify(|limit| {
+                    if *max < limit.0 {
+                        *limit = (*max, in_use);
+                    }
+                })
+                .or_insert((*max, in_use));
+        }
+        limits
+    }
+}
+
+/// 响应体传输完成或客户端断开后才释放许可
+pub fn hold_permit(resp: Response, permit: OwnedSemaphorePermit) -> Response {
+    resp.map(|body| {
+        let stream = body.into_data_stream().map(move |chunk| {
+            let _ = &permit;
+            chunk
+        });
+        Body::from_stream(stream)
+    })
+}
@@ -0,0 +1,259 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    Json,
+};
+use parking_lot::Mutex;
+use reqwest::Client;
+use ring::{hmac, rand::SecureRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::api::ApiResponse;
+use crate::config::ChangeWebhooksConfig;
+use crate::AdminState;
+
+/// 保留的投递记录条数
+const MAX_DELIVERIES: usize = 200;
+
+/// 一次投递（一个事件发往一个地址）的记录，重试过程中原地更新
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub id: String,
+    pub event: &'static str,
+    pub url: String,
+    pub time: String,
+    pub attempts: u32,
+    /// 最后一次尝试的响应状态码，连接失败时为 null
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub delivered: bool,
+    /// 还会继续重试
+    pub pending: bool,
+    pub duration_ms: u64,
+}
+
+/// 待投递的事件，请求体只序列化一次，各地址共用
+struct Outgoing {
+    id: String,
+    event: &'static str,
+    body: Vec<u8>,
+}
+
+struct Endpoint {
+    events: Vec<String>,
+    tx: mpsc::UnboundedSender<Arc<Outgoing>>,
+}
+
+/// 配置变更 Webhook：每个地址一个投递队列，按变更顺序逐个投递，失败时退避重试
+#[derive(Clone, Default)]
+pub struct ChangeWebhooks {
+    endpoints: Arc<Vec<Endpoint>>,
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>,
+}
+
+impl ChangeWebhooks {
+    pub fn new(client: Client, config: &ChangeWebhooksConfig) -> Self {
+        let deliveries = Arc::new(Mutex::new(VecDeque::new()));
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                let worker = Worker {
+                    client: client.clone(),
+                    url: endpoint.url.clone(),
+                    key: endpoint
+                        .secret
+                        .as_ref()
+                        .map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())),
+                    timeout: Duration::from_secs(config.timeout_secs),
+                    max_attempts: config.max_attempts.max(1),
+                    deliveries: deliveries.clone(),
+                };
+                tokio::spawn(worker.run(rx));
+                tracing::info!(url = %endpoint.url, "Change webhook enabled");
+                Endpoint {
+                    events: endpoint.events.clone(),
+                    tx,
+                }
+            })
+            .collect();
+        Self {
+            endpoints: Arc::new(endpoints),
+            deliveries,
+        }
+    }
+
+    /// 加入订阅了该事件的各地址的投递队列，不等待投递结果
+    pub fn notify(&self, event: &'static str, data: Value) {
+        let subscribed = |endpoint: &&Endpoint| {
+            endpoint.events.is_empty() || endpoint.events.iter().any(|e| e == event)
+        };
+        if !self.endpoints.iter().any(|e| subscribed(&e)) {
+            return;
+        }
+        let id = delivery_id();
+        let payload = serde_json::json!({
+            "id": id,
+            "event": event,
+            "timestamp": chrono::Local::now().to_rfc3339(),
+            "data": data,
+        });
+        let outgoing = Arc::new(Outgoing {
+            id,
+            event,
+            body: serde_json::to_vec(&payload).unwrap_or_default(),
+        });
+        for endpoint in self.endpoints.iter().filter(subscribed) {
+            let _ = endpoint.tx.send(outgoing.clone());
+        }
+    }
+
+    /// 最近的投递记录，新的在前
+    pub fn recent(&self, limit: usize) -> Vec<Delivery> {
+        self.deliveries
+            .lock()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// 随机事件 ID，接收方可据此对重试去重
+fn delivery_id() -> String {
+    let mut bytes = [0u8; 16];
+    let _ = ring::rand::SystemRandom::new().fill(&mut bytes);
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct Worker {
+    client: Client,
+    url: String,
+    key: Option<hmac::Key>,
+    timeout: Duration,
+    max_attempts: u32,
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>,
+}
+
+impl Worker {
+    async fn run(self, mut rx: mpsc::UnboundedReceiver<Arc<Outgoing>>) {
+        while let Some(outgoing) = rx.recv().await {
+            self.deliver(&outgoing).await;
+        }
+    }
+
+    async fn deliver(&self, outgoing: &Outgoing) {
+        let signature = self
+            .key
+            .as_ref()
+            .map(|key| format!("sha256={}", hex(hmac::sign(key, &outgoing.body).as_ref())));
+        self.record(Delivery {
+            id: outgoing.id.clone(),
+            event: outgoing.event,
+            url: self.url.clone(),
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            attempts: 0,
+            status: None,
+            error: None,
+            delivered: false,
+            pending: true,
+            duration_ms: 0,
+        });
+
+        let start = Instant::now();
+        for attempt in 1..=self.max_attempts {
+            let mut req = self
+                .client
+                .post(&self.url)
+                .timeout(self.timeout)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-proxy-event", outgoing.event)
+                .header("x-proxy-delivery", &outgoing.id)
+                .body(outgoing.body.clone());
+            if let Some(ref signature) = signature {
+                req = req.header("x-proxy-signature", signature);
+            }
+            let (status, error) = match req.send().await {
+                Ok(resp) if resp.status().is_success() => (Some(resp.status().as_u16()), None),
+                Ok(resp) => (
+                    Some(resp.status().as_u16()),
+                    Some(format!("webhook returned {}", resp.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let delivered = error.is_none();
+            let pending = !delivered && attempt < self.max_attempts;
+            match error {
+                Some(ref error) if pending => {
+                    tracing::debug!(url = %self.url, event = outgoing.event, attempt, error = %error, "Change webhook failed, retrying");
+                }
+                Some(ref error) => {
+                    tracing::warn!(url = %self.url, event = outgoing.event, attempts = attempt, error = %error, "Change webhook delivery failed");
+                }
+                None => {
+                    tracing::debug!(url = %self.url, event = outgoing.event, "Change webhook delivered");
+                }
+            }
+            self.update(&outgoing.id, |delivery| {
+                delivery.attempts = attempt;
+                delivery.status = status;
+                delivery.error = error;
+                delivery.delivered = delivered;
+                delivery.pending = pending;
+                delivery.duration_ms = start.elapsed().as_millis() as u64;
+            });
+            if !pending {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(1 << (attempt - 1).min(6))).await;
+        }
+    }
+
+    fn record(&self, delivery: Delivery) {
+        let mut deliveries = self.deliveries.lock();
+        if deliveries.len() >= MAX_DELIVERIES {
+            deliveries.pop_front();
+        }
+        deliveries.push_back(delivery);
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Delivery)) {
+        let mut deliveries = self.deliveries.lock();
+        if let Some(delivery) = deliveries
+            .iter_mut()
+            .rev()
+            .find(|d| d.id == id && d.url == self.url)
+        {
+            f(delivery);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+/// 最近的配置变更 Webhook 投递记录
+pub async fn deliveries_handler(
+    State(state): State<AdminState>,
+    Query(query): Query<DeliveryQuery>,
+) -> Json<ApiResponse<Vec<Delivery>>> {
+    Json(ApiResponse::ok(state.webhooks.recent(query.limit)))
+}